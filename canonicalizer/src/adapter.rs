@@ -0,0 +1,539 @@
+//! Per-exchange symbol adapters.
+//!
+//! Each venue implements [`ExchangeAdapter`] to map its native symbols to the
+//! canonical `BASE-QUOTE` form and back. Adapters live in a runtime registry
+//! keyed by exchange name, pre-populated with the built-in venues, so
+//! applications can add or replace venues with
+//! [`CanonicalService::register_adapter`](crate::CanonicalService::register_adapter)
+//! without changing this crate.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::error::CanonicalError;
+use crate::http_client;
+
+/// Symbol mapping for one exchange.
+#[async_trait]
+pub trait ExchangeAdapter: Send + Sync {
+    /// Convert a native symbol into canonical `BASE-QUOTE` form, or `None`
+    /// when it cannot be parsed.
+    fn canonicalize(&self, pair: &str) -> Option<String>;
+
+    /// Like [`canonicalize`](Self::canonicalize), but reporting why a symbol
+    /// could not be mapped. Adapters that can tell the reasons apart override
+    /// this; the default reports [`CanonicalError::Unparseable`].
+    fn try_canonicalize(&self, pair: &str) -> Result<String, CanonicalError> {
+        self.canonicalize(pair)
+            .ok_or_else(|| CanonicalError::Unparseable(pair.to_string()))
+    }
+
+    /// Convert a canonical symbol back into the exchange's native form.
+    fn denormalize(&self, canonical: &str) -> Option<String>;
+
+    /// Whether native symbols run base and quote together, as in `BTCUSDT`,
+    /// so a wrong split can leave a quote asset inside the base.
+    fn concatenated(&self) -> bool {
+        false
+    }
+
+    /// Load whatever listing metadata [`canonicalize`](Self::canonicalize)
+    /// relies on. Called by [`CanonicalService::init`](crate::CanonicalService::init).
+    async fn refresh_metadata(&self) {}
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn ExchangeAdapter>>>;
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// The adapter registry, holding the built-in venues until others are added.
+pub(crate) fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(|| {
+        let binance: Arc<dyn ExchangeAdapter> = Arc::new(BinanceAdapter);
+        let dashed: Arc<dyn ExchangeAdapter> = Arc::new(SeparatedAdapter { sep: '-' });
+        let mut m: HashMap<String, Arc<dyn ExchangeAdapter>> = HashMap::new();
+        m.insert("binance".into(), binance.clone());
+        m.insert("bybit".into(), binance.clone());
+        m.insert("mexc".into(), binance);
+        m.insert("coinbase".into(), Arc::new(CoinbaseAdapter));
+        m.insert("gate".into(), Arc::new(SeparatedAdapter { sep: '_' }));
+        m.insert("kucoin".into(), dashed.clone());
+        m.insert("okx".into(), dashed);
+        m.insert("hyperliquid".into(), Arc::new(HyperliquidAdapter));
+        m.insert("deribit".into(), Arc::new(DeribitAdapter));
+        m.insert("kraken".into(), Arc::new(KrakenAdapter));
+        m.insert("bitfinex".into(), Arc::new(BitfinexAdapter));
+        RwLock::new(m)
+    })
+}
+
+/// Cached list of Binance quote assets, longest first.
+static BINANCE_QUOTES: OnceLock<Vec<String>> = OnceLock::new();
+
+pub(crate) fn binance_quotes() -> &'static Vec<String> {
+    BINANCE_QUOTES.get_or_init(default_binance_quotes)
+}
+
+#[cfg(test)]
+pub(crate) fn set_binance_quotes(quotes: Vec<&str>) {
+    let mut qs: Vec<String> = quotes.into_iter().map(|s| s.to_lowercase()).collect();
+    qs.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    let _ = BINANCE_QUOTES.set(qs);
+}
+
+async fn fetch_binance_quotes() -> Result<Vec<String>, reqwest::Error> {
+    let client = http_client::builder().build()?;
+    let v: serde_json::Value = client
+        .get("https://api.binance.us/api/v3/exchangeInfo")
+        .send()
+        .await?
+        .json()
+        .await?;
+    let mut set = HashSet::new();
+    if let Some(symbols) = v.get("symbols").and_then(|s| s.as_array()) {
+        for sym in symbols {
+            if let Some(q) = sym.get("quoteAsset").and_then(|q| q.as_str()) {
+                set.insert(q.to_lowercase());
+            }
+        }
+    }
+    let mut quotes: Vec<String> = set.into_iter().collect();
+    quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    Ok(quotes)
+}
+
+fn parse_env_quotes(env: &str) -> Vec<String> {
+    let mut quotes: Vec<String> = env
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    quotes
+}
+
+fn default_binance_quotes() -> Vec<String> {
+    const DEFAULT: [&str; 7] = ["usdt", "usdc", "busd", "usd", "btc", "eth", "bnb"];
+    let mut quotes: Vec<String> = DEFAULT.iter().map(|q| q.to_string()).collect();
+    quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    quotes
+}
+
+/// Concatenated Binance symbols such as `btcusdt`, split on the longest known
+/// quote asset. Also used for Bybit and MEXC, which name pairs the same way.
+pub struct BinanceAdapter;
+
+#[async_trait]
+impl ExchangeAdapter for BinanceAdapter {
+    fn canonicalize(&self, symbol: &str) -> Option<String> {
+        self.try_canonicalize(symbol).ok()
+    }
+
+    /// Binance coin-margined perpetuals append `_PERP` to a USD pair, and
+    /// quarterly futures of either margin type their expiry: `BTCUSD_PERP`
+    /// maps to `BTC-USD`, the COIN-M `BTCUSD_241227` to `BTC-USD-20241227` and
+    /// the USD-M `BTCUSDT_250627` to `BTC-USDT-20250627`, matching the dated
+    /// Deribit symbols.
+    fn try_canonicalize(&self, symbol: &str) -> Result<String, CanonicalError> {
+        let lower = symbol.to_lowercase();
+        let (pair, contract) = match lower.split_once('_') {
+            Some((pair, contract)) => (pair, Some(contract)),
+            None => (lower.as_str(), None),
+        };
+        // COIN-M contracts are USD pairs even when spot lists no USD quote.
+        let quote = binance_quotes()
+            .iter()
+            .map(String::as_str)
+            .find(|q| pair.ends_with(q))
+            .or_else(|| (contract.is_some() && pair.ends_with("usd")).then_some("usd"))
+            .ok_or_else(|| CanonicalError::UnknownQuote(symbol.to_string()))?;
+        let base = &pair[..pair.len() - quote.len()];
+        if base.is_empty() {
+            return Err(CanonicalError::EmptyBase(symbol.to_string()));
+        }
+        let pair = format!("{}-{}", base.to_uppercase(), quote.to_uppercase());
+        match contract {
+            None => Ok(pair),
+            Some("perp") if quote == "usd" => Ok(pair),
+            Some(date) if date.len() == 6 && date.bytes().all(|b| b.is_ascii_digit()) => {
+                Ok(format!("{pair}-20{date}"))
+            }
+            Some(_) => Err(CanonicalError::Unparseable(symbol.to_string())),
+        }
+    }
+
+    /// `BTC-USDT` as `BTCUSDT`; dated contracts as `BTCUSD_241227`.
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let mut parts = canonical.split('-');
+        let (base, quote) = (parts.next()?, parts.next()?);
+        match parts.next() {
+            None => Some(format!("{base}{quote}").to_uppercase()),
+            Some(date) if date.len() == 8 && parts.next().is_none() => {
+                Some(format!("{base}{quote}_{}", &date[2..]).to_uppercase())
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn concatenated(&self) -> bool {
+        true
+    }
+
+    /// Load the quote asset list from the `BINANCE_QUOTES` environment
+    /// variable or the public `exchangeInfo` endpoint, falling back to a small
+    /// built-in list on network errors. Only the first load takes effect.
+    async fn refresh_metadata(&self) {
+        if BINANCE_QUOTES.get().is_some() {
+            return;
+        }
+
+        if let Ok(env) = std::env::var("BINANCE_QUOTES") {
+            let _ = BINANCE_QUOTES.set(parse_env_quotes(&env));
+            return;
+        }
+
+        match fetch_binance_quotes().await {
+            Ok(quotes) if !quotes.is_empty() => {
+                let _ = BINANCE_QUOTES.set(quotes);
+            }
+            Ok(_) => {
+                let _ = BINANCE_QUOTES.set(default_binance_quotes());
+            }
+            Err(e) => {
+                warn!("failed to fetch Binance quotes: {}", e);
+                let _ = BINANCE_QUOTES.set(default_binance_quotes());
+            }
+        }
+    }
+}
+
+/// Coinbase products, already `BASE-QUOTE` apart from case.
+pub struct CoinbaseAdapter;
+
+impl ExchangeAdapter for CoinbaseAdapter {
+    fn canonicalize(&self, symbol: &str) -> Option<String> {
+        let lower = symbol.to_lowercase().replace('_', "-");
+
+        if let Some((base, quote)) = lower.split_once('-') {
+            return Some(format!("{}-{}", base.to_uppercase(), quote.to_uppercase()));
+        }
+
+        // Attempt to detect a known quote asset when no separator is present.
+        const QUOTES: [&str; 6] = ["usdt", "usdc", "usd", "btc", "eth", "eur"];
+        for q in QUOTES {
+            if lower.ends_with(q) {
+                let base = &lower[..lower.len() - q.len()];
+                if !base.is_empty() {
+                    return Some(format!("{}-{}", base.to_uppercase(), q.to_uppercase()));
+                }
+            }
+        }
+
+        Some(lower.to_uppercase())
+    }
+
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        Some(canonical.to_uppercase())
+    }
+}
+
+/// Gate.io pairs are `BASE_QUOTE`, KuCoin and OKX symbols `BASE-QUOTE`, OKX
+/// adding a contract suffix such as `-SWAP` for derivatives.
+pub struct SeparatedAdapter {
+    /// Separator the exchange puts between base and quote.
+    pub sep: char,
+}
+
+impl ExchangeAdapter for SeparatedAdapter {
+    fn canonicalize(&self, inst_id: &str) -> Option<String> {
+        self.try_canonicalize(inst_id).ok()
+    }
+
+    fn try_canonicalize(&self, inst_id: &str) -> Result<String, CanonicalError> {
+        let mut parts = inst_id.split(['-', '_']);
+        let base = parts
+            .next()
+            .filter(|b| !b.is_empty())
+            .ok_or_else(|| CanonicalError::EmptyBase(inst_id.to_string()))?;
+        let quote = parts
+            .next()
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| CanonicalError::UnknownQuote(inst_id.to_string()))?;
+        Ok(format!("{}-{}", base.to_uppercase(), quote.to_uppercase()))
+    }
+
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let (base, quote) = canonical.split_once('-')?;
+        Some(format!("{base}{}{quote}", self.sep).to_uppercase())
+    }
+}
+
+/// Hyperliquid perpetuals are named by coin alone and quoted in USD.
+pub struct HyperliquidAdapter;
+
+impl HyperliquidAdapter {
+    /// `coin` as Hyperliquid names it. Coin names are case-sensitive: the
+    /// leading lowercase `k` of a contract on 1000 units, as in `kPEPE`, is
+    /// kept and the rest is upper-cased.
+    pub fn coin(coin: &str) -> String {
+        match coin.strip_prefix('k') {
+            Some(rest) if !rest.is_empty() && !rest.contains(|c: char| c.is_lowercase()) => {
+                coin.to_string()
+            }
+            _ => coin.to_uppercase(),
+        }
+    }
+}
+
+impl ExchangeAdapter for HyperliquidAdapter {
+    fn canonicalize(&self, coin: &str) -> Option<String> {
+        let coin = coin.trim();
+        if coin.is_empty() || coin.contains(['-', '_', '/']) {
+            return None;
+        }
+        Some(format!("{}-USD", Self::coin(coin)))
+    }
+
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let (coin, quote) = canonical.rsplit_once('-')?;
+        quote.eq_ignore_ascii_case("USD").then(|| Self::coin(coin))
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+/// Deribit futures are named `BTC-PERPETUAL` or `BTC-27DEC24`, inverse
+/// contracts being quoted in USD and linear ones naming their pair, as in
+/// `ETH_USDC-PERPETUAL`. Dated futures keep their expiry as a `YYYYMMDD`
+/// suffix, e.g. `BTC-USD-20241227`, so each expiry is its own symbol.
+/// Options are not mapped.
+pub struct DeribitAdapter;
+
+impl DeribitAdapter {
+    /// `27DEC24` as `20241227`.
+    fn expiry(date: &str) -> Option<String> {
+        if date.len() < 6 || !date.is_ascii() {
+            return None;
+        }
+        let (day, rest) = date.split_at(date.len() - 5);
+        let (month, year) = rest.split_at(3);
+        let day: u32 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+        let month = MONTHS.iter().position(|m| *m == month)? + 1;
+        let year: u32 = year.parse().ok()?;
+        Some(format!("20{year:02}{month:02}{day:02}"))
+    }
+}
+
+impl ExchangeAdapter for DeribitAdapter {
+    fn canonicalize(&self, instrument: &str) -> Option<String> {
+        let upper = instrument.trim().to_uppercase();
+        let (pair, contract) = upper.split_once('-')?;
+        let (base, quote) = pair.split_once('_').unwrap_or((pair, "USD"));
+        if base.is_empty() || quote.is_empty() {
+            return None;
+        }
+        if contract == "PERPETUAL" {
+            return Some(format!("{base}-{quote}"));
+        }
+        let expiry = Self::expiry(contract)?;
+        Some(format!("{base}-{quote}-{expiry}"))
+    }
+
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let upper = canonical.to_uppercase();
+        let mut parts = upper.split('-');
+        let (base, quote) = (parts.next()?, parts.next()?);
+        let pair = if quote == "USD" {
+            base.to_string()
+        } else {
+            format!("{base}_{quote}")
+        };
+        let Some(date) = parts.next() else {
+            return Some(format!("{pair}-PERPETUAL"));
+        };
+        if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let month: usize = date[4..6].parse().ok()?;
+        let day: u32 = date[6..].parse().ok()?;
+        Some(format!(
+            "{pair}-{day}{}{}",
+            MONTHS.get(month.checked_sub(1)?)?,
+            &date[2..4]
+        ))
+    }
+}
+
+/// Kraken asset codes that differ from the canonical ones.
+const KRAKEN_ASSETS: [(&str, &str); 2] = [("XBT", "BTC"), ("XDG", "DOGE")];
+/// Bitfinex asset codes that differ from the canonical ones.
+const BITFINEX_ASSETS: [(&str, &str); 5] = [
+    ("UST", "USDT"),
+    ("UDC", "USDC"),
+    ("TSD", "TUSD"),
+    ("DSH", "DASH"),
+    ("IOT", "IOTA"),
+];
+
+/// Canonical code for a venue asset code, via the venue's alias table.
+fn canonical_asset(table: &[(&str, &str)], code: &str) -> String {
+    let code = code.to_uppercase();
+    table
+        .iter()
+        .find(|(native, _)| *native == code)
+        .map_or(code, |(_, canon)| canon.to_string())
+}
+
+/// Venue asset code for a canonical one, via the venue's alias table.
+fn native_asset(table: &[(&str, &str)], code: &str) -> String {
+    let code = code.to_uppercase();
+    table
+        .iter()
+        .find(|(_, canon)| *canon == code)
+        .map_or(code, |(native, _)| native.to_string())
+}
+
+/// Kraken names pairs as websocket `XBT/USD`, REST altnames such as
+/// `XBTUSDT`, or legacy ids such as `XXBTZUSD` whose four-letter legs carry
+/// an `X` (crypto) or `Z` (fiat) prefix. `XBT` and `XDG` become `BTC` and
+/// `DOGE`.
+pub struct KrakenAdapter;
+
+impl KrakenAdapter {
+    const QUOTES: [&'static str; 12] = [
+        "USDT", "USDC", "USD", "EUR", "GBP", "JPY", "CAD", "CHF", "AUD", "XBT", "ETH", "DAI",
+    ];
+}
+
+impl ExchangeAdapter for KrakenAdapter {
+    fn canonicalize(&self, pair: &str) -> Option<String> {
+        let upper = pair.trim().to_uppercase();
+        let (base, quote) = if let Some((base, quote)) = upper.split_once('/') {
+            (base, quote)
+        } else if upper.len() == 8
+            && upper.starts_with('X')
+            && matches!(upper.as_bytes()[4], b'X' | b'Z')
+        {
+            (&upper[1..4], &upper[5..])
+        } else {
+            let quote = Self::QUOTES
+                .iter()
+                .find(|q| upper.len() > q.len() && upper.ends_with(*q))?;
+            upper.split_at(upper.len() - quote.len())
+        };
+        if base.is_empty() || quote.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{}-{}",
+            canonical_asset(&KRAKEN_ASSETS, base),
+            canonical_asset(&KRAKEN_ASSETS, quote)
+        ))
+    }
+
+    /// `BTC-USD` as the websocket name `XBT/USD`.
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let (base, quote) = canonical.split_once('-')?;
+        Some(format!(
+            "{}/{}",
+            native_asset(&KRAKEN_ASSETS, base),
+            native_asset(&KRAKEN_ASSETS, quote)
+        ))
+    }
+}
+
+/// Bitfinex trading pairs are `tBTCUSD`, or `tTESTBTC:TESTUSD` when a leg is
+/// longer than three letters. Funding symbols (`fUSD`) are not mapped. `UST`
+/// is Bitfinex's code for Tether and becomes `USDT`.
+pub struct BitfinexAdapter;
+
+impl ExchangeAdapter for BitfinexAdapter {
+    fn canonicalize(&self, symbol: &str) -> Option<String> {
+        let symbol = symbol.trim();
+        let pair = symbol.strip_prefix('t').unwrap_or(symbol).to_uppercase();
+        let (base, quote) = match pair.split_once(':') {
+            Some(legs) => legs,
+            None if pair.len() == 6 && pair.is_ascii() => pair.split_at(3),
+            None => return None,
+        };
+        if base.is_empty() || quote.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{}-{}",
+            canonical_asset(&BITFINEX_ASSETS, base),
+            canonical_asset(&BITFINEX_ASSETS, quote)
+        ))
+    }
+
+    fn denormalize(&self, canonical: &str) -> Option<String> {
+        let (base, quote) = canonical.split_once('-')?;
+        let base = native_asset(&BITFINEX_ASSETS, base);
+        let quote = native_asset(&BITFINEX_ASSETS, quote);
+        Some(if base.len() == 3 && quote.len() == 3 {
+            format!("t{base}{quote}")
+        } else {
+            format!("t{base}:{quote}")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denormalize_round_trips_native_symbols() {
+        let cases: [(&dyn ExchangeAdapter, &str); 12] = [
+            (&BinanceAdapter, "BTCUSD_241227"),
+            (&BinanceAdapter, "BTCUSDT_250627"),
+            (&CoinbaseAdapter, "ETH-USD"),
+            (&SeparatedAdapter { sep: '_' }, "BTC_USDT"),
+            (&SeparatedAdapter { sep: '-' }, "SOL-USDC"),
+            (&HyperliquidAdapter, "ETH"),
+            (&HyperliquidAdapter, "kPEPE"),
+            (&DeribitAdapter, "ETH_USDC-PERPETUAL"),
+            (&DeribitAdapter, "BTC-7MAR25"),
+            (&KrakenAdapter, "XDG/USD"),
+            (&BitfinexAdapter, "tBTCUST"),
+            (&BitfinexAdapter, "tTESTBTC:TESTUSD"),
+        ];
+        for (adapter, native) in cases {
+            let canon = adapter.canonicalize(native).unwrap();
+            assert_eq!(adapter.denormalize(&canon).as_deref(), Some(native));
+        }
+        assert_eq!(
+            BinanceAdapter.denormalize("BTC-USDT").as_deref(),
+            Some("BTCUSDT")
+        );
+        assert_eq!(HyperliquidAdapter.denormalize("BTC-USDT"), None);
+    }
+
+    #[test]
+    fn kraken_and_bitfinex_codes_map_to_canonical_assets() {
+        for pair in ["XBT/USD", "XXBTZUSD", "XBTUSD", "xbtusd"] {
+            assert_eq!(KrakenAdapter.canonicalize(pair).as_deref(), Some("BTC-USD"));
+        }
+        assert_eq!(
+            KrakenAdapter.canonicalize("XDGUSDT").as_deref(),
+            Some("DOGE-USDT")
+        );
+        assert_eq!(
+            KrakenAdapter.canonicalize("XETHXXBT").as_deref(),
+            Some("ETH-BTC")
+        );
+        assert_eq!(
+            BitfinexAdapter.canonicalize("tBTCUST").as_deref(),
+            Some("BTC-USDT")
+        );
+        assert_eq!(
+            BitfinexAdapter.canonicalize("tETHUSD").as_deref(),
+            Some("ETH-USD")
+        );
+        assert_eq!(BitfinexAdapter.canonicalize("fUSD"), None);
+    }
+}
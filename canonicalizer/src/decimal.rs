@@ -0,0 +1,128 @@
+//! Exact decimal prices and quantities.
+//!
+//! Events carry prices and quantities as decimal strings so no precision is
+//! lost on the wire. Parsing them as `f64` gives that back up: a small-cap
+//! price such as `0.00000123` or a large quantity cannot be represented
+//! exactly. The helpers here read them as [`Decimal`] instead, both from the
+//! typed events of this crate and from raw event JSON such as trades, tickers
+//! and book diffs. Requires the `decimal` feature.
+
+use std::str::FromStr;
+
+pub use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::events::{
+    Bar, Fill, Funding, Liquidation, OpenInterest, Order, Position, TermStructure,
+};
+
+/// Parse a decimal string, also accepting scientific notation such as `1e-8`.
+pub fn parse(s: &str) -> Option<Decimal> {
+    let s = s.trim();
+    Decimal::from_str(s)
+        .or_else(|_| Decimal::from_scientific(s))
+        .ok()
+}
+
+/// A decimal string or JSON number.
+pub fn from_value(v: &Value) -> Option<Decimal> {
+    match v {
+        Value::String(s) => parse(s),
+        Value::Number(n) => parse(&n.to_string()),
+        _ => None,
+    }
+}
+
+/// Field `key` of a raw event, e.g. `"p"` of a trade or `"bp"` of a ticker.
+pub fn field(event: &Value, key: &str) -> Option<Decimal> {
+    from_value(event.get(key)?)
+}
+
+/// `(price, qty)` levels of one side (`"bids"` or `"asks"`) of a book
+/// snapshot or diff. Levels that do not parse are skipped.
+pub fn levels(event: &Value, side: &str) -> Vec<(Decimal, Decimal)> {
+    event
+        .get(side)
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| Some((from_value(lvl.get(0)?)?, from_value(lvl.get(1)?)?)))
+        .collect()
+}
+
+/// `*_decimal` accessors for the string fields of an event.
+macro_rules! decimal_accessors {
+    ($ty:ty { $($field:ident => $method:ident),+ $(,)? }) => {
+        impl $ty {
+            $(
+                #[doc = concat!("`", stringify!($field), "` as a [`Decimal`].")]
+                pub fn $method(&self) -> Option<Decimal> {
+                    parse(&self.$field)
+                }
+            )+
+        }
+    };
+}
+
+decimal_accessors!(Bar {
+    open => open_decimal,
+    high => high_decimal,
+    low => low_decimal,
+    close => close_decimal,
+    volume => volume_decimal,
+});
+decimal_accessors!(Funding { rate => rate_decimal });
+decimal_accessors!(OpenInterest { open_interest => open_interest_decimal });
+decimal_accessors!(TermStructure { basis => basis_decimal });
+decimal_accessors!(Liquidation {
+    price => price_decimal,
+    quantity => quantity_decimal,
+});
+decimal_accessors!(Order {
+    price => price_decimal,
+    quantity => quantity_decimal,
+});
+decimal_accessors!(Fill {
+    price => price_decimal,
+    quantity => quantity_decimal,
+});
+decimal_accessors!(Position {
+    free => free_decimal,
+    locked => locked_decimal,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn raw_events_read_exactly() {
+        let trade = json!({"type": "trade", "p": "0.00000123", "q": 1e-8, "ts": 1});
+        assert_eq!(field(&trade, "p"), Decimal::from_str("0.00000123").ok());
+        assert_eq!(field(&trade, "q"), Decimal::from_str("0.00000001").ok());
+        assert_eq!(field(&trade, "ts"), Some(Decimal::ONE));
+        assert!(field(&trade, "type").is_none());
+
+        let diff = json!({"bids": [["100.10", "2"], ["bad", "1"]], "asks": []});
+        let bids = levels(&diff, "bids");
+        assert_eq!(bids, [(Decimal::new(10010, 2), Decimal::TWO)]);
+        assert!(levels(&diff, "asks").is_empty());
+        assert_eq!(parse("1.5E-3"), Some(Decimal::new(15, 4)));
+    }
+
+    #[test]
+    fn typed_events_have_decimal_accessors() {
+        let bar: Bar = serde_json::from_value(json!({
+            "agent": "binance", "type": "ohlcv", "s": "PEPE-USDT", "i": 60,
+            "o": "0.00000101", "h": "0.00000110", "l": "0.00000100",
+            "c": "0.00000109", "v": "123456789012.5", "ts": 0
+        }))
+        .unwrap();
+        assert_eq!(bar.close_decimal(), Decimal::from_str("0.00000109").ok());
+        assert_eq!(
+            bar.volume_decimal(),
+            Decimal::from_str("123456789012.5").ok()
+        );
+    }
+}
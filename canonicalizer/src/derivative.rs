@@ -0,0 +1,248 @@
+//! Canonical names of derivative contracts.
+//!
+//! Spot markets are named `BASE-QUOTE`. A derivative appends its contract to
+//! the pair it is priced in, so it never shares a name with the spot market:
+//!
+//! - perpetuals end in `PERP`: `BTC-USDT-PERP`
+//! - dated futures end in their expiry date: `BTC-USD-20250627`
+//! - options add strike and `C` or `P`: `BTC-USD-20250627-30000-C`
+//!
+//! [`DerivativeSymbol`] builds these with [`Display`](fmt::Display) and reads
+//! them back with [`FromStr`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{AssetClass, CanonicalError};
+
+/// Right granted by an option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+/// Contract part of a derivative name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Contract {
+    Perp,
+    /// Expiry as `YYYYMMDD`.
+    Future {
+        expiry: u32,
+    },
+    Option {
+        expiry: u32,
+        strike: f64,
+        right: OptionRight,
+    },
+}
+
+/// Parsed canonical derivative name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivativeSymbol {
+    pub base: String,
+    pub quote: String,
+    pub contract: Contract,
+}
+
+impl DerivativeSymbol {
+    /// Perpetual on the canonical `pair`, e.g. `BTC-USDT`.
+    pub fn perp(pair: &str) -> Option<Self> {
+        Self::new(pair, Contract::Perp)
+    }
+
+    /// Future on `pair` expiring on `expiry` (`YYYYMMDD`).
+    pub fn future(pair: &str, expiry: u32) -> Option<Self> {
+        Self::new(pair, Contract::Future { expiry })
+    }
+
+    /// Option on `pair` expiring on `expiry` (`YYYYMMDD`).
+    pub fn option(pair: &str, expiry: u32, strike: f64, right: OptionRight) -> Option<Self> {
+        Self::new(
+            pair,
+            Contract::Option {
+                expiry,
+                strike,
+                right,
+            },
+        )
+    }
+
+    fn new(pair: &str, contract: Contract) -> Option<Self> {
+        let (base, quote) = pair.split_once('-')?;
+        if base.is_empty() || quote.is_empty() || quote.contains('-') {
+            return None;
+        }
+        match contract {
+            Contract::Perp => {}
+            Contract::Future { expiry } => valid_date(expiry)?,
+            Contract::Option { expiry, strike, .. } => {
+                valid_date(expiry)?;
+                (strike.is_finite() && strike > 0.0).then_some(())?;
+            }
+        }
+        Some(Self {
+            base: base.to_uppercase(),
+            quote: quote.to_uppercase(),
+            contract,
+        })
+    }
+
+    /// The underlying `BASE-QUOTE` pair.
+    pub fn pair(&self) -> String {
+        format!("{}-{}", self.base, self.quote)
+    }
+
+    pub fn asset_class(&self) -> AssetClass {
+        match self.contract {
+            Contract::Perp => AssetClass::Perp,
+            Contract::Future { .. } => AssetClass::Future,
+            Contract::Option { .. } => AssetClass::Option,
+        }
+    }
+
+    /// Expiry as `YYYYMMDD`, if the contract expires.
+    pub fn expiry(&self) -> Option<u32> {
+        match self.contract {
+            Contract::Perp => None,
+            Contract::Future { expiry } | Contract::Option { expiry, .. } => Some(expiry),
+        }
+    }
+}
+
+fn valid_date(date: u32) -> Option<()> {
+    let (year, month, day) = (date / 10_000, date / 100 % 100, date % 100);
+    ((1970..=9999).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day))
+        .then_some(())
+}
+
+/// UTC date of a Unix timestamp in seconds, as `YYYYMMDD`.
+pub fn expiry_date(secs: i64) -> u32 {
+    // Civil-from-days conversion on the proleptic Gregorian calendar.
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year * 10_000 + month * 100 + day) as u32
+}
+
+impl fmt::Display for DerivativeSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-", self.base, self.quote)?;
+        match self.contract {
+            Contract::Perp => f.write_str("PERP"),
+            Contract::Future { expiry } => write!(f, "{expiry}"),
+            Contract::Option {
+                expiry,
+                strike,
+                right,
+            } => {
+                let right = match right {
+                    OptionRight::Call => 'C',
+                    OptionRight::Put => 'P',
+                };
+                write!(f, "{expiry}-{strike}-{right}")
+            }
+        }
+    }
+}
+
+impl FromStr for DerivativeSymbol {
+    type Err = CanonicalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || CanonicalError::Unparseable(s.to_string());
+        let upper = s.trim().to_uppercase();
+        let parts: Vec<&str> = upper.split('-').collect();
+        let date = |p: &str| {
+            (p.len() == 8)
+                .then(|| p.parse::<u32>().ok())
+                .flatten()
+                .ok_or_else(err)
+        };
+        let (pair, contract) = match parts.as_slice() {
+            [base, quote, "PERP"] => (format!("{base}-{quote}"), Contract::Perp),
+            [base, quote, expiry] => (
+                format!("{base}-{quote}"),
+                Contract::Future {
+                    expiry: date(expiry)?,
+                },
+            ),
+            [base, quote, expiry, strike, right] => (
+                format!("{base}-{quote}"),
+                Contract::Option {
+                    expiry: date(expiry)?,
+                    strike: strike.parse().map_err(|_| err())?,
+                    right: match *right {
+                        "C" => OptionRight::Call,
+                        "P" => OptionRight::Put,
+                        _ => return Err(err()),
+                    },
+                },
+            ),
+            _ => return Err(err()),
+        };
+        Self::new(&pair, contract).ok_or_else(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        let perp = DerivativeSymbol::perp("btc-usdt").unwrap();
+        assert_eq!(perp.to_string(), "BTC-USDT-PERP");
+        assert_eq!(perp.asset_class(), AssetClass::Perp);
+
+        let future = DerivativeSymbol::future("BTC-USD", 20250627).unwrap();
+        assert_eq!(future.to_string(), "BTC-USD-20250627");
+
+        let call = DerivativeSymbol::option("BTC-USD", 20250627, 30000.0, OptionRight::Call);
+        assert_eq!(call.unwrap().to_string(), "BTC-USD-20250627-30000-C");
+        let put = DerivativeSymbol::option("ETH-USD", 20250627, 2.5, OptionRight::Put);
+        assert_eq!(put.unwrap().to_string(), "ETH-USD-20250627-2.5-P");
+
+        for name in [
+            "BTC-USDT-PERP",
+            "BTC-USD-20250627",
+            "BTC-USD-20250627-30000-C",
+            "ETH-USD-20250627-2.5-P",
+        ] {
+            let parsed: DerivativeSymbol = name.parse().unwrap();
+            assert_eq!(parsed.to_string(), name);
+        }
+        let parsed: DerivativeSymbol = "btc-usd-20250627-30000-c".parse().unwrap();
+        assert_eq!(parsed.pair(), "BTC-USD");
+        assert_eq!(parsed.expiry(), Some(20250627));
+        assert_eq!(parsed.asset_class(), AssetClass::Option);
+    }
+
+    #[test]
+    fn spot_and_malformed_names_are_rejected() {
+        for name in [
+            "BTC-USDT",
+            "BTC-USD-2025",
+            "BTC-USD-20251327",
+            "BTC-USD-20250627-0-C",
+            "BTC-USD-20250627-30000-X",
+            "-USD-PERP",
+        ] {
+            assert!(name.parse::<DerivativeSymbol>().is_err(), "{name}");
+        }
+        assert!(DerivativeSymbol::perp("BTCUSDT").is_none());
+    }
+
+    #[test]
+    fn expiry_date_is_the_utc_calendar_day() {
+        assert_eq!(expiry_date(0), 19700101);
+        assert_eq!(expiry_date(1_751_011_200), 20250627);
+        assert_eq!(expiry_date(1_709_164_800 + 86_399), 20240229);
+    }
+}
@@ -0,0 +1,32 @@
+use thiserror::Error;
+
+/// Why a native symbol could not be canonicalized.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CanonicalError {
+    #[error("no adapter registered for exchange {0}")]
+    UnknownExchange(String),
+    #[error("no known quote asset in {0}")]
+    UnknownQuote(String),
+    #[error("empty base asset in {0}")]
+    EmptyBase(String),
+    #[error("unrecognised symbol {0}")]
+    Unparseable(String),
+    #[error("base asset of {0} ends in a quote asset")]
+    QuoteInBase(String),
+    #[error("{0} is not listed on the exchange")]
+    NotListed(String),
+}
+
+impl CanonicalError {
+    /// Short snake_case name of the variant, for metric labels.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::UnknownExchange(_) => "unknown_exchange",
+            Self::UnknownQuote(_) => "unknown_quote",
+            Self::EmptyBase(_) => "empty_base",
+            Self::Unparseable(_) => "unparseable",
+            Self::QuoteInBase(_) => "quote_in_base",
+            Self::NotListed(_) => "not_listed",
+        }
+    }
+}
@@ -1,4 +1,121 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::derivative::expiry_date;
+use crate::{AssetClass, DerivativeSymbol, L2Diff, OptionRight, Snapshot};
+
+/// Version of the event schemas in this module, stamped on every emitted
+/// event as `schema_version`. Bumped when a field changes meaning or is
+/// removed; new optional fields do not bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Delivery metadata the ingestor stamps on emitted events with `--envelope`,
+/// appended to the event's own JSON object after its existing fields.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Envelope<T> {
+    #[serde(flatten)]
+    pub event: T,
+    /// [`SCHEMA_VERSION`] of the producer.
+    pub schema_version: u32,
+    /// ULID unique to this event, for de-duplicating redelivered events.
+    pub event_id: String,
+    /// Time the ingestor emitted the event, in milliseconds.
+    pub ingest_ts: i64,
+    /// Per-source counter, starting at 1 when the ingestor starts, so
+    /// consumers of the full stream can spot dropped events. Sinks whose
+    /// routes filter events see gaps for the events they did not take.
+    pub source_seq: u64,
+}
+
+/// Trade print from an exchange.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trade {
+    /// Source exchange name.
+    pub agent: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Exchange trade id, where the venue assigns numeric ones.
+    #[serde(rename = "t", default)]
+    pub trade_id: Option<i64>,
+    /// Trade price as a string.
+    #[serde(rename = "p")]
+    pub price: String,
+    /// Trade quantity as a string.
+    #[serde(rename = "q")]
+    pub quantity: String,
+    /// Asset class, set on derivatives trades.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Clock skew against the exchange when the trade was received, in
+    /// milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skew: Option<i64>,
+    /// `"contracts"` when [`quantity`](Self::quantity) counts contracts
+    /// rather than units of the base asset, as on Binance COIN-M.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q_unit: Option<String>,
+    /// Face value of one contract in the quote currency, set alongside
+    /// `q_unit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_size: Option<String>,
+    /// Trade timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Best bid and ask of one market.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BookTicker {
+    /// Source exchange name.
+    pub agent: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Best bid price.
+    #[serde(rename = "bp")]
+    pub bid_price: String,
+    /// Quantity at the best bid.
+    #[serde(rename = "bq")]
+    pub bid_qty: String,
+    /// Best ask price.
+    #[serde(rename = "ap")]
+    pub ask_price: String,
+    /// Quantity at the best ask.
+    #[serde(rename = "aq")]
+    pub ask_qty: String,
+    /// Top-of-book or fair mid, added by the fair mid sink.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mid: Option<f64>,
+    /// `top` or `fair`, the kind of `mid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mid_kind: Option<String>,
+    /// Quote group the fair mid was consolidated over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_group: Option<String>,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Event timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
 
 /// Funding rate update from an exchange.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +128,19 @@ pub struct Funding {
     /// Funding rate as a string.
     #[serde(rename = "r")]
     pub rate: String,
+    /// Hours each funding payment covers, set by venues whose rate is not
+    /// the usual 8h one, e.g. `1` for Hyperliquid's hourly funding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub funding_interval_h: Option<u32>,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     /// Event timestamp in milliseconds.
     #[serde(rename = "ts")]
     pub timestamp: i64,
@@ -25,6 +155,15 @@ pub struct OpenInterest {
     /// Open interest quantity.
     #[serde(rename = "oi")]
     pub open_interest: String,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "ts")]
     pub timestamp: i64,
 }
@@ -38,6 +177,15 @@ pub struct TermStructure {
     /// Basis value or similar metric.
     #[serde(rename = "b")]
     pub basis: String,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "ts")]
     pub timestamp: i64,
 }
@@ -57,6 +205,15 @@ pub struct Liquidation {
     /// Side of the position being liquidated (BUY/SELL).
     #[serde(rename = "side")]
     pub side: String,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "ts")]
     pub timestamp: i64,
 }
@@ -90,6 +247,15 @@ pub struct Bar {
     /// Traded volume during the interval.
     #[serde(rename = "v")]
     pub volume: String,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     /// Start timestamp of the bar in milliseconds.
     #[serde(rename = "ts")]
     pub timestamp: i64,
@@ -147,6 +313,13 @@ pub struct OptionChain {
     pub r#type: String,
     /// Canonical underlying symbol (e.g. `BTC-USDT`).
     pub s: String,
+    /// Asset class tag, always [`AssetClass::Option`].
+    #[serde(default = "option_class")]
+    pub ac: AssetClass,
+    /// Settlement currency of the contracts, empty on chains captured before
+    /// it was recorded.
+    #[serde(default)]
+    pub settle: String,
     /// Expiration timestamp (seconds since Unix epoch).
     pub expiry: i64,
     /// Collection of option quotes at this expiry.
@@ -154,6 +327,29 @@ pub struct OptionChain {
     /// Implied volatility surface points for this chain.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub surface: Vec<OptionSurfacePoint>,
+    /// Snapshot time in milliseconds, set for historical (backfilled) chains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ts: Option<i64>,
+    /// Settlement price of the underlying index on the snapshot day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement_price: Option<f64>,
+}
+
+impl OptionChain {
+    /// Canonical name of one of the chain's contracts, e.g.
+    /// `BTC-USD-20250627-30000-C`.
+    pub fn contract(&self, quote: &OptionQuote) -> Option<DerivativeSymbol> {
+        let right = match quote.kind.as_str() {
+            "CALL" => OptionRight::Call,
+            "PUT" => OptionRight::Put,
+            _ => return None,
+        };
+        DerivativeSymbol::option(&self.s, expiry_date(self.expiry), quote.strike, right)
+    }
+}
+
+fn option_class() -> AssetClass {
+    AssetClass::Option
 }
 
 /// Order update representing state changes on an exchange.
@@ -246,6 +442,25 @@ pub struct Listing {
     /// Lot size or quantity increment.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lot_size: Option<String>,
+    /// Price increment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_size: Option<String>,
+    /// Event timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Removal of a previously tradable symbol from an exchange.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Delisting {
+    /// Source exchange name.
+    pub agent: String,
+    /// Event type, always `"delisting"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
     /// Event timestamp in milliseconds.
     #[serde(rename = "ts")]
     pub timestamp: i64,
@@ -280,16 +495,410 @@ pub struct FeeSchedule {
     pub timestamp: i64,
 }
 
+/// Change of a venue's position relative to its next funding settlement.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FundingWindow {
+    /// Venue settling funding.
+    pub agent: String,
+    /// Event type, always `"funding_window"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// `"pre"` before settlement, `"post"` after it, `"idle"` otherwise.
+    pub state: String,
+    /// Settlement time the window surrounds, or the next one when idle, in
+    /// milliseconds.
+    pub settlement_ts: i64,
+    /// Event timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Lead/lag report between two venues quoting the same symbol.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LeadLag {
+    /// Event type, always `"lead_lag"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Venue whose price moves came first.
+    pub leader: String,
+    /// Venue whose matching moves followed.
+    pub follower: String,
+    /// Price moves seen on the leader during the report window.
+    pub leader_moves: u64,
+    /// Leader moves followed by a same-direction follower move.
+    pub followed: u64,
+    /// Mean delay between leader and follower moves in milliseconds.
+    pub mean_lag_ms: f64,
+    /// Median delay between leader and follower moves in milliseconds.
+    pub median_lag_ms: i64,
+    /// Lag in milliseconds at which the cross-correlation of the leader's
+    /// mid returns with the follower's later ones peaks.
+    #[serde(default)]
+    pub xcorr_lag_ms: i64,
+    /// Cross-correlation at `xcorr_lag_ms`, between -1 and 1.
+    #[serde(default)]
+    pub xcorr: f64,
+    /// Quote group `symbol` is keyed by, when venues quoting different but
+    /// equivalent quote assets are compared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_group: Option<String>,
+    /// Asset class of the compared books, set on derivatives.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Report timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Resting quantity within one distance of the mid price.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DepthBand {
+    /// Distance from the mid in basis points.
+    pub bps: f64,
+    /// Bid quantity priced at most `bps` below the mid.
+    pub bid_qty: f64,
+    /// Ask quantity priced at most `bps` above the mid.
+    pub ask_qty: f64,
+    /// Value of `bid_qty` in the quote asset.
+    pub bid_notional: f64,
+    /// Value of `ask_qty` in the quote asset.
+    pub ask_notional: f64,
+}
+
+/// Cumulative order book depth in price bands around the mid.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DepthProfile {
+    /// Source exchange name.
+    pub agent: String,
+    /// Event type, always `"depth_profile"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Mid price of the book.
+    pub mid: f64,
+    /// Depth per band, narrowest first.
+    pub bands: Vec<DepthBand>,
+    /// Asset class, set on derivatives books.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Timestamp of the book in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// A trade, or a burst of same-side trades, larger than the recent trade size
+/// distribution of its venue and instrument.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LargePrint {
+    /// Venue the trades were printed on.
+    pub agent: String,
+    /// Event type, always `"large_print"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// `"trade"` for a single print, `"burst"` for several back-to-back ones.
+    pub kind: String,
+    /// Aggressor side inferred by the tick rule, `"buy"` or `"sell"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    /// Traded quantity in the base asset.
+    pub qty: f64,
+    /// Traded value in the quote asset.
+    pub notional: f64,
+    /// Trades making up the print.
+    pub trades: u64,
+    /// Distinct prices traded at.
+    pub levels: u64,
+    /// Whether the print walked through more than one price level.
+    pub swept: bool,
+    /// Size threshold the print exceeded.
+    pub threshold: f64,
+    /// Percentile of recent trade sizes the threshold was taken at.
+    pub percentile: f64,
+    /// Asset class, set on derivatives trades.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Timestamp of the last trade in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Official daily close or settlement price of one market.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettlementPrice {
+    /// Source exchange name.
+    pub agent: String,
+    /// Event type, always `"settlement_price"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Asset class of the priced market, `index` for index delivery prices.
+    pub ac: AssetClass,
+    /// `close` for a daily candle close, `delivery` for an index delivery price.
+    pub kind: String,
+    pub price: f64,
+    /// UTC trading day the price belongs to, `YYYY-MM-DD`.
+    pub date: String,
+    /// Time the price was fixed in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Suspicious trade prints on one venue and symbol over a report window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WashTradeSuspect {
+    /// Venue the trades were printed on.
+    pub agent: String,
+    /// Event type, always `"wash_trade_suspect"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Trades seen during the window.
+    pub trades: u64,
+    /// Trades matching a wash-trade pattern.
+    pub flagged: u64,
+    /// Share of the window's traded quantity that was flagged, 0 to 1.
+    pub score: f64,
+    /// Asset class, set on derivatives trades.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Report timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Per-exchange ingestion statistics for one reporting window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IngestStats {
+    /// Source exchange name.
+    pub agent: String,
+    /// Event type, always `"ingest_stats"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Length of the reporting window in seconds.
+    pub window_secs: u64,
+    /// Emitted events per event type.
+    pub messages: BTreeMap<String, u64>,
+    /// Messages that could not be parsed.
+    pub unparseable: u64,
+    /// Sequence gaps detected in the exchange streams.
+    pub gaps: u64,
+    /// Websocket reconnects.
+    pub reconnects: u64,
+    /// Symbols that could not be canonicalized, per reason.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unmapped: BTreeMap<String, u64>,
+    /// Order book diffs merged into a later one by conflation.
+    #[serde(default)]
+    pub conflated: u64,
+    /// Events dropped because a sink's queue was full or its write failed,
+    /// counted once per sink.
+    #[serde(default)]
+    pub drops: u64,
+    /// Report timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+/// Change of state of a per-event-type freshness SLO on one exchange.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SloAlert {
+    /// Exchange whose stream is stale.
+    pub agent: String,
+    /// Event type, always `"slo_alert"`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Event type the SLO applies to, e.g. `trade`.
+    pub event_type: String,
+    /// `firing` when the SLO is breached, `resolved` once events are fresh again.
+    pub state: String,
+    /// Time since the newest event of that type, in milliseconds.
+    pub lag_ms: i64,
+    /// Allowed lag in milliseconds.
+    pub slo_ms: u64,
+    /// Alert timestamp in milliseconds.
+    #[serde(rename = "ts")]
+    pub timestamp: i64,
+}
+
+macro_rules! events {
+    ($($(#[$doc:meta])* $variant:ident($ty:ty) = $tag:literal,)*) => {
+        /// Any line the ingestor emits, keyed by its `type` field.
+        ///
+        /// Most structs in this module carry their own `type`, so the enum is
+        /// not `#[serde(tag = "type")]`: serializing writes the event's fields
+        /// and adds `type` where the struct lacks one, and deserializing reads
+        /// `type` and parses the whole object as that variant. Types without
+        /// a struct, such as `rate_limit` or `build_info`, are kept as
+        /// [`Event::Other`] rather than dropped. Fields a struct does not
+        /// declare, such as the [`Envelope`] ones, are ignored; parse an
+        /// `Envelope<Event>` to keep those.
+        #[derive(Debug, Clone)]
+        pub enum Event {
+            $($(#[$doc])* $variant($ty),)*
+            /// Any other event, as read.
+            Other(Value),
+        }
+
+        impl Event {
+            /// The event's `type`, e.g. `trade`.
+            pub fn kind(&self) -> &str {
+                match self {
+                    $(Event::$variant(_) => $tag,)*
+                    Event::Other(v) => v.get("type").and_then(|t| t.as_str()).unwrap_or_default(),
+                }
+            }
+        }
+
+        impl Serialize for Event {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut v = match self {
+                    $(Event::$variant(e) => serde_json::to_value(e).map_err(S::Error::custom)?,)*
+                    Event::Other(v) => return v.serialize(serializer),
+                };
+                if let Value::Object(fields) = &mut v {
+                    fields
+                        .entry("type")
+                        .or_insert_with(|| self.kind().into());
+                }
+                v.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Event {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let v = Value::deserialize(deserializer)?;
+                let kind = v
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .ok_or_else(|| D::Error::missing_field("type"))?;
+                match kind {
+                    $($tag => serde_json::from_value(v).map(Event::$variant).map_err(D::Error::custom),)*
+                    _ => Ok(Event::Other(v)),
+                }
+            }
+        }
+    };
+}
+
+events! {
+    Trade(Trade) = "trade",
+    L2Diff(L2Diff) = "l2_diff",
+    Snapshot(Snapshot) = "snapshot",
+    BookTicker(BookTicker) = "book_ticker",
+    Funding(Funding) = "funding",
+    OpenInterest(OpenInterest) = "open_interest",
+    /// Futures basis, emitted as `term`.
+    TermStructure(TermStructure) = "term",
+    Liquidation(Liquidation) = "liquidation",
+    /// Candle, emitted as `ohlcv`.
+    Bar(Bar) = "ohlcv",
+    OptionChain(OptionChain) = "option_chain",
+    Order(Order) = "order",
+    Fill(Fill) = "fill",
+    Position(Position) = "position",
+    Listing(Listing) = "listing",
+    Delisting(Delisting) = "delisting",
+    FeeSchedule(FeeSchedule) = "fee_schedule",
+    FundingWindow(FundingWindow) = "funding_window",
+    LeadLag(LeadLag) = "lead_lag",
+    DepthProfile(DepthProfile) = "depth_profile",
+    LargePrint(LargePrint) = "large_print",
+    SettlementPrice(SettlementPrice) = "settlement_price",
+    WashTradeSuspect(WashTradeSuspect) = "wash_trade_suspect",
+    IngestStats(IngestStats) = "ingest_stats",
+    SloAlert(SloAlert) = "slo_alert",
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn events_round_trip_by_type() {
+        let lines = [
+            r#"{"agent":"binance","type":"trade","s":"BTC-USDT","t":42,"p":"100.5","q":"0.1","skew":3,"ts":1700000000000}"#,
+            r#"{"agent":"binance","type":"trade","s":"BTC-USDT","t":7,"p":"100","q":"2","ac":"perp","settle":"USDT","id":"BTC-USDT-PERP","skew":0,"ts":1}"#,
+            r#"{"agent":"coinbase","type":"l2_diff","s":"BTC-USD","bids":[["100","1"]],"asks":[],"ts":2}"#,
+            r#"{"agent":"coinbase","type":"snapshot","s":"BTC-USD","bids":[],"asks":[["101","3"]],"ts":3}"#,
+            r#"{"agent":"okx","type":"book_ticker","s":"ETH-USDT","bp":"9","bq":"1","ap":"10","aq":"2","mid":9.5,"mid_kind":"top","ts":4}"#,
+            r#"{"agent":"bybit","type":"funding","s":"BTC-USDT","r":"0.0001","ts":5}"#,
+            r#"{"agent":"bybit","type":"open_interest","s":"BTC-USDT","oi":"12.5","ts":6}"#,
+            r#"{"agent":"binance","type":"term","s":"BTC-USDT","b":"0.01","ts":7}"#,
+            r#"{"agent":"binance","type":"liquidation","s":"BTC-USDT","p":"99","q":"1","side":"SELL","ts":8}"#,
+            r#"{"agent":"binance","type":"ohlcv","s":"BTC-USDT","i":60,"o":"1","h":"2","l":"0.5","c":"1.5","v":"10","ts":9}"#,
+            r#"{"agent":"sim","type":"order","s":"BTC-USDT","id":"o1","side":"BUY","st":"NEW","p":"100","q":"1","ts":10}"#,
+            r#"{"agent":"sim","type":"fill","s":"BTC-USDT","oid":"o1","tid":"t1","p":"100","q":"1","ts":11}"#,
+            r#"{"agent":"sim","type":"position","s":"BTC","f":"1","l":"0","ts":12}"#,
+            r#"{"agent":"binance","type":"rate_limit","used":5}"#,
+        ];
+        for line in lines {
+            let event: Event = serde_json::from_str(line).expect(line);
+            let expected: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(event.kind(), expected["type"]);
+            assert_eq!(serde_json::to_value(&event).unwrap(), expected, "{line}");
+        }
+
+        let trade: Event = serde_json::from_str(lines[0]).unwrap();
+        assert!(matches!(
+            trade,
+            Event::Trade(Trade {
+                trade_id: Some(42),
+                ..
+            })
+        ));
+        assert!(matches!(
+            serde_json::from_str(lines[13]).unwrap(),
+            Event::Other(_)
+        ));
+        assert!(serde_json::from_str::<Event>(r#"{"type":"trade","s":"X"}"#).is_err());
+        assert!(serde_json::from_str::<Event>(r#"{"s":"X"}"#).is_err());
+
+        let enveloped: Envelope<Event> = serde_json::from_str(
+            r#"{"agent":"bybit","type":"funding","s":"BTC-USDT","r":"0.0001","ts":5,"schema_version":1,"event_id":"01H","ingest_ts":6,"source_seq":1}"#,
+        )
+        .unwrap();
+        assert_eq!(enveloped.event.kind(), "funding");
+        assert_eq!(enveloped.source_seq, 1);
+    }
+
     #[test]
     fn option_chain_serialises() {
         let chain = OptionChain {
             agent: "binance".into(),
             r#type: "option_chain".into(),
             s: "BTC-USD".into(),
+            ac: AssetClass::Option,
+            settle: "USD".into(),
             expiry: 1_700_000_000,
             options: vec![OptionQuote {
                 strike: 30000.0,
@@ -310,10 +919,23 @@ mod tests {
                 expiry: 1_700_000_000,
                 iv: 0.55,
             }],
+            ts: None,
+            settlement_price: None,
         };
 
         let json = serde_json::to_string(&chain).expect("serialize");
         let back: OptionChain = serde_json::from_str(&json).expect("deserialize");
         assert_eq!(back, chain);
+        assert_eq!(
+            chain.contract(&chain.options[0]).unwrap().to_string(),
+            "BTC-USD-20231114-30000-C"
+        );
+
+        // Chains captured before `ac` and `settle` were added still parse.
+        let old = r#"{"agent":"binance","type":"option_chain","s":"BTC-USD","expiry":1700000000,"options":[]}"#;
+        let Event::OptionChain(old) = serde_json::from_str::<Event>(old).unwrap() else {
+            panic!("expected an option chain");
+        };
+        assert_eq!((old.ac, old.settle.as_str()), (AssetClass::Option, ""));
     }
 }
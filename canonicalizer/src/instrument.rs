@@ -0,0 +1,122 @@
+//! Contract-type aware instrument keys.
+//!
+//! A canonical `BASE-QUOTE` symbol alone does not identify an instrument: spot
+//! `BTC-USDT` and the USDT-margined perpetual `BTC-USDT` share it. Events for
+//! non-spot instruments therefore carry an asset class tag (`ac`) and their
+//! settlement currency (`settle`), and [`InstrumentKey`] combines all three for
+//! use as a map key. Derivative events also carry an unambiguous `id` in the
+//! [`DerivativeSymbol`](crate::DerivativeSymbol) scheme, e.g. `BTC-USDT-PERP`.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DerivativeSymbol, Symbol};
+
+/// Contract type an instrument trades as.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetClass {
+    #[default]
+    Spot,
+    Perp,
+    /// Dated future.
+    Future,
+    Option,
+    Index,
+}
+
+impl AssetClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AssetClass::Spot => "spot",
+            AssetClass::Perp => "perp",
+            AssetClass::Future => "future",
+            AssetClass::Option => "option",
+            AssetClass::Index => "index",
+        }
+    }
+}
+
+impl fmt::Display for AssetClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Symbol, asset class and settlement currency identifying one instrument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct InstrumentKey {
+    #[serde(rename = "s")]
+    pub symbol: Symbol,
+    #[serde(rename = "ac")]
+    pub class: AssetClass,
+    pub settle: Symbol,
+}
+
+impl InstrumentKey {
+    /// Build a key, settling in the quote asset when `settle` is not given.
+    pub fn new(symbol: Symbol, class: AssetClass, settle: Option<&str>) -> Self {
+        let settle = match settle {
+            Some(s) => Symbol::intern(&s.to_uppercase()),
+            None => Symbol::intern(symbol.split_once('-').map_or(&*symbol, |(_, q)| q)),
+        };
+        Self {
+            symbol,
+            class,
+            settle,
+        }
+    }
+
+    /// Key for an emitted JSON event. Events without an `ac` tag are spot and
+    /// events without `settle` settle in their quote asset.
+    pub fn from_event(v: &serde_json::Value) -> Option<Self> {
+        let symbol = Symbol::intern(v.get("s")?.as_str()?);
+        let class = v
+            .get("ac")
+            .and_then(|c| AssetClass::deserialize(c).ok())
+            .unwrap_or_default();
+        let settle = v.get("settle").and_then(|s| s.as_str());
+        Some(Self::new(symbol, class, settle))
+    }
+
+    /// Canonical derivative name of a perpetual or dated future, whose symbol
+    /// already ends in its `YYYYMMDD` expiry.
+    pub fn id(&self) -> Option<DerivativeSymbol> {
+        match self.class {
+            AssetClass::Perp => DerivativeSymbol::perp(&self.symbol),
+            AssetClass::Future => self.symbol.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InstrumentKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.symbol, self.class, self.settle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_and_perp_keys_differ() {
+        let spot =
+            InstrumentKey::from_event(&serde_json::json!({"s": "BTC-USDT", "type": "trade"}))
+                .unwrap();
+        let perp = InstrumentKey::from_event(
+            &serde_json::json!({"s": "BTC-USDT", "type": "mark_price", "ac": "perp", "settle": "USDT"}),
+        )
+        .unwrap();
+        assert_eq!(spot.class, AssetClass::Spot);
+        assert_eq!(spot.settle.as_str(), "USDT");
+        assert_ne!(spot, perp);
+        assert_eq!(perp.to_string(), "BTC-USDT:perp:USDT");
+        assert_eq!(perp.id().unwrap().to_string(), "BTC-USDT-PERP");
+        assert!(spot.id().is_none());
+    }
+}
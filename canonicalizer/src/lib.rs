@@ -14,155 +14,321 @@
 //! (`1`, `true`, `yes`). Disabling certificate verification is strongly
 //! discouraged for production use.
 //!
-//! Additional exchanges can be supported by extending
-//! [`CanonicalService::canonical_pair`].
+//! Each exchange's rules live in an [`ExchangeAdapter`]; additional venues
+//! can be supported by registering one with
+//! [`CanonicalService::register_adapter`]. Individual mis-mapped listings can be
+//! corrected without code changes through an [`overrides`] file, loaded from
+//! the path in the `CANONICAL_OVERRIDES` environment variable.
+//!
+//! Perpetuals, dated futures and options have their own names built and
+//! parsed by [`DerivativeSymbol`], e.g. `BTC-USDT-PERP`.
+//!
+//! Tick sizes, lot sizes and trading status of each market are kept in an
+//! [`InstrumentRegistry`], filled from exchange metadata with
+//! [`CanonicalService::update_instruments`]. In validation mode, turned on
+//! with [`CanonicalService::set_validation`] or the `CANONICAL_VALIDATE`
+//! environment variable, pairs are checked against those listings.
+//!
+//! [`process_line`] canonicalizes one JSON event line in process, the same
+//! rewrite the `canonicalizer --json` binary applies to its stdin.
+//!
+//! [`QuoteGroups`] let cross-venue analytics treat quote assets such as `USD`,
+//! `USDT` and `USDC` as one.
+//!
+//! With the `decimal` feature, the [`decimal`] module reads event prices and
+//! quantities as exact `rust_decimal::Decimal` values instead of `f64`.
+//!
+//! With the `proto` feature, the [`proto`] module encodes event lines as the
+//! protobuf messages of `proto/events.proto`.
 
+pub mod adapter;
+#[cfg(feature = "decimal")]
+pub mod decimal;
+pub mod derivative;
+pub mod error;
 pub mod events;
 mod http_client;
+pub mod instrument;
+pub mod overrides;
+#[cfg(feature = "proto")]
+pub mod proto;
+pub mod quote_group;
+pub mod registry;
+pub mod symbol;
 
+pub use adapter::ExchangeAdapter;
+pub use derivative::{Contract, DerivativeSymbol, OptionRight};
+pub use error::CanonicalError;
 pub use events::{
-    Bar, FeeSchedule, FeeTier, Fill, Listing, OptionChain, OptionGreeks, OptionQuote,
-    OptionSurfacePoint, Order, Position,
+    Bar, BookTicker, Delisting, DepthBand, DepthProfile, Envelope, Event, FeeSchedule, FeeTier,
+    Fill, FundingWindow, IngestStats, LargePrint, LeadLag, Listing, OptionChain, OptionGreeks,
+    OptionQuote, OptionSurfacePoint, Order, Position, SettlementPrice, SloAlert, Trade,
+    WashTradeSuspect, SCHEMA_VERSION,
 };
+pub use instrument::{AssetClass, InstrumentKey};
+pub use overrides::SymbolOverrides;
+pub use quote_group::{QuoteGroup, QuoteGroups};
+pub use registry::{InstrumentInfo, InstrumentRegistry, InstrumentStatus};
+pub use symbol::Symbol;
 
-use std::collections::HashSet;
-use std::sync::OnceLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
 pub struct CanonicalService;
 
-/// Cached list of Binance quote assets. Populated at startup via [`init`].
-static BINANCE_QUOTES: OnceLock<Vec<String>> = OnceLock::new();
+/// Operator-provided mapping overrides. Set via [`CanonicalService::load_overrides`].
+static OVERRIDES: OnceLock<SymbolOverrides> = OnceLock::new();
+
+/// Memoised results of [`CanonicalService::canonical_symbol`] keyed by
+/// exchange and native pair.
+type SymbolCache = RwLock<HashMap<String, HashMap<String, Option<Symbol>>>>;
+static SYMBOL_CACHE: OnceLock<SymbolCache> = OnceLock::new();
+
+/// Trading rules per instrument. Filled via [`CanonicalService::update_instruments`].
+static INSTRUMENTS: OnceLock<RwLock<InstrumentRegistry>> = OnceLock::new();
+
+/// Whether pairs are checked against listings. Set via [`CanonicalService::set_validation`].
+static VALIDATE: AtomicBool = AtomicBool::new(false);
 
 impl CanonicalService {
-    /// Initialise any resources required by the service. Currently this loads
-    /// the list of Binance quote assets from the public `exchangeInfo` endpoint
-    /// (unless provided via the `BINANCE_QUOTES` environment variable).
+    /// Initialise any resources required by the service: the overrides file
+    /// named by `CANONICAL_OVERRIDES`, validation mode if `CANONICAL_VALIDATE`
+    /// is truthy, and the metadata of every registered [`ExchangeAdapter`],
+    /// such as the Binance quote asset list.
     ///
-    /// Network errors are logged and fall back to a small built-in list.
+    /// Network errors are logged and adapters fall back to built-in defaults.
     pub async fn init() {
-        if BINANCE_QUOTES.get().is_some() {
-            return;
+        if let Ok(v) = std::env::var("CANONICAL_VALIDATE") {
+            if matches!(v.to_lowercase().as_str(), "1" | "true" | "yes") {
+                Self::set_validation(true);
+            }
+        }
+        if let Ok(path) = std::env::var("CANONICAL_OVERRIDES") {
+            if let Err(e) = Self::load_overrides(&path) {
+                warn!("failed to load symbol overrides from {}: {}", path, e);
+            }
         }
 
-        if let Ok(env) = std::env::var("BINANCE_QUOTES") {
-            let quotes = Self::parse_env_quotes(&env);
-            let _ = BINANCE_QUOTES.set(quotes);
-            return;
+        let adapters: Vec<Arc<dyn ExchangeAdapter>> = adapter::registry()
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        for adapter in adapters {
+            adapter.refresh_metadata().await;
         }
+    }
 
-        match Self::fetch_binance_quotes().await {
-            Ok(quotes) if !quotes.is_empty() => {
-                let _ = BINANCE_QUOTES.set(quotes);
-            }
-            Ok(_) => {
-                let _ = BINANCE_QUOTES.set(Self::default_binance_quotes());
-            }
-            Err(e) => {
-                warn!("failed to fetch Binance quotes: {}", e);
-                let _ = BINANCE_QUOTES.set(Self::default_binance_quotes());
-            }
+    /// Register `adapter` for `exchange`, replacing any existing one. Cached
+    /// [`canonical_symbol`](Self::canonical_symbol) results for the exchange
+    /// are dropped so the new rules apply immediately.
+    pub fn register_adapter(exchange: &str, adapter: Arc<dyn ExchangeAdapter>) {
+        let exchange = exchange.to_lowercase();
+        if let Some(cache) = SYMBOL_CACHE.get() {
+            cache.write().unwrap().remove(&exchange);
         }
+        adapter::registry()
+            .write()
+            .unwrap()
+            .insert(exchange, adapter);
+    }
+
+    /// The adapter registered for `exchange`, if any.
+    pub fn adapter(exchange: &str) -> Option<Arc<dyn ExchangeAdapter>> {
+        adapter::registry()
+            .read()
+            .unwrap()
+            .get(&exchange.to_lowercase())
+            .cloned()
     }
 
     /// Convert `pair` as used by `exchange` into the canonical `BASE-QUOTE`
     /// representation. Returns `None` if the exchange is unknown or the pair
-    /// cannot be parsed.
+    /// cannot be parsed; see [`try_canonical_pair`](Self::try_canonical_pair)
+    /// for the reason.
     pub fn canonical_pair(exchange: &str, pair: &str) -> Option<String> {
-        match exchange.to_lowercase().as_str() {
-            "binance" => Self::canonicalize_binance(pair),
-            "coinbase" => Some(Self::canonicalize_coinbase(pair)),
-            _ => None,
-        }
+        Self::try_canonical_pair(exchange, pair).ok()
     }
 
-    fn binance_quotes() -> &'static Vec<String> {
-        BINANCE_QUOTES.get_or_init(Self::default_binance_quotes)
-    }
-
-    async fn fetch_binance_quotes() -> Result<Vec<String>, reqwest::Error> {
-        let client = http_client::builder().build()?;
-        let v: serde_json::Value = client
-            .get("https://api.binance.us/api/v3/exchangeInfo")
-            .send()
-            .await?
-            .json()
-            .await?;
-        let mut set = HashSet::new();
-        if let Some(symbols) = v.get("symbols").and_then(|s| s.as_array()) {
-            for sym in symbols {
-                if let Some(q) = sym.get("quoteAsset").and_then(|q| q.as_str()) {
-                    set.insert(q.to_lowercase());
-                }
+    /// Like [`canonical_pair`](Self::canonical_pair), but reports why a pair
+    /// could not be mapped.
+    ///
+    /// Pinned symbols from the overrides file take precedence over the
+    /// exchange's adapter, whose results then have asset aliases applied. In
+    /// validation mode, adapter results are then checked with
+    /// [`InstrumentRegistry::validate`]. Wrapped assets are unwrapped last,
+    /// on venues the overrides opt in, unless that names a listed market.
+    pub fn try_canonical_pair(exchange: &str, pair: &str) -> Result<String, CanonicalError> {
+        if let Some(pinned) = OVERRIDES.get().and_then(|o| o.lookup(exchange, pair)) {
+            return Ok(pinned.to_string());
+        }
+        let canon = Self::map_pair(exchange, pair)?;
+        if VALIDATE.load(Ordering::Relaxed) {
+            if let Some(registry) = INSTRUMENTS.get() {
+                registry.read().unwrap().validate(exchange, &canon)?;
+            } else {
+                InstrumentRegistry::default().validate(exchange, &canon)?;
             }
         }
-        let mut quotes: Vec<String> = set.into_iter().collect();
-        quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
-        Ok(quotes)
+        Ok(match OVERRIDES.get() {
+            Some(o) => o.unwrap(exchange, canon, |s| Self::instrument(exchange, s).is_some()),
+            None => canon,
+        })
     }
 
-    fn parse_env_quotes(env: &str) -> Vec<String> {
-        let mut quotes: Vec<String> = env
-            .split(',')
-            .map(|s| s.trim().to_lowercase())
-            .filter(|s| !s.is_empty())
-            .collect();
-        quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
-        quotes
-    }
-
-    fn default_binance_quotes() -> Vec<String> {
-        const DEFAULT: [&str; 7] = ["usdt", "usdc", "busd", "usd", "btc", "eth", "bnb"];
-        let mut quotes: Vec<String> = DEFAULT.iter().map(|q| q.to_string()).collect();
-        quotes.sort_by_key(|s| std::cmp::Reverse(s.len()));
-        quotes
-    }
-
-    fn canonicalize_binance(symbol: &str) -> Option<String> {
-        let lower = symbol.to_lowercase();
-        for q in Self::binance_quotes() {
-            if lower.ends_with(q) {
-                let base = &lower[..lower.len() - q.len()];
-                if base.is_empty() {
-                    return None;
-                }
-                return Some(format!("{}-{}", base.to_uppercase(), q.to_uppercase()));
-            }
+    /// Adapter result for `pair` with asset aliases applied, unvalidated.
+    pub(crate) fn map_pair(exchange: &str, pair: &str) -> Result<String, CanonicalError> {
+        let adapter = Self::adapter(exchange)
+            .ok_or_else(|| CanonicalError::UnknownExchange(exchange.to_string()))?;
+        let canon = adapter.try_canonicalize(pair)?;
+        Ok(match OVERRIDES.get() {
+            Some(o) => o.alias(exchange, canon),
+            None => canon,
+        })
+    }
+
+    /// Turn validation of canonicalized pairs against exchange listings on
+    /// or off. Pairs failing it are reported as unmapped with
+    /// [`CanonicalError::QuoteInBase`] or [`CanonicalError::NotListed`].
+    pub fn set_validation(on: bool) {
+        VALIDATE.store(on, Ordering::Relaxed);
+        if let Some(cache) = SYMBOL_CACHE.get() {
+            cache.write().unwrap().clear();
         }
-        None
     }
 
-    fn canonicalize_coinbase(symbol: &str) -> String {
-        let lower = symbol.to_lowercase().replace('_', "-");
+    /// Convert a canonical symbol into `exchange`'s native form. Asset aliases
+    /// from the overrides file are not reversed.
+    pub fn denormalize(exchange: &str, canonical: &str) -> Option<String> {
+        Self::adapter(exchange)?.denormalize(canonical)
+    }
 
-        if let Some((base, quote)) = lower.split_once('-') {
-            return format!("{}-{}", base.to_uppercase(), quote.to_uppercase());
+    /// `exchange`'s native symbol for the canonical `canonical`, the inverse
+    /// of [`canonical_pair`](Self::canonical_pair), e.g. `BTC-USDT` to
+    /// `BTCUSDT` on Binance or `BTC-USD` to `BTC-USD` on Coinbase.
+    ///
+    /// Pinned symbols from the overrides file are reversed first, then the
+    /// native name recorded in the [`InstrumentRegistry`], which also undoes
+    /// asset aliases. Otherwise the adapter's
+    /// [`denormalize`](ExchangeAdapter::denormalize) rules apply.
+    pub fn native_pair(exchange: &str, canonical: &str) -> Option<String> {
+        if let Some(native) = OVERRIDES.get().and_then(|o| o.native(exchange, canonical)) {
+            return Some(native.to_string());
+        }
+        if let Some(info) = Self::instrument(exchange, &canonical.to_uppercase()) {
+            return Some(info.native);
         }
+        Self::denormalize(exchange, canonical)
+    }
 
-        // Attempt to detect a known quote asset when no separator is present.
-        const QUOTES: [&str; 6] = ["usdt", "usdc", "usd", "btc", "eth", "eur"];
-        for q in QUOTES {
-            if lower.ends_with(q) {
-                let base = &lower[..lower.len() - q.len()];
-                if !base.is_empty() {
-                    return format!("{}-{}", base.to_uppercase(), q.to_uppercase());
-                }
-            }
+    /// Load symbol overrides from the JSON, TOML or YAML file at `path`. Only the first
+    /// successful load takes effect; call before any symbols are resolved.
+    pub fn load_overrides(path: &str) -> std::io::Result<()> {
+        if OVERRIDES.get().is_some() {
+            return Ok(());
         }
+        let overrides = SymbolOverrides::from_path(path)?;
+        let _ = OVERRIDES.set(overrides);
+        Ok(())
+    }
+
+    /// Like [`canonical_pair`](Self::canonical_pair) but returns an interned
+    /// [`Symbol`] and memoises the result, so repeated lookups for the same
+    /// native pair neither re-parse nor allocate.
+    ///
+    /// Results are cached for the lifetime of the process; call [`init`](Self::init)
+    /// first so Binance pairs are parsed against the full quote list.
+    pub fn canonical_symbol(exchange: &str, pair: &str) -> Option<Symbol> {
+        let cache = SYMBOL_CACHE.get_or_init(|| RwLock::new(HashMap::new()));
+        if let Some(hit) = cache
+            .read()
+            .unwrap()
+            .get(exchange)
+            .and_then(|m| m.get(pair))
+        {
+            return hit.clone();
+        }
+        let sym = Self::canonical_pair(exchange, pair).map(|c| Symbol::intern(&c));
+        cache
+            .write()
+            .unwrap()
+            .entry(exchange.to_string())
+            .or_default()
+            .insert(pair.to_string(), sym.clone());
+        sym
+    }
+
+    /// Trading rules for the canonical `symbol` on `exchange`, if loaded.
+    pub fn instrument(exchange: &str, symbol: &str) -> Option<InstrumentInfo> {
+        INSTRUMENTS
+            .get()?
+            .read()
+            .unwrap()
+            .get(exchange, symbol)
+            .cloned()
+    }
 
-        lower.to_uppercase()
+    /// Modify the process-wide [`InstrumentRegistry`], e.g. to load a fresh
+    /// metadata response.
+    ///
+    /// In validation mode, or when wrapped assets are unwrapped, memoised
+    /// symbols are dropped so they are checked against the new listings.
+    pub fn update_instruments<R>(f: impl FnOnce(&mut InstrumentRegistry) -> R) -> R {
+        let registry = INSTRUMENTS.get_or_init(Default::default);
+        let result = f(&mut registry.write().unwrap());
+        let unwraps = OVERRIDES.get().is_some_and(|o| !o.unwrap_assets.is_empty());
+        if VALIDATE.load(Ordering::Relaxed) || unwraps {
+            if let Some(cache) = SYMBOL_CACHE.get() {
+                cache.write().unwrap().clear();
+            }
+        }
+        result
     }
 
     #[cfg(test)]
     pub fn set_binance_quotes(quotes: Vec<&str>) {
-        let mut qs: Vec<String> = quotes.into_iter().map(|s| s.to_lowercase()).collect();
-        qs.sort_by(|a, b| b.len().cmp(&a.len()));
-        let _ = BINANCE_QUOTES.set(qs);
+        adapter::set_binance_quotes(quotes);
+    }
+}
+
+/// Canonicalize one JSON event line, as the `canonicalizer --json` binary
+/// does: the `s` field is rewritten to the canonical pair of the event's
+/// `agent` and exchange. Lines that are not JSON, or whose symbol cannot be
+/// mapped, are returned unchanged. Blank lines give `None`.
+///
+/// Call [`CanonicalService::init`] first so the adapters have their metadata.
+pub fn process_line(line: &str) -> Option<String> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let Ok(mut v) = serde_json::from_str::<serde_json::Value>(line) else {
+        return Some(line.to_string());
+    };
+    let canon = match (v.get("agent"), v.get("s")) {
+        (Some(serde_json::Value::String(exchange)), Some(serde_json::Value::String(pair))) => {
+            CanonicalService::canonical_pair(exchange, pair)
+        }
+        _ => None,
+    };
+    match canon {
+        Some(canon) => {
+            v["s"] = serde_json::Value::String(canon);
+            Some(v.to_string())
+        }
+        None => Some(line.to_string()),
     }
 }
 
+/// [`process_line`] over a batch of lines, dropping blank ones.
+pub fn process_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    lines.into_iter().filter_map(process_line).collect()
+}
+
 /// Canonical representation of an incremental level-2 order book update.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct L2Diff {
@@ -173,6 +339,15 @@ pub struct L2Diff {
     pub symbol: String,
     pub bids: Vec<[String; 2]>,
     pub asks: Vec<[String; 2]>,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "ts")]
     pub timestamp: i64,
 }
@@ -193,6 +368,9 @@ impl L2Diff {
             symbol: sym,
             bids,
             asks,
+            ac: None,
+            settle: None,
+            id: None,
             timestamp: ts,
         }
     }
@@ -212,6 +390,15 @@ pub struct Snapshot {
     pub symbol: String,
     pub bids: Vec<[String; 2]>,
     pub asks: Vec<[String; 2]>,
+    /// Asset class, set on derivatives events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac: Option<AssetClass>,
+    /// Settlement currency of a derivatives contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle: Option<String>,
+    /// Contract name, e.g. `BTC-USDT-PERP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "ts")]
     pub timestamp: i64,
 }
@@ -232,6 +419,9 @@ impl Snapshot {
             symbol: sym,
             bids,
             asks,
+            ac: None,
+            settle: None,
+            id: None,
             timestamp: ts,
         }
     }
@@ -243,7 +433,8 @@ impl Snapshot {
 
 #[cfg(test)]
 mod tests {
-    use super::CanonicalService;
+    use super::{process_line, process_lines, CanonicalError, CanonicalService, ExchangeAdapter};
+    use std::sync::Arc;
     use std::sync::Once;
 
     fn setup() {
@@ -290,8 +481,203 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bybit_symbols_share_binance_quote_parsing() {
+        setup();
+        assert_eq!(
+            CanonicalService::canonical_pair("bybit", "BTCUSDT"),
+            Some("BTC-USDT".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("mexc", "PEPEUSDT"),
+            Some("PEPE-USDT".to_string())
+        );
+    }
+
+    #[test]
+    fn okx_instruments_are_canonicalized() {
+        assert_eq!(
+            CanonicalService::canonical_pair("okx", "BTC-USDT"),
+            Some("BTC-USDT".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("okx", "eth-usdc-swap"),
+            Some("ETH-USDC".to_string())
+        );
+        assert_eq!(CanonicalService::canonical_pair("okx", "BTCUSDT"), None);
+        assert_eq!(
+            CanonicalService::canonical_pair("kucoin", "SOL-USDT"),
+            Some("SOL-USDT".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("gate", "btc_usdt"),
+            Some("BTC-USDT".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("hyperliquid", "kPEPE"),
+            Some("kPEPE-USD".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("hyperliquid", "BTC-USD"),
+            None
+        );
+    }
+
+    #[test]
+    fn binance_coin_margined_futures_are_quoted_in_usd() {
+        assert_eq!(
+            CanonicalService::canonical_pair("binance", "BTCUSD_PERP"),
+            Some("BTC-USD".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("binance", "ethusd_241227"),
+            Some("ETH-USD-20241227".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("binance", "BTCUSDT_250627"),
+            Some("BTC-USDT-20250627".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("binance", "BTCUSDT_PERP"),
+            None
+        );
+    }
+
+    #[test]
+    fn failures_carry_their_reason() {
+        setup();
+        assert_eq!(
+            CanonicalService::try_canonical_pair("nowhere", "btcusdt"),
+            Err(CanonicalError::UnknownExchange("nowhere".into()))
+        );
+        assert_eq!(
+            CanonicalService::try_canonical_pair("binance", "btcxyz"),
+            Err(CanonicalError::UnknownQuote("btcxyz".into()))
+        );
+        assert_eq!(
+            CanonicalService::try_canonical_pair("binance", "usdt"),
+            Err(CanonicalError::EmptyBase("usdt".into()))
+        );
+        let err = CanonicalService::try_canonical_pair("okx", "BTC").unwrap_err();
+        assert_eq!(err.kind(), "unknown_quote");
+        assert_eq!(
+            CanonicalService::try_canonical_pair("hyperliquid", "BTC-USD").map_err(|e| e.kind()),
+            Err("unparseable")
+        );
+    }
+
+    #[test]
+    fn custom_venues_register_adapters() {
+        struct Slash;
+        impl ExchangeAdapter for Slash {
+            fn canonicalize(&self, pair: &str) -> Option<String> {
+                let (base, quote) = pair.split_once('/')?;
+                Some(format!("{base}-{quote}").to_uppercase())
+            }
+            fn denormalize(&self, canonical: &str) -> Option<String> {
+                Some(canonical.replace('-', "/"))
+            }
+        }
+
+        assert_eq!(CanonicalService::canonical_pair("slashex", "BTC/USD"), None);
+        CanonicalService::register_adapter("SlashEx", Arc::new(Slash));
+        assert_eq!(
+            CanonicalService::canonical_pair("slashex", "btc/usd"),
+            Some("BTC-USD".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_symbol("slashex", "eth/usd").as_deref(),
+            Some("ETH-USD")
+        );
+        assert_eq!(
+            CanonicalService::denormalize("slashex", "ETH-USD"),
+            Some("ETH/USD".to_string())
+        );
+        assert_eq!(
+            CanonicalService::denormalize("deribit", "BTC-USD-20241227"),
+            Some("BTC-27DEC24".to_string())
+        );
+    }
+
+    #[test]
+    fn deribit_futures_encode_their_expiry() {
+        assert_eq!(
+            CanonicalService::canonical_pair("deribit", "BTC-PERPETUAL"),
+            Some("BTC-USD".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("deribit", "ETH_USDC-PERPETUAL"),
+            Some("ETH-USDC".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("deribit", "BTC-27DEC24"),
+            Some("BTC-USD-20241227".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("deribit", "ETH-7MAR25"),
+            Some("ETH-USD-20250307".to_string())
+        );
+        assert_eq!(
+            CanonicalService::canonical_pair("deribit", "BTC-27SEP24-60000-C"),
+            None
+        );
+    }
+
+    #[test]
+    fn canonical_symbol_is_cached_and_interned() {
+        setup();
+        let a = CanonicalService::canonical_symbol("binance", "btcusdt").unwrap();
+        let b = CanonicalService::canonical_symbol("binance", "btcusdt").unwrap();
+        assert_eq!(a.as_str(), "BTC-USDT");
+        assert_eq!(a, b);
+        assert_eq!(CanonicalService::canonical_symbol("acme", "btcusd"), None);
+    }
+
     #[test]
     fn unknown_exchange_returns_none() {
-        assert_eq!(CanonicalService::canonical_pair("kraken", "btcusd"), None);
+        assert_eq!(CanonicalService::canonical_pair("acme", "btcusd"), None);
+    }
+
+    #[test]
+    fn event_lines_are_canonicalized_in_process() {
+        setup();
+        assert_eq!(
+            process_line(r#"{"agent":"binance","s":"btcusdt","p":"1"}"#).as_deref(),
+            Some(r#"{"agent":"binance","p":"1","s":"BTC-USDT"}"#)
+        );
+        assert_eq!(process_line("not json").as_deref(), Some("not json"));
+        assert_eq!(process_line("  "), None);
+        let out = process_lines([r#"{"agent":"coinbase","s":"eth-usd"}"#, "", "{}"]);
+        assert_eq!(out, [r#"{"agent":"coinbase","s":"ETH-USD"}"#, "{}"]);
+    }
+
+    #[test]
+    fn native_pairs_prefer_registered_instruments() {
+        setup();
+        assert_eq!(
+            CanonicalService::native_pair("binance", "BTC-USDT").as_deref(),
+            Some("BTCUSDT")
+        );
+        assert_eq!(
+            CanonicalService::native_pair("coinbase", "btc-usd").as_deref(),
+            Some("BTC-USD")
+        );
+        assert_eq!(CanonicalService::native_pair("nowhere", "BTC-USD"), None);
+
+        CanonicalService::update_instruments(|r| {
+            r.insert(super::InstrumentInfo {
+                exchange: "natex".into(),
+                symbol: "BTC-USD".into(),
+                native: "XXBTZUSD".into(),
+                status: super::InstrumentStatus::Trading,
+                tick_size: None,
+                lot_size: None,
+                min_notional: None,
+            })
+        });
+        assert_eq!(
+            CanonicalService::native_pair("natex", "BTC-USD").as_deref(),
+            Some("XXBTZUSD")
+        );
     }
 }
@@ -0,0 +1,355 @@
+//! Operator-provided symbol mapping overrides.
+//!
+//! The automatic rules in [`CanonicalService`](crate::CanonicalService)
+//! occasionally mis-map exotic listings. An overrides file pins exchange-native
+//! symbols to canonical ones and aliases asset codes, on every venue or on one
+//! only, e.g.:
+//!
+//! ```json
+//! {
+//!   "symbols": { "binance": { "wbtcbtc": "WBTC-BTC" } },
+//!   "assets": { "WBTC": "BTC", "USDT.e": "USDT" },
+//!   "venue_assets": { "bitfinex": { "MNA": "MANA" } }
+//! }
+//! ```
+//!
+//! Files ending in `.toml`, `.yaml` or `.yml` are read as TOML or YAML with
+//! the same layout, anything else as JSON:
+//!
+//! ```toml
+//! [symbols.binance]
+//! manausdt = "MANA-USDT"
+//!
+//! [assets]
+//! WBTC = "BTC"
+//! ```
+//!
+//! Symbol entries are consulted before any heuristics and used verbatim; asset
+//! aliases are applied to the base and quote of heuristically derived pairs,
+//! the venue's own aliases first. They extend the built-in venue codes, such
+//! as Kraken's `XBT`, that the exchange adapters already translate.
+//!
+//! Listing venues in `"unwrap_assets": ["uniswap"]` additionally collapses
+//! well-known wrapped and bridged tokens on those venues to their underlying
+//! asset, e.g. `WBTC` and `BTCB` to `BTC` or `USDC.e` to `USDC`, so DEX and
+//! CEX markets share one canonical symbol. It is off by default, since a
+//! wrapped token can trade at a discount to its underlying, and applies after
+//! the explicit aliases. A pair is left wrapped when unwrapping would make its
+//! base and quote equal, as for `WBTC-BTC`, or name a market the venue lists
+//! itself.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Wrapped or bridged asset code → underlying asset, used with
+/// [`SymbolOverrides::unwrap`].
+const WRAPPED_ASSETS: [(&str, &str); 14] = [
+    ("WBTC", "BTC"),
+    ("BTCB", "BTC"),
+    ("BTC.B", "BTC"),
+    ("CBBTC", "BTC"),
+    ("TBTC", "BTC"),
+    ("WETH", "ETH"),
+    ("WETH.E", "ETH"),
+    ("WBNB", "BNB"),
+    ("WAVAX", "AVAX"),
+    ("WMATIC", "MATIC"),
+    ("WSOL", "SOL"),
+    ("USDC.E", "USDC"),
+    ("USDT.E", "USDT"),
+    ("DAI.E", "DAI"),
+];
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct SymbolOverrides {
+    /// Exchange name → native symbol → canonical `BASE-QUOTE` symbol.
+    /// Native symbols are ordered so reverse lookups are deterministic.
+    #[serde(default)]
+    pub symbols: HashMap<String, BTreeMap<String, String>>,
+    /// Asset alias → canonical asset code.
+    #[serde(default)]
+    pub assets: HashMap<String, String>,
+    /// Exchange name → venue asset code → canonical asset code.
+    #[serde(default)]
+    pub venue_assets: HashMap<String, HashMap<String, String>>,
+    /// Exchanges whose wrapped and bridged assets collapse to their
+    /// underlying.
+    #[serde(default)]
+    pub unwrap_assets: Vec<String>,
+}
+
+impl SymbolOverrides {
+    /// Parse overrides from JSON, normalising keys for case-insensitive lookup.
+    pub fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s).map(Self::normalized)
+    }
+
+    /// Parse overrides from TOML, normalising keys for case-insensitive lookup.
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s).map(Self::normalized)
+    }
+
+    /// Parse overrides from YAML, normalising keys for case-insensitive lookup.
+    pub fn from_yaml(s: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(s).map(Self::normalized)
+    }
+
+    /// Read and parse an overrides file, choosing the format by extension.
+    pub fn from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+        };
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&s).map_err(|e| invalid(&e)),
+            Some("yaml" | "yml") => Self::from_yaml(&s).map_err(|e| invalid(&e)),
+            _ => Self::from_json(&s).map_err(|e| invalid(&e)),
+        }
+    }
+
+    fn normalized(raw: Self) -> Self {
+        Self {
+            symbols: raw
+                .symbols
+                .into_iter()
+                .map(|(ex, m)| {
+                    let m = m
+                        .into_iter()
+                        .map(|(k, v)| (k.to_lowercase(), v.to_uppercase()))
+                        .collect();
+                    (ex.to_lowercase(), m)
+                })
+                .collect(),
+            assets: upper_keys(raw.assets),
+            venue_assets: raw
+                .venue_assets
+                .into_iter()
+                .map(|(ex, m)| (ex.to_lowercase(), upper_keys(m)))
+                .collect(),
+            unwrap_assets: raw
+                .unwrap_assets
+                .into_iter()
+                .map(|ex| ex.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Pinned canonical symbol for `pair` on `exchange`, if any.
+    pub fn lookup(&self, exchange: &str, pair: &str) -> Option<&str> {
+        self.symbols
+            .get(&exchange.to_lowercase())?
+            .get(&pair.to_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Native symbol pinned to `canonical` on `exchange`, in lowercase, if
+    /// any. The alphabetically first one wins when several share it.
+    pub fn native(&self, exchange: &str, canonical: &str) -> Option<&str> {
+        self.symbols
+            .get(&exchange.to_lowercase())?
+            .iter()
+            .find(|(_, canon)| canon.eq_ignore_ascii_case(canonical))
+            .map(|(native, _)| native.as_str())
+    }
+
+    /// Apply `exchange`'s asset aliases, then the global ones, to both legs
+    /// of a canonical `BASE-QUOTE` symbol.
+    pub fn alias(&self, exchange: &str, canonical: String) -> String {
+        let venue = self.venue_assets.get(&exchange.to_lowercase());
+        if self.assets.is_empty() && venue.is_none() {
+            return canonical;
+        }
+        let asset = |code: &str| {
+            let code = venue.and_then(|v| v.get(code)).map_or(code, String::as_str);
+            self.assets
+                .get(code)
+                .map_or(code, String::as_str)
+                .to_string()
+        };
+        match canonical.split_once('-') {
+            Some((base, quote)) => format!("{}-{}", asset(base), asset(quote)),
+            None => canonical,
+        }
+    }
+
+    /// Whether [`unwrap`](Self::unwrap) applies to `exchange`.
+    pub fn unwraps(&self, exchange: &str) -> bool {
+        self.unwrap_assets
+            .iter()
+            .any(|ex| ex.eq_ignore_ascii_case(exchange))
+    }
+
+    /// Collapse the wrapped assets of a canonical `BASE-QUOTE` symbol on
+    /// `exchange` to their underlying, if [`unwrap_assets`](Self::unwrap_assets)
+    /// lists it. The symbol is kept when unwrapping would make base and quote
+    /// equal or yield a symbol `listed` on the exchange, since the wrapped
+    /// market is then a different one.
+    pub fn unwrap(
+        &self,
+        exchange: &str,
+        canonical: String,
+        listed: impl Fn(&str) -> bool,
+    ) -> String {
+        if !self.unwraps(exchange) {
+            return canonical;
+        }
+        let Some((base, quote)) = canonical.split_once('-') else {
+            return canonical;
+        };
+        let underlying = |code: &str| {
+            WRAPPED_ASSETS
+                .iter()
+                .find(|(w, _)| *w == code)
+                .map(|(_, u)| *u)
+        };
+        let (b, q) = (underlying(base), underlying(quote));
+        if b.is_none() && q.is_none() {
+            return canonical;
+        }
+        let (b, q) = (b.unwrap_or(base), q.unwrap_or(quote));
+        if b == q {
+            return canonical;
+        }
+        let unwrapped = format!("{b}-{q}");
+        if listed(&unwrapped) {
+            canonical
+        } else {
+            unwrapped
+        }
+    }
+}
+
+fn upper_keys(m: HashMap<String, String>) -> HashMap<String, String> {
+    m.into_iter()
+        .map(|(k, v)| (k.to_uppercase(), v.to_uppercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolOverrides;
+
+    #[test]
+    fn lookup_and_alias_are_case_insensitive() {
+        let o = SymbolOverrides::from_json(
+            r#"{"symbols":{"Binance":{"WBTCBTC":"wbtc-btc"}},"assets":{"WBTC":"BTC","USDT.e":"USDT"}}"#,
+        )
+        .unwrap();
+        assert_eq!(o.lookup("binance", "wbtcbtc"), Some("WBTC-BTC"));
+        assert_eq!(o.lookup("coinbase", "wbtcbtc"), None);
+        assert_eq!(o.native("BINANCE", "wbtc-btc"), Some("wbtcbtc"));
+        assert_eq!(o.native("binance", "BTC-USDT"), None);
+        assert_eq!(o.alias("binance", "WBTC-USDT.E".into()), "BTC-USDT");
+        assert_eq!(o.alias("binance", "ETH-USD".into()), "ETH-USD");
+
+        // several native symbols pinned to one canonical symbol
+        let o = SymbolOverrides::from_json(
+            r#"{"symbols":{"binance":{"xbtusdt":"BTC-USDT","btcusdt":"BTC-USDT","wbtcusdt":"BTC-USDT"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(o.native("binance", "BTC-USDT"), Some("btcusdt"));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_heuristics() {
+        use crate::adapter::{BinanceAdapter, ExchangeAdapter};
+
+        let o = SymbolOverrides::from_json(
+            r#"{"symbols":{"binance":{"wbtcbtc":"WBTC-BTC"}},"assets":{"WBTC":"BTC"}}"#,
+        )
+        .unwrap();
+        // the pinned symbol keeps WBTC, which the aliased adapter result drops
+        assert_eq!(o.lookup("binance", "wbtcbtc"), Some("WBTC-BTC"));
+        let heuristic = BinanceAdapter.canonicalize("wbtcbtc").unwrap();
+        assert_eq!(o.alias("binance", heuristic), "BTC-BTC");
+        let unpinned = BinanceAdapter.canonicalize("wbtcusdt").unwrap();
+        assert_eq!(o.alias("binance", unpinned), "BTC-USDT");
+    }
+
+    #[test]
+    fn venue_aliases_apply_to_their_venue_only() {
+        use crate::adapter::{BitfinexAdapter, ExchangeAdapter};
+
+        let o = SymbolOverrides::from_json(
+            r#"{"assets":{"WBTC":"BTC"},"venue_assets":{"Bitfinex":{"mna":"MANA","WBT":"WBTC"}}}"#,
+        )
+        .unwrap();
+        // the adapter keeps Bitfinex's three-letter codes it does not know
+        let mana = BitfinexAdapter.canonicalize("tMNAUSD").unwrap();
+        assert_eq!(mana, "MNA-USD");
+        assert_eq!(o.alias("bitfinex", mana), "MANA-USD");
+        // venue aliases feed into the global ones
+        assert_eq!(o.alias("bitfinex", "WBT-USD".into()), "BTC-USD");
+        assert_eq!(o.alias("okx", "MNA-USD".into()), "MNA-USD");
+    }
+
+    #[test]
+    fn wrapped_assets_collapse_only_on_listed_venues() {
+        let unlisted = |_: &str| false;
+        let off = SymbolOverrides::from_json(r#"{"assets":{"XETH":"WETH"}}"#).unwrap();
+        assert_eq!(
+            off.unwrap("uniswap", "WBTC-USDC.E".into(), unlisted),
+            "WBTC-USDC.E"
+        );
+
+        let on = SymbolOverrides::from_json(r#"{"unwrap_assets":["Uniswap","binance"]}"#).unwrap();
+        assert_eq!(
+            on.unwrap("uniswap", "WBTC-USDC.E".into(), unlisted),
+            "BTC-USDC"
+        );
+        assert_eq!(
+            on.unwrap("binance", "BTCB-USDT".into(), unlisted),
+            "BTC-USDT"
+        );
+        assert_eq!(on.unwrap("okx", "WBTC-USDT".into(), unlisted), "WBTC-USDT");
+        assert_eq!(
+            on.unwrap("binance", "STETH-ETH".into(), unlisted),
+            "STETH-ETH"
+        );
+        // base and quote would be the same asset
+        assert_eq!(
+            on.unwrap("binance", "WBTC-BTC".into(), unlisted),
+            "WBTC-BTC"
+        );
+        // the venue lists the underlying market itself
+        let listed = |s: &str| s == "ETH-USDT";
+        assert_eq!(
+            on.unwrap("binance", "WETH-USDT".into(), listed),
+            "WETH-USDT"
+        );
+        assert_eq!(on.unwrap("binance", "WBNB-USDT".into(), listed), "BNB-USDT");
+    }
+
+    #[test]
+    fn toml_and_yaml_files_are_read_by_extension() {
+        let dir = std::env::temp_dir().join(format!("overrides-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let toml = dir.join("overrides.toml");
+        std::fs::write(
+            &toml,
+            "[symbols.Binance]\nMANAUSDT = \"mana-usdt\"\n\n[venue_assets.bitfinex]\nMNA = \"MANA\"\n",
+        )
+        .unwrap();
+        let yaml = dir.join("overrides.yml");
+        std::fs::write(
+            &yaml,
+            "symbols:\n  binance:\n    manausdt: MANA-USDT\nassets:\n  wbtc: BTC\n",
+        )
+        .unwrap();
+
+        let o = SymbolOverrides::from_path(&toml).unwrap();
+        assert_eq!(o.lookup("binance", "manausdt"), Some("MANA-USDT"));
+        assert_eq!(o.alias("bitfinex", "MNA-USD".into()), "MANA-USD");
+        let o = SymbolOverrides::from_path(&yaml).unwrap();
+        assert_eq!(o.lookup("binance", "MANAUSDT"), Some("MANA-USDT"));
+        assert_eq!(o.alias("okx", "WBTC-USDT".into()), "BTC-USDT");
+
+        std::fs::write(&yaml, "symbols: [").unwrap();
+        let err = SymbolOverrides::from_path(&yaml).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,419 @@
+//! Protobuf encoding of canonical events.
+//!
+//! The message types mirror `proto/events.proto`, so consumers in other
+//! languages can generate their own from that file. [`encode_line`] turns an
+//! emitted JSON line into a length-delimited [`Event`]; trades, book updates,
+//! tickers, funding, open interest, liquidations and bars get typed bodies,
+//! every other event type travels as its JSON object in [`event::Body::Json`].
+//! String fields a typed body has no room for, such as deployment labels
+//! added downstream, travel in [`Event::labels`]; a line with any other extra
+//! field keeps its JSON body so nothing is dropped. Requires the `proto`
+//! feature.
+
+use std::collections::BTreeMap;
+
+use prost::Message;
+use serde_json::Value;
+
+use crate::AssetClass;
+
+/// One emitted event, see `proto/events.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost(string, tag = "1")]
+    pub agent: String,
+    #[prost(string, tag = "2")]
+    pub r#type: String,
+    #[prost(string, tag = "3")]
+    pub symbol: String,
+    #[prost(int64, tag = "4")]
+    pub ts: i64,
+    #[prost(uint32, tag = "5")]
+    pub schema_version: u32,
+    #[prost(string, tag = "6")]
+    pub event_id: String,
+    #[prost(int64, tag = "7")]
+    pub ingest_ts: i64,
+    #[prost(uint64, tag = "8")]
+    pub source_seq: u64,
+    #[prost(string, tag = "20")]
+    pub ac: String,
+    #[prost(string, tag = "21")]
+    pub settle: String,
+    #[prost(string, tag = "22")]
+    pub contract: String,
+    /// String fields of the line outside the typed body, by key.
+    #[prost(btree_map = "string, string", tag = "23")]
+    pub labels: BTreeMap<String, String>,
+    #[prost(oneof = "event::Body", tags = "10, 11, 12, 13, 14, 15, 16, 17, 99")]
+    pub body: Option<event::Body>,
+}
+
+pub mod event {
+    /// Type-specific fields of an [`Event`](super::Event).
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Body {
+        #[prost(message, tag = "10")]
+        Trade(super::Trade),
+        #[prost(message, tag = "11")]
+        L2Diff(super::BookUpdate),
+        #[prost(message, tag = "12")]
+        Snapshot(super::BookUpdate),
+        #[prost(message, tag = "13")]
+        BookTicker(super::BookTicker),
+        #[prost(message, tag = "14")]
+        Funding(super::Funding),
+        #[prost(message, tag = "15")]
+        OpenInterest(super::OpenInterest),
+        #[prost(message, tag = "16")]
+        Liquidation(super::Liquidation),
+        #[prost(message, tag = "17")]
+        Bar(super::Bar),
+        #[prost(string, tag = "99")]
+        Json(String),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Trade {
+    #[prost(int64, optional, tag = "1")]
+    pub trade_id: Option<i64>,
+    #[prost(string, tag = "2")]
+    pub price: String,
+    #[prost(string, tag = "3")]
+    pub qty: String,
+    #[prost(int64, optional, tag = "4")]
+    pub skew: Option<i64>,
+    #[prost(string, optional, tag = "5")]
+    pub q_unit: Option<String>,
+    #[prost(string, optional, tag = "6")]
+    pub contract_size: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Level {
+    #[prost(string, tag = "1")]
+    pub price: String,
+    #[prost(string, tag = "2")]
+    pub qty: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BookUpdate {
+    #[prost(message, repeated, tag = "1")]
+    pub bids: Vec<Level>,
+    #[prost(message, repeated, tag = "2")]
+    pub asks: Vec<Level>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BookTicker {
+    #[prost(string, tag = "1")]
+    pub bid_price: String,
+    #[prost(string, tag = "2")]
+    pub bid_qty: String,
+    #[prost(string, tag = "3")]
+    pub ask_price: String,
+    #[prost(string, tag = "4")]
+    pub ask_qty: String,
+    #[prost(double, optional, tag = "5")]
+    pub mid: Option<f64>,
+    #[prost(string, tag = "6")]
+    pub mid_kind: String,
+    #[prost(string, tag = "7")]
+    pub quote_group: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Funding {
+    #[prost(string, tag = "1")]
+    pub rate: String,
+    #[prost(uint32, optional, tag = "2")]
+    pub interval_h: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OpenInterest {
+    #[prost(string, tag = "1")]
+    pub open_interest: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Liquidation {
+    #[prost(string, tag = "1")]
+    pub price: String,
+    #[prost(string, tag = "2")]
+    pub qty: String,
+    #[prost(string, tag = "3")]
+    pub side: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Bar {
+    #[prost(uint64, tag = "1")]
+    pub interval: u64,
+    #[prost(string, tag = "2")]
+    pub open: String,
+    #[prost(string, tag = "3")]
+    pub high: String,
+    #[prost(string, tag = "4")]
+    pub low: String,
+    #[prost(string, tag = "5")]
+    pub close: String,
+    #[prost(string, tag = "6")]
+    pub volume: String,
+}
+
+fn levels(levels: Vec<[String; 2]>) -> Vec<Level> {
+    levels
+        .into_iter()
+        .map(|[price, qty]| Level { price, qty })
+        .collect()
+}
+
+/// Keys of an emitted line stored in [`Event`]'s own fields.
+const COMMON: [&str; 11] = [
+    "agent",
+    "type",
+    "s",
+    "ts",
+    "schema_version",
+    "event_id",
+    "ingest_ts",
+    "source_seq",
+    "ac",
+    "settle",
+    "id",
+];
+
+/// Asset class, settlement currency and contract id of a typed event.
+type Contract = (Option<AssetClass>, Option<String>, Option<String>);
+
+/// Typed body of `e` and its contract, if its type has a message.
+fn body(e: crate::events::Event) -> Option<(event::Body, Contract)> {
+    use crate::events::Event as E;
+    use event::Body;
+
+    Some(match e {
+        E::Trade(t) => (
+            Body::Trade(Trade {
+                trade_id: t.trade_id,
+                price: t.price,
+                qty: t.quantity,
+                skew: t.skew,
+                q_unit: t.q_unit,
+                contract_size: t.contract_size,
+            }),
+            (t.ac, t.settle, t.id),
+        ),
+        E::L2Diff(d) => (
+            Body::L2Diff(BookUpdate {
+                bids: levels(d.bids),
+                asks: levels(d.asks),
+            }),
+            (d.ac, d.settle, d.id),
+        ),
+        E::Snapshot(s) => (
+            Body::Snapshot(BookUpdate {
+                bids: levels(s.bids),
+                asks: levels(s.asks),
+            }),
+            (s.ac, s.settle, s.id),
+        ),
+        E::BookTicker(t) => (
+            Body::BookTicker(BookTicker {
+                bid_price: t.bid_price,
+                bid_qty: t.bid_qty,
+                ask_price: t.ask_price,
+                ask_qty: t.ask_qty,
+                mid: t.mid,
+                mid_kind: t.mid_kind.unwrap_or_default(),
+                quote_group: t.quote_group.unwrap_or_default(),
+            }),
+            (t.ac, t.settle, t.id),
+        ),
+        E::Funding(f) => (
+            Body::Funding(Funding {
+                rate: f.rate,
+                interval_h: f.funding_interval_h,
+            }),
+            (f.ac, f.settle, f.id),
+        ),
+        E::OpenInterest(oi) => (
+            Body::OpenInterest(OpenInterest {
+                open_interest: oi.open_interest,
+            }),
+            (oi.ac, oi.settle, oi.id),
+        ),
+        E::Liquidation(l) => (
+            Body::Liquidation(Liquidation {
+                price: l.price,
+                qty: l.quantity,
+                side: l.side,
+            }),
+            (l.ac, l.settle, l.id),
+        ),
+        E::Bar(b) => (
+            Body::Bar(Bar {
+                interval: b.interval,
+                open: b.open,
+                high: b.high,
+                low: b.low,
+                close: b.close,
+                volume: b.volume,
+            }),
+            (b.ac, b.settle, b.id),
+        ),
+        _ => return None,
+    })
+}
+
+/// Typed body of `v` with its contract and the string fields it leaves out,
+/// or `None` when `v` has no message or a non-string field it would drop.
+fn typed(v: &Value) -> Option<(event::Body, Contract, BTreeMap<String, String>)> {
+    let e = serde_json::from_value::<crate::events::Event>(v.clone()).ok()?;
+    let kept = serde_json::to_value(&e).ok()?;
+    let (body, contract) = body(e)?;
+    let mut labels = BTreeMap::new();
+    for (k, x) in v.as_object()? {
+        if COMMON.contains(&k.as_str()) || kept.get(k).is_some() {
+            continue;
+        }
+        labels.insert(k.clone(), x.as_str()?.to_string());
+    }
+    Some((body, contract, labels))
+}
+
+/// [`Event`] of one emitted JSON line. Lines that are not JSON objects are
+/// carried whole in the `json` body.
+pub fn from_line(line: &str) -> Event {
+    let v = match serde_json::from_str::<Value>(line) {
+        Ok(v @ Value::Object(_)) => v,
+        _ => {
+            return Event {
+                body: Some(event::Body::Json(line.to_string())),
+                ..Default::default()
+            }
+        }
+    };
+    let text = |k: &str| {
+        v.get(k)
+            .and_then(|x| x.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let int = |k: &str| v.get(k).and_then(|x| x.as_i64()).unwrap_or_default();
+    let mut event = Event {
+        agent: text("agent"),
+        r#type: text("type"),
+        symbol: text("s"),
+        ts: int("ts"),
+        schema_version: int("schema_version") as u32,
+        event_id: text("event_id"),
+        ingest_ts: int("ingest_ts"),
+        source_seq: int("source_seq") as u64,
+        ac: text("ac"),
+        settle: text("settle"),
+        contract: text("id"),
+        ..Default::default()
+    };
+    match typed(&v) {
+        Some((body, (ac, settle, id), labels)) => {
+            event.ac = ac.map(|a| a.to_string()).unwrap_or_default();
+            event.settle = settle.unwrap_or_default();
+            event.contract = id.unwrap_or_default();
+            event.labels = labels;
+            event.body = Some(body);
+        }
+        None => event.body = Some(event::Body::Json(v.to_string())),
+    }
+    event
+}
+
+/// Length-delimited protobuf encoding of one emitted JSON line.
+pub fn encode_line(line: &str) -> Vec<u8> {
+    from_line(line).encode_length_delimited_to_vec()
+}
+
+/// Decode one length-delimited [`Event`] from the front of `buf`.
+pub fn decode(buf: &[u8]) -> Result<Event, prost::DecodeError> {
+    Event::decode_length_delimited(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_encode_with_typed_or_json_bodies() {
+        let trade = r#"{"agent":"binance","type":"trade","s":"BTC-USDT","t":42,"p":"100.5","q":"0.1","ac":"perp","id":"BTC-USDT-PERP","ts":1700000000000,"schema_version":1,"event_id":"01H","ingest_ts":1700000000005,"source_seq":7}"#;
+        let e = decode(&encode_line(trade)).unwrap();
+        assert_eq!(
+            (e.agent.as_str(), e.symbol.as_str(), e.ts),
+            ("binance", "BTC-USDT", 1_700_000_000_000)
+        );
+        assert_eq!((e.source_seq, e.schema_version), (7, 1));
+        assert_eq!(e.contract, "BTC-USDT-PERP");
+        assert_eq!(
+            e.body,
+            Some(event::Body::Trade(Trade {
+                trade_id: Some(42),
+                price: "100.5".into(),
+                qty: "0.1".into(),
+                skew: None,
+                q_unit: None,
+                contract_size: None,
+            }))
+        );
+
+        let diff = r#"{"agent":"coinbase","type":"l2_diff","s":"BTC-USD","bids":[["100","1"]],"asks":[],"ts":2}"#;
+        let Some(event::Body::L2Diff(book)) = decode(&encode_line(diff)).unwrap().body else {
+            panic!("expected an l2_diff body");
+        };
+        assert_eq!(book.bids[0].qty, "1");
+
+        let stats = r#"{"agent":"binance","type":"rate_limit","used":5}"#;
+        let Some(event::Body::Json(json)) = decode(&encode_line(stats)).unwrap().body else {
+            panic!("expected a json body");
+        };
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            serde_json::from_str::<Value>(stats).unwrap()
+        );
+        assert_eq!(
+            from_line("not json").body,
+            Some(event::Body::Json("not json".into()))
+        );
+    }
+
+    #[test]
+    fn extra_fields_travel_as_labels_or_keep_the_json_body() {
+        let labelled = r#"{"agent":"bybit","type":"funding","s":"BTC-USDT","r":"0.0001","ac":"perp","settle":"USDT","id":"BTC-USDT-PERP","ts":5,"deployment":"eu-1","tenant":"desk-a"}"#;
+        let e = decode(&encode_line(labelled)).unwrap();
+        assert_eq!(
+            (e.ac.as_str(), e.settle.as_str(), e.contract.as_str()),
+            ("perp", "USDT", "BTC-USDT-PERP")
+        );
+        assert_eq!(
+            e.body,
+            Some(event::Body::Funding(Funding {
+                rate: "0.0001".into(),
+                interval_h: None,
+            }))
+        );
+        assert_eq!(e.labels.len(), 2);
+        assert_eq!(e.labels["deployment"], "eu-1");
+        assert_eq!(e.labels["tenant"], "desk-a");
+
+        let nested = r#"{"agent":"bybit","type":"funding","s":"BTC-USDT","r":"0.0001","ts":5,"meta":{"shard":3}}"#;
+        let e = decode(&encode_line(nested)).unwrap();
+        let Some(event::Body::Json(json)) = e.body else {
+            panic!("expected a json body");
+        };
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            serde_json::from_str::<Value>(nested).unwrap()
+        );
+        assert!(e.labels.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Quote-equivalence groups.
+//!
+//! Canonical symbols keep the quote asset each venue lists, so `BTC-USD` on
+//! Coinbase and `BTC-USDT` on Binance are different markets. Cross-venue
+//! analytics that should compare them anyway can put `USD`, `USDT` and `USDC`
+//! in one [`QuoteGroup`]; [`QuoteGroups::key`] then maps every member quote to
+//! the group's name, e.g. both symbols to `BTC-USD`.
+
+use std::collections::BTreeMap;
+
+/// Quote assets treated as interchangeable, under the group's name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteGroup {
+    /// Quote asset the group's symbols are keyed by, e.g. `USD`.
+    pub name: String,
+    /// Member quote assets, uppercase.
+    pub quotes: Vec<String>,
+}
+
+/// Configured quote groups. Empty by default, so no symbols are merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuoteGroups {
+    groups: Vec<QuoteGroup>,
+}
+
+impl QuoteGroups {
+    /// Groups from a `name -> quotes` map, such as the `quote_groups` table of
+    /// the ingestor config. Groups are ordered by name; a quote listed in
+    /// several groups belongs to the first.
+    pub fn new(groups: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut groups: Vec<QuoteGroup> = groups
+            .iter()
+            .map(|(name, quotes)| QuoteGroup {
+                name: name.to_uppercase(),
+                quotes: quotes.iter().map(|q| q.to_uppercase()).collect(),
+            })
+            .collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Group the quote asset `quote` belongs to.
+    pub fn group(&self, quote: &str) -> Option<&QuoteGroup> {
+        self.groups
+            .iter()
+            .find(|g| g.quotes.iter().any(|q| q.eq_ignore_ascii_case(quote)))
+    }
+
+    /// Symbol under which the canonical `symbol` is compared across venues,
+    /// and the group of its quote, e.g. `BTC-USDT` as `BTC-USD` in group
+    /// `USD`. Symbols outside every group are returned unchanged.
+    pub fn key<'a>(&'a self, symbol: &str) -> (String, Option<&'a QuoteGroup>) {
+        let mut parts = symbol.splitn(3, '-');
+        let (Some(base), Some(quote)) = (parts.next(), parts.next()) else {
+            return (symbol.to_string(), None);
+        };
+        let Some(group) = self.group(quote) else {
+            return (symbol.to_string(), None);
+        };
+        let key = match parts.next() {
+            Some(rest) => format!("{base}-{}-{rest}", group.name),
+            None => format!("{base}-{}", group.name),
+        };
+        (key, Some(group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_quotes_share_the_group_key() {
+        let groups = QuoteGroups::new(&BTreeMap::from([(
+            "usd".to_string(),
+            vec!["USD".into(), "usdt".into(), "USDC".into()],
+        )]));
+        let (key, group) = groups.key("BTC-USDT");
+        assert_eq!(key, "BTC-USD");
+        assert_eq!(group.unwrap().name, "USD");
+        assert_eq!(groups.key("BTC-USD").0, "BTC-USD");
+        assert_eq!(groups.key("ETH-USDC-PERP").0, "ETH-USD-PERP");
+        assert_eq!(groups.key("BTC-EUR"), ("BTC-EUR".to_string(), None));
+        assert_eq!(QuoteGroups::default().key("BTC-USDT").0, "BTC-USDT");
+    }
+}
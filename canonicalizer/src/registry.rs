@@ -0,0 +1,423 @@
+//! Trading rules per instrument.
+//!
+//! [`InstrumentRegistry`] holds the tick size, lot size, minimum notional and
+//! trading status of each market, keyed by exchange and canonical symbol. It
+//! is filled from the exchanges' own metadata responses (Binance
+//! `exchangeInfo`, Coinbase `/products`) so prices and quantities can be
+//! rounded to valid increments and halted or delisted markets filtered out.
+//! Each load replaces the exchange's previous listing, so markets that
+//! disappear from it are dropped. The process-wide registry is read through
+//! [`CanonicalService::instrument`](crate::CanonicalService::instrument).
+//!
+//! Derivative markets of a venue have rules of their own and are kept under
+//! `<venue>_<market>`, e.g. `binance_futures` and `binance_coinm` next to
+//! `binance` spot.
+//!
+//! With [`CanonicalService::set_validation`](crate::CanonicalService::set_validation)
+//! every canonicalized pair is also checked by [`InstrumentRegistry::validate`].
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{adapter, CanonicalError, CanonicalService};
+
+/// Whether an instrument can currently be traded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstrumentStatus {
+    Trading,
+    /// Temporarily not trading, e.g. halted or cancel-only.
+    Halted,
+    /// Removed from the exchange.
+    Delisted,
+}
+
+/// Trading rules of one market on one exchange.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentInfo {
+    pub exchange: String,
+    /// Canonical `BASE-QUOTE` symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Symbol as used by the exchange.
+    pub native: String,
+    pub status: InstrumentStatus,
+    /// Price increment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_size: Option<String>,
+    /// Quantity increment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lot_size: Option<String>,
+    /// Smallest order value in the quote asset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_notional: Option<String>,
+}
+
+impl InstrumentInfo {
+    pub fn is_trading(&self) -> bool {
+        self.status == InstrumentStatus::Trading
+    }
+
+    /// Decimal places of the tick size, e.g. 2 for `0.01000000`.
+    pub fn price_precision(&self) -> Option<u32> {
+        self.tick_size.as_deref().and_then(precision)
+    }
+
+    /// Decimal places of the lot size.
+    pub fn qty_precision(&self) -> Option<u32> {
+        self.lot_size.as_deref().and_then(precision)
+    }
+
+    /// Round `price` to the nearest tick. Returns `price` unchanged when the
+    /// tick size is unknown.
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_to(price, self.tick_size.as_deref(), f64::round)
+    }
+
+    /// Round `qty` down to a whole number of lots, so it never exceeds the
+    /// requested quantity. Returns `qty` unchanged when the lot size is
+    /// unknown.
+    pub fn round_qty(&self, qty: f64) -> f64 {
+        round_to(qty, self.lot_size.as_deref(), f64::floor)
+    }
+}
+
+fn precision(step: &str) -> Option<u32> {
+    step.parse::<f64>().ok().filter(|s| *s > 0.0)?;
+    let decimals = step
+        .split_once('.')
+        .map_or("", |(_, frac)| frac.trim_end_matches('0'));
+    Some(decimals.len() as u32)
+}
+
+fn round_to(value: f64, step: Option<&str>, round: fn(f64) -> f64) -> f64 {
+    let Some(step_str) = step else {
+        return value;
+    };
+    let (Ok(step), Some(places)) = (step_str.parse::<f64>(), precision(step_str)) else {
+        return value;
+    };
+    // Snap away the float error left by the division and multiplication.
+    let units = round((value / step * 1e9).round() / 1e9);
+    let scale = 10f64.powi(places as i32);
+    (units * step * scale).round() / scale
+}
+
+/// Instrument metadata keyed by exchange and canonical symbol.
+#[derive(Debug, Default)]
+pub struct InstrumentRegistry {
+    instruments: HashMap<String, HashMap<String, InstrumentInfo>>,
+    /// Base assets of every instrument loaded for any exchange.
+    bases: HashSet<String>,
+}
+
+impl InstrumentRegistry {
+    /// Add or replace the rules for `info.exchange` and `info.symbol`.
+    pub fn insert(&mut self, info: InstrumentInfo) {
+        if let Some((base, _)) = info.symbol.split_once('-') {
+            self.bases.insert(base.to_string());
+        }
+        self.instruments
+            .entry(info.exchange.to_lowercase())
+            .or_default()
+            .insert(info.symbol.clone(), info);
+    }
+
+    /// Replace every instrument of `exchange` with `infos`, returning how many
+    /// were read.
+    fn replace(&mut self, exchange: &str, infos: Vec<InstrumentInfo>) -> usize {
+        let n = infos.len();
+        let market = infos.into_iter().map(|i| (i.symbol.clone(), i)).collect();
+        self.instruments.insert(exchange.to_lowercase(), market);
+        self.bases = self
+            .instruments
+            .values()
+            .flat_map(|m| m.keys())
+            .filter_map(|s| s.split_once('-'))
+            .map(|(base, _)| base.to_string())
+            .collect();
+        n
+    }
+
+    /// Rules for the canonical `symbol` on `exchange`.
+    pub fn get(&self, exchange: &str, symbol: &str) -> Option<&InstrumentInfo> {
+        self.instruments.get(&exchange.to_lowercase())?.get(symbol)
+    }
+
+    /// All known instruments of `exchange`, in no particular order.
+    pub fn instruments<'a>(&'a self, exchange: &str) -> impl Iterator<Item = &'a InstrumentInfo> {
+        self.instruments
+            .get(&exchange.to_lowercase())
+            .into_iter()
+            .flat_map(|m| m.values())
+    }
+
+    /// Instruments of `exchange` and of its derivative markets, such as
+    /// `binance_futures` for `binance`.
+    fn markets<'a>(
+        &'a self,
+        exchange: &str,
+    ) -> impl Iterator<Item = &'a HashMap<String, InstrumentInfo>> {
+        let exchange = exchange.to_lowercase();
+        self.instruments
+            .iter()
+            .filter(move |(name, _)| {
+                name.strip_prefix(exchange.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('_'))
+            })
+            .map(|(_, m)| m)
+    }
+
+    /// Check a pair produced for `exchange` against its listings. Pairs listed
+    /// on the exchange or one of its derivative markets pass. On exchanges
+    /// whose symbols have no separator, a base asset ending in a known quote
+    /// asset, such as `BTCUSDT` in `BTCUSDT-USDT`, is reported as
+    /// [`CanonicalError::QuoteInBase`] unless it is listed as a base asset
+    /// somewhere. Any other pair missing from a loaded exchange is reported as
+    /// [`CanonicalError::NotListed`].
+    pub fn validate(&self, exchange: &str, canonical: &str) -> Result<(), CanonicalError> {
+        if self.markets(exchange).any(|m| m.contains_key(canonical)) {
+            return Ok(());
+        }
+        let base = canonical.split('-').next().unwrap_or_default();
+        let concatenated = CanonicalService::adapter(exchange).is_some_and(|a| a.concatenated());
+        if concatenated && !self.bases.contains(base) {
+            let base = base.to_lowercase();
+            let quotes = adapter::binance_quotes();
+            let quote_suffix = !quotes.contains(&base)
+                && quotes
+                    .iter()
+                    .any(|q| base.len() >= q.len() + 2 && base.ends_with(q.as_str()));
+            if quote_suffix {
+                return Err(CanonicalError::QuoteInBase(canonical.to_string()));
+            }
+        }
+        if self.markets(exchange).any(|m| !m.is_empty()) {
+            return Err(CanonicalError::NotListed(canonical.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Load every symbol of a Binance `exchangeInfo` response in place of
+    /// the exchange's previous ones, returning how many were read. Also
+    /// accepts the USDⓈ-M and COIN-M futures `exchangeInfo`, loaded as
+    /// `binance_futures` and `binance_coinm`, whose COIN-M status field is
+    /// `contractStatus`. A response without a symbol list leaves the exchange
+    /// untouched.
+    pub fn load_binance_exchange_info(&mut self, exchange: &str, v: &Value) -> usize {
+        let Some(symbols) = v.get("symbols").and_then(|s| s.as_array()) else {
+            return 0;
+        };
+        let mut infos = Vec::new();
+        for sym in symbols {
+            let Some(native) = sym.get("symbol").and_then(|s| s.as_str()) else {
+                continue;
+            };
+            let status = sym
+                .get("status")
+                .or_else(|| sym.get("contractStatus"))
+                .and_then(|s| s.as_str())
+                .unwrap_or_default();
+            let filter = |kind: &str, field: &str| {
+                sym.get("filters")?
+                    .as_array()?
+                    .iter()
+                    .find(|f| f.get("filterType").and_then(|t| t.as_str()) == Some(kind))?
+                    .get(field)?
+                    .as_str()
+                    .map(str::to_string)
+            };
+            infos.push(InstrumentInfo {
+                exchange: exchange.to_string(),
+                symbol: canonical(exchange, native),
+                native: native.to_string(),
+                status: match status {
+                    "TRADING" => InstrumentStatus::Trading,
+                    "HALT" | "PRE_TRADING" | "POST_TRADING" | "END_OF_DAY" | "AUCTION_MATCH"
+                    | "PENDING_TRADING" => InstrumentStatus::Halted,
+                    _ => InstrumentStatus::Delisted,
+                },
+                tick_size: filter("PRICE_FILTER", "tickSize"),
+                lot_size: filter("LOT_SIZE", "stepSize"),
+                min_notional: filter("NOTIONAL", "minNotional")
+                    .or_else(|| filter("MIN_NOTIONAL", "minNotional")),
+            });
+        }
+        self.replace(exchange, infos)
+    }
+
+    /// Load every product of a Coinbase `/products` response in place of the
+    /// previous ones, returning how many were read. A response that is not a
+    /// product list leaves them untouched.
+    pub fn load_coinbase_products(&mut self, v: &Value) -> usize {
+        let Some(products) = v.as_array() else {
+            return 0;
+        };
+        let mut infos = Vec::new();
+        for prod in products {
+            let Some(native) = prod.get("id").and_then(|s| s.as_str()) else {
+                continue;
+            };
+            let str_of = |k: &str| prod.get(k).and_then(|s| s.as_str()).map(str::to_string);
+            let flag = |k: &str| prod.get(k).and_then(|b| b.as_bool()).unwrap_or(false);
+            let status = match prod.get("status").and_then(|s| s.as_str()) {
+                Some("delisted") => InstrumentStatus::Delisted,
+                Some("online") if !flag("trading_disabled") && !flag("cancel_only") => {
+                    InstrumentStatus::Trading
+                }
+                _ => InstrumentStatus::Halted,
+            };
+            infos.push(InstrumentInfo {
+                exchange: "coinbase".to_string(),
+                symbol: canonical("coinbase", native),
+                native: native.to_string(),
+                status,
+                tick_size: str_of("quote_increment"),
+                lot_size: str_of("base_increment"),
+                min_notional: str_of("min_market_funds"),
+            });
+        }
+        self.replace("coinbase", infos)
+    }
+}
+
+/// Canonical pair of a listed `native` symbol, mapped by the adapter of the
+/// venue `exchange` is a market of. Listings are not validated, as they are
+/// what pairs are validated against.
+fn canonical(exchange: &str, native: &str) -> String {
+    let venue = exchange
+        .split_once('_')
+        .map_or(exchange, |(venue, _)| venue);
+    CanonicalService::map_pair(venue, native).unwrap_or_else(|_| native.to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn binance_exchange_info_is_loaded_with_filters_and_status() {
+        let mut reg = InstrumentRegistry::default();
+        let info = json!({"symbols": [
+            {"symbol": "BTCUSDT", "status": "TRADING", "filters": [
+                {"filterType": "PRICE_FILTER", "tickSize": "0.01000000"},
+                {"filterType": "LOT_SIZE", "stepSize": "0.00001000"},
+                {"filterType": "NOTIONAL", "minNotional": "5.00000000"}
+            ]},
+            {"symbol": "LUNAUSDT", "status": "BREAK", "filters": []}
+        ]});
+        assert_eq!(reg.load_binance_exchange_info("binance", &info), 2);
+
+        let btc = reg.get("binance", "BTC-USDT").unwrap();
+        assert!(btc.is_trading());
+        assert_eq!(btc.native, "BTCUSDT");
+        assert_eq!(btc.min_notional.as_deref(), Some("5.00000000"));
+        assert_eq!(btc.price_precision(), Some(2));
+        assert_eq!(btc.qty_precision(), Some(5));
+        assert_eq!(btc.round_price(42_000.126), 42_000.13);
+        assert_eq!(btc.round_qty(0.123_456_7), 0.12345);
+
+        let luna = reg.get("Binance", "LUNA-USDT").unwrap();
+        assert_eq!(luna.status, InstrumentStatus::Delisted);
+        assert_eq!(luna.round_price(0.1234), 0.1234);
+        assert_eq!(
+            reg.instruments("binance")
+                .filter(|i| i.is_trading())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn coinbase_products_are_loaded_with_status() {
+        let mut reg = InstrumentRegistry::default();
+        let products = json!([
+            {"id": "ETH-USD", "status": "online", "quote_increment": "0.01",
+                "base_increment": "0.00000001", "min_market_funds": "1"},
+            {"id": "XYZ-USD", "status": "online", "cancel_only": true},
+            {"id": "OLD-USD", "status": "delisted"}
+        ]);
+        assert_eq!(reg.load_coinbase_products(&products), 3);
+        let eth = reg.get("coinbase", "ETH-USD").unwrap();
+        assert!(eth.is_trading());
+        assert_eq!(eth.qty_precision(), Some(8));
+        assert_eq!(eth.round_price(2500.004), 2500.0);
+        assert_eq!(
+            reg.get("coinbase", "XYZ-USD").unwrap().status,
+            InstrumentStatus::Halted
+        );
+        assert_eq!(
+            reg.get("coinbase", "OLD-USD").unwrap().status,
+            InstrumentStatus::Delisted
+        );
+        assert_eq!(precision("1"), Some(0));
+        assert_eq!(precision("0"), None);
+
+        // A refresh drops products missing from it; a failed one keeps them.
+        let refreshed = json!([{"id": "ETH-USD", "status": "online"}]);
+        assert_eq!(reg.load_coinbase_products(&refreshed), 1);
+        assert!(reg.get("coinbase", "OLD-USD").is_none());
+        assert_eq!(reg.load_coinbase_products(&serde_json::Value::Null), 0);
+        assert!(reg.get("coinbase", "ETH-USD").is_some());
+    }
+
+    #[test]
+    fn pairs_are_validated_against_listings() {
+        let mut reg = InstrumentRegistry::default();
+        assert_eq!(
+            reg.validate("binance", "BTCUSDT-USDT"),
+            Err(CanonicalError::QuoteInBase("BTCUSDT-USDT".into()))
+        );
+        assert_eq!(reg.validate("binance", "BTC-USDT"), Ok(()));
+        assert_eq!(reg.validate("binance", "USDC-USDT"), Ok(()));
+
+        let products = json!([{"id": "ETH-USD", "status": "online"}]);
+        reg.load_coinbase_products(&products);
+        assert_eq!(reg.validate("coinbase", "ETH-USD"), Ok(()));
+        assert_eq!(
+            reg.validate("coinbase", "XYZ-USD"),
+            Err(CanonicalError::NotListed("XYZ-USD".into()))
+        );
+        assert_eq!(
+            CanonicalError::NotListed("XYZ-USD".into()).kind(),
+            "not_listed"
+        );
+
+        // Venues with separators cannot produce the artifact.
+        for (exchange, pair) in [
+            ("okx", "STETH-USDT"),
+            ("okx", "FDUSD-USDT"),
+            ("okx", "PYUSD-USDT"),
+            ("gate", "CBETH-USDT"),
+        ] {
+            assert_eq!(reg.validate(exchange, pair), Ok(()), "{pair}");
+        }
+        // Futures-only and dated COIN-M contracts are listed by their
+        // markets, and listed bases pass on other concatenating venues.
+        assert_eq!(
+            reg.validate("bybit", "WBETH-USDT"),
+            Err(CanonicalError::QuoteInBase("WBETH-USDT".into()))
+        );
+        let spot = json!({"symbols": [{"symbol": "WBETHETH", "status": "TRADING"}]});
+        let usdm = json!({"symbols": [{"symbol": "1000PEPEUSDT", "status": "TRADING"}]});
+        let coinm = json!({"symbols": [
+            {"symbol": "BTCUSD_PERP", "contractStatus": "TRADING"},
+            {"symbol": "ETHUSD_250328", "contractStatus": "TRADING"}
+        ]});
+        reg.load_binance_exchange_info("binance", &spot);
+        reg.load_binance_exchange_info("binance_futures", &usdm);
+        reg.load_binance_exchange_info("binance_coinm", &coinm);
+        assert_eq!(reg.validate("bybit", "WBETH-USDT"), Ok(()));
+        for pair in ["1000PEPE-USDT", "BTC-USD", "ETH-USD-20250328"] {
+            assert_eq!(reg.validate("binance", pair), Ok(()), "{pair}");
+        }
+        assert_eq!(
+            reg.validate("binance", "DOGE-USDT"),
+            Err(CanonicalError::NotListed("DOGE-USDT".into()))
+        );
+        assert!(reg.get("binance_coinm", "BTC-USD").is_some());
+    }
+}
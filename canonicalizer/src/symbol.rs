@@ -0,0 +1,115 @@
+//! Interned canonical symbols.
+//!
+//! Canonical symbols repeat in every event, so [`Symbol`] stores them as a
+//! shared `Arc<str>` handed out by a process-wide table. Cloning a `Symbol` is
+//! a reference count bump and equal symbols share the same allocation.
+
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+static TABLE: OnceLock<RwLock<HashSet<Arc<str>>>> = OnceLock::new();
+
+/// Interned canonical symbol such as `BTC-USDT`.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// Return the interned symbol for `s`, allocating only the first time a
+    /// given string is seen.
+    pub fn intern(s: &str) -> Self {
+        let table = TABLE.get_or_init(|| RwLock::new(HashSet::new()));
+        if let Some(existing) = table.read().unwrap().get(s) {
+            return Self(existing.clone());
+        }
+        let mut set = table.write().unwrap();
+        if let Some(existing) = set.get(s) {
+            return Self(existing.clone());
+        }
+        let arc: Arc<str> = Arc::from(s);
+        set.insert(arc.clone());
+        Self(arc)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::intern(s)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(s: Symbol) -> Self {
+        s.0.to_string()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Self::intern(&s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+    use std::sync::Arc;
+
+    #[test]
+    fn equal_strings_share_allocation() {
+        let a = Symbol::intern("BTC-USDT");
+        let b = Symbol::from("BTC-USDT");
+        assert_eq!(a, b);
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(serde_json::to_string(&a).unwrap(), "\"BTC-USDT\"");
+        let back: Symbol = serde_json::from_str("\"BTC-USDT\"").unwrap();
+        assert!(Arc::ptr_eq(&a.0, &back.0));
+    }
+}
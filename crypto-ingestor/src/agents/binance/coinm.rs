@@ -0,0 +1,154 @@
+//! Binance COIN-M perpetual futures agent.
+//!
+//! Streams aggregate trades and mark price with the current funding rate for
+//! `*USD_PERP` contracts over combined streams from `binance_coinm_ws_url`,
+//! polling open interest from `binance_coinm_rest_url` when `--open-interest`
+//! is enabled. Messages share the USDⓈ-M format and are parsed by
+//! [`futures::parse_event`](super::futures::parse_event): `BTCUSD_PERP`
+//! becomes `BTC-USD` tagged `"ac": "perp"` and settling in `BTC`.
+
+use tokio::sync::mpsc;
+
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+};
+
+use super::futures::{open_interest_task, stream_task};
+use crate::agents::AgentFactory;
+
+/// Binance futures allow 200 streams per connection.
+const MAX_STREAMS_PER_CONN: usize = 200;
+const STREAM_KINDS: [&str; 2] = ["aggTrade", "markPrice@1s"];
+
+/// Fetch all trading coin-margined perpetual symbols.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "binance_coinm",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::BinanceCoinm);
+    limiter.acquire(1, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/dapi/v1/exchangeInfo"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .get("symbols")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| IngestorError::Other("binance coin-m unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|s| s.get("contractStatus").and_then(|st| st.as_str()) == Some("TRADING"))
+        .filter(|s| s.get("contractType").and_then(|c| c.as_str()) == Some("PERPETUAL"))
+        .filter_map(|s| s.get("symbol").and_then(|s| s.as_str()))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+pub struct BinanceCoinmAgent {
+    symbols: Vec<String>,
+    ws_url: String,
+    rest_url: Option<String>,
+    open_interest: bool,
+    max_reconnect_delay_secs: u64,
+}
+
+impl BinanceCoinmAgent {
+    pub fn new(symbols: Vec<String>, ws_url: String, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            ws_url,
+            rest_url: cfg.binance_coinm_rest_url.clone(),
+            open_interest: cfg.open_interest,
+            max_reconnect_delay_secs: cfg.binance_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for BinanceCoinmAgent {
+    fn name(&self) -> &'static str {
+        "binance_coinm"
+    }
+
+    async fn run(
+        &mut self,
+        shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let mut handles = Vec::new();
+
+        let per_conn = (MAX_STREAMS_PER_CONN / STREAM_KINDS.len()).max(1);
+        for chunk in self.symbols.chunks(per_conn) {
+            let streams: Vec<String> = chunk
+                .iter()
+                .flat_map(|s| STREAM_KINDS.map(|k| format!("{s}@{k}")))
+                .collect();
+            let url = format!("{}/stream?streams={}", self.ws_url, streams.join("/"));
+            handles.push(tokio::spawn(stream_task(
+                "binance_coinm",
+                url,
+                // No depth stream, so no book snapshots.
+                None,
+                shutdown.clone(),
+                tx.clone(),
+                self.max_reconnect_delay_secs,
+            )));
+        }
+
+        if let (Some(rest_url), true) = (&self.rest_url, self.open_interest) {
+            handles.push(tokio::spawn(open_interest_task(
+                self.symbols.clone(),
+                format!("{rest_url}/dapi/v1/openInterest"),
+                Exchange::BinanceCoinm,
+                shutdown.clone(),
+                tx.clone(),
+            )));
+        }
+
+        for h in handles {
+            let _ = h.await;
+        }
+        Ok(())
+    }
+}
+
+pub struct BinanceCoinmFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for BinanceCoinmFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let Some(ws_url) = cfg.binance_coinm_ws_url.clone() else {
+            tracing::error!("binance_coinm_ws_url not set");
+            return None;
+        };
+        let symbols = if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
+            let rest_url = cfg.binance_coinm_rest_url.as_deref()?;
+            match fetch_all_symbols(rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch binance coin-m symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(BinanceCoinmAgent::new(symbols, ws_url, cfg)))
+    }
+}
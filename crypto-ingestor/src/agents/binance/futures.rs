@@ -0,0 +1,675 @@
+//! Binance USDⓈ-M perpetual futures agent.
+//!
+//! Streams aggregate trades, depth diffs, mark price with the current funding
+//! rate, and liquidations for each configured symbol over combined streams
+//! from `binance_futures_ws_url`. Open interest is polled from
+//! `binance_futures_rest_url` when `--open-interest` is enabled, and basis is
+//! polled more often around funding settlements. Events are tagged
+//! `"ac": "perp"` and carry the contract `id`, e.g. `BTC-USDT-PERP`, so they
+//! never collide with spot events for the same symbol.
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    funding_window::{self, FundingPhase, FundingSchedule, BINANCE_FUNDING_HOURS},
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+
+use crate::agents::{
+    perp_id, symbol_or_raw, AgentFactory, BookSnapshot, BookSync, STREAM_SEQ_GAPS,
+};
+use canonicalizer::{AssetClass, CanonicalService, Symbol};
+
+/// Binance futures allow 200 streams per connection.
+const MAX_STREAMS_PER_CONN: usize = 200;
+const STREAM_KINDS: [&str; 4] = ["aggTrade", "depth@100ms", "markPrice@1s", "forceOrder"];
+const OPEN_INTEREST_POLL: Duration = Duration::from_secs(60);
+/// Basis polling interval, shortened around funding settlements.
+const TERM_POLL: Duration = Duration::from_secs(60);
+const TERM_POLL_NEAR_FUNDING: Duration = Duration::from_secs(10);
+
+/// Fetch all trading USDT-margined perpetual symbols.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "binance_futures",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::BinanceFutures);
+    limiter.acquire(1, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/fapi/v1/exchangeInfo"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .get("symbols")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| IngestorError::Other("binance futures unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|s| s.get("status").and_then(|st| st.as_str()) == Some("TRADING"))
+        .filter(|s| s.get("contractType").and_then(|c| c.as_str()) == Some("PERPETUAL"))
+        .filter(|s| s.get("quoteAsset").and_then(|q| q.as_str()) == Some("USDT"))
+        .filter_map(|s| s.get("symbol").and_then(|s| s.as_str()))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+pub struct BinanceFuturesAgent {
+    symbols: Vec<String>,
+    ws_url: String,
+    rest_url: Option<String>,
+    open_interest: bool,
+    funding_window: Option<Duration>,
+    funding_hours: Vec<u32>,
+    max_reconnect_delay_secs: u64,
+}
+
+impl BinanceFuturesAgent {
+    pub fn new(symbols: Vec<String>, ws_url: String, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            ws_url,
+            rest_url: cfg.binance_futures_rest_url.clone(),
+            open_interest: cfg.open_interest,
+            funding_window: Some(Duration::from_secs(60 * cfg.funding_window_mins))
+                .filter(|w| !w.is_zero()),
+            funding_hours: cfg
+                .funding_hours
+                .get("binance_futures")
+                .cloned()
+                .unwrap_or_else(|| BINANCE_FUNDING_HOURS.to_vec()),
+            max_reconnect_delay_secs: cfg.binance_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for BinanceFuturesAgent {
+    fn name(&self) -> &'static str {
+        "binance_futures"
+    }
+
+    async fn run(
+        &mut self,
+        shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let mut handles = Vec::new();
+
+        let per_conn = (MAX_STREAMS_PER_CONN / STREAM_KINDS.len()).max(1);
+        for chunk in self.symbols.chunks(per_conn) {
+            let streams: Vec<String> = chunk
+                .iter()
+                .flat_map(|s| STREAM_KINDS.map(|k| format!("{s}@{k}")))
+                .collect();
+            let url = format!("{}/stream?streams={}", self.ws_url, streams.join("/"));
+            handles.push(tokio::spawn(stream_task(
+                "binance_futures",
+                url,
+                self.rest_url.as_ref().map(|u| format!("{u}/fapi/v1/depth")),
+                shutdown.clone(),
+                tx.clone(),
+                self.max_reconnect_delay_secs,
+            )));
+        }
+
+        let funding = self
+            .funding_window
+            .map(|w| FundingSchedule::new("binance", &self.funding_hours, w));
+        if let Some(schedule) = funding.clone() {
+            handles.push(tokio::spawn(funding_window::run(
+                schedule,
+                shutdown.clone(),
+                tx.clone(),
+            )));
+        }
+        if let Some(rest_url) = &self.rest_url {
+            if self.open_interest {
+                handles.push(tokio::spawn(open_interest_task(
+                    self.symbols.clone(),
+                    format!("{rest_url}/fapi/v1/openInterest"),
+                    Exchange::BinanceFutures,
+                    shutdown.clone(),
+                    tx.clone(),
+                )));
+            }
+            handles.push(tokio::spawn(term_structure_task(
+                self.symbols.clone(),
+                rest_url.clone(),
+                shutdown.clone(),
+                tx.clone(),
+                funding,
+            )));
+        }
+
+        for h in handles {
+            let _ = h.await;
+        }
+        Ok(())
+    }
+}
+
+pub struct BinanceFuturesFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for BinanceFuturesFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let Some(ws_url) = cfg.binance_futures_ws_url.clone() else {
+            tracing::error!("binance_futures_ws_url not set");
+            return None;
+        };
+        let symbols = if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
+            let rest_url = cfg.binance_futures_rest_url.as_deref()?;
+            match fetch_all_symbols(rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch binance futures symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(BinanceFuturesAgent::new(symbols, ws_url, cfg)))
+    }
+}
+
+/// Stream combined `url` until shutdown, reconnecting with backoff. `name`
+/// labels dead letters and ingest stats. With a REST `depth_url`, each book
+/// starts from a depth snapshot: a symbol's first depth updates, and those
+/// after a gap, are held back until the snapshot has been published.
+pub(crate) async fn stream_task(
+    name: &'static str,
+    url: String,
+    depth_url: Option<String>,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    max_reconnect_delay_secs: u64,
+) {
+    let mut attempt: u32 = 0;
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+    let snapshots = http_client::builder().build().ok().zip(depth_url);
+    loop {
+        if *shutdown.borrow() {
+            break;
+        }
+        tracing::info!(%url, "connecting");
+        match http_client::connect_ws(&url).await {
+            Ok((mut ws, _)) => {
+                tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                attempt = 0;
+                // Depth update ids restart with each connection.
+                let mut depth_ids: HashMap<String, i64> = HashMap::new();
+                let mut books = BookSync::default();
+                loop {
+                    tokio::select! {
+                        _ = shutdown.changed() => {
+                            if *shutdown.borrow() {
+                                let _ = ws.close(None).await;
+                                return;
+                            }
+                        }
+                        Some((symbol, snapshot)) = books.snapshot() => {
+                            for line in books.publish(&symbol, snapshot) {
+                                if tx.send(line).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
+                        msg = ws.next() => {
+                            watchdog.feed();
+                            match msg {
+                                Some(Ok(Message::Text(txt))) => {
+                                    let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                        Ok(v) => v,
+                                        Err(e) => {
+                                            dead_letter::record(name, &txt, &e);
+                                            continue;
+                                        }
+                                    };
+                                    let gap = depth_gap(&v, &mut depth_ids);
+                                    if gap {
+                                        tracing::warn!(stream=?v.get("stream"), "depth sequence gap");
+                                    }
+                                    let range = snapshots.as_ref().and(depth_range(&v));
+                                    let lines = parse_event(&v, &mut last_trade_ids);
+                                    if lines.is_empty() && v.get("id").is_none() {
+                                        dead_letter::record_no_event(name, &txt);
+                                    }
+                                    for line in lines {
+                                        let line = match (range, &snapshots) {
+                                            (Some((raw, first, last)), Some((client, depth_url))) => {
+                                                match books.diff(raw, (first, last), gap, line) {
+                                                    Some(diff) => diff,
+                                                    None => {
+                                                        let (client, depth_url, symbol) = (client.clone(), depth_url.clone(), raw.to_string());
+                                                        books.resync(raw, async move {
+                                                            fetch_snapshot(&client, &depth_url, &symbol).await
+                                                        });
+                                                        continue;
+                                                    }
+                                                }
+                                            }
+                                            _ => line,
+                                        };
+                                        if tx.send(line).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                Some(Ok(_)) => {}
+                                Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                None => { tracing::warn!("stream ended"); break; }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!(error=%e, "connect failed");
+            }
+        }
+
+        attempt = attempt.saturating_add(1);
+        ingest_stats::record(name, Counter::Reconnect);
+        let exp: u32 = attempt.saturating_sub(1).min(4);
+        let delay = (1u64 << exp).min(max_reconnect_delay_secs);
+        let sleep = Duration::from_secs(delay);
+        tracing::info!(?sleep, "reconnecting");
+        tokio::select! {
+            _ = tokio::time::sleep(sleep) => {},
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Symbol and first and last update id `U`/`u` of a depth update.
+pub fn depth_range(v: &serde_json::Value) -> Option<(&str, u64, u64)> {
+    let v = v.get("data").unwrap_or(v);
+    if v.get("e").and_then(|e| e.as_str()) != Some("depthUpdate") {
+        return None;
+    }
+    Some((
+        v.get("s")?.as_str()?,
+        v.get("U")?.as_u64()?,
+        v.get("u")?.as_u64()?,
+    ))
+}
+
+/// Check a depth update's `pu` against the previous update's `u` for its
+/// symbol, returning `true` when updates were missed. Gaps are counted in
+/// [`STREAM_SEQ_GAPS`].
+pub fn depth_gap(v: &serde_json::Value, last_ids: &mut HashMap<String, i64>) -> bool {
+    let v = v.get("data").unwrap_or(v);
+    if v.get("e").and_then(|e| e.as_str()) != Some("depthUpdate") {
+        return false;
+    }
+    let (Some(sym), Some(prev), Some(last)) = (
+        v.get("s").and_then(|s| s.as_str()),
+        v.get("pu").and_then(|u| u.as_i64()),
+        v.get("u").and_then(|u| u.as_i64()),
+    ) else {
+        return false;
+    };
+    match last_ids.insert(sym.to_string(), last) {
+        Some(expected) if expected != prev => {
+            STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+            ingest_stats::record("binance_futures", Counter::Gap);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Fetch the REST depth of `symbol` from `depth_url` as a `snapshot` event.
+async fn fetch_snapshot(
+    client: &reqwest::Client,
+    depth_url: &str,
+    symbol: &str,
+) -> Option<BookSnapshot> {
+    let url = format!("{depth_url}?symbol={}&limit=1000", symbol.to_uppercase());
+    let limiter = rate_limit::limiter(Exchange::BinanceFutures);
+    // A 1000-level USD-M depth request weighs 20.
+    limiter.acquire(20, Priority::High).await;
+    let resp = match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot failed");
+            return None;
+        }
+    };
+    match resp.json::<serde_json::Value>().await {
+        Ok(v) => snapshot_event(symbol, &v),
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot parse failed");
+            None
+        }
+    }
+}
+
+/// `lastUpdateId` and canonical `snapshot` line of the perpetual `raw` from a
+/// REST depth response.
+pub fn snapshot_event(raw: &str, resp: &serde_json::Value) -> Option<BookSnapshot> {
+    let last_update_id = resp.get("lastUpdateId")?.as_u64()?;
+    let sym = symbol_or_raw("binance", &raw.to_uppercase());
+    let line = serde_json::json!({
+        "agent": "binance",
+        "type": "snapshot",
+        "s": sym,
+        "ac": AssetClass::Perp,
+        "settle": perp_settle(raw, &sym),
+        "id": perp_id(&sym),
+        "bids": levels(resp.get("bids")),
+        "asks": levels(resp.get("asks")),
+        "ts": resp
+            .get("E")
+            .and_then(|t| t.as_i64())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis())
+    });
+    Some((last_update_id, line.to_string()))
+}
+
+/// Settlement currency of a futures contract: coin-margined symbols such as
+/// `BTCUSD_PERP` settle in the base asset, USDⓈ-margined ones in the quote.
+/// Face value in USD of a coin-margined contract: 100 for BTC and 10 for
+/// every other coin. USDⓈ-M quantities are in the base asset instead.
+fn coinm_contract_size(raw: &str) -> Option<&'static str> {
+    if !raw.contains('_') {
+        return None;
+    }
+    Some(if raw.starts_with("BTCUSD_") {
+        "100"
+    } else {
+        "10"
+    })
+}
+
+fn perp_settle<'a>(raw: &str, canon: &'a str) -> &'a str {
+    match canon.split_once('-') {
+        Some((base, _)) if raw.contains('_') => base,
+        Some((_, quote)) => quote,
+        None => canon,
+    }
+}
+
+/// Convert a futures stream event, bare or wrapped in a combined stream
+/// envelope, into canonical event lines. A mark price update yields both a
+/// `mark_price` and a `funding` event. COIN-M trades and liquidations count
+/// contracts, so they carry `"q_unit": "contracts"` and the contract's USD
+/// `contract_size`.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Vec<String> {
+    let v = v.get("data").unwrap_or(v);
+    let ev = v.get("e").and_then(|e| e.as_str()).unwrap_or("");
+    // Liquidations carry the symbol inside the order.
+    let order = v.get("o");
+    let Some(raw) = v
+        .get("s")
+        .or_else(|| order.and_then(|o| o.get("s")))
+        .and_then(|s| s.as_str())
+    else {
+        return Vec::new();
+    };
+    let sym = symbol_or_raw("binance", raw);
+    let settle = perp_settle(raw, &sym);
+    let id = perp_id(&sym);
+    let dec = |src: Option<&serde_json::Value>, k: &str| {
+        src.and_then(|s| s.get(k))
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    let ts = |k: &str| v.get(k).and_then(|x| x.as_i64()).unwrap_or_default();
+
+    let mut events = match ev {
+        "aggTrade" => {
+            let trade_id = v.get("a").and_then(|t| t.as_i64()).filter(|id| *id > 0);
+            if let Some(id) = trade_id {
+                last_trade_ids.insert(sym.clone(), id);
+            }
+            vec![serde_json::json!({
+                "agent": "binance",
+                "type": "trade",
+                "s": sym,
+                "ac": AssetClass::Perp,
+                "settle": settle,
+                "id": id,
+                "t": trade_id,
+                "p": dec(Some(v), "p"),
+                "q": dec(Some(v), "q"),
+                "ts": ts("T"),
+                "skew": clock::current_skew_ms()
+            })]
+        }
+        "depthUpdate" => vec![serde_json::json!({
+            "agent": "binance",
+            "type": "l2_diff",
+            "s": sym,
+            "ac": AssetClass::Perp,
+            "settle": settle,
+            "id": id,
+            "bids": levels(v.get("b")),
+            "asks": levels(v.get("a")),
+            "ts": ts("E")
+        })],
+        "markPriceUpdate" => vec![
+            serde_json::json!({
+                "agent": "binance",
+                "type": "mark_price",
+                "s": sym,
+                "ac": AssetClass::Perp,
+                "settle": settle,
+                "id": id,
+                "p": dec(Some(v), "p"),
+                "ts": ts("E")
+            }),
+            serde_json::json!({
+                "agent": "binance",
+                "type": "funding",
+                "s": sym,
+                "ac": AssetClass::Perp,
+                "settle": settle,
+                "id": id,
+                "r": dec(Some(v), "r"),
+                "ts": ts("E")
+            }),
+        ],
+        "forceOrder" => vec![serde_json::json!({
+            "agent": "binance",
+            "type": "liquidation",
+            "s": sym,
+            "ac": AssetClass::Perp,
+            "settle": settle,
+            "id": id,
+            "p": dec(order, "p"),
+            "q": dec(order, "q"),
+            "side": order.and_then(|o| o.get("S")).and_then(|s| s.as_str()).unwrap_or("?"),
+            "ts": ts("E")
+        })],
+        _ => Vec::new(),
+    };
+    if let Some(size) = coinm_contract_size(raw) {
+        for e in events.iter_mut().filter(|e| e.get("q").is_some()) {
+            e["q_unit"] = "contracts".into();
+            e["contract_size"] = size.into();
+        }
+    }
+    events.into_iter().map(|e| e.to_string()).collect()
+}
+
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
+
+/// Poll open interest for `symbols` from the `openInterest` `endpoint`.
+pub(crate) async fn open_interest_task(
+    symbols: Vec<String>,
+    endpoint: String,
+    exchange: Exchange,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+) {
+    let client = match http_client::builder().build() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let mut poll = tokio::time::interval(OPEN_INTEREST_POLL);
+    poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+            _ = poll.tick() => {
+                let limiter = rate_limit::limiter(exchange);
+                for sym in &symbols {
+                    let url = format!("{}?symbol={}", endpoint, sym.to_uppercase());
+                    limiter.acquire(1, Priority::Low).await;
+                    let Ok(resp) = client
+                        .get(&url)
+                        .send()
+                        .await
+                        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+                    else {
+                        continue;
+                    };
+                    let Ok(v) = resp.json::<serde_json::Value>().await else { continue };
+                    let Some(raw) = v.get("symbol").and_then(|s| s.as_str()) else { continue };
+                    let canon = CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
+                    let oi = v
+                        .get("openInterest")
+                        .and_then(|o| o.as_str())
+                        .and_then(parse_decimal_str)
+                        .unwrap_or_else(|| "?".to_string());
+                    let line = serde_json::json!({
+                        "agent": "binance",
+                        "type": "open_interest",
+                        "s": canon,
+                        "ac": AssetClass::Perp,
+                        "settle": perp_settle(raw, &canon),
+                        "id": perp_id(&canon),
+                        "oi": oi,
+                        "ts": v.get("time").and_then(|t| t.as_i64()).unwrap_or_default()
+                    }).to_string();
+                    if tx.send(line).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn term_structure_task(
+    symbols: Vec<String>,
+    rest_url: String,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    funding: Option<FundingSchedule>,
+) {
+    let client = match http_client::builder().build() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let mut delay = Duration::ZERO;
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+            _ = tokio::time::sleep(delay) => {
+                let near_funding = funding.as_ref().is_some_and(|f| {
+                    f.phase(chrono::Utc::now().timestamp_millis()).0 != FundingPhase::Idle
+                });
+                delay = if near_funding { TERM_POLL_NEAR_FUNDING } else { TERM_POLL };
+                let limiter = rate_limit::limiter(Exchange::BinanceFutures);
+                for sym in &symbols {
+                    let url = format!("{}/futures/data/basis?symbol={}&period=5m&limit=1", rest_url, sym.to_uppercase());
+                    limiter.acquire(1, Priority::Low).await;
+                    if let Ok(resp) = client
+                        .get(&url)
+                        .send()
+                        .await
+                        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+                    {
+                        if let Ok(resp) = resp.json::<serde_json::Value>().await {
+                            if let Some(arr) = resp.as_array().and_then(|a| a.first()) {
+                                let raw = arr.get("symbol").and_then(|s| s.as_str()).unwrap_or("?");
+                                let canon = CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
+                                let basis = arr
+                                    .get("basis")
+                                    .and_then(|b| b.as_str())
+                                    .and_then(parse_decimal_str)
+                                    .unwrap_or_else(|| "?".to_string());
+                                let ts = arr.get("timestamp").and_then(|t| t.as_i64()).unwrap_or_default();
+                                let line = serde_json::json!({
+                                    "agent": "binance",
+                                    "type": "term",
+                                    "s": canon,
+                                    "ac": AssetClass::Perp,
+                                    "settle": perp_settle(raw, &canon),
+                                    "id": perp_id(&canon),
+                                    "b": basis,
+                                    "ts": ts
+                                }).to_string();
+                                let _ = tx.send(line).await;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -4,13 +4,40 @@ use canonicalizer::{CanonicalService, FeeSchedule, FeeTier, Listing};
 use chrono::Utc;
 use tokio::time::{interval, Duration, MissedTickBehavior};
 
-use crate::{error::IngestorError, http_client, sink::DynSink};
+use crate::{
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+    sink::DynSink,
+};
+
+/// Request weight of the spot `exchangeInfo` endpoint.
+pub const EXCHANGE_INFO_WEIGHT: i64 = 20;
 
 /// Poll Binance REST endpoints for listing and fee metadata and emit canonical events.
-pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink) {
+/// The USDⓈ-M and COIN-M `exchangeInfo` are loaded into the instrument
+/// registry as `binance_futures` and `binance_coinm` alongside spot.
+pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink, cfg: &Settings) {
     let mut prev_listings: HashMap<String, Listing> = HashMap::new();
     let mut prev_fee: Option<FeeSchedule> = None;
+    let derivatives: Vec<(&str, String)> = [
+        (
+            "binance_futures",
+            cfg.binance_futures_rest_url.as_deref(),
+            "fapi",
+        ),
+        (
+            "binance_coinm",
+            cfg.binance_coinm_rest_url.as_deref(),
+            "dapi",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(market, url, api)| Some((market, format!("{}/{api}/v1/exchangeInfo", url?))))
+    .collect();
 
+    load_derivatives(&derivatives).await;
     if let Ok((listings, fee)) = fetch().await {
         for listing in listings.values() {
             if let Ok(line) = serde_json::to_string(listing) {
@@ -33,6 +60,7 @@ pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink
                 if *shutdown.borrow() { break; }
             }
             _ = ticker.tick() => {
+                load_derivatives(&derivatives).await;
                 match fetch().await {
                     Ok((listings, fee)) => {
                         for (sym, listing) in &listings {
@@ -59,6 +87,43 @@ pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink
     }
 }
 
+/// Load the `exchangeInfo` of each `(market, url)` into the instrument
+/// registry.
+async fn load_derivatives(markets: &[(&str, String)]) {
+    let client = match http_client::builder().build() {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!(error=%e, "binance derivatives metadata client");
+            return;
+        }
+    };
+    for (market, url) in markets {
+        let limiter = rate_limit::limiter(match *market {
+            "binance_coinm" => Exchange::BinanceCoinm,
+            _ => Exchange::BinanceFutures,
+        });
+        limiter.acquire(1, Priority::Low).await;
+        let resp = match client
+            .get(url)
+            .send()
+            .await
+            .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        {
+            Ok(r) => r.json::<serde_json::Value>().await,
+            Err(e) => Err(e),
+        };
+        match resp {
+            Ok(info) => {
+                let n = CanonicalService::update_instruments(|r| {
+                    r.load_binance_exchange_info(market, &info)
+                });
+                tracing::debug!(%market, instruments = n, "loaded exchange info");
+            }
+            Err(e) => tracing::error!(error=%e, %market, "binance derivatives metadata fetch"),
+        }
+    }
+}
+
 async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorError> {
     let client = http_client::builder()
         .build()
@@ -68,10 +133,13 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
             symbol: None,
         })?;
 
+    let limiter = rate_limit::binance();
+    limiter.acquire(EXCHANGE_INFO_WEIGHT, Priority::Low).await;
     let exchange_info: serde_json::Value = client
         .get("https://api.binance.us/api/v3/exchangeInfo")
         .send()
         .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
         .map_err(|e| IngestorError::Http {
             source: e,
             exchange: "binance",
@@ -86,10 +154,16 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
         })?;
 
     // poll system status endpoint for completeness; ignore errors/response
+    limiter.acquire(1, Priority::Low).await;
     let _ = client
         .get("https://api.binance.us/sapi/v1/system/status")
         .send()
-        .await;
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()));
+
+    CanonicalService::update_instruments(|r| {
+        r.load_binance_exchange_info("binance", &exchange_info)
+    });
 
     let ts = Utc::now().timestamp_millis();
     let mut listings = HashMap::new();
@@ -101,19 +175,22 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
             let raw = sym.get("symbol").and_then(|s| s.as_str()).unwrap_or("");
             let base = sym.get("baseAsset").and_then(|s| s.as_str()).unwrap_or("");
             let quote = sym.get("quoteAsset").and_then(|s| s.as_str()).unwrap_or("");
-            let lot_size = sym
-                .get("filters")
-                .and_then(|f| f.as_array())
-                .and_then(|fa| {
-                    fa.iter().find_map(|flt| {
-                        if flt.get("filterType").and_then(|t| t.as_str()) == Some("LOT_SIZE") {
-                            flt.get("stepSize").and_then(|s| s.as_str())
-                        } else {
-                            None
-                        }
+            let filter = |kind: &str, field: &str| {
+                sym.get("filters")
+                    .and_then(|f| f.as_array())
+                    .and_then(|fa| {
+                        fa.iter().find_map(|flt| {
+                            if flt.get("filterType").and_then(|t| t.as_str()) == Some(kind) {
+                                flt.get(field).and_then(|s| s.as_str())
+                            } else {
+                                None
+                            }
+                        })
                     })
-                })
-                .map(|s| s.to_string());
+                    .map(|s| s.to_string())
+            };
+            let lot_size = filter("LOT_SIZE", "stepSize");
+            let tick_size = filter("PRICE_FILTER", "tickSize");
             let canon =
                 CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
             let listing = Listing {
@@ -123,6 +200,7 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
                 base: base.to_string(),
                 quote: quote.to_string(),
                 lot_size,
+                tick_size,
                 timestamp: ts,
             };
             listings.insert(raw.to_string(), listing);
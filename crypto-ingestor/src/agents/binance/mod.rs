@@ -1,25 +1,33 @@
 use futures_util::{SinkExt, StreamExt};
+pub mod coinm;
+pub mod futures;
 pub mod metadata;
 pub mod ohlcv;
 pub mod options;
 use std::collections::{HashMap, HashSet};
 use tokio::sync::mpsc;
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
 use crate::clock;
 use crate::{
     agent::Agent,
     config::Settings,
+    dead_letter,
     error::IngestorError,
     http_client,
+    ingest_stats::{self, Counter},
     parse::parse_decimal_str,
+    rate_limit::{self, Priority},
+    watchdog::{self, Watchdog},
 };
 
-use super::{shared_symbols, AgentFactory};
-use canonicalizer::CanonicalService;
+use super::universe::Universe;
+use super::{listing_events, shared_symbols, symbol_or_raw, AgentFactory, SnapshotPacer};
+use canonicalizer::{CanonicalService, Symbol};
 
 const MAX_STREAMS_PER_CONN: usize = 1024; // per Binance docs
 const STREAMS_PER_SYMBOL: usize = 3; // trade, depth diff, book ticker
+const MAX_SUBSCRIBE_RETRIES: u32 = 3;
 
 /// Fetch all tradable symbols from Binance US REST API.
 pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
@@ -30,10 +38,15 @@ pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
             exchange: "binance",
             symbol: None,
         })?;
+    let limiter = rate_limit::binance();
+    limiter
+        .acquire(metadata::EXCHANGE_INFO_WEIGHT, Priority::High)
+        .await;
     let resp: serde_json::Value = client
         .get("https://api.binance.us/api/v3/exchangeInfo")
         .send()
         .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
         .map_err(|e| IngestorError::Http {
             source: e,
             exchange: "binance",
@@ -72,31 +85,38 @@ pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
 
 pub struct BinanceAgent {
     symbols: Vec<String>,
+    universe: Universe,
     ws_url: String,
     max_reconnect_delay_secs: u64,
     refresh_interval_mins: u64,
-    futures_ws_url: Option<String>,
-    futures_rest_url: Option<String>,
-    open_interest: bool,
+    resync_secs: u64,
+    new_listing_window: std::time::Duration,
 }
 
 impl BinanceAgent {
     pub async fn new(symbols: Option<Vec<String>>, cfg: &Settings) -> Result<Self, IngestorError> {
-        let symbols = match symbols {
-            Some(v) => v,
-            None => fetch_all_symbols().await?,
+        let (symbols, universe) = match symbols {
+            Some(v) => (v.clone(), Universe::Symbols(v)),
+            None => (fetch_all_symbols().await?, Universe::All),
         };
 
         Ok(Self {
             symbols,
+            universe,
             ws_url: cfg.binance_ws_url.clone(),
             max_reconnect_delay_secs: cfg.binance_max_reconnect_delay_secs,
             refresh_interval_mins: cfg.binance_refresh_interval_mins,
-            futures_ws_url: cfg.binance_futures_ws_url.clone(),
-            futures_rest_url: cfg.binance_futures_rest_url.clone(),
-            open_interest: cfg.open_interest,
+            resync_secs: cfg.subscription_resync_secs,
+            new_listing_window: std::time::Duration::from_secs(60 * cfg.new_listing_window_mins),
         })
     }
+
+    /// Re-evaluate `universe` on each symbol refresh instead of the symbols
+    /// the agent was created with.
+    pub fn with_universe(mut self, universe: Universe) -> Self {
+        self.universe = universe;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -127,59 +147,25 @@ impl Agent for BinanceAgent {
             symbol_txs.push(sym_tx);
             let shutdown_rx = shutdown.clone();
             let max_delay = self.max_reconnect_delay_secs;
+            let resync = self.resync_secs;
             let ws_url = self.ws_url.clone();
             let tx_clone = out_tx.clone();
             handles.push(tokio::spawn(async move {
-                connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay).await;
-            }));
-        }
-        // additional aggregated streams not tied to symbol subsets
-        if let Some(ws_url) = &self.futures_ws_url {
-            let shutdown_clone = shutdown.clone();
-            let tx_clone = out_tx.clone();
-            let url = ws_url.clone();
-            handles.push(tokio::spawn(async move {
-                mark_price_task(&url, shutdown_clone, tx_clone).await;
-            }));
-
-            let shutdown_clone = shutdown.clone();
-            let tx_clone = out_tx.clone();
-            let url = ws_url.clone();
-            handles.push(tokio::spawn(async move {
-                funding_rate_task(&url, shutdown_clone, tx_clone).await;
-            }));
-
-            if self.open_interest {
-                let shutdown_clone = shutdown.clone();
-                let tx_clone = out_tx.clone();
-                let url = ws_url.clone();
-                handles.push(tokio::spawn(async move {
-                    open_interest_task(&url, shutdown_clone, tx_clone).await;
-                }));
-            }
-
-            let shutdown_clone = shutdown.clone();
-            let tx_clone = out_tx.clone();
-            let url = ws_url.clone();
-            handles.push(tokio::spawn(async move {
-                liquidation_task(&url, shutdown_clone, tx_clone).await;
-            }));
-        }
-
-        if let Some(rest_url) = &self.futures_rest_url {
-            let symbols_clone = self.symbols.clone();
-            let shutdown_clone = shutdown.clone();
-            let tx_clone = out_tx.clone();
-            let url = rest_url.clone();
-            handles.push(tokio::spawn(async move {
-                term_structure_task(symbols_clone, &url, shutdown_clone, tx_clone).await;
+                connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay, resync).await;
             }));
         }
+        let mut snapshots = HashMap::new();
         for sym in self.symbols.clone() {
             let tx_clone = out_tx.clone();
-            handles.push(tokio::spawn(async move {
-                snapshot_task(sym, tx_clone).await;
-            }));
+            let shutdown_snap = shutdown.clone();
+            let task_sym = sym.clone();
+            snapshots.insert(
+                sym,
+                tokio::spawn(async move {
+                    snapshot_task(task_sym, shutdown_snap, tx_clone, std::time::Duration::ZERO)
+                        .await;
+                }),
+            );
         }
 
         let mut refresh = tokio::time::interval(std::time::Duration::from_secs(
@@ -193,7 +179,7 @@ impl Agent for BinanceAgent {
                     if *shutdown.borrow() { break; }
                 }
                 _ = refresh.tick() => {
-                    match fetch_all_symbols().await {
+                    match self.universe.resolve("binance").await {
                         Ok(new_symbols) => {
                             let new_set: HashSet<_> = new_symbols.iter().cloned().collect();
                             let old_set: HashSet<_> = self.symbols.iter().cloned().collect();
@@ -204,6 +190,24 @@ impl Agent for BinanceAgent {
                                 tracing::info!("symbol refresh: no changes");
                             } else {
                                 tracing::info!(?added, ?removed, total=new_symbols.len(), "symbol refresh");
+                                for line in listing_events("binance", &added, &removed) {
+                                    let _ = out_tx.send(line).await;
+                                }
+                                for sym in &removed {
+                                    if let Some(h) = snapshots.remove(sym) {
+                                        h.abort();
+                                    }
+                                }
+                                // New listings get accelerated snapshots for a while.
+                                for sym in added {
+                                    let tx_clone = out_tx.clone();
+                                    let shutdown_snap = shutdown.clone();
+                                    let boost = self.new_listing_window;
+                                    let task_sym = sym.clone();
+                                    snapshots.insert(sym, tokio::spawn(async move {
+                                        snapshot_task(task_sym, shutdown_snap, tx_clone, boost).await;
+                                    }));
+                                }
                                 // historical funding and open interest backfill removed
                                 self.symbols = new_symbols;
 
@@ -227,9 +231,10 @@ impl Agent for BinanceAgent {
                                         let shutdown_rx = shutdown.clone();
                                         let tx_conn = out_tx.clone();
                                         let max_delay = self.max_reconnect_delay_secs;
+                                        let resync = self.resync_secs;
                                         let ws_url = self.ws_url.clone();
                                         handles.push(tokio::spawn(async move {
-                                            connection_task(rx, shutdown_rx, tx_conn, ws_url, max_delay).await;
+                                            connection_task(rx, shutdown_rx, tx_conn, ws_url, max_delay, resync).await;
                                         }));
                                     }
                                 } else {
@@ -254,7 +259,7 @@ impl Agent for BinanceAgent {
             }
         }
 
-        for h in handles {
+        for h in handles.into_iter().chain(snapshots.into_values()) {
             let _ = h.await;
         }
 
@@ -267,25 +272,39 @@ pub struct BinanceFactory;
 #[async_trait::async_trait]
 impl AgentFactory for BinanceFactory {
     async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
-        let symbols = if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
-            match shared_symbols().await {
-                Ok((b, _)) => Some(b),
+        let (symbols, universe) = match Universe::parse(spec, cfg.universe_categories.as_deref()) {
+            Some(Ok(universe)) => match universe.resolve("binance").await {
+                Ok(symbols) => (symbols, universe),
                 Err(e) => {
-                    tracing::error!(error=%e, "failed to fetch shared symbols");
+                    tracing::error!(error=%e, "failed to resolve binance symbol universe");
                     return None;
                 }
+            },
+            Some(Err(e)) => {
+                tracing::error!(error=%e, %spec, "invalid binance symbol universe");
+                return None;
+            }
+            None if spec.is_empty() || spec.eq_ignore_ascii_case("all") => {
+                match shared_symbols().await {
+                    Ok((b, _)) => (b, Universe::All),
+                    Err(e) => {
+                        tracing::error!(error=%e, "failed to fetch shared symbols");
+                        return None;
+                    }
+                }
             }
-        } else {
-            Some(
-                spec.split(',')
+            None => {
+                let symbols = spec
+                    .split(',')
                     .map(|s| s.trim().to_lowercase())
                     .filter(|s| !s.is_empty())
-                    .collect::<Vec<_>>(),
-            )
+                    .collect::<Vec<_>>();
+                (symbols.clone(), Universe::Symbols(symbols))
+            }
         };
 
-        match BinanceAgent::new(symbols, cfg).await {
-            Ok(agent) => Some(Box::new(agent)),
+        match BinanceAgent::new(Some(symbols), cfg).await {
+            Ok(agent) => Some(Box::new(agent.with_universe(universe))),
             Err(e) => {
                 tracing::error!(error=%e, "failed to create binance agent");
                 None
@@ -300,9 +319,10 @@ async fn connection_task(
     tx: mpsc::Sender<String>,
     ws_url: String,
     max_reconnect_delay_secs: u64,
+    resync_secs: u64,
 ) {
     let mut attempt: u32 = 0;
-    let mut last_trade_ids: HashMap<String, i64> = HashMap::new();
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
 
     loop {
         if *shutdown.borrow() {
@@ -311,16 +331,25 @@ async fn connection_task(
 
         tracing::info!(url = %ws_url, "connecting");
         let mut current_symbols = symbols_rx.borrow().clone();
-        match connect_async(&ws_url).await {
+        match http_client::connect_ws(&ws_url).await {
             Ok((mut ws, _)) => {
                 tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
                 attempt = 0;
 
-                if let Err(e) = send_subscribe(&mut ws, &current_symbols).await {
+                let mut pending = PendingRequests::default();
+                if let Err(e) = send_subscribe(&mut ws, &mut pending, &current_symbols).await {
                     tracing::error!(error=%e, "failed to send subscription");
                     continue;
                 }
 
+                let resync_period = std::time::Duration::from_secs(resync_secs.max(1));
+                let mut resync = tokio::time::interval_at(
+                    tokio::time::Instant::now() + resync_period,
+                    resync_period,
+                );
+                resync.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
                 loop {
                     tokio::select! {
                         _ = shutdown.changed() => {
@@ -330,6 +359,11 @@ async fn connection_task(
                                 return;
                             }
                         }
+                        _ = resync.tick() => {
+                            if send_streams(&mut ws, &mut pending, "LIST_SUBSCRIPTIONS", &[], 0).await.is_err() {
+                                break;
+                            }
+                        }
                         changed = symbols_rx.changed() => {
                             if changed.is_ok() {
                                 let new_syms = symbols_rx.borrow().clone();
@@ -340,10 +374,10 @@ async fn connection_task(
                                     let to_unsub: Vec<_> = old_set.difference(&new_set).cloned().collect();
 
                                     if !to_unsub.is_empty() {
-                                        let _ = send_unsubscribe(&mut ws, &to_unsub).await;
+                                        let _ = send_unsubscribe(&mut ws, &mut pending, &to_unsub).await;
                                     }
                                     if !to_sub.is_empty() {
-                                        if let Err(e) = send_subscribe(&mut ws, &to_sub).await {
+                                        if let Err(e) = send_subscribe(&mut ws, &mut pending, &to_sub).await {
                                             tracing::error!(error=%e, "failed to update subscription");
                                             break;
                                         }
@@ -354,145 +388,88 @@ async fn connection_task(
                                 break;
                             }
                         }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
                         msg = ws.next() => {
+                            watchdog.feed();
                             match msg {
                                 Some(Ok(Message::Text(txt))) => {
-                                    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&txt) {
-                                        if v.get("id").and_then(|id| id.as_i64()) == Some(1) {
+                                    let parsed = serde_json::from_str::<serde_json::Value>(&txt);
+                                    if let Err(e) = &parsed {
+                                        dead_letter::record("binance", &txt, e);
+                                    }
+                                    if let Ok(v) = parsed {
+                                        let request = v
+                                            .get("id")
+                                            .and_then(|id| id.as_u64())
+                                            .and_then(|id| pending.complete(id));
+                                        if let Some(req) = request.as_ref().filter(|r| r.method == "LIST_SUBSCRIPTIONS") {
                                             if let Some(err) = v.get("error") {
-                                                tracing::error!(?err, "subscription error");
-                                                break;
+                                                tracing::warn!(?err, id = req.id, "LIST_SUBSCRIPTIONS failed");
+                                                continue;
+                                            }
+                                            let active: HashSet<String> = v
+                                                .get("result")
+                                                .and_then(|r| r.as_array())
+                                                .map(|a| a.iter().filter_map(|s| s.as_str().map(str::to_string)).collect())
+                                                .unwrap_or_default();
+                                            let desired: HashSet<String> = symbol_streams(&current_symbols).into_iter().collect();
+                                            let missing: Vec<_> = desired.difference(&active).cloned().collect();
+                                            let extra: Vec<_> = active.difference(&desired).cloned().collect();
+                                            if missing.is_empty() && extra.is_empty() {
+                                                tracing::debug!("subscriptions in sync");
                                             } else {
-                                                tracing::info!("subscription acknowledged");
+                                                tracing::warn!(?missing, ?extra, "subscription drift; resyncing");
+                                                if !extra.is_empty() {
+                                                    let _ = send_streams(&mut ws, &mut pending, "UNSUBSCRIBE", &extra, 0).await;
+                                                }
+                                                if !missing.is_empty() {
+                                                    if let Err(e) = send_streams(&mut ws, &mut pending, "SUBSCRIBE", &missing, 0).await {
+                                                        tracing::error!(error=%e, "failed to resync subscription");
+                                                        break;
+                                                    }
+                                                }
                                             }
                                             continue;
                                         }
-
-                                        let ev = v.get("e").and_then(|e| e.as_str()).unwrap_or("");
-                                        let raw = v.get("s").and_then(|s| s.as_str()).unwrap_or("?");
-                                        let sym = CanonicalService::canonical_pair("binance", raw)
-                                            .unwrap_or_else(|| raw.to_string());
-                                        match ev {
-                                            "trade" => {
-                                                let trade_id = v
-                                                    .get("t")
-                                                    .and_then(|t| t.as_i64())
-                                                    .filter(|id| *id > 0);
-                                                if let Some(id) = trade_id {
-                                                    if let Some(last) = last_trade_ids.get_mut(&sym) {
-                                                      *last = id;
-                                                    } else {
-                                                        last_trade_ids.insert(sym.clone(), id);
+                                        if let Some(req) = request {
+                                            if let Some(err) = v.get("error") {
+                                                tracing::error!(
+                                                    ?err,
+                                                    id = req.id,
+                                                    method = req.method,
+                                                    streams = ?req.streams,
+                                                    "subscription request rejected"
+                                                );
+                                                if req.attempts < MAX_SUBSCRIBE_RETRIES {
+                                                    if let Err(e) = send_streams(&mut ws, &mut pending, req.method, &req.streams, req.attempts + 1).await {
+                                                        tracing::error!(error=%e, "failed to retry subscription");
+                                                        break;
                                                     }
+                                                } else {
+                                                    tracing::error!(
+                                                        method = req.method,
+                                                        streams = ?req.streams,
+                                                        "giving up on rejected streams"
+                                                    );
                                                 }
-                                                let px = match v
-                                                    .get("p")
-                                                    .and_then(|p| p.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                {
-                                                    Some(p) => p,
-                                                    None => {                                                        "?".to_string()
-                                                    }
-                                                };
-                                                let qty = match v
-                                                    .get("q")
-                                                    .and_then(|q| q.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                {
-                                                    Some(q) => q,
-                                                    None => {                                                        "?".to_string()
-                                                    }
-                                                };
-                                                  let ts = v.get("T").and_then(|x| x.as_i64()).unwrap_or_default();
-                                                  let skew = clock::current_skew_ms();
-                                                let line = serde_json::json!({
-                                                    "agent": "binance",
-                                                    "type": "trade",
-                                                    "s": sym,
-                                                    "t": trade_id,
-                                                    "p": px,
-                                                    "q": qty,
-                                                    "ts": ts,
-                                                    "skew": skew
-                                                })
-                                                .to_string();
-                                                  if tx.send(line).await.is_err() {
-                                                      break;
-                                                  }
-                                            }
-                                            "depthUpdate" => {
-                                                let bids = v
-                                                    .get("b")
-                                                    .and_then(|b| b.as_array())
-                                                    .cloned()
-                                                    .unwrap_or_default()
-                                                    .into_iter()
-                                                    .filter_map(|lvl| {
-                                                        let p = lvl.get(0)?.as_str()?.to_string();
-                                                        let q = lvl.get(1)?.as_str()?.to_string();
-                                                        Some([p, q])
-                                                    })
-                                                    .collect::<Vec<[String;2]>>();
-                                                let asks = v
-                                                    .get("a")
-                                                    .and_then(|a| a.as_array())
-                                                    .cloned()
-                                                    .unwrap_or_default()
-                                                    .into_iter()
-                                                    .filter_map(|lvl| {
-                                                        let p = lvl.get(0)?.as_str()?.to_string();
-                                                        let q = lvl.get(1)?.as_str()?.to_string();
-                                                        Some([p, q])
-                                                    })
-                                                    .collect::<Vec<[String;2]>>();
-                                                let ts = v.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
-                                                let line = serde_json::json!({
-                                                    "agent": "binance",
-                                                    "type": "l2_diff",
-                                                    "s": sym,
-                                                    "bids": bids,
-                                                    "asks": asks,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_ok() {
-                                                } else { break; }
+                                            } else {
+                                                tracing::info!(id = req.id, method = req.method, "subscription acknowledged");
                                             }
-                                            "bookTicker" => {
-                                                let bid_px = v
-                                                    .get("b")
-                                                    .and_then(|p| p.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let bid_qty = v
-                                                    .get("B")
-                                                    .and_then(|q| q.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let ask_px = v
-                                                    .get("a")
-                                                    .and_then(|p| p.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let ask_qty = v
-                                                    .get("A")
-                                                    .and_then(|q| q.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let ts = v.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
-                                                let line = serde_json::json!({
-                                                    "agent": "binance",
-                                                    "type": "book_ticker",
-                                                    "s": sym,
-                                                    "bp": bid_px,
-                                                    "bq": bid_qty,
-                                                    "ap": ask_px,
-                                                    "aq": ask_qty,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_ok() {
-                                                } else { break; }
+                                            continue;
+                                        }
+
+                                        let parsed = parse_event(&v, &mut last_trade_ids);
+                                        if parsed.is_none() && v.get("id").is_none() {
+                                            dead_letter::record_no_event("binance", &txt);
+                                        }
+                                        if let Some(line) = parsed {
+                                            if tx.send(line).await.is_err() {
+                                                break;
                                             }
-                                            _ => {}
                                         }
                                     } else {
                                         tracing::warn!("non-json text msg");
@@ -514,6 +491,7 @@ async fn connection_task(
         }
 
         attempt = attempt.saturating_add(1);
+        ingest_stats::record("binance", Counter::Reconnect);
         let exp: u32 = attempt.saturating_sub(1).min(4);
         let delay = (1u64 << exp).min(max_reconnect_delay_secs);
         let sleep = std::time::Duration::from_secs(delay);
@@ -531,7 +509,104 @@ async fn connection_task(
     }
 }
 
-async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
+/// Convert a spot stream event (trade, depth diff or book ticker) into an
+/// output line. Returns `None` for other events.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Option<String> {
+    let ev = v.get("e").and_then(|e| e.as_str()).unwrap_or("");
+    let raw = v.get("s").and_then(|s| s.as_str()).unwrap_or("?");
+    let sym = symbol_or_raw("binance", raw);
+    let line = match ev {
+        "trade" => {
+            let trade_id = v.get("t").and_then(|t| t.as_i64()).filter(|id| *id > 0);
+            if let Some(id) = trade_id {
+                if let Some(last) = last_trade_ids.get_mut(&sym) {
+                    *last = id;
+                } else {
+                    last_trade_ids.insert(sym.clone(), id);
+                }
+            }
+            let px = v
+                .get("p")
+                .and_then(|p| p.as_str())
+                .and_then(parse_decimal_str)
+                .unwrap_or_else(|| "?".to_string());
+            let qty = v
+                .get("q")
+                .and_then(|q| q.as_str())
+                .and_then(parse_decimal_str)
+                .unwrap_or_else(|| "?".to_string());
+            let ts = v.get("T").and_then(|x| x.as_i64()).unwrap_or_default();
+            let skew = clock::current_skew_ms();
+            serde_json::json!({
+                "agent": "binance",
+                "type": "trade",
+                "s": sym,
+                "t": trade_id,
+                "p": px,
+                "q": qty,
+                "ts": ts,
+                "skew": skew
+            })
+        }
+        "depthUpdate" => {
+            let ts = v.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
+            serde_json::json!({
+                "agent": "binance",
+                "type": "l2_diff",
+                "s": sym,
+                "bids": levels(v.get("b")),
+                "asks": levels(v.get("a")),
+                "ts": ts
+            })
+        }
+        "bookTicker" => {
+            let dec = |k: &str| {
+                v.get(k)
+                    .and_then(|p| p.as_str())
+                    .and_then(parse_decimal_str)
+                    .unwrap_or_else(|| "?".to_string())
+            };
+            let ts = v.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
+            serde_json::json!({
+                "agent": "binance",
+                "type": "book_ticker",
+                "s": sym,
+                "bp": dec("b"),
+                "bq": dec("B"),
+                "ap": dec("a"),
+                "aq": dec("A"),
+                "ts": ts
+            })
+        }
+        _ => return None,
+    };
+    Some(line.to_string())
+}
+
+/// `[price, qty]` string pairs from a depth array.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = lvl.get(0)?.as_str()?.to_string();
+            let q = lvl.get(1)?.as_str()?.to_string();
+            Some([p, q])
+        })
+        .collect()
+}
+
+/// Periodically publish REST book snapshots for `symbol`, at the accelerated
+/// rate for the first `boost` of its life and paced by volatility after.
+async fn snapshot_task(
+    symbol: String,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    boost: std::time::Duration,
+) {
     let client = match http_client::builder().build() {
         Ok(c) => c,
         Err(e) => {
@@ -539,13 +614,22 @@ async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
             return;
         }
     };
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    let boost_until = tokio::time::Instant::now() + boost;
+    let mut pacer = SnapshotPacer::default();
     loop {
         let url = format!(
             "https://api.binance.us/api/v3/depth?symbol={}&limit=1000",
             symbol.to_uppercase()
         );
-        match client.get(&url).send().await {
+        let limiter = rate_limit::binance();
+        // A 1000-level depth request weighs 50.
+        limiter.acquire(50, Priority::High).await;
+        match client
+            .get(&url)
+            .send()
+            .await
+            .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        {
             Ok(resp) => match resp.json::<serde_json::Value>().await {
                 Ok(v) => {
                     let bids = v
@@ -584,6 +668,7 @@ async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
                         "ts": ts
                     })
                     .to_string();
+                    pacer.observe(&line, tokio::time::Instant::now());
                     let _ = tx.send(line).await;
                 }
                 Err(e) => {
@@ -594,40 +679,18 @@ async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
                 tracing::error!(error=%e, symbol=%symbol, "snapshot failed");
             }
         }
-        interval.tick().await;
+        tokio::select! {
+            _ = tokio::time::sleep(pacer.delay(boost_until)) => {}
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
     }
 }
 
-async fn send_subscribe(
-    ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
-    symbols: &[String],
-) -> Result<(), tokio_tungstenite::tungstenite::Error> {
-    let params = symbols
-        .iter()
-        .flat_map(|s| {
-            [
-                format!("{}@trade", s),
-                format!("{}@depth@100ms", s),
-                format!("{}@bookTicker", s),
-            ]
-        })
-        .collect::<Vec<_>>();
-    let sub_msg = serde_json::json!({
-        "method": "SUBSCRIBE",
-        "params": params,
-        "id": 1,
-    });
-    ws.send(Message::Text(sub_msg.to_string())).await
-}
-
-async fn send_unsubscribe(
-    ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
-    symbols: &[String],
-) -> Result<(), tokio_tungstenite::tungstenite::Error> {
-    if symbols.is_empty() {
-        return Ok(());
-    }
-    let params = symbols
+/// Stream names subscribed for each symbol on a spot connection.
+fn symbol_streams(symbols: &[String]) -> Vec<String> {
+    symbols
         .iter()
         .flat_map(|s| {
             [
@@ -636,256 +699,77 @@ async fn send_unsubscribe(
                 format!("{}@bookTicker", s),
             ]
         })
-        .collect::<Vec<_>>();
-    let msg = serde_json::json!({
-        "method": "UNSUBSCRIBE",
-        "params": params,
-        "id": 1,
-    });
-    ws.send(Message::Text(msg.to_string())).await
+        .collect()
 }
 
-async fn mark_price_task(
-    base_ws_url: &str,
-    shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-) {
-    let url = format!("{}/stream?streams=!markPrice@arr", base_ws_url);
-    aggregated_ws_loop(&url, "mark_price", shutdown, tx, |item| {
-        let raw = item.get("s").and_then(|s| s.as_str()).unwrap_or("?");
-        let sym =
-            CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
-        let price = item
-            .get("p")
-            .and_then(|p| p.as_str())
-            .and_then(parse_decimal_str)
-            .unwrap_or_else(|| "?".to_string());
-        let ts = item.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
-        let line = serde_json::json!({
-            "agent": "binance",
-            "type": "mark_price",
-            "s": sym,
-            "p": price,
-            "ts": ts
-        })
-        .to_string();
-        (line, ts)
-    })
-    .await;
+/// A request sent on a connection that has not been answered yet.
+struct PendingRequest {
+    id: u64,
+    method: &'static str,
+    streams: Vec<String>,
+    attempts: u32,
 }
 
-async fn funding_rate_task(
-    base_ws_url: &str,
-    shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-) {
-    let url = format!("{}/stream?streams=!fundingRate@arr", base_ws_url);
-    aggregated_ws_loop(&url, "funding", shutdown, tx, |item| {
-        let raw = item.get("s").and_then(|s| s.as_str()).unwrap_or("?");
-        let sym =
-            CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
-        let rate = item
-            .get("r")
-            .and_then(|p| p.as_str())
-            .and_then(parse_decimal_str)
-            .unwrap_or_else(|| "?".to_string());
-        let ts = item.get("T").and_then(|x| x.as_i64()).unwrap_or_default();
-        let line = serde_json::json!({
-            "agent": "binance",
-            "type": "funding",
-            "s": sym,
-            "r": rate,
-            "ts": ts
-        })
-        .to_string();
-        (line, ts)
-    })
-    .await;
+/// Requests in flight on a single connection, keyed by their unique id so
+/// acks and errors can be matched to the streams they concern.
+#[derive(Default)]
+struct PendingRequests {
+    next_id: u64,
+    inflight: HashMap<u64, PendingRequest>,
 }
 
-async fn open_interest_task(
-    base_ws_url: &str,
-    shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-) {
-    let url = format!("{}/stream?streams=!openInterest@arr", base_ws_url);
-    aggregated_ws_loop(&url, "open_interest", shutdown, tx, |item| {
-        let raw = item.get("s").and_then(|s| s.as_str()).unwrap_or("?");
-        let sym =
-            CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
-        let oi = item
-            .get("oi")
-            .and_then(|p| p.as_str())
-            .and_then(parse_decimal_str)
-            .unwrap_or_else(|| "?".to_string());
-        let ts = item.get("T").and_then(|x| x.as_i64()).unwrap_or_default();
-        let line = serde_json::json!({
-            "agent": "binance",
-            "type": "open_interest",
-            "s": sym,
-            "oi": oi,
-            "ts": ts
-        })
-        .to_string();
-        (line, ts)
-    })
-    .await;
-}
+impl PendingRequests {
+    fn track(&mut self, method: &'static str, streams: &[String], attempts: u32) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.inflight.insert(
+            id,
+            PendingRequest {
+                id,
+                method,
+                streams: streams.to_vec(),
+                attempts,
+            },
+        );
+        id
+    }
 
-async fn liquidation_task(
-    base_ws_url: &str,
-    shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-) {
-    let url = format!("{}/stream?streams=!forceOrder@arr", base_ws_url);
-    aggregated_ws_loop(&url, "liquidation", shutdown, tx, |item| {
-        let raw = item.get("s").and_then(|s| s.as_str()).unwrap_or("?");
-        let sym =
-            CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
-        let o = item.get("o").and_then(|o| o.as_object());
-        let price = o
-            .and_then(|m| m.get("p"))
-            .and_then(|p| p.as_str())
-            .and_then(parse_decimal_str)
-            .unwrap_or_else(|| "?".to_string());
-        let qty = o
-            .and_then(|m| m.get("q"))
-            .and_then(|p| p.as_str())
-            .and_then(parse_decimal_str)
-            .unwrap_or_else(|| "?".to_string());
-        let side = o
-            .and_then(|m| m.get("S"))
-            .and_then(|s| s.as_str())
-            .unwrap_or("?")
-            .to_string();
-        let ts = item.get("E").and_then(|x| x.as_i64()).unwrap_or_default();
-        let line = serde_json::json!({
-            "agent": "binance",
-            "type": "liquidation",
-            "s": sym,
-            "p": price,
-            "q": qty,
-            "side": side,
-            "ts": ts
-        })
-        .to_string();
-        (line, ts)
-    })
-    .await;
+    fn complete(&mut self, id: u64) -> Option<PendingRequest> {
+        self.inflight.remove(&id)
+    }
 }
 
-async fn term_structure_task(
-    symbols: Vec<String>,
-    rest_url: &str,
-    mut shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-) {
-    let client = match http_client::builder().build() {
-        Ok(c) => c,
-        Err(_) => return,
+async fn send_streams(
+    ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+    pending: &mut PendingRequests,
+    method: &'static str,
+    params: &[String],
+    attempts: u32,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let id = pending.track(method, params, attempts);
+    let msg = if method == "LIST_SUBSCRIPTIONS" {
+        serde_json::json!({ "method": method, "id": id })
+    } else {
+        serde_json::json!({ "method": method, "params": params, "id": id })
     };
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
-    loop {
-        tokio::select! {
-            _ = shutdown.changed() => {
-                if *shutdown.borrow() { break; }
-            }
-            _ = interval.tick() => {
-                for sym in &symbols {
-                    let url = format!("{}/futures/data/basis?symbol={}&period=5m&limit=1", rest_url, sym.to_uppercase());
-                    if let Ok(resp) = client.get(&url).send().await {
-                        if let Ok(resp) = resp.json::<serde_json::Value>().await {
-                            if let Some(arr) = resp.as_array().and_then(|a| a.first()) {
-                                let raw = arr.get("symbol").and_then(|s| s.as_str()).unwrap_or("?");
-                                let canon = CanonicalService::canonical_pair("binance", raw).unwrap_or_else(|| raw.to_string());
-                                let basis = arr
-                                    .get("basis")
-                                    .and_then(|b| b.as_str())
-                                    .and_then(parse_decimal_str)
-                                    .unwrap_or_else(|| "?".to_string());
-                                let ts = arr.get("timestamp").and_then(|t| t.as_i64()).unwrap_or_default();
-                                let line = serde_json::json!({
-                                    "agent": "binance",
-                                    "type": "term",
-                                    "s": canon,
-                                    "b": basis,
-                                    "ts": ts
-                                }).to_string();
-                                let _ = tx.send(line).await;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+    ws.send(Message::Text(msg.to_string())).await
 }
 
-async fn aggregated_ws_loop<F>(
-    url: &str,
-    _metric: &str,
-    mut shutdown: tokio::sync::watch::Receiver<bool>,
-    tx: mpsc::Sender<String>,
-    mut build: F,
-) where
-    F: FnMut(&serde_json::Value) -> (String, i64),
-{
-    let mut attempt: u32 = 0;
-    loop {
-        if *shutdown.borrow() {
-            break;
-        }
-        tracing::info!(%url, "connecting");
-        match connect_async(url).await {
-            Ok((mut ws, _)) => {
-                attempt = 0;
-                loop {
-                    tokio::select! {
-                        _ = shutdown.changed() => {
-                            if *shutdown.borrow() {
-                                let _ = ws.close(None).await;
-                                return;
-                            }
-                        }
-                        msg = ws.next() => {
-                            match msg {
-                                Some(Ok(Message::Text(txt))) => {
-                                    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&txt) {
-                                        if let Some(arr) = v.get("data").and_then(|d| d.as_array()) {
-                                            for item in arr {
-                                                let (line, _ts) = build(item);
-                                                let _ = tx.send(line).await;
-                                            }
-                                        }
-                                    }
-                                },
-                                Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
-                                Some(Ok(Message::Close(_))) => { break; }
-                                Some(Ok(_)) => {}
-                                Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
-                                None => { break; }
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                tracing::error!(error=%e, "connect failed");
-            }
-        }
+async fn send_subscribe(
+    ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+    pending: &mut PendingRequests,
+    symbols: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    send_streams(ws, pending, "SUBSCRIBE", &symbol_streams(symbols), 0).await
+}
 
-        attempt = attempt.saturating_add(1);
-        let exp: u32 = attempt.saturating_sub(1).min(4);
-        let delay = (1u64 << exp).min(16);
-        let sleep = std::time::Duration::from_secs(delay);
-        tracing::info!(?sleep, "reconnecting");
-        tokio::select! {
-            _ = tokio::time::sleep(sleep) => {},
-            _ = shutdown.changed() => {
-                if *shutdown.borrow() {
-                    break;
-                }
-            }
-        }
+async fn send_unsubscribe(
+    ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+    pending: &mut PendingRequests,
+    symbols: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    if symbols.is_empty() {
+        return Ok(());
     }
+    send_streams(ws, pending, "UNSUBSCRIBE", &symbol_streams(symbols), 0).await
 }
@@ -4,7 +4,16 @@ use canonicalizer::{Bar, CanonicalService};
 use futures_util::future::join_all;
 use tokio::sync::mpsc;
 
-use crate::{agent::Agent, config::Settings, error::IngestorError, http_client};
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Priority},
+};
+
+/// Request weight of a klines call.
+pub const KLINES_WEIGHT: i64 = 2;
 
 pub struct BinanceOhlcvAgent {
     symbols: Vec<String>,
@@ -22,18 +31,19 @@ impl BinanceOhlcvAgent {
     }
 }
 
-fn interval_str(secs: u64) -> String {
+/// Binance kline interval name for `secs`, e.g. `1h`.
+pub fn interval_str(secs: u64) -> String {
     const MINUTE: u64 = 60;
     const HOUR: u64 = 60 * MINUTE;
     const DAY: u64 = 24 * HOUR;
     const WEEK: u64 = 7 * DAY;
-    if secs % WEEK == 0 {
+    if secs.is_multiple_of(WEEK) {
         format!("{}w", secs / WEEK)
-    } else if secs % DAY == 0 {
+    } else if secs.is_multiple_of(DAY) {
         format!("{}d", secs / DAY)
-    } else if secs % HOUR == 0 {
+    } else if secs.is_multiple_of(HOUR) {
         format!("{}h", secs / HOUR)
-    } else if secs % MINUTE == 0 {
+    } else if secs.is_multiple_of(MINUTE) {
         format!("{}m", secs / MINUTE)
     } else {
         format!("{}s", secs)
@@ -47,10 +57,13 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
         interval_str(interval)
     );
     let mut delay = Duration::from_millis(500);
+    let limiter = rate_limit::binance();
     for _ in 0..3 {
+        limiter.acquire(KLINES_WEIGHT, Priority::Low).await;
         match client.get(&url).send().await {
             Ok(resp) => {
                 let status = resp.status();
+                limiter.observe(status, resp.headers());
                 if status.is_success() {
                     if let Ok(v) = resp.json::<serde_json::Value>().await {
                         if let Some(bar) = parse_bar(symbol, interval, &v) {
@@ -58,7 +71,10 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
                         }
                     }
                     break;
-                } else if status.as_u16() == 429 || status.is_server_error() {
+                } else if status.as_u16() == 429 {
+                    // the limiter now holds back the retry
+                    continue;
+                } else if status.is_server_error() {
                     tokio::time::sleep(delay).await;
                     delay *= 2;
                 } else {
@@ -72,13 +88,26 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
 }
 
 pub fn parse_bar(symbol: &str, interval: u64, v: &serde_json::Value) -> Option<Bar> {
-    let first = v.as_array()?.get(0)?.as_array()?;
-    let ts = first.get(0)?.as_i64()?;
-    let open = first.get(1)?.as_str()?.to_string();
-    let high = first.get(2)?.as_str()?.to_string();
-    let low = first.get(3)?.as_str()?.to_string();
-    let close = first.get(4)?.as_str()?.to_string();
-    let volume = first.get(5)?.as_str()?.to_string();
+    parse_bars(symbol, interval, v).into_iter().next()
+}
+
+/// Parse every row of a klines response.
+pub fn parse_bars(symbol: &str, interval: u64, v: &serde_json::Value) -> Vec<Bar> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|row| parse_kline(symbol, interval, row))
+        .collect()
+}
+
+fn parse_kline(symbol: &str, interval: u64, row: &serde_json::Value) -> Option<Bar> {
+    let row = row.as_array()?;
+    let ts = row.first()?.as_i64()?;
+    let open = row.get(1)?.as_str()?.to_string();
+    let high = row.get(2)?.as_str()?.to_string();
+    let low = row.get(3)?.as_str()?.to_string();
+    let close = row.get(4)?.as_str()?.to_string();
+    let volume = row.get(5)?.as_str()?.to_string();
     let sym =
         CanonicalService::canonical_pair("binance", symbol).unwrap_or_else(|| symbol.to_string());
     Some(Bar {
@@ -91,6 +120,9 @@ pub fn parse_bar(symbol: &str, interval: u64, v: &serde_json::Value) -> Option<B
         low,
         close,
         volume,
+        ac: None,
+        settle: None,
+        id: None,
         timestamp: ts,
     })
 }
@@ -115,6 +147,23 @@ impl Agent for BinanceOhlcvAgent {
             })?;
 
         loop {
+            // Hold the round back until all of it fits the minute's budget
+            // rather than letting it crowd out snapshots request by request.
+            let round = (self.symbols.len() * self.intervals.len()) as i64 * KLINES_WEIGHT;
+            let wait = rate_limit::binance().forecast(round, Priority::Low);
+            if !wait.is_zero() {
+                tracing::info!(
+                    ?wait,
+                    weight = round,
+                    "deferring binance bar poll to stay within budget"
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {},
+                    _ = shutdown.changed() => {
+                        if *shutdown.borrow() { break; }
+                    }
+                }
+            }
             let mut futs = Vec::new();
             for s in &self.symbols {
                 for &i in &self.intervals {
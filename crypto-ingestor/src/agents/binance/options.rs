@@ -3,11 +3,19 @@ use std::{
     time::Duration,
 };
 
-use canonicalizer::{CanonicalService, OptionChain, OptionGreeks, OptionQuote, OptionSurfacePoint};
+use canonicalizer::{
+    AssetClass, CanonicalService, OptionChain, OptionGreeks, OptionQuote, OptionSurfacePoint,
+};
 use serde_json::Value;
 use tokio::sync::mpsc;
 
-use crate::{agent::Agent, config::Settings, error::IngestorError, http_client};
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+};
 
 pub struct BinanceOptionsAgent {
     symbols: Vec<String>,
@@ -54,7 +62,14 @@ impl Agent for BinanceOptionsAgent {
                         "{}/optionChain?symbol={}&expiry={}",
                         self.rest_url, sym, exp
                     );
-                    match client.get(&url).send().await {
+                    let limiter = rate_limit::limiter(Exchange::BinanceOptions);
+                    limiter.acquire(1, Priority::Low).await;
+                    match client
+                        .get(&url)
+                        .send()
+                        .await
+                        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+                    {
                         Ok(resp) => match resp.json::<Value>().await {
                             Ok(v) => {
                                 if let Some(chain) = parse_chain(sym, &exp, &v) {
@@ -96,7 +111,14 @@ impl Agent for BinanceOptionsAgent {
 
 async fn fetch_expiries(client: &reqwest::Client, base: &str, symbol: &str) -> Vec<String> {
     let url = format!("{}/optionInfo?symbol={}", base, symbol);
-    if let Ok(resp) = client.get(&url).send().await {
+    let limiter = rate_limit::limiter(Exchange::BinanceOptions);
+    limiter.acquire(1, Priority::Low).await;
+    if let Ok(resp) = client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
         if let Ok(v) = resp.json::<Value>().await {
             if let Some(arr) = v.get("data").and_then(|d| d.as_array()) {
                 let mut set = HashSet::new();
@@ -121,6 +143,7 @@ async fn fetch_expiries(client: &reqwest::Client, base: &str, symbol: &str) -> V
 
 fn parse_chain(symbol: &str, expiry: &str, v: &Value) -> Option<OptionChain> {
     let canon = CanonicalService::canonical_pair("binance", symbol)?;
+    let settle = canon.split_once('-').map(|(_, q)| q.to_string())?;
     let expiry_ts = parse_expiry(expiry)?;
 
     let mut options = Vec::new();
@@ -159,9 +182,13 @@ fn parse_chain(symbol: &str, expiry: &str, v: &Value) -> Option<OptionChain> {
         agent: "binance".to_string(),
         r#type: "option_chain".to_string(),
         s: canon,
+        ac: AssetClass::Option,
+        settle,
         expiry: expiry_ts,
         options,
         surface,
+        ts: None,
+        settlement_price: None,
     })
 }
 
@@ -217,7 +244,7 @@ pub struct BinanceOptionsFactory;
 #[async_trait::async_trait]
 impl crate::agents::AgentFactory for BinanceOptionsFactory {
     async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
-        let mut symbols: Vec<String> = if spec.trim().is_empty() {
+        let symbols: Vec<String> = if spec.trim().is_empty() {
             cfg.binance_options_symbols.clone()
         } else {
             spec.split(',')
@@ -229,7 +256,7 @@ impl crate::agents::AgentFactory for BinanceOptionsFactory {
             tracing::error!("no binance option symbols specified");
             return None;
         }
-        let agent = BinanceOptionsAgent::new(symbols.drain(..).collect(), cfg);
+        let agent = BinanceOptionsAgent::new(symbols, cfg);
         Some(Box::new(agent))
     }
 }
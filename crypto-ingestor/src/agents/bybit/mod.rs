@@ -0,0 +1,487 @@
+//! Bybit v5 public market data for spot and USDT linear perpetuals.
+//!
+//! Both categories stream `publicTrade` and `orderbook.{depth}` topics. Spot
+//! tickers carry no quotes, so spot top of book comes from `orderbook.1`;
+//! linear `tickers` updates provide the book ticker together with funding,
+//! open interest and mark price, which are emitted as their own events.
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{perp_id, symbol_or_raw, AgentFactory};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::{AssetClass, Symbol};
+
+/// Topics per subscribe request; Bybit spot rejects more than ten.
+const SUBSCRIBE_BATCH: usize = 10;
+/// Bybit recommends a heartbeat every 20 seconds.
+const PING_INTERVAL_SECS: u64 = 20;
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Bybit product category served by one agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Spot,
+    Linear,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Spot => "spot",
+            Category::Linear => "linear",
+        }
+    }
+}
+
+/// Fetch all trading USDT-quoted symbols of `category`.
+pub async fn fetch_all_symbols(
+    rest_url: &str,
+    category: Category,
+) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "bybit",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Bybit);
+    limiter.acquire(1, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/v5/market/instruments-info"))
+        .query(&[("category", category.as_str()), ("limit", "1000")])
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let list = resp
+        .pointer("/result/list")
+        .and_then(|l| l.as_array())
+        .ok_or_else(|| IngestorError::Other("bybit unexpected response".into()))?;
+    Ok(list
+        .iter()
+        .filter(|i| i.get("status").and_then(|s| s.as_str()) == Some("Trading"))
+        .filter(|i| i.get("quoteCoin").and_then(|q| q.as_str()) == Some("USDT"))
+        .filter_map(|i| i.get("symbol").and_then(|s| s.as_str()).map(str::to_string))
+        .collect())
+}
+
+pub struct BybitAgent {
+    category: Category,
+    symbols: Vec<String>,
+    ws_url: String,
+    orderbook_depth: u32,
+    max_reconnect_delay_secs: u64,
+}
+
+impl BybitAgent {
+    pub fn new(category: Category, symbols: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            category,
+            symbols,
+            ws_url: format!(
+                "{}/{}",
+                cfg.bybit_ws_url.trim_end_matches('/'),
+                category.as_str()
+            ),
+            orderbook_depth: cfg.bybit_orderbook_depth,
+            max_reconnect_delay_secs: cfg.bybit_max_reconnect_delay_secs,
+        }
+    }
+
+    fn topics(&self) -> Vec<String> {
+        self.symbols
+            .iter()
+            .flat_map(|s| {
+                let quotes = match self.category {
+                    Category::Spot => format!("orderbook.1.{s}"),
+                    Category::Linear => format!("tickers.{s}"),
+                };
+                [
+                    format!("publicTrade.{s}"),
+                    format!("orderbook.{}.{s}", self.orderbook_depth),
+                    quotes,
+                ]
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for BybitAgent {
+    fn name(&self) -> &'static str {
+        "bybit"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let topics = self.topics();
+        let stream = Some(self.category.as_str());
+        let mut attempt: u32 = 0;
+        let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+        let mut tickers: HashMap<Symbol, TickerQuote> = HashMap::new();
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            tracing::info!(url = %self.ws_url, "connecting");
+            match http_client::connect_ws(&self.ws_url).await {
+                Ok((mut ws, _)) => {
+                    tracing::info!("connected");
+                    let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                    attempt = 0;
+                    tickers.clear();
+
+                    if let Err(e) = send_subscribe(&mut ws, &topics).await {
+                        tracing::error!(error=%e, "failed to send subscription");
+                        continue;
+                    }
+
+                    let ping_period = std::time::Duration::from_secs(PING_INTERVAL_SECS);
+                    let mut ping = tokio::time::interval_at(
+                        tokio::time::Instant::now() + ping_period,
+                        ping_period,
+                    );
+
+                    loop {
+                        tokio::select! {
+                            _ = shutdown.changed() => {
+                                if *shutdown.borrow() {
+                                    tracing::info!("shutdown signal - closing connection");
+                                    let _ = ws.close(None).await;
+                                    return Ok(());
+                                }
+                            }
+                            _ = ping.tick() => {
+                                let msg = serde_json::json!({"op": "ping"}).to_string();
+                                if let Err(e) = ws.send(Message::Text(msg)).await {
+                                    tracing::error!(error=%e, "failed to send ping");
+                                    break;
+                                }
+                            }
+                            idle = watchdog.tick() => {
+                                if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                    break;
+                                }
+                            }
+                            msg = ws.next() => {
+                                watchdog.feed();
+                                match msg {
+                                    Some(Ok(Message::Text(txt))) => {
+                                        let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                            Ok(v) => v,
+                                            Err(e) => {
+                                                dead_letter::record_in("bybit", stream, &txt, &e);
+                                                continue;
+                                            }
+                                        };
+                                        if v.get("op").is_some() {
+                                            if v.get("success").and_then(|s| s.as_bool()) == Some(false) {
+                                                tracing::error!(msg=?v.get("ret_msg"), "bybit request rejected");
+                                            }
+                                            continue;
+                                        }
+                                        let lines = parse_event(&v, self.category, &mut last_trade_ids, &mut tickers);
+                                        let topic = v.get("topic").and_then(|t| t.as_str());
+                                        if lines.is_empty() && topic.is_some_and(|t| !t.starts_with("tickers.")) {
+                                            dead_letter::record_no_event_in("bybit", stream, &txt);
+                                        }
+                                        for line in lines {
+                                            if tx.send(line).await.is_err() {
+                                                return Ok(());
+                                            }
+                                        }
+                                    }
+                                    Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                    Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                    Some(Ok(_)) => { }
+                                    Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                    None => { tracing::warn!("stream ended"); break; }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error=%e, "connect failed");
+                }
+            }
+
+            attempt = attempt.saturating_add(1);
+            ingest_stats::record("bybit", Counter::Reconnect);
+            let exp: u32 = attempt.saturating_sub(1).min(4);
+            let delay = (1u64 << exp).min(self.max_reconnect_delay_secs);
+            let sleep = std::time::Duration::from_secs(delay);
+
+            tracing::info!(?sleep, "reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        tracing::info!("shutdown during backoff");
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Factory for `bybit:<category>:<symbols>` specs, e.g.
+/// `bybit:spot:btcusdt,ethusdt` or `bybit:linear:all`. The category defaults
+/// to spot.
+pub struct BybitFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for BybitFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let (category, list) = match spec.split_once(':') {
+            Some((c, rest)) if c.eq_ignore_ascii_case("spot") => (Category::Spot, rest),
+            Some((c, rest)) if c.eq_ignore_ascii_case("linear") => (Category::Linear, rest),
+            Some((c, _)) => {
+                tracing::error!(category=%c, "unknown bybit category");
+                return None;
+            }
+            None if spec.eq_ignore_ascii_case("linear") => (Category::Linear, ""),
+            None if spec.eq_ignore_ascii_case("spot") => (Category::Spot, ""),
+            None => (Category::Spot, spec),
+        };
+        let symbols = if list.is_empty() {
+            vec!["BTCUSDT".to_string()]
+        } else if list.eq_ignore_ascii_case("all") {
+            match fetch_all_symbols(&cfg.bybit_rest_url, category).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch bybit symbols");
+                    return None;
+                }
+            }
+        } else {
+            list.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(BybitAgent::new(category, symbols, cfg)))
+    }
+}
+
+async fn send_subscribe(
+    ws: &mut WsStream,
+    topics: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    for batch in topics.chunks(SUBSCRIBE_BATCH) {
+        let msg = serde_json::json!({"op": "subscribe", "args": batch});
+        ws.send(Message::Text(msg.to_string())).await?;
+    }
+    Ok(())
+}
+
+/// Top of book of a linear ticker, merged from its snapshot and deltas.
+#[derive(Debug, Clone, Default)]
+pub struct TickerQuote {
+    /// Bid price, bid size, ask price and ask size.
+    fields: [Option<String>; 4],
+}
+
+/// Convert a Bybit topic push into canonical event lines. `tickers` holds the
+/// merged linear ticker quotes between calls.
+pub fn parse_event(
+    v: &serde_json::Value,
+    category: Category,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+    tickers: &mut HashMap<Symbol, TickerQuote>,
+) -> Vec<String> {
+    let topic = v.get("topic").and_then(|t| t.as_str()).unwrap_or("");
+    let Some((kind, raw)) = topic.split_once('.') else {
+        return Vec::new();
+    };
+    // `orderbook.{depth}.{symbol}` carries the depth before the symbol.
+    let (kind, depth, raw) = match raw.split_once('.') {
+        Some((depth, raw)) => (kind, depth.parse::<u32>().ok(), raw),
+        None => (kind, None, raw),
+    };
+    let sym = symbol_or_raw("bybit", raw);
+    let ts = v.get("ts").and_then(|t| t.as_i64()).unwrap_or_default();
+    let tag = |mut e: serde_json::Value| {
+        if category == Category::Linear {
+            let settle = sym.split_once('-').map_or(&*sym, |(_, q)| q).to_string();
+            e["ac"] = serde_json::json!(AssetClass::Perp);
+            e["settle"] = serde_json::json!(settle);
+            e["id"] = serde_json::json!(perp_id(&sym));
+        }
+        e.to_string()
+    };
+    let dec = |d: &serde_json::Value, k: &str| {
+        d.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+    };
+
+    let mut out = Vec::new();
+    match kind {
+        "publicTrade" => {
+            for d in v
+                .get("data")
+                .and_then(|d| d.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let trade_id = d
+                    .get("i")
+                    .and_then(|i| i.as_str())
+                    .and_then(|i| i.parse::<i64>().ok())
+                    .filter(|id| *id > 0);
+                if let Some(id) = trade_id {
+                    last_trade_ids.insert(sym.clone(), id);
+                }
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "trade",
+                    "s": sym,
+                    "t": trade_id,
+                    "p": dec(d, "p").unwrap_or_else(|| "?".into()),
+                    "q": dec(d, "v").unwrap_or_else(|| "?".into()),
+                    "ts": d.get("T").and_then(|t| t.as_i64()).unwrap_or(ts),
+                    "skew": clock::current_skew_ms()
+                })));
+            }
+        }
+        "orderbook" => {
+            let Some(d) = v.get("data") else {
+                return out;
+            };
+            let bids = levels(d.get("b"));
+            let asks = levels(d.get("a"));
+            if category == Category::Spot && depth == Some(1) {
+                let (Some([bp, bq]), Some([ap, aq])) = (bids.first(), asks.first()) else {
+                    return out;
+                };
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "book_ticker",
+                    "s": sym,
+                    "bp": bp,
+                    "bq": bq,
+                    "ap": ap,
+                    "aq": aq,
+                    "ts": ts
+                })));
+                return out;
+            }
+            let typ = match v.get("type").and_then(|t| t.as_str()) {
+                Some("delta") => "l2_diff",
+                _ => "snapshot",
+            };
+            out.push(tag(serde_json::json!({
+                "agent": "bybit",
+                "type": typ,
+                "s": sym,
+                "bids": bids,
+                "asks": asks,
+                "ts": ts
+            })));
+        }
+        "tickers" => {
+            let Some(d) = v.get("data") else {
+                return out;
+            };
+            // Linear ticker deltas only carry the fields that changed, so the
+            // quote is merged into the last one seen for the symbol.
+            let quote = tickers.entry(sym.clone()).or_default();
+            if v.get("type").and_then(|t| t.as_str()) == Some("snapshot") {
+                *quote = TickerQuote::default();
+            }
+            let mut changed = false;
+            for (slot, k) in
+                quote
+                    .fields
+                    .iter_mut()
+                    .zip(["bid1Price", "bid1Size", "ask1Price", "ask1Size"])
+            {
+                if let Some(x) = dec(d, k) {
+                    *slot = Some(x);
+                    changed = true;
+                }
+            }
+            if let (true, [Some(bp), Some(bq), Some(ap), Some(aq)]) = (changed, &quote.fields) {
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "book_ticker",
+                    "s": sym,
+                    "bp": bp,
+                    "bq": bq,
+                    "ap": ap,
+                    "aq": aq,
+                    "ts": ts
+                })));
+            }
+            if let Some(r) = dec(d, "fundingRate") {
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "funding",
+                    "s": sym,
+                    "r": r,
+                    "ts": ts
+                })));
+            }
+            if let Some(oi) = dec(d, "openInterest") {
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "open_interest",
+                    "s": sym,
+                    "oi": oi,
+                    "ts": ts
+                })));
+            }
+            if let Some(p) = dec(d, "markPrice") {
+                out.push(tag(serde_json::json!({
+                    "agent": "bybit",
+                    "type": "mark_price",
+                    "s": sym,
+                    "p": p,
+                    "ts": ts
+                })));
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+/// `[price, qty]` string pairs from a Bybit book side.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
@@ -4,7 +4,12 @@ use canonicalizer::{CanonicalService, FeeSchedule, FeeTier, Listing};
 use chrono::Utc;
 use tokio::time::{interval, Duration, MissedTickBehavior};
 
-use crate::{error::IngestorError, http_client, sink::DynSink};
+use crate::{
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Priority},
+    sink::DynSink,
+};
 
 /// Poll Coinbase REST endpoints for listing and fee metadata and emit canonical events.
 pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink) {
@@ -68,10 +73,13 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
             symbol: None,
         })?;
 
+    let limiter = rate_limit::coinbase();
+    limiter.acquire(1, Priority::Low).await;
     let products: serde_json::Value = client
         .get("https://api.exchange.coinbase.com/products")
         .send()
         .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
         .map_err(|e| IngestorError::Http {
             source: e,
             exchange: "coinbase",
@@ -85,15 +93,19 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
             symbol: None,
         })?;
 
+    limiter.acquire(1, Priority::Low).await;
     let fee_resp = client
         .get("https://api.exchange.coinbase.com/fees")
         .send()
-        .await;
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()));
     let fee_value = match fee_resp {
         Ok(resp) => resp.json::<serde_json::Value>().await.unwrap_or_default(),
         Err(_) => serde_json::Value::Null,
     };
 
+    CanonicalService::update_instruments(|r| r.load_coinbase_products(&products));
+
     let ts = Utc::now().timestamp_millis();
     let mut listings = HashMap::new();
     if let Some(arr) = products.as_array() {
@@ -114,6 +126,10 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
                 .get("base_increment")
                 .and_then(|s| s.as_str())
                 .map(|s| s.to_string());
+            let tick_size = prod
+                .get("quote_increment")
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string());
             let canon =
                 CanonicalService::canonical_pair("coinbase", id).unwrap_or_else(|| id.to_string());
             let listing = Listing {
@@ -123,6 +139,7 @@ async fn fetch() -> Result<(HashMap<String, Listing>, FeeSchedule), IngestorErro
                 base: base.to_string(),
                 quote: quote.to_string(),
                 lot_size,
+                tick_size,
                 timestamp: ts,
             };
             listings.insert(id.to_string(), listing);
@@ -2,19 +2,28 @@ use futures_util::{SinkExt, StreamExt};
 pub mod metadata;
 pub mod ohlcv;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
 use tokio::sync::mpsc;
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
-use super::{shared_symbols, AgentFactory};
+use super::universe::Universe;
+use super::{
+    listing_events, shared_symbols, symbol_or_raw, AgentFactory, Resyncs, SnapshotPacer,
+    STREAM_SEQ_GAPS,
+};
 use crate::clock;
 use crate::{
     agent::Agent,
     config::Settings,
+    dead_letter,
     error::IngestorError,
     http_client,
+    ingest_stats::{self, Counter},
     parse::parse_decimal_str,
+    rate_limit::{self, Priority},
+    watchdog::{self, Watchdog},
 };
-use canonicalizer::CanonicalService;
+use canonicalizer::{CanonicalService, Symbol};
 
 /// Fetch all tradable USD product IDs from Coinbase.
 pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
@@ -25,10 +34,13 @@ pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
             exchange: "coinbase",
             symbol: None,
         })?;
+    let limiter = rate_limit::coinbase();
+    limiter.acquire(1, Priority::High).await;
     let products: serde_json::Value = client
         .get("https://api.exchange.coinbase.com/products")
         .send()
         .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
         .map_err(|e| IngestorError::Http {
             source: e,
             exchange: "coinbase",
@@ -58,20 +70,33 @@ pub async fn fetch_all_symbols() -> Result<Vec<String>, IngestorError> {
 
 pub struct CoinbaseAgent {
     symbols: Vec<String>,
+    universe: Universe,
     ws_url: String,
     max_reconnect_delay_secs: u64,
     refresh_interval_mins: u64,
+    resync_secs: u64,
+    new_listing_window: std::time::Duration,
 }
 
 impl CoinbaseAgent {
     pub fn new(symbols: Vec<String>, cfg: &Settings) -> Self {
         Self {
+            universe: Universe::Symbols(symbols.clone()),
             symbols,
             ws_url: cfg.coinbase_ws_url.clone(),
             max_reconnect_delay_secs: cfg.coinbase_max_reconnect_delay_secs,
             refresh_interval_mins: cfg.coinbase_refresh_interval_mins,
+            resync_secs: cfg.subscription_resync_secs,
+            new_listing_window: std::time::Duration::from_secs(60 * cfg.new_listing_window_mins),
         }
     }
+
+    /// Re-evaluate `universe` on each symbol refresh instead of the symbols
+    /// the agent was created with.
+    pub fn with_universe(mut self, universe: Universe) -> Self {
+        self.universe = universe;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -87,7 +112,7 @@ impl Agent for CoinbaseAgent {
     ) -> Result<(), IngestorError> {
         let mut handle = None;
         let mut sym_tx = None;
-        let mut snap_handles = Vec::new();
+        let mut snapshots = HashMap::new();
 
         if !self.symbols.is_empty() {
             let (s_tx, rx) = tokio::sync::watch::channel(self.symbols.clone());
@@ -96,14 +121,21 @@ impl Agent for CoinbaseAgent {
             let tx_clone = tx.clone();
             let ws_url = self.ws_url.clone();
             let max_delay = self.max_reconnect_delay_secs;
+            let resync = self.resync_secs;
             handle = Some(tokio::spawn(async move {
-                connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay).await;
+                connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay, resync).await;
             }));
             for sym in self.symbols.clone() {
                 let tx_snap = tx.clone();
-                snap_handles.push(tokio::spawn(async move {
-                    snapshot_task(sym, tx_snap).await;
-                }));
+                let shutdown_snap = shutdown.clone();
+                let task_sym = sym.clone();
+                snapshots.insert(
+                    sym,
+                    tokio::spawn(async move {
+                        snapshot_task(task_sym, shutdown_snap, tx_snap, std::time::Duration::ZERO)
+                            .await;
+                    }),
+                );
             }
         }
 
@@ -118,7 +150,7 @@ impl Agent for CoinbaseAgent {
                     if *shutdown.borrow() { break; }
                 }
                 _ = refresh.tick() => {
-                    match fetch_all_symbols().await {
+                    match self.universe.resolve("coinbase").await {
                         Ok(new_symbols) => {
                             let new_set: HashSet<_> = new_symbols.iter().cloned().collect();
                             let old_set: HashSet<_> = self.symbols.iter().cloned().collect();
@@ -129,6 +161,24 @@ impl Agent for CoinbaseAgent {
                                 tracing::info!("symbol refresh: no changes");
                             } else {
                                 tracing::info!(?added, ?removed, total=new_symbols.len(), "symbol refresh");
+                                for line in listing_events("coinbase", &added, &removed) {
+                                    let _ = tx.send(line).await;
+                                }
+                                for sym in &removed {
+                                    if let Some(h) = snapshots.remove(sym) {
+                                        h.abort();
+                                    }
+                                }
+                                // New listings get accelerated snapshots for a while.
+                                for sym in added {
+                                    let tx_snap = tx.clone();
+                                    let shutdown_snap = shutdown.clone();
+                                    let boost = self.new_listing_window;
+                                    let task_sym = sym.clone();
+                                    snapshots.insert(sym, tokio::spawn(async move {
+                                        snapshot_task(task_sym, shutdown_snap, tx_snap, boost).await;
+                                    }));
+                                }
                                 self.symbols = new_symbols;
 
                                 if self.symbols.is_empty() {
@@ -147,8 +197,9 @@ impl Agent for CoinbaseAgent {
                                     let tx_clone = tx.clone();
                                     let ws_url = self.ws_url.clone();
                                     let max_delay = self.max_reconnect_delay_secs;
+                                    let resync = self.resync_secs;
                                     handle = Some(tokio::spawn(async move {
-                                        connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay).await;
+                                        connection_task(rx, shutdown_rx, tx_clone, ws_url, max_delay, resync).await;
                                     }));
                                 }
                             }
@@ -165,7 +216,7 @@ impl Agent for CoinbaseAgent {
         if let Some(h) = handle {
             let _ = h.await;
         }
-        for h in snap_handles {
+        for h in snapshots.into_values() {
             let _ = h.await;
         }
 
@@ -178,23 +229,41 @@ pub struct CoinbaseFactory;
 #[async_trait::async_trait]
 impl AgentFactory for CoinbaseFactory {
     async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
-        let symbols = if spec.is_empty() {
-            vec!["BTC-USD".to_string()]
-        } else if spec.eq_ignore_ascii_case("all") {
-            match shared_symbols().await {
-                Ok((_, c)) => c,
+        let (symbols, universe) = match Universe::parse(spec, cfg.universe_categories.as_deref()) {
+            Some(Ok(universe)) => match universe.resolve("coinbase").await {
+                Ok(symbols) => (symbols, universe),
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to resolve coinbase symbol universe");
+                    return None;
+                }
+            },
+            Some(Err(e)) => {
+                tracing::error!(error=%e, %spec, "invalid coinbase symbol universe");
+                return None;
+            }
+            None if spec.is_empty() => {
+                let symbols = vec!["BTC-USD".to_string()];
+                (symbols.clone(), Universe::Symbols(symbols))
+            }
+            None if spec.eq_ignore_ascii_case("all") => match shared_symbols().await {
+                Ok((_, c)) => (c, Universe::All),
                 Err(e) => {
                     tracing::error!(error=%e, "failed to fetch shared symbols");
                     return None;
                 }
+            },
+            None => {
+                let symbols = spec
+                    .split(',')
+                    .map(|s| s.trim().to_uppercase())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>();
+                (symbols.clone(), Universe::Symbols(symbols))
             }
-        } else {
-            spec.split(',')
-                .map(|s| s.trim().to_uppercase())
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<_>>()
         };
-        Some(Box::new(CoinbaseAgent::new(symbols, cfg)))
+        Some(Box::new(
+            CoinbaseAgent::new(symbols, cfg).with_universe(universe),
+        ))
     }
 }
 
@@ -204,20 +273,27 @@ async fn connection_task(
     tx: mpsc::Sender<String>,
     ws_url: String,
     max_reconnect_delay_secs: u64,
+    resync_secs: u64,
 ) {
     let mut attempt: u32 = 0;
-    let mut last_trade_ids: HashMap<String, i64> = HashMap::new();
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+    let client = http_client::builder().build().ok();
+    // Gap resyncs outlive reconnects but not the task.
+    let mut resyncs = Resyncs::default();
 
     loop {
         if *shutdown.borrow() {
             break;
         }
 
+        // Sequence numbers restart from the first message of each connection.
+        let mut sequences: HashMap<String, u64> = HashMap::new();
         tracing::info!(url = %ws_url, "connecting");
         let mut current_symbols = symbols_rx.borrow().clone();
-        match connect_async(&ws_url).await {
+        match http_client::connect_ws(&ws_url).await {
             Ok((mut ws, _)) => {
                 tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
                 attempt = 0;
 
                 if let Err(e) = send_subscribe(&mut ws, &current_symbols).await {
@@ -225,6 +301,13 @@ async fn connection_task(
                     continue;
                 }
 
+                let resync_period = std::time::Duration::from_secs(resync_secs.max(1));
+                let mut resync = tokio::time::interval_at(
+                    tokio::time::Instant::now() + resync_period,
+                    resync_period,
+                );
+                resync.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
                 loop {
                     tokio::select! {
                         _ = shutdown.changed() => {
@@ -234,6 +317,14 @@ async fn connection_task(
                                 return;
                             }
                         }
+                        _ = resync.tick() => {
+                            // Re-subscribing is idempotent; the server answers
+                            // with a `subscriptions` message that is audited below.
+                            if let Err(e) = send_subscribe(&mut ws, &current_symbols).await {
+                                tracing::error!(error=%e, "failed to resync subscription");
+                                break;
+                            }
+                        }
                         changed = symbols_rx.changed() => {
                             if changed.is_ok() {
                                 let new_syms = symbols_rx.borrow().clone();
@@ -262,211 +353,61 @@ async fn connection_task(
                                 break;
                             }
                         }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
                         msg = ws.next() => {
+                            watchdog.feed();
                             match msg {
                                 Some(Ok(Message::Text(txt))) => {
-                                    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&txt) {
+                                    let parsed = serde_json::from_str::<serde_json::Value>(&txt);
+                                    if let Err(e) = &parsed {
+                                        dead_letter::record("coinbase", &txt, e);
+                                    }
+                                    if let Ok(v) = parsed {
                                         let typ = v.get("type").and_then(|t| t.as_str()).unwrap_or("");
                                         match typ {
-                                            "match" => {
-                                                let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
-                                                let sym = CanonicalService::canonical_pair("coinbase", raw).unwrap_or_else(|| raw.to_string());
-                                                // Missing or non-positive trade IDs are represented as JSON null.
-                                                let trade_id = v
-                                                    .get("trade_id")
-                                                    .and_then(|id| id.as_i64())
-                                                    .filter(|id| *id > 0);
-                                                let price = v
-                                                    .get("price")
-                                                    .and_then(|p| p.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let size = v
-                                                    .get("size")
-                                                    .and_then(|q| q.as_str())
-                                                    .and_then(parse_decimal_str)
-                                                    .unwrap_or_else(|| "?".to_string());
-                                                let ts = v
-                                                    .get("time")
-                                                    .and_then(|t| t.as_str())
-                                                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
-                                                    .map(|dt| dt.timestamp_millis())
-                                                    .unwrap_or_default();
-                                                let line = serde_json::json!({
-                                                    "agent": "coinbase",
-                                                    "type": "trade",
-                                                    "s": sym,
-                                                    "t": trade_id,
-                                                    "p": price,
-                                                    "q": size,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_err() {
-                                                    let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
-                                                    let sym = CanonicalService::canonical_pair("coinbase", raw)
-                                                        .unwrap_or_else(|| raw.to_string());
-                                                    // Missing or non-positive trade IDs are represented as JSON null.
-                                                    let trade_id = v
-                                                        .get("trade_id")
-                                                        .and_then(|id| id.as_i64())
-                                                        .filter(|id| *id > 0);
-                                                    if let Some(id) = trade_id {
-                                                        if let Some(last) = last_trade_ids.get_mut(&sym) {
-                                                            *last = id;
-                                                        } else {
-                                                            last_trade_ids.insert(sym.clone(), id);
-                                                        }
-                                                    }
-                                                    let price = match v
-                                                        .get("price")
-                                                        .and_then(|p| p.as_str())
-                                                        .and_then(parse_decimal_str)
-                                                    {
-                                                        Some(p) => p,
-                                                        None => {
-                                                            "?".to_string()
-                                                        }
-                                                    };
-                                                    let size = match v
-                                                        .get("size")
-                                                        .and_then(|q| q.as_str())
-                                                        .and_then(parse_decimal_str)
-                                                    {
-                                                        Some(q) => q,
-                                                        None => {
-                                                            "?".to_string()
-                                                        }
-                                                    };
-                                                    let ts = v
-                                                        .get("time")
-                                                        .and_then(|t| t.as_str())
-                                                        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
-                                                        .map(|dt| dt.timestamp_millis())
-                                                        .unwrap_or_default();
-                                                    let skew = clock::current_skew_ms();
-                                                    let line = serde_json::json!({
-                                                        "agent": "coinbase",
-                                                        "type": "trade",
-                                                        "s": sym,
-                                                        "t": trade_id,
-                                                        "p": price,
-                                                        "q": size,
-                                                        "ts": ts,
-                                                        "skew": skew
-                                                    })
-                                                    .to_string();
-                                                    if tx.send(line).await.is_err() {
+                                            "subscriptions" => {
+                                                let extra = subscription_extras(&v, &current_symbols);
+                                                if !extra.is_empty() {
+                                                    tracing::warn!(?extra, "subscription drift; unsubscribing");
+                                                    let _ = send_unsubscribe(&mut ws, &extra).await;
+                                                }
+                                                let missing = subscription_missing(&v, &current_symbols);
+                                                if !missing.is_empty() {
+                                                    tracing::warn!(?missing, "subscription drift; resubscribing");
+                                                    if let Err(e) = send_subscribe(&mut ws, &missing).await {
+                                                        tracing::error!(error=%e, "failed to resync subscription");
                                                         break;
                                                     }
                                                 }
-                                            },
-                                            "l2update" => {
-                                                let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
-                                                let sym = CanonicalService::canonical_pair("coinbase", raw).unwrap_or_else(|| raw.to_string());
-                                                let mut bids = Vec::new();
-                                                let mut asks = Vec::new();
-                                                if let Some(changes) = v.get("changes").and_then(|c| c.as_array()) {
-                                                    for c in changes {
-                                                        if let (Some(side), Some(p), Some(sz)) = (
-                                                            c.get(0).and_then(|s| s.as_str()),
-                                                            c.get(1).and_then(|p| p.as_str()),
-                                                            c.get(2).and_then(|q| q.as_str()),
-                                                        ) {
-                                                            let price = parse_decimal_str(p);
-                                                            let qty = parse_decimal_str(sz);
-                                                            if let (Some(price), Some(qty)) = (price, qty) {
-                                                                if side == "buy" {
-                                                                    bids.push([price, qty]);
-                                                                } else {
-                                                                    asks.push([price, qty]);
-                                                                }
+                                            }
+                                            _ => {
+                                                if typ == "l2update" && sequence_gap(&v, &mut sequences) {
+                                                    let product = v.get("product_id").and_then(|p| p.as_str()).unwrap_or_default().to_string();
+                                                    tracing::warn!(%product, "level2 sequence gap; re-snapshotting");
+                                                    if let Some(client) = client.clone() {
+                                                        let tx = tx.clone();
+                                                        let symbol = product.clone();
+                                                        resyncs.start(&product, async move {
+                                                            if let Some(line) = fetch_snapshot(&client, &symbol).await {
+                                                                let _ = tx.send(line).await;
                                                             }
-                                                        }
+                                                        });
+                                                    }
+                                                }
+                                                let parsed = parse_event(&v, &mut last_trade_ids);
+                                                if parsed.is_none() && !matches!(typ, "heartbeat" | "error") {
+                                                    dead_letter::record_no_event("coinbase", &txt);
+                                                }
+                                                if let Some(line) = parsed {
+                                                    if tx.send(line).await.is_err() {
+                                                        break;
                                                     }
                                                 }
-                                                let ts = v
-                                                    .get("time")
-                                                    .and_then(|t| t.as_str())
-                                                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
-                                                    .map(|dt| dt.timestamp_millis())
-                                                    .unwrap_or_default();
-                                                let line = serde_json::json!({
-                                                    "agent": "coinbase",
-                                                    "type": "l2_diff",
-                                                    "s": sym,
-                                                    "bids": bids,
-                                                    "asks": asks,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_ok() {
-                                                } else { break; }
-                                            }
-                                            "snapshot" => {
-                                                let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
-                                                let sym = CanonicalService::canonical_pair("coinbase", raw).unwrap_or_else(|| raw.to_string());
-                                                let bids = v
-                                                    .get("bids")
-                                                    .and_then(|b| b.as_array())
-                                                    .cloned()
-                                                    .unwrap_or_default()
-                                                    .into_iter()
-                                                    .filter_map(|lvl| {
-                                                        let p = lvl.get(0)?.as_str()?.to_string();
-                                                        let q = lvl.get(1)?.as_str()?.to_string();
-                                                        Some([p, q])
-                                                    })
-                                                    .collect::<Vec<[String;2]>>();
-                                                let asks = v
-                                                    .get("asks")
-                                                    .and_then(|a| a.as_array())
-                                                    .cloned()
-                                                    .unwrap_or_default()
-                                                    .into_iter()
-                                                    .filter_map(|lvl| {
-                                                        let p = lvl.get(0)?.as_str()?.to_string();
-                                                        let q = lvl.get(1)?.as_str()?.to_string();
-                                                        Some([p, q])
-                                                    })
-                                                    .collect::<Vec<[String;2]>>();
-                                                let ts = chrono::Utc::now().timestamp_millis();
-                                                let line = serde_json::json!({
-                                                    "agent": "coinbase",
-                                                    "type": "snapshot",
-                                                    "s": sym,
-                                                    "bids": bids,
-                                                    "asks": asks,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_ok() {
-                                                } else { break; }
-                                            }
-                                            "ticker" => {
-                                                let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
-                                                let sym = CanonicalService::canonical_pair("coinbase", raw).unwrap_or_else(|| raw.to_string());
-                                                let bid_px = v.get("best_bid").and_then(|p| p.as_str()).and_then(parse_decimal_str).unwrap_or_else(|| "?".to_string());
-                                                let bid_qty = v.get("best_bid_size").and_then(|q| q.as_str()).and_then(parse_decimal_str).unwrap_or_else(|| "?".to_string());
-                                                let ask_px = v.get("best_ask").and_then(|p| p.as_str()).and_then(parse_decimal_str).unwrap_or_else(|| "?".to_string());
-                                                let ask_qty = v.get("best_ask_size").and_then(|q| q.as_str()).and_then(parse_decimal_str).unwrap_or_else(|| "?".to_string());
-                                                let ts = v
-                                                    .get("time")
-                                                    .and_then(|t| t.as_str())
-                                                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
-                                                    .map(|dt| dt.timestamp_millis())
-                                                    .unwrap_or_default();
-                                                let line = serde_json::json!({
-                                                    "agent": "coinbase",
-                                                    "type": "book_ticker",
-                                                    "s": sym,
-                                                    "bp": bid_px,
-                                                    "bq": bid_qty,
-                                                    "ap": ask_px,
-                                                    "aq": ask_qty,
-                                                    "ts": ts
-                                                }).to_string();
-                                                if tx.send(line).await.is_ok() {
-                                                } else { break; }
                                             }
-                                            _ => {}
                                         }
                                     } else {
                                         tracing::warn!("non-json text msg");
@@ -487,11 +428,14 @@ async fn connection_task(
             }
         }
 
-        if symbols_rx.borrow().is_empty() {
+        // The symbol channel also closes on shutdown, possibly before the
+        // shutdown signal is seen; that is not a reconnect.
+        if *shutdown.borrow() || symbols_rx.borrow().is_empty() {
             break;
         }
 
         attempt = attempt.saturating_add(1);
+        ingest_stats::record("coinbase", Counter::Reconnect);
         let exp: u32 = attempt.saturating_sub(1).min(4);
         let delay = (1u64 << exp).min(max_reconnect_delay_secs);
         let sleep = std::time::Duration::from_secs(delay);
@@ -509,6 +453,61 @@ async fn connection_task(
     }
 }
 
+const CHANNELS: [&str; 3] = ["matches", "level2", "ticker"];
+
+/// Product ids per channel reported by a `subscriptions` message.
+fn subscribed_products(v: &serde_json::Value) -> HashMap<String, HashSet<String>> {
+    let mut out = HashMap::new();
+    for ch in v
+        .get("channels")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let Some(name) = ch.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let ids = ch
+            .get("product_ids")
+            .and_then(|p| p.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|p| p.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        out.insert(name.to_string(), ids);
+    }
+    out
+}
+
+/// Desired products missing from at least one channel.
+fn subscription_missing(v: &serde_json::Value, desired: &[String]) -> Vec<String> {
+    let active = subscribed_products(v);
+    desired
+        .iter()
+        .filter(|p| {
+            CHANNELS
+                .iter()
+                .any(|ch| !active.get(*ch).is_some_and(|ids| ids.contains(*p)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Subscribed products that are no longer desired.
+fn subscription_extras(v: &serde_json::Value, desired: &[String]) -> Vec<String> {
+    let active = subscribed_products(v);
+    let desired: HashSet<&String> = desired.iter().collect();
+    let mut extra: HashSet<String> = HashSet::new();
+    for ch in CHANNELS {
+        if let Some(ids) = active.get(ch) {
+            extra.extend(ids.iter().filter(|p| !desired.contains(p)).cloned());
+        }
+    }
+    extra.into_iter().collect()
+}
+
 async fn send_subscribe(
     ws: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
     symbols: &[String],
@@ -516,7 +515,7 @@ async fn send_subscribe(
     let msg = serde_json::json!({
         "type": "subscribe",
         "product_ids": symbols,
-        "channels": ["matches", "level2", "ticker"],
+        "channels": CHANNELS,
     });
     ws.send(Message::Text(msg.to_string())).await
 }
@@ -531,12 +530,157 @@ async fn send_unsubscribe(
     let msg = serde_json::json!({
         "type": "unsubscribe",
         "product_ids": symbols,
-        "channels": ["matches", "level2", "ticker"],
+        "channels": CHANNELS,
     });
     ws.send(Message::Text(msg.to_string())).await
 }
 
-async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
+/// Convert a feed message (match, level2 update or snapshot, ticker) into an
+/// output line. Returns `None` for other message types.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Option<String> {
+    let typ = v.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    let raw = v.get("product_id").and_then(|s| s.as_str()).unwrap_or("?");
+    let sym = symbol_or_raw("coinbase", raw);
+    let dec = |k: &str| {
+        v.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    let time = || {
+        v.get("time")
+            .and_then(|t| t.as_str())
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|dt| dt.timestamp_millis())
+            .unwrap_or_default()
+    };
+    let line = match typ {
+        "match" => {
+            // Missing or non-positive trade IDs are represented as JSON null.
+            let trade_id = v
+                .get("trade_id")
+                .and_then(|id| id.as_i64())
+                .filter(|id| *id > 0);
+            if let Some(id) = trade_id {
+                if let Some(last) = last_trade_ids.get_mut(&sym) {
+                    *last = id;
+                } else {
+                    last_trade_ids.insert(sym.clone(), id);
+                }
+            }
+            let skew = clock::current_skew_ms();
+            serde_json::json!({
+                "agent": "coinbase",
+                "type": "trade",
+                "s": sym,
+                "t": trade_id,
+                "p": dec("price"),
+                "q": dec("size"),
+                "ts": time(),
+                "skew": skew
+            })
+        }
+        "l2update" => {
+            let mut bids = Vec::new();
+            let mut asks = Vec::new();
+            if let Some(changes) = v.get("changes").and_then(|c| c.as_array()) {
+                for c in changes {
+                    if let (Some(side), Some(p), Some(sz)) = (
+                        c.get(0).and_then(|s| s.as_str()),
+                        c.get(1).and_then(|p| p.as_str()),
+                        c.get(2).and_then(|q| q.as_str()),
+                    ) {
+                        if let (Some(price), Some(qty)) =
+                            (parse_decimal_str(p), parse_decimal_str(sz))
+                        {
+                            if side == "buy" {
+                                bids.push([price, qty]);
+                            } else {
+                                asks.push([price, qty]);
+                            }
+                        }
+                    }
+                }
+            }
+            serde_json::json!({
+                "agent": "coinbase",
+                "type": "l2_diff",
+                "s": sym,
+                "bids": bids,
+                "asks": asks,
+                "ts": time()
+            })
+        }
+        "snapshot" => serde_json::json!({
+            "agent": "coinbase",
+            "type": "snapshot",
+            "s": sym,
+            "bids": levels(v.get("bids")),
+            "asks": levels(v.get("asks")),
+            "ts": chrono::Utc::now().timestamp_millis()
+        }),
+        "ticker" => serde_json::json!({
+            "agent": "coinbase",
+            "type": "book_ticker",
+            "s": sym,
+            "bp": dec("best_bid"),
+            "bq": dec("best_bid_size"),
+            "ap": dec("best_ask"),
+            "aq": dec("best_ask_size"),
+            "ts": time()
+        }),
+        _ => return None,
+    };
+    Some(line.to_string())
+}
+
+/// Record the `sequence` of a level2 message, returning `true` when messages
+/// were skipped since the previous one for the same product. Late or repeated
+/// messages are ignored. Gaps are counted in [`STREAM_SEQ_GAPS`].
+pub fn sequence_gap(v: &serde_json::Value, sequences: &mut HashMap<String, u64>) -> bool {
+    let (Some(product), Some(seq)) = (
+        v.get("product_id").and_then(|p| p.as_str()),
+        v.get("sequence").and_then(|s| s.as_u64()),
+    ) else {
+        return false;
+    };
+    let prev = sequences.get(product).copied();
+    if prev.is_some_and(|prev| seq <= prev) {
+        return false;
+    }
+    sequences.insert(product.to_string(), seq);
+    if prev.is_some_and(|prev| seq > prev + 1) {
+        STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+        ingest_stats::record("coinbase", Counter::Gap);
+        return true;
+    }
+    false
+}
+
+/// `[price, qty]` string pairs from a book side array.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = lvl.get(0)?.as_str()?.to_string();
+            let q = lvl.get(1)?.as_str()?.to_string();
+            Some([p, q])
+        })
+        .collect()
+}
+
+/// Periodically publish REST book snapshots for `symbol`, at the accelerated
+/// rate for the first `boost` of its life and paced by volatility after.
+async fn snapshot_task(
+    symbol: String,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    boost: std::time::Duration,
+) {
     let client = match http_client::builder().build() {
         Ok(c) => c,
         Err(e) => {
@@ -544,61 +688,61 @@ async fn snapshot_task(symbol: String, tx: mpsc::Sender<String>) {
             return;
         }
     };
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    let boost_until = tokio::time::Instant::now() + boost;
+    let mut pacer = SnapshotPacer::default();
     loop {
-        let url = format!(
-            "https://api.exchange.coinbase.com/products/{}/book?level=2",
-            symbol
-        );
-        match client.get(&url).send().await {
-            Ok(resp) => match resp.json::<serde_json::Value>().await {
-                Ok(v) => {
-                    let bids = v
-                        .get("bids")
-                        .and_then(|b| b.as_array())
-                        .cloned()
-                        .unwrap_or_default()
-                        .into_iter()
-                        .filter_map(|lvl| {
-                            let p = lvl.get(0)?.as_str()?.to_string();
-                            let q = lvl.get(1)?.as_str()?.to_string();
-                            Some([p, q])
-                        })
-                        .collect::<Vec<[String; 2]>>();
-                    let asks = v
-                        .get("asks")
-                        .and_then(|a| a.as_array())
-                        .cloned()
-                        .unwrap_or_default()
-                        .into_iter()
-                        .filter_map(|lvl| {
-                            let p = lvl.get(0)?.as_str()?.to_string();
-                            let q = lvl.get(1)?.as_str()?.to_string();
-                            Some([p, q])
-                        })
-                        .collect::<Vec<[String; 2]>>();
-                    let sym = CanonicalService::canonical_pair("coinbase", &symbol)
-                        .unwrap_or_else(|| symbol.clone());
-                    let ts = chrono::Utc::now().timestamp_millis();
-                    let line = serde_json::json!({
-                        "agent": "coinbase",
-                        "type": "snapshot",
-                        "s": sym,
-                        "bids": bids,
-                        "asks": asks,
-                        "ts": ts
-                    })
-                    .to_string();
-                    let _ = tx.send(line).await;
-                }
-                Err(e) => {
-                    tracing::error!(error=%e, symbol=%symbol, "snapshot parse failed");
-                }
-            },
-            Err(e) => {
-                tracing::error!(error=%e, symbol=%symbol, "snapshot failed");
+        if let Some(line) = fetch_snapshot(&client, &symbol).await {
+            pacer.observe(&line, tokio::time::Instant::now());
+            let _ = tx.send(line).await;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(pacer.delay(boost_until)) => {}
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
             }
         }
-        interval.tick().await;
+    }
+}
+
+/// Fetch the REST level2 book for `symbol` as a `snapshot` event.
+async fn fetch_snapshot(client: &reqwest::Client, symbol: &str) -> Option<String> {
+    let url = format!(
+        "https://api.exchange.coinbase.com/products/{}/book?level=2",
+        symbol
+    );
+    let limiter = rate_limit::coinbase();
+    limiter.acquire(1, Priority::High).await;
+    let resp = match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!(error=%e, symbol=%symbol, "snapshot failed");
+            return None;
+        }
+    };
+    match resp.json::<serde_json::Value>().await {
+        Ok(v) => {
+            let sym = CanonicalService::canonical_pair("coinbase", symbol)
+                .unwrap_or_else(|| symbol.to_string());
+            let ts = chrono::Utc::now().timestamp_millis();
+            let line = serde_json::json!({
+                "agent": "coinbase",
+                "type": "snapshot",
+                "s": sym,
+                "bids": levels(v.get("bids")),
+                "asks": levels(v.get("asks")),
+                "ts": ts
+            })
+            .to_string();
+            Some(line)
+        }
+        Err(e) => {
+            tracing::error!(error=%e, symbol=%symbol, "snapshot parse failed");
+            None
+        }
     }
 }
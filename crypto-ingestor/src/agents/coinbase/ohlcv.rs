@@ -4,7 +4,16 @@ use canonicalizer::{Bar, CanonicalService};
 use futures_util::future::join_all;
 use tokio::sync::mpsc;
 
-use crate::{agent::Agent, config::Settings, error::IngestorError, http_client};
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Priority},
+};
+
+/// Rate limit cost of a candles request.
+pub const CANDLES_WEIGHT: i64 = 1;
 
 pub struct CoinbaseOhlcvAgent {
     symbols: Vec<String>,
@@ -34,10 +43,13 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
         symbol, interval
     );
     let mut delay = Duration::from_millis(500);
+    let limiter = rate_limit::coinbase();
     for _ in 0..3 {
+        limiter.acquire(CANDLES_WEIGHT, Priority::Low).await;
         match client.get(&url).send().await {
             Ok(resp) => {
                 let status = resp.status();
+                limiter.observe(status, resp.headers());
                 if status.is_success() {
                     if let Ok(v) = resp.json::<serde_json::Value>().await {
                         if let Some(bar) = parse_bar(symbol, interval, &v) {
@@ -45,7 +57,10 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
                         }
                     }
                     break;
-                } else if status.as_u16() == 429 || status.is_server_error() {
+                } else if status.as_u16() == 429 {
+                    // the limiter now holds back the retry
+                    continue;
+                } else if status.is_server_error() {
                     tokio::time::sleep(delay).await;
                     delay *= 2;
                 } else {
@@ -59,13 +74,26 @@ pub async fn fetch_bar(client: &reqwest::Client, symbol: &str, interval: u64) ->
 }
 
 pub fn parse_bar(symbol: &str, interval: u64, v: &serde_json::Value) -> Option<Bar> {
-    let first = v.as_array()?.get(0)?.as_array()?;
-    let ts = first.get(0)?.as_i64()? * 1000; // seconds to ms
-    let low = val_to_string(first.get(1)?);
-    let high = val_to_string(first.get(2)?);
-    let open = val_to_string(first.get(3)?);
-    let close = val_to_string(first.get(4)?);
-    let volume = val_to_string(first.get(5)?);
+    parse_candle(symbol, interval, v.as_array()?.first()?)
+}
+
+/// Parse every row of a candles response, newest first as Coinbase sends them.
+pub fn parse_bars(symbol: &str, interval: u64, v: &serde_json::Value) -> Vec<Bar> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|row| parse_candle(symbol, interval, row))
+        .collect()
+}
+
+fn parse_candle(symbol: &str, interval: u64, row: &serde_json::Value) -> Option<Bar> {
+    let row = row.as_array()?;
+    let ts = row.first()?.as_i64()? * 1000; // seconds to ms
+    let low = val_to_string(row.get(1)?);
+    let high = val_to_string(row.get(2)?);
+    let open = val_to_string(row.get(3)?);
+    let close = val_to_string(row.get(4)?);
+    let volume = val_to_string(row.get(5)?);
     let sym =
         CanonicalService::canonical_pair("coinbase", symbol).unwrap_or_else(|| symbol.to_string());
     Some(Bar {
@@ -78,6 +106,9 @@ pub fn parse_bar(symbol: &str, interval: u64, v: &serde_json::Value) -> Option<B
         low,
         close,
         volume,
+        ac: None,
+        settle: None,
+        id: None,
         timestamp: ts,
     })
 }
@@ -101,6 +132,23 @@ impl Agent for CoinbaseOhlcvAgent {
                 symbol: None,
             })?;
         loop {
+            // Hold the round back until all of it fits the minute's budget
+            // rather than letting it crowd out snapshots request by request.
+            let round = (self.symbols.len() * self.intervals.len()) as i64 * CANDLES_WEIGHT;
+            let wait = rate_limit::coinbase().forecast(round, Priority::Low);
+            if !wait.is_zero() {
+                tracing::info!(
+                    ?wait,
+                    weight = round,
+                    "deferring coinbase bar poll to stay within budget"
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {},
+                    _ = shutdown.changed() => {
+                        if *shutdown.borrow() { break; }
+                    }
+                }
+            }
             let mut futs = Vec::new();
             for s in &self.symbols {
                 for &i in &self.intervals {
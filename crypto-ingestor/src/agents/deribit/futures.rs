@@ -0,0 +1,474 @@
+//! Deribit perpetuals and dated futures over the JSON-RPC WebSocket.
+//!
+//! Each instrument is subscribed to `trades`, `book` and `ticker`, and
+//! perpetuals also to `perpetual` for their funding rate. Book channels send a
+//! snapshot followed by changes chained through `prev_change_id`; a broken
+//! chain reconnects so the book restarts from a fresh snapshot. Inverse
+//! contracts map to `BTC-USD` and settle in the coin, linear ones such as
+//! `ETH_USDC-PERPETUAL` to `ETH-USDC` settling in USDC. Dated futures carry
+//! their expiry in the symbol, e.g. `BTC-USD-20241227`. Quantities are in
+//! Deribit's contract units: USD for inverse contracts, the coin for linear.
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::agents::{perp_id, symbol_or_raw, AgentFactory, STREAM_SEQ_GAPS};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::{AssetClass, Symbol};
+use rust_decimal::Decimal;
+
+/// Seconds between server heartbeat test requests; the connection is closed
+/// by Deribit when one goes unanswered.
+const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+/// Currencies listed by `deribit:all`.
+const CURRENCIES: [&str; 2] = ["BTC", "ETH"];
+/// Instruments per subscribe request.
+const SUBSCRIBE_BATCH: usize = 50;
+const INTERVAL: &str = "100ms";
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+fn http_err(e: reqwest::Error) -> IngestorError {
+    IngestorError::Http {
+        source: e,
+        exchange: "deribit",
+        symbol: None,
+    }
+}
+
+/// Fetch every live BTC and ETH perpetual and dated future.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let client = http_client::builder().build().map_err(http_err)?;
+    let mut instruments = Vec::new();
+    let limiter = rate_limit::limiter(Exchange::Deribit);
+    for currency in CURRENCIES {
+        limiter.acquire(1, Priority::High).await;
+        let resp: serde_json::Value = client
+            .get(format!(
+                "{rest_url}/public/get_instruments?currency={currency}&kind=future&expired=false"
+            ))
+            .send()
+            .await
+            .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+            .map_err(http_err)?
+            .json()
+            .await
+            .map_err(http_err)?;
+        let arr = resp
+            .get("result")
+            .and_then(|r| r.as_array())
+            .ok_or_else(|| IngestorError::Other("deribit unexpected response".into()))?;
+        instruments.extend(
+            arr.iter()
+                .filter(|i| i.get("is_active").and_then(|a| a.as_bool()) != Some(false))
+                .filter_map(|i| i.get("instrument_name")?.as_str().map(str::to_string)),
+        );
+    }
+    Ok(instruments)
+}
+
+pub struct DeribitAgent {
+    instruments: Vec<String>,
+    ws_url: String,
+    max_reconnect_delay_secs: u64,
+}
+
+impl DeribitAgent {
+    pub fn new(instruments: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            instruments,
+            ws_url: cfg.deribit_ws_url.clone(),
+            max_reconnect_delay_secs: cfg.deribit_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for DeribitAgent {
+    fn name(&self) -> &'static str {
+        "deribit"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let mut attempt: u32 = 0;
+        let mut request_id: u64 = 0;
+        let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            tracing::info!(url = %self.ws_url, "connecting");
+            match http_client::connect_ws(&self.ws_url).await {
+                Ok((mut ws, _)) => {
+                    tracing::info!("connected");
+                    let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                    attempt = 0;
+                    // Book change ids restart with each subscription.
+                    let mut change_ids: HashMap<String, i64> = HashMap::new();
+
+                    if let Err(e) =
+                        send_subscribe(&mut ws, &mut request_id, &self.instruments).await
+                    {
+                        tracing::error!(error=%e, "failed to send subscription");
+                        continue;
+                    }
+
+                    loop {
+                        tokio::select! {
+                            _ = shutdown.changed() => {
+                                if *shutdown.borrow() {
+                                    tracing::info!("shutdown signal - closing connection");
+                                    let _ = ws.close(None).await;
+                                    return Ok(());
+                                }
+                            }
+                            idle = watchdog.tick() => {
+                                if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                    break;
+                                }
+                            }
+                            msg = ws.next() => {
+                                watchdog.feed();
+                                match msg {
+                                    Some(Ok(Message::Text(txt))) => {
+                                        let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                            Ok(v) => v,
+                                            Err(e) => {
+                                                dead_letter::record("deribit", &txt, &e);
+                                                continue;
+                                            }
+                                        };
+                                        if let Some(err) = v.get("error") {
+                                            tracing::error!(error=?err, "deribit request rejected");
+                                            continue;
+                                        }
+                                        match v.get("method").and_then(|m| m.as_str()) {
+                                            Some("heartbeat") => {
+                                                let test_request = v.get("params").and_then(|p| p.get("type")).and_then(|t| t.as_str()) == Some("test_request");
+                                                if test_request {
+                                                    request_id += 1;
+                                                    let msg = rpc(request_id, "public/test", serde_json::json!({}));
+                                                    if let Err(e) = ws.send(Message::Text(msg)).await {
+                                                        tracing::error!(error=%e, "failed to answer heartbeat");
+                                                        break;
+                                                    }
+                                                }
+                                            }
+                                            Some("subscription") => {
+                                                if book_gap(&v, &mut change_ids) {
+                                                    tracing::warn!(channel=?v.get("params").and_then(|p| p.get("channel")), "book change id gap; reconnecting");
+                                                    let _ = ws.close(None).await;
+                                                    break;
+                                                }
+                                                let lines = parse_event(&v, &mut last_trade_ids);
+                                                if lines.is_empty() {
+                                                    dead_letter::record_no_event("deribit", &txt);
+                                                }
+                                                for line in lines {
+                                                    if tx.send(line).await.is_err() {
+                                                        return Ok(());
+                                                    }
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                    Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                    Some(Ok(_)) => { }
+                                    Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                    None => { tracing::warn!("stream ended"); break; }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error=%e, "connect failed");
+                }
+            }
+
+            attempt = attempt.saturating_add(1);
+            ingest_stats::record("deribit", Counter::Reconnect);
+            let exp: u32 = attempt.saturating_sub(1).min(4);
+            let delay = (1u64 << exp).min(self.max_reconnect_delay_secs);
+            let sleep = Duration::from_secs(delay);
+
+            tracing::info!(?sleep, "reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        tracing::info!("shutdown during backoff");
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct DeribitFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for DeribitFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let instruments = if spec.is_empty() {
+            vec!["BTC-PERPETUAL".to_string(), "ETH-PERPETUAL".to_string()]
+        } else if spec.eq_ignore_ascii_case("all") {
+            match fetch_all_symbols(&cfg.deribit_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch deribit instruments");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(DeribitAgent::new(instruments, cfg)))
+    }
+}
+
+fn rpc(id: u64, method: &str, params: serde_json::Value) -> String {
+    serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}).to_string()
+}
+
+fn is_perpetual(instrument: &str) -> bool {
+    instrument.ends_with("-PERPETUAL")
+}
+
+/// Enable server heartbeats, then subscribe to every channel of each
+/// instrument.
+async fn send_subscribe(
+    ws: &mut WsStream,
+    request_id: &mut u64,
+    instruments: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    *request_id += 1;
+    let heartbeat = rpc(
+        *request_id,
+        "public/set_heartbeat",
+        serde_json::json!({"interval": HEARTBEAT_INTERVAL_SECS}),
+    );
+    ws.send(Message::Text(heartbeat)).await?;
+    for batch in instruments.chunks(SUBSCRIBE_BATCH) {
+        let mut channels = Vec::new();
+        for inst in batch {
+            channels.push(format!("trades.{inst}.{INTERVAL}"));
+            channels.push(format!("book.{inst}.{INTERVAL}"));
+            channels.push(format!("ticker.{inst}.{INTERVAL}"));
+            if is_perpetual(inst) {
+                channels.push(format!("perpetual.{inst}.{INTERVAL}"));
+            }
+        }
+        *request_id += 1;
+        let msg = rpc(
+            *request_id,
+            "public/subscribe",
+            serde_json::json!({"channels": channels}),
+        );
+        ws.send(Message::Text(msg)).await?;
+    }
+    Ok(())
+}
+
+/// Track the `change_id` chain of book notifications per channel, returning
+/// `true` when a change does not follow the previous one. Gaps are counted in
+/// [`STREAM_SEQ_GAPS`].
+pub fn book_gap(v: &serde_json::Value, change_ids: &mut HashMap<String, i64>) -> bool {
+    let Some(params) = v.get("params") else {
+        return false;
+    };
+    let Some(channel) = params.get("channel").and_then(|c| c.as_str()) else {
+        return false;
+    };
+    if !channel.starts_with("book.") {
+        return false;
+    }
+    let Some(data) = params.get("data") else {
+        return false;
+    };
+    let Some(change_id) = data.get("change_id").and_then(|c| c.as_i64()) else {
+        return false;
+    };
+    let prev = change_ids.insert(channel.to_string(), change_id);
+    if data.get("type").and_then(|t| t.as_str()) == Some("snapshot") {
+        return false;
+    }
+    let expected = data.get("prev_change_id").and_then(|c| c.as_i64());
+    match (prev, expected) {
+        (Some(prev), Some(expected)) if prev != expected => {
+            STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+            ingest_stats::record("deribit", Counter::Gap);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Convert a Deribit subscription notification into canonical event lines.
+/// Tickers yield best bid/ask, mark price and open interest events; the
+/// `perpetual` channel yields funding.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Vec<String> {
+    let Some(params) = v.get("params") else {
+        return Vec::new();
+    };
+    let (Some(channel), Some(data)) = (
+        params.get("channel").and_then(|c| c.as_str()),
+        params.get("data"),
+    ) else {
+        return Vec::new();
+    };
+    let mut parts = channel.split('.');
+    let (Some(kind), Some(instrument)) = (parts.next(), parts.next()) else {
+        return Vec::new();
+    };
+    let sym = symbol_or_raw("deribit", instrument);
+    let ac = if is_perpetual(instrument) {
+        AssetClass::Perp
+    } else {
+        AssetClass::Future
+    };
+    // Linear contracts settle in the quote, inverse ones in the coin.
+    let pair = instrument.split('-').next().unwrap_or(instrument);
+    let settle = pair.split_once('_').map_or(pair, |(_, quote)| quote);
+    // Dated futures are already named by expiry, e.g. `BTC-USD-20241227`.
+    let id = match ac {
+        AssetClass::Perp => perp_id(&sym),
+        _ => sym.to_string(),
+    };
+    let dec = |src: &serde_json::Value, k: &str| num(src.get(k)).unwrap_or_else(|| "?".to_string());
+    let ts = |src: &serde_json::Value| {
+        src.get("timestamp")
+            .and_then(|t| t.as_i64())
+            .unwrap_or_default()
+    };
+    let event = |typ: &str, ts: i64| {
+        serde_json::json!({
+            "agent": "deribit",
+            "type": typ,
+            "s": sym,
+            "ac": ac,
+            "settle": settle,
+            "id": id,
+            "ts": ts
+        })
+    };
+
+    let events = match kind {
+        "trades" => data
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|t| {
+                // ETH trade ids are prefixed, e.g. `ETH-123456`.
+                let trade_id = t
+                    .get("trade_id")
+                    .and_then(|id| id.as_str())
+                    .and_then(|id| id.rsplit('-').next())
+                    .and_then(|id| id.parse::<i64>().ok())
+                    .filter(|id| *id > 0);
+                if let Some(id) = trade_id {
+                    last_trade_ids.insert(sym.clone(), id);
+                }
+                let mut e = event("trade", ts(t));
+                e["t"] = trade_id.into();
+                e["p"] = dec(t, "price").into();
+                e["q"] = dec(t, "amount").into();
+                e["skew"] = clock::current_skew_ms().into();
+                e
+            })
+            .collect(),
+        "book" => {
+            let typ = match data.get("type").and_then(|t| t.as_str()) {
+                Some("snapshot") => "snapshot",
+                _ => "l2_diff",
+            };
+            let mut e = event(typ, ts(data));
+            e["bids"] = serde_json::json!(levels(data.get("bids")));
+            e["asks"] = serde_json::json!(levels(data.get("asks")));
+            vec![e]
+        }
+        "ticker" => {
+            let mut quote = event("book_ticker", ts(data));
+            quote["bp"] = dec(data, "best_bid_price").into();
+            quote["bq"] = dec(data, "best_bid_amount").into();
+            quote["ap"] = dec(data, "best_ask_price").into();
+            quote["aq"] = dec(data, "best_ask_amount").into();
+            let mut mark = event("mark_price", ts(data));
+            mark["p"] = dec(data, "mark_price").into();
+            let mut oi = event("open_interest", ts(data));
+            oi["oi"] = dec(data, "open_interest").into();
+            vec![quote, mark, oi]
+        }
+        "perpetual" => {
+            let mut funding = event("funding", ts(data));
+            funding["r"] = dec(data, "interest").into();
+            vec![funding]
+        }
+        _ => Vec::new(),
+    };
+    events.into_iter().map(|e| e.to_string()).collect()
+}
+
+/// Deribit sends numbers, which may print in exponent form (`1.2e-5`).
+fn num(v: Option<&serde_json::Value>) -> Option<String> {
+    match v? {
+        serde_json::Value::String(s) => parse_decimal_str(s),
+        serde_json::Value::Number(n) => {
+            let s = n.to_string();
+            parse_decimal_str(&s).or_else(|| {
+                Decimal::from_scientific(&s)
+                    .ok()
+                    .map(|d| d.normalize().to_string())
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `[price, qty]` pairs from `[action, price, amount]` book changes, deleted
+/// levels having quantity zero. Snapshots use the `new` action throughout.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = num(lvl.get(1))?;
+            let q = match lvl.get(0)?.as_str()? {
+                "delete" => "0".to_string(),
+                _ => num(lvl.get(2))?,
+            };
+            Some([p, q])
+        })
+        .collect()
+}
@@ -0,0 +1,353 @@
+//! Historical option chain backfill from Deribit's public history API.
+//!
+//! For every day in the lookback window the last option trade of each
+//! instrument is used to rebuild one chain per expiry, tagged with the day's
+//! index delivery (settlement) price. The agent exits once the backfill is done.
+
+pub mod futures;
+
+use std::collections::{BTreeMap, HashMap};
+
+use canonicalizer::{AssetClass, OptionChain, OptionQuote, OptionSurfacePoint};
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+};
+
+/// Trades requested per page from the history endpoint (API maximum).
+const TRADES_PER_PAGE: usize = 1000;
+/// Upper bound on pages fetched per currency and day.
+const MAX_PAGES_PER_DAY: usize = 100;
+
+pub struct DeribitOptionsBackfillAgent {
+    currencies: Vec<String>,
+    history_url: String,
+    days: u64,
+}
+
+impl DeribitOptionsBackfillAgent {
+    pub fn new(currencies: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            currencies,
+            history_url: cfg.deribit_history_url.clone(),
+            days: cfg.deribit_backfill_days,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for DeribitOptionsBackfillAgent {
+    fn name(&self) -> &'static str {
+        "deribit_options_backfill"
+    }
+
+    async fn run(
+        &mut self,
+        shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let client = http_client::builder()
+            .build()
+            .map_err(|e| IngestorError::Http {
+                source: e,
+                exchange: "deribit",
+                symbol: None,
+            })?;
+
+        let today = Utc::now().date_naive();
+        for currency in &self.currencies {
+            let settlements =
+                fetch_delivery_prices(&client, &self.history_url, currency, self.days).await;
+            for back in (1..=self.days as i64).rev() {
+                if *shutdown.borrow() {
+                    return Ok(());
+                }
+                let day = today - Duration::days(back);
+                let start = day_start_ms(day);
+                let end = start + 86_400_000;
+                let trades =
+                    fetch_day_trades(&client, &self.history_url, currency, start, end).await;
+                let chains = build_chains(currency, end, &trades, settlements.get(&day).copied());
+                tracing::info!(%currency, %day, trades = trades.len(), chains = chains.len(), "deribit backfill day");
+                for chain in chains {
+                    if tx
+                        .send(serde_json::to_string(&chain).unwrap())
+                        .await
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        tracing::info!("deribit options backfill complete");
+        Ok(())
+    }
+}
+
+fn day_start_ms(day: NaiveDate) -> i64 {
+    Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+        .timestamp_millis()
+}
+
+async fn fetch_day_trades(
+    client: &reqwest::Client,
+    base: &str,
+    currency: &str,
+    start: i64,
+    end: i64,
+) -> Vec<Value> {
+    let mut trades = Vec::new();
+    let mut from = start;
+    let limiter = rate_limit::limiter(Exchange::Deribit);
+    for _ in 0..MAX_PAGES_PER_DAY {
+        let url = format!(
+            "{}/public/get_last_trades_by_currency_and_time?currency={}&kind=option&start_timestamp={}&end_timestamp={}&count={}&sorting=asc",
+            base, currency, from, end, TRADES_PER_PAGE
+        );
+        limiter.acquire(1, Priority::Low).await;
+        let v = match client
+            .get(&url)
+            .send()
+            .await
+            .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        {
+            Ok(resp) => match resp.json::<Value>().await {
+                Ok(v) => v,
+                Err(e) => {
+                    tracing::error!(error=%e, %currency, "failed to decode deribit trades");
+                    break;
+                }
+            },
+            Err(e) => {
+                tracing::error!(error=%e, %currency, "deribit trades request failed");
+                break;
+            }
+        };
+        let result = v.get("result");
+        let page = result
+            .and_then(|r| r.get("trades"))
+            .and_then(|t| t.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let last_ts = page
+            .last()
+            .and_then(|t| t.get("timestamp"))
+            .and_then(|t| t.as_i64());
+        trades.extend(page);
+        let has_more = result
+            .and_then(|r| r.get("has_more"))
+            .and_then(|h| h.as_bool())
+            .unwrap_or(false);
+        match last_ts {
+            Some(ts) if has_more && ts + 1 < end => from = ts + 1,
+            _ => break,
+        }
+    }
+    trades
+}
+
+async fn fetch_delivery_prices(
+    client: &reqwest::Client,
+    base: &str,
+    currency: &str,
+    days: u64,
+) -> HashMap<NaiveDate, f64> {
+    let url = format!(
+        "{}/public/get_delivery_prices?index_name={}_usd&offset=0&count={}",
+        base,
+        currency.to_lowercase(),
+        days + 1
+    );
+    let limiter = rate_limit::limiter(Exchange::Deribit);
+    limiter.acquire(1, Priority::Low).await;
+    match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => match resp.json::<Value>().await {
+            Ok(v) => parse_delivery_prices(&v),
+            Err(e) => {
+                tracing::error!(error=%e, %currency, "failed to decode deribit delivery prices");
+                HashMap::new()
+            }
+        },
+        Err(e) => {
+            tracing::error!(error=%e, %currency, "deribit delivery prices request failed");
+            HashMap::new()
+        }
+    }
+}
+
+/// Index delivery prices by day from a `get_delivery_prices` response.
+pub fn parse_delivery_prices(v: &Value) -> HashMap<NaiveDate, f64> {
+    v.get("result")
+        .and_then(|r| r.get("data"))
+        .and_then(|d| d.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let date = NaiveDate::parse_from_str(item.get("date")?.as_str()?, "%Y-%m-%d").ok()?;
+            Some((date, item.get("delivery_price")?.as_f64()?))
+        })
+        .collect()
+}
+
+/// Split an instrument name such as `BTC-27SEP24-60000-C` into expiry
+/// (seconds, at Deribit's 08:00 UTC expiry time), strike and contract type.
+fn parse_instrument(name: &str) -> Option<(i64, f64, &'static str)> {
+    let mut parts = name.split('-');
+    let _underlying = parts.next()?;
+    let date = NaiveDate::parse_from_str(parts.next()?, "%d%b%y").ok()?;
+    let strike = parts.next()?.replace('d', ".").parse().ok()?;
+    let kind = match parts.next()? {
+        "C" => "CALL",
+        "P" => "PUT",
+        _ => return None,
+    };
+    let expiry = Utc
+        .from_utc_datetime(&date.and_hms_opt(8, 0, 0)?)
+        .timestamp();
+    Some((expiry, strike, kind))
+}
+
+/// Build one chain per expiry from a day of trades, keeping the last trade of
+/// each instrument. Prices are converted from the coin to USD using the
+/// trade's index price and implied volatility from percent to a ratio.
+fn build_chains(
+    currency: &str,
+    ts: i64,
+    trades: &[Value],
+    settlement_price: Option<f64>,
+) -> Vec<OptionChain> {
+    let mut last: HashMap<&str, &Value> = HashMap::new();
+    for t in trades {
+        let Some(name) = t.get("instrument_name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let newer = last.get(name).is_none_or(|prev| {
+            t.get("timestamp").and_then(|x| x.as_i64())
+                >= prev.get("timestamp").and_then(|x| x.as_i64())
+        });
+        if newer {
+            last.insert(name, t);
+        }
+    }
+
+    let mut by_expiry: BTreeMap<i64, Vec<OptionQuote>> = BTreeMap::new();
+    for (name, t) in last {
+        let Some((expiry, strike, kind)) = parse_instrument(name) else {
+            continue;
+        };
+        let index = t.get("index_price").and_then(|x| x.as_f64());
+        let price = t.get("price").and_then(|x| x.as_f64());
+        let iv = t.get("iv").and_then(|x| x.as_f64()).map(|iv| iv / 100.0);
+        by_expiry.entry(expiry).or_default().push(OptionQuote {
+            strike,
+            kind: kind.to_string(),
+            bid: None,
+            ask: None,
+            last: price.zip(index).map(|(p, i)| p * i),
+            iv,
+            greeks: None,
+        });
+    }
+
+    let symbol = format!("{}-USD", currency.to_uppercase());
+    by_expiry
+        .into_iter()
+        .map(|(expiry, mut options)| {
+            options.sort_by(|a, b| {
+                a.strike
+                    .total_cmp(&b.strike)
+                    .then_with(|| a.kind.cmp(&b.kind))
+            });
+            let surface = options
+                .iter()
+                .filter_map(|q| {
+                    q.iv.map(|iv| OptionSurfacePoint {
+                        strike: q.strike,
+                        expiry,
+                        iv,
+                    })
+                })
+                .collect();
+            OptionChain {
+                agent: "deribit".to_string(),
+                r#type: "option_chain".to_string(),
+                s: symbol.clone(),
+                ac: AssetClass::Option,
+                settle: currency.to_uppercase(),
+                expiry,
+                options,
+                surface,
+                ts: Some(ts),
+                settlement_price,
+            }
+        })
+        .collect()
+}
+
+pub struct DeribitOptionsBackfillFactory;
+
+#[async_trait::async_trait]
+impl super::AgentFactory for DeribitOptionsBackfillFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let currencies: Vec<String> = if spec.trim().is_empty() {
+            vec!["BTC".to_string()]
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        Some(Box::new(DeribitOptionsBackfillAgent::new(currencies, cfg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_chains_keeps_last_trade_per_instrument() {
+        let trades = vec![
+            serde_json::json!({"instrument_name": "BTC-27SEP24-60000-C", "timestamp": 1, "price": 0.05, "index_price": 60000.0, "iv": 50.0}),
+            serde_json::json!({"instrument_name": "BTC-27SEP24-60000-C", "timestamp": 2, "price": 0.06, "index_price": 60000.0, "iv": 55.0}),
+            serde_json::json!({"instrument_name": "BTC-27SEP24-50000-P", "timestamp": 3, "price": 0.01, "index_price": 60000.0, "iv": 60.0}),
+            serde_json::json!({"instrument_name": "BTC-5JUL24-70000-C", "timestamp": 4, "price": 0.002, "index_price": 60000.0}),
+        ];
+        let chains = build_chains("btc", 1_000, &trades, Some(59_000.0));
+        assert_eq!(chains.len(), 2);
+        let sep = chains
+            .iter()
+            .find(|c| c.options.len() == 2)
+            .expect("sep chain");
+        assert_eq!(sep.s, "BTC-USD");
+        assert_eq!(sep.ts, Some(1_000));
+        assert_eq!(sep.settlement_price, Some(59_000.0));
+        assert_eq!(sep.options[0].kind, "PUT");
+        let call = &sep.options[1];
+        assert!((call.last.unwrap() - 3600.0).abs() < 1e-6);
+        assert!((call.iv.unwrap() - 0.55).abs() < 1e-9);
+        assert_eq!(sep.surface.len(), 2);
+    }
+
+    #[test]
+    fn parse_instrument_reads_expiry_strike_and_kind() {
+        let (expiry, strike, kind) = parse_instrument("ETH-5JUL24-3500-P").unwrap();
+        assert_eq!(expiry, 1_720_166_400);
+        assert_eq!(strike, 3500.0);
+        assert_eq!(kind, "PUT");
+        assert!(parse_instrument("BTC-PERPETUAL").is_none());
+    }
+}
@@ -0,0 +1,562 @@
+//! Gate.io spot market data over the v4 WebSocket API.
+//!
+//! Each currency pair is subscribed to `spot.trades`, `spot.book_ticker` and
+//! `spot.order_book_update`. Book updates are incremental diffs pushed every
+//! 100ms; the order book channel takes one pair per request. Each book starts
+//! with a REST snapshot: its first updates, and those after an update id gap,
+//! are held back until the snapshot has been published (see [`BookSync`]).
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{listing_events, symbol_or_raw, AgentFactory, BookSnapshot, BookSync, STREAM_SEQ_GAPS};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::{CanonicalService, Symbol};
+
+/// Pairs per trade/ticker subscribe request.
+const SUBSCRIBE_BATCH: usize = 100;
+/// Gate.io closes connections without traffic; ping well inside its timeout.
+const PING_INTERVAL_SECS: u64 = 20;
+const BOOK_UPDATE_INTERVAL: &str = "100ms";
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Fetch all tradable USDT-quoted currency pairs.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "gate",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Gate);
+    limiter.acquire(1, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/api/v4/spot/currency_pairs"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .as_array()
+        .ok_or_else(|| IngestorError::Other("gate unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|p| p.get("trade_status").and_then(|s| s.as_str()) == Some("tradable"))
+        .filter(|p| p.get("quote").and_then(|q| q.as_str()) == Some("USDT"))
+        .filter_map(|p| p.get("id").and_then(|s| s.as_str()).map(str::to_string))
+        .collect())
+}
+
+pub struct GateAgent {
+    symbols: Vec<String>,
+    /// Follow the exchange's pair list instead of a fixed set.
+    all: bool,
+    ws_url: String,
+    rest_url: String,
+    max_reconnect_delay_secs: u64,
+    refresh_interval_mins: u64,
+}
+
+impl GateAgent {
+    pub fn new(symbols: Vec<String>, all: bool, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            all,
+            ws_url: cfg.gate_ws_url.clone(),
+            rest_url: cfg.gate_rest_url.clone(),
+            max_reconnect_delay_secs: cfg.gate_max_reconnect_delay_secs,
+            refresh_interval_mins: cfg.gate_refresh_interval_mins,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for GateAgent {
+    fn name(&self) -> &'static str {
+        "gate"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let (sym_tx, sym_rx) = tokio::sync::watch::channel(self.symbols.clone());
+        let handle = tokio::spawn(connection_task(
+            sym_rx,
+            shutdown.clone(),
+            tx.clone(),
+            self.ws_url.clone(),
+            self.rest_url.clone(),
+            self.max_reconnect_delay_secs,
+        ));
+
+        let mut refresh = tokio::time::interval(std::time::Duration::from_secs(
+            60 * self.refresh_interval_mins.max(1),
+        ));
+        refresh.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        refresh.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() { break; }
+                }
+                _ = refresh.tick(), if self.all => {
+                    match fetch_all_symbols(&self.rest_url).await {
+                        Ok(new_symbols) => {
+                            let new_set: HashSet<_> = new_symbols.iter().cloned().collect();
+                            let old_set: HashSet<_> = self.symbols.iter().cloned().collect();
+                            let added: Vec<_> = new_set.difference(&old_set).cloned().collect();
+                            let removed: Vec<_> = old_set.difference(&new_set).cloned().collect();
+                            if added.is_empty() && removed.is_empty() {
+                                tracing::info!("symbol refresh: no changes");
+                                continue;
+                            }
+                            tracing::info!(?added, ?removed, total=new_symbols.len(), "symbol refresh");
+                            for line in listing_events("gate", &added, &removed) {
+                                let _ = tx.send(line).await;
+                            }
+                            self.symbols = new_symbols;
+                            let _ = sym_tx.send(self.symbols.clone());
+                        }
+                        Err(e) => tracing::error!(error=%e, "failed to refresh symbols"),
+                    }
+                }
+            }
+        }
+
+        drop(sym_tx);
+        let _ = handle.await;
+        Ok(())
+    }
+}
+
+pub struct GateFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for GateFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let all = spec.eq_ignore_ascii_case("all");
+        let symbols = if spec.is_empty() {
+            vec!["BTC_USDT".to_string()]
+        } else if all {
+            match fetch_all_symbols(&cfg.gate_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch gate symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(GateAgent::new(symbols, all, cfg)))
+    }
+}
+
+async fn connection_task(
+    mut symbols_rx: tokio::sync::watch::Receiver<Vec<String>>,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    ws_url: String,
+    rest_url: String,
+    max_reconnect_delay_secs: u64,
+) {
+    let mut attempt: u32 = 0;
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+    let client = http_client::builder().build().ok();
+
+    loop {
+        if *shutdown.borrow() {
+            break;
+        }
+
+        tracing::info!(url = %ws_url, "connecting");
+        let mut current_symbols = symbols_rx.borrow().clone();
+        match http_client::connect_ws(&ws_url).await {
+            Ok((mut ws, _)) => {
+                tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                attempt = 0;
+                // Book update ids are checked per subscription.
+                let mut update_ids: HashMap<String, u64> = HashMap::new();
+                let mut books = BookSync::default();
+
+                if let Err(e) = send_event(&mut ws, "subscribe", &current_symbols).await {
+                    tracing::error!(error=%e, "failed to send subscription");
+                    continue;
+                }
+
+                let ping_period = std::time::Duration::from_secs(PING_INTERVAL_SECS);
+                let mut ping = tokio::time::interval_at(
+                    tokio::time::Instant::now() + ping_period,
+                    ping_period,
+                );
+
+                loop {
+                    tokio::select! {
+                        _ = shutdown.changed() => {
+                            if *shutdown.borrow() {
+                                tracing::info!("shutdown signal - closing connection");
+                                let _ = ws.close(None).await;
+                                return;
+                            }
+                        }
+                        Some((symbol, snapshot)) = books.snapshot() => {
+                            for line in books.publish(&symbol, snapshot) {
+                                if tx.send(line).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        _ = ping.tick() => {
+                            let msg = serde_json::json!({"time": chrono::Utc::now().timestamp(), "channel": "spot.ping"});
+                            if let Err(e) = ws.send(Message::Text(msg.to_string())).await {
+                                tracing::error!(error=%e, "failed to send ping");
+                                break;
+                            }
+                        }
+                        changed = symbols_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            let new_syms = symbols_rx.borrow().clone();
+                            let new_set: HashSet<_> = new_syms.iter().cloned().collect();
+                            let old_set: HashSet<_> = current_symbols.iter().cloned().collect();
+                            let to_sub: Vec<_> = new_set.difference(&old_set).cloned().collect();
+                            let to_unsub: Vec<_> = old_set.difference(&new_set).cloned().collect();
+                            let _ = send_event(&mut ws, "unsubscribe", &to_unsub).await;
+                            if let Err(e) = send_event(&mut ws, "subscribe", &to_sub).await {
+                                tracing::error!(error=%e, "failed to update subscription");
+                                break;
+                            }
+                            current_symbols = new_syms;
+                        }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
+                        msg = ws.next() => {
+                            watchdog.feed();
+                            match msg {
+                                Some(Ok(Message::Text(txt))) => {
+                                    let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                        Ok(v) => v,
+                                        Err(e) => {
+                                            dead_letter::record("gate", &txt, &e);
+                                            continue;
+                                        }
+                                    };
+                                    if let Some(err) = v.get("error").filter(|e| !e.is_null()) {
+                                        tracing::error!(channel=?v.get("channel"), error=?err, "gate request rejected");
+                                        continue;
+                                    }
+                                    let gap = book_gap(&v, &mut update_ids);
+                                    if gap {
+                                        tracing::warn!(result=?v.get("result").and_then(|r| r.get("s")), "order book update id gap");
+                                    }
+                                    if replayed_trade(&v, &last_trade_ids) {
+                                        tracing::debug!(result=?v.get("result").and_then(|r| r.get("id")), "dropping replayed trade");
+                                        continue;
+                                    }
+                                    let parsed = parse_event(&v, &mut last_trade_ids);
+                                    if parsed.is_none() && v.get("event").and_then(|e| e.as_str()) == Some("update") {
+                                        dead_letter::record_no_event("gate", &txt);
+                                    }
+                                    if let Some(mut line) = parsed {
+                                        if let Some((symbol, first, last)) = book_update_range(&v) {
+                                            match books.diff(symbol, (first, last), gap, line) {
+                                                Some(diff) => line = diff,
+                                                None => {
+                                                    if let Some(client) = client.clone() {
+                                                        let (rest_url, pair) = (rest_url.clone(), symbol.to_string());
+                                                        books.resync(symbol, async move {
+                                                            fetch_snapshot(&client, &rest_url, &pair).await
+                                                        });
+                                                    }
+                                                    continue;
+                                                }
+                                            }
+                                        }
+                                        if tx.send(line).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                Some(Ok(_)) => { }
+                                Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                None => { tracing::warn!("stream ended"); break; }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!(error=%e, "connect failed");
+            }
+        }
+
+        attempt = attempt.saturating_add(1);
+        ingest_stats::record("gate", Counter::Reconnect);
+        let exp: u32 = attempt.saturating_sub(1).min(4);
+        let delay = (1u64 << exp).min(max_reconnect_delay_secs);
+        let sleep = std::time::Duration::from_secs(delay);
+
+        tracing::info!(?sleep, "reconnecting");
+        tokio::select! {
+            _ = tokio::time::sleep(sleep) => {},
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    tracing::info!("shutdown during backoff");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Send a `subscribe` or `unsubscribe` event for the trade, book ticker and
+/// order book channels of `symbols`.
+async fn send_event(
+    ws: &mut WsStream,
+    event: &str,
+    symbols: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let time = chrono::Utc::now().timestamp();
+    let mut requests = Vec::new();
+    for batch in symbols.chunks(SUBSCRIBE_BATCH) {
+        for channel in ["spot.trades", "spot.book_ticker"] {
+            requests.push(serde_json::json!({"time": time, "channel": channel, "event": event, "payload": batch}));
+        }
+    }
+    for s in symbols {
+        requests.push(serde_json::json!({
+            "time": time,
+            "channel": "spot.order_book_update",
+            "event": event,
+            "payload": [s, BOOK_UPDATE_INTERVAL],
+        }));
+    }
+    for msg in requests {
+        ws.send(Message::Text(msg.to_string())).await?;
+    }
+    Ok(())
+}
+
+/// Currency pair and first and last update id `U`/`u` of an order book
+/// update.
+pub fn book_update_range(v: &serde_json::Value) -> Option<(&str, u64, u64)> {
+    if v.get("channel").and_then(|c| c.as_str()) != Some("spot.order_book_update")
+        || v.get("event").and_then(|e| e.as_str()) != Some("update")
+    {
+        return None;
+    }
+    let r = v.get("result")?;
+    Some((
+        r.get("s")?.as_str()?,
+        r.get("U")?.as_u64()?,
+        r.get("u")?.as_u64()?,
+    ))
+}
+
+/// Check an order book update's first id `U` against the last id `u` of the
+/// previous update for its pair, returning `true` when updates were missed.
+/// Gaps are counted in [`STREAM_SEQ_GAPS`].
+pub fn book_gap(v: &serde_json::Value, update_ids: &mut HashMap<String, u64>) -> bool {
+    let Some((pair, first, last)) = book_update_range(v) else {
+        return false;
+    };
+    match update_ids.insert(pair.to_string(), last) {
+        Some(prev) if first > prev + 1 => {
+            STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+            ingest_stats::record("gate", Counter::Gap);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Fetch the top 100 levels of the REST book of `pair` as a `snapshot`
+/// event.
+async fn fetch_snapshot(
+    client: &reqwest::Client,
+    rest_url: &str,
+    pair: &str,
+) -> Option<BookSnapshot> {
+    let url =
+        format!("{rest_url}/api/v4/spot/order_book?currency_pair={pair}&limit=100&with_id=true");
+    let limiter = rate_limit::limiter(Exchange::Gate);
+    limiter.acquire(1, Priority::High).await;
+    let resp = match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!(error=%e, %pair, "snapshot failed");
+            return None;
+        }
+    };
+    match resp.json::<serde_json::Value>().await {
+        Ok(v) => snapshot_event(pair, &v),
+        Err(e) => {
+            tracing::error!(error=%e, %pair, "snapshot parse failed");
+            None
+        }
+    }
+}
+
+/// Update id and canonical `snapshot` line of a REST order book response.
+pub fn snapshot_event(pair: &str, resp: &serde_json::Value) -> Option<BookSnapshot> {
+    let id = resp.get("id")?.as_u64()?;
+    let line = serde_json::json!({
+        "agent": "gate",
+        "type": "snapshot",
+        "s": symbol_or_raw("gate", pair),
+        "bids": levels(resp.get("bids")),
+        "asks": levels(resp.get("asks")),
+        "ts": resp
+            .get("current")
+            .and_then(|t| t.as_i64())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis())
+    });
+    Some((id, line.to_string()))
+}
+
+/// Whether `v` is a trade at or below the last trade id seen for its pair,
+/// e.g. one replayed after a reconnect.
+pub fn replayed_trade(v: &serde_json::Value, last_trade_ids: &HashMap<Symbol, i64>) -> bool {
+    if v.get("channel").and_then(|c| c.as_str()) != Some("spot.trades") {
+        return false;
+    }
+    let Some(r) = v.get("result") else {
+        return false;
+    };
+    let (Some(raw), Some(id)) = (
+        r.get("currency_pair").and_then(|s| s.as_str()),
+        r.get("id").and_then(|i| i.as_i64()),
+    ) else {
+        return false;
+    };
+    // Looked up without `symbol_or_raw` so unmapped pairs are counted once,
+    // by `parse_event`.
+    let sym =
+        CanonicalService::canonical_symbol("gate", raw).unwrap_or_else(|| Symbol::intern(raw));
+    last_trade_ids.get(&sym).is_some_and(|last| id <= *last)
+}
+
+/// Convert a Gate.io channel update into a canonical event line. Trades at
+/// or below the last trade id of their pair are dropped.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Option<String> {
+    if v.get("event").and_then(|e| e.as_str()) != Some("update") {
+        return None;
+    }
+    let channel = v.get("channel").and_then(|c| c.as_str())?;
+    let r = v.get("result")?;
+    let raw = r
+        .get("currency_pair")
+        .or_else(|| r.get("s"))
+        .and_then(|s| s.as_str())?;
+    let sym = symbol_or_raw("gate", raw);
+    let dec = |k: &str| {
+        r.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+
+    let line = match channel {
+        "spot.trades" => {
+            let id = r.get("id").and_then(|i| i.as_i64());
+            if let Some(id) = id {
+                match last_trade_ids.get_mut(&sym) {
+                    Some(last) if id <= *last => return None,
+                    Some(last) => *last = id,
+                    None => {
+                        last_trade_ids.insert(sym.clone(), id);
+                    }
+                }
+            }
+            // Millisecond timestamps carry a fractional part, e.g. "1606292218213.4578".
+            let ts = r
+                .get("create_time_ms")
+                .and_then(|t| t.as_str())
+                .and_then(|t| t.parse::<f64>().ok())
+                .map(|t| t as i64)
+                .unwrap_or_default();
+            serde_json::json!({
+                "agent": "gate",
+                "type": "trade",
+                "s": sym,
+                "t": id,
+                "p": dec("price"),
+                "q": dec("amount"),
+                "ts": ts,
+                "skew": clock::current_skew_ms()
+            })
+        }
+        "spot.book_ticker" => serde_json::json!({
+            "agent": "gate",
+            "type": "book_ticker",
+            "s": sym,
+            "bp": dec("b"),
+            "bq": dec("B"),
+            "ap": dec("a"),
+            "aq": dec("A"),
+            "ts": r.get("t").and_then(|t| t.as_i64()).unwrap_or_default()
+        }),
+        "spot.order_book_update" => serde_json::json!({
+            "agent": "gate",
+            "type": "l2_diff",
+            "s": sym,
+            "bids": levels(r.get("b")),
+            "asks": levels(r.get("a")),
+            "ts": r.get("t").and_then(|t| t.as_i64()).unwrap_or_default()
+        }),
+        _ => return None,
+    };
+    Some(line.to_string())
+}
+
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
@@ -0,0 +1,359 @@
+//! Hyperliquid perpetuals over the info WebSocket.
+//!
+//! Each coin is subscribed to `trades`, `l2Book` and `activeAssetCtx`. Book
+//! pushes are full snapshots of the top levels; asset contexts carry the
+//! current funding rate, open interest and mark price. Funding is paid
+//! hourly, so funding events carry `"funding_interval_h": 1`; multiply `r` by
+//! 8 to compare it with the 8h rates of other venues. All contracts are
+//! quoted in USD and settle in USDC, so coins map to `COIN-USD` perps.
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{perp_id, symbol_or_raw, AgentFactory};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::adapter::HyperliquidAdapter;
+use canonicalizer::{AssetClass, Symbol};
+
+/// Hyperliquid drops connections idle for 60 seconds.
+const PING_INTERVAL_SECS: u64 = 30;
+const SUBSCRIPTIONS: [&str; 3] = ["trades", "l2Book", "activeAssetCtx"];
+const SETTLE: &str = "USDC";
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Fetch every listed perpetual coin.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "hyperliquid",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Hyperliquid);
+    // Info requests other than the light book and mid queries weigh 20.
+    limiter.acquire(20, Priority::High).await;
+    let resp: serde_json::Value = client
+        .post(format!("{rest_url}/info"))
+        .json(&serde_json::json!({"type": "meta"}))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .get("universe")
+        .and_then(|u| u.as_array())
+        .ok_or_else(|| IngestorError::Other("hyperliquid unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|a| a.get("isDelisted").and_then(|d| d.as_bool()) != Some(true))
+        .filter_map(|a| a.get("name").and_then(|n| n.as_str()).map(str::to_string))
+        .collect())
+}
+
+pub struct HyperliquidAgent {
+    coins: Vec<String>,
+    ws_url: String,
+    max_reconnect_delay_secs: u64,
+}
+
+impl HyperliquidAgent {
+    pub fn new(coins: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            coins,
+            ws_url: cfg.hyperliquid_ws_url.clone(),
+            max_reconnect_delay_secs: cfg.hyperliquid_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for HyperliquidAgent {
+    fn name(&self) -> &'static str {
+        "hyperliquid"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let mut attempt: u32 = 0;
+        let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            tracing::info!(url = %self.ws_url, "connecting");
+            match http_client::connect_ws(&self.ws_url).await {
+                Ok((mut ws, _)) => {
+                    tracing::info!("connected");
+                    let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                    attempt = 0;
+
+                    if let Err(e) = send_subscribe(&mut ws, &self.coins).await {
+                        tracing::error!(error=%e, "failed to send subscription");
+                        continue;
+                    }
+
+                    let ping_period = Duration::from_secs(PING_INTERVAL_SECS);
+                    let mut ping = tokio::time::interval_at(
+                        tokio::time::Instant::now() + ping_period,
+                        ping_period,
+                    );
+
+                    loop {
+                        tokio::select! {
+                            _ = shutdown.changed() => {
+                                if *shutdown.borrow() {
+                                    tracing::info!("shutdown signal - closing connection");
+                                    let _ = ws.close(None).await;
+                                    return Ok(());
+                                }
+                            }
+                            _ = ping.tick() => {
+                                let msg = serde_json::json!({"method": "ping"});
+                                if let Err(e) = ws.send(Message::Text(msg.to_string())).await {
+                                    tracing::error!(error=%e, "failed to send ping");
+                                    break;
+                                }
+                            }
+                            idle = watchdog.tick() => {
+                                if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                    break;
+                                }
+                            }
+                            msg = ws.next() => {
+                                watchdog.feed();
+                                match msg {
+                                    Some(Ok(Message::Text(txt))) => {
+                                        let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                            Ok(v) => v,
+                                            Err(e) => {
+                                                dead_letter::record("hyperliquid", &txt, &e);
+                                                continue;
+                                            }
+                                        };
+                                        if v.get("channel").and_then(|c| c.as_str()) == Some("error") {
+                                            tracing::error!(data=?v.get("data"), "hyperliquid request rejected");
+                                            continue;
+                                        }
+                                        let lines = parse_event(&v, &mut last_trade_ids);
+                                        let channel = v.get("channel").and_then(|c| c.as_str());
+                                        if lines.is_empty() && !matches!(channel, Some("subscriptionResponse" | "pong")) {
+                                            dead_letter::record_no_event("hyperliquid", &txt);
+                                        }
+                                        for line in lines {
+                                            if tx.send(line).await.is_err() {
+                                                return Ok(());
+                                            }
+                                        }
+                                    }
+                                    Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                    Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                    Some(Ok(_)) => { }
+                                    Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                    None => { tracing::warn!("stream ended"); break; }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error=%e, "connect failed");
+                }
+            }
+
+            attempt = attempt.saturating_add(1);
+            ingest_stats::record("hyperliquid", Counter::Reconnect);
+            let exp: u32 = attempt.saturating_sub(1).min(4);
+            let delay = (1u64 << exp).min(self.max_reconnect_delay_secs);
+            let sleep = Duration::from_secs(delay);
+
+            tracing::info!(?sleep, "reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        tracing::info!("shutdown during backoff");
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct HyperliquidFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for HyperliquidFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let coins = if spec.is_empty() {
+            vec!["BTC".to_string()]
+        } else if spec.eq_ignore_ascii_case("all") {
+            match fetch_all_symbols(&cfg.hyperliquid_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch hyperliquid coins");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| HyperliquidAdapter::coin(s.trim()))
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(HyperliquidAgent::new(coins, cfg)))
+    }
+}
+
+/// Subscriptions are per coin and channel; Hyperliquid has no batch form.
+async fn send_subscribe(
+    ws: &mut WsStream,
+    coins: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    for coin in coins {
+        for channel in SUBSCRIPTIONS {
+            let msg = serde_json::json!({
+                "method": "subscribe",
+                "subscription": {"type": channel, "coin": coin},
+            });
+            ws.send(Message::Text(msg.to_string())).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert a Hyperliquid channel push into canonical event lines. Trade
+/// pushes may carry several trades and asset contexts yield funding, open
+/// interest and mark price events.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Vec<String> {
+    let channel = v.get("channel").and_then(|c| c.as_str()).unwrap_or("");
+    let Some(data) = v.get("data") else {
+        return Vec::new();
+    };
+    let symbol = |coin: &str| symbol_or_raw("hyperliquid", coin);
+    let dec = |src: &serde_json::Value, k: &str| {
+        src.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+
+    let events = match channel {
+        "trades" => data
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|t| {
+                let sym = symbol(t.get("coin")?.as_str()?);
+                let trade_id = t.get("tid").and_then(|id| id.as_i64()).filter(|id| *id > 0);
+                if let Some(id) = trade_id {
+                    last_trade_ids.insert(sym.clone(), id);
+                }
+                Some(serde_json::json!({
+                    "agent": "hyperliquid",
+                    "type": "trade",
+                    "s": sym,
+                    "ac": AssetClass::Perp,
+                    "settle": SETTLE,
+                    "id": perp_id(&sym),
+                    "t": trade_id,
+                    "p": dec(t, "px"),
+                    "q": dec(t, "sz"),
+                    "ts": t.get("time").and_then(|x| x.as_i64()).unwrap_or_default(),
+                    "skew": clock::current_skew_ms()
+                }))
+            })
+            .collect(),
+        "l2Book" => {
+            let Some(coin) = data.get("coin").and_then(|c| c.as_str()) else {
+                return Vec::new();
+            };
+            let sides = data.get("levels").and_then(|l| l.as_array());
+            let side = |i: usize| levels(sides.and_then(|s| s.get(i)));
+            let sym = symbol(coin);
+            vec![serde_json::json!({
+                "agent": "hyperliquid",
+                "type": "snapshot",
+                "s": sym,
+                "ac": AssetClass::Perp,
+                "settle": SETTLE,
+                "id": perp_id(&sym),
+                "bids": side(0),
+                "asks": side(1),
+                "ts": data.get("time").and_then(|x| x.as_i64()).unwrap_or_default()
+            })]
+        }
+        "activeAssetCtx" => {
+            let (Some(coin), Some(ctx)) =
+                (data.get("coin").and_then(|c| c.as_str()), data.get("ctx"))
+            else {
+                return Vec::new();
+            };
+            let sym = symbol(coin);
+            // Asset contexts carry no timestamp; stamp them on receipt.
+            let ts = chrono::Utc::now().timestamp_millis();
+            let base = |typ: &str, field: &str, value: String| {
+                serde_json::json!({
+                    "agent": "hyperliquid",
+                    "type": typ,
+                    "s": sym,
+                    "ac": AssetClass::Perp,
+                    "settle": SETTLE,
+                    "id": perp_id(&sym),
+                    field: value,
+                    "ts": ts
+                })
+            };
+            let mut funding = base("funding", "r", dec(ctx, "funding"));
+            funding["funding_interval_h"] = 1.into();
+            vec![
+                funding,
+                base("open_interest", "oi", dec(ctx, "openInterest")),
+                base("mark_price", "p", dec(ctx, "markPx")),
+            ]
+        }
+        _ => Vec::new(),
+    };
+    events.into_iter().map(|e| e.to_string()).collect()
+}
+
+/// `[price, qty]` pairs from `{"px", "sz", "n"}` book levels.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get("px")?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get("sz")?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
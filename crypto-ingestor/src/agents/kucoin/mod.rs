@@ -0,0 +1,547 @@
+//! KuCoin spot market data.
+//!
+//! KuCoin does not expose a fixed websocket URL: every connection starts with
+//! a `bullet-public` REST call returning a token, the endpoint to dial and the
+//! ping interval the server expects. Tokens expire, so the agent reconnects
+//! with a fresh one every [`TOKEN_REFRESH`]. Each symbol is subscribed to the
+//! `match`, `level2` and `ticker` topics.
+//!
+//! Level2 updates are diffs, so a symbol's book starts with a REST snapshot:
+//! its first updates, and those after a sequence gap, are held back until
+//! the snapshot has been published (see [`BookSync`]).
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{symbol_or_raw, AgentFactory, BookSnapshot, BookSync, STREAM_SEQ_GAPS};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::Symbol;
+
+/// Symbols per topic subscription; KuCoin accepts at most 100.
+const SUBSCRIBE_BATCH: usize = 100;
+/// Reconnect with a new token well before the 24 hour expiry.
+const TOKEN_REFRESH: Duration = Duration::from_secs(12 * 60 * 60);
+const TOPICS: [&str; 3] = ["/market/match", "/market/level2", "/market/ticker"];
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+fn http_err(e: reqwest::Error) -> IngestorError {
+    IngestorError::Http {
+        source: e,
+        exchange: "kucoin",
+        symbol: None,
+    }
+}
+
+/// Fetch all tradable USDT-quoted symbols.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Kucoin);
+    limiter.acquire(4, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/api/v2/symbols"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+    let arr = resp
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| IngestorError::Other("kucoin unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|s| s.get("enableTrading").and_then(|e| e.as_bool()) == Some(true))
+        .filter(|s| s.get("quoteCurrency").and_then(|q| q.as_str()) == Some("USDT"))
+        .filter_map(|s| s.get("symbol").and_then(|s| s.as_str()).map(str::to_string))
+        .collect())
+}
+
+/// Websocket URL and ping interval from a `bullet-public` response.
+fn ws_endpoint(bullet: &serde_json::Value, connect_id: u64) -> Option<(String, Duration)> {
+    let data = bullet.get("data")?;
+    let token = data.get("token")?.as_str()?;
+    let server = data.get("instanceServers")?.as_array()?.first()?;
+    let endpoint = server.get("endpoint")?.as_str()?;
+    let ping_ms = server
+        .get("pingInterval")
+        .and_then(|p| p.as_u64())
+        .unwrap_or(18_000);
+    Some((
+        format!("{endpoint}?token={token}&connectId={connect_id}"),
+        Duration::from_millis(ping_ms),
+    ))
+}
+
+async fn fetch_ws_endpoint(
+    client: &reqwest::Client,
+    rest_url: &str,
+    connect_id: u64,
+) -> Result<(String, Duration), IngestorError> {
+    let limiter = rate_limit::limiter(Exchange::Kucoin);
+    limiter.acquire(10, Priority::High).await;
+    let bullet: serde_json::Value = client
+        .post(format!("{rest_url}/api/v1/bullet-public"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+    ws_endpoint(&bullet, connect_id)
+        .ok_or_else(|| IngestorError::Other(format!("kucoin bullet response: {bullet}")))
+}
+
+pub struct KucoinAgent {
+    symbols: Vec<String>,
+    rest_url: String,
+    max_reconnect_delay_secs: u64,
+}
+
+impl KucoinAgent {
+    pub fn new(symbols: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            rest_url: cfg.kucoin_rest_url.clone(),
+            max_reconnect_delay_secs: cfg.kucoin_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for KucoinAgent {
+    fn name(&self) -> &'static str {
+        "kucoin"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let client = http_client::builder().build().map_err(http_err)?;
+        let mut attempt: u32 = 0;
+        let mut request_id: u64 = 0;
+        let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            let connect_id = chrono::Utc::now().timestamp_millis() as u64;
+            let endpoint = fetch_ws_endpoint(&client, &self.rest_url, connect_id).await;
+            match endpoint {
+                Err(e) => tracing::error!(error=%e, "failed to obtain websocket token"),
+                Ok((url, ping_every)) => match http_client::connect_ws(&url).await {
+                    Ok((mut ws, _)) => {
+                        tracing::info!("connected");
+                        let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                        attempt = 0;
+                        // Level2 sequence numbers restart with each connection.
+                        let mut sequences: HashMap<String, u64> = HashMap::new();
+                        let mut books = BookSync::default();
+
+                        if let Err(e) =
+                            send_subscribe(&mut ws, &mut request_id, &self.symbols).await
+                        {
+                            tracing::error!(error=%e, "failed to send subscription");
+                            continue;
+                        }
+
+                        let mut ping = tokio::time::interval_at(
+                            tokio::time::Instant::now() + ping_every,
+                            ping_every,
+                        );
+                        let refresh = tokio::time::sleep(TOKEN_REFRESH);
+                        tokio::pin!(refresh);
+
+                        loop {
+                            tokio::select! {
+                                _ = shutdown.changed() => {
+                                    if *shutdown.borrow() {
+                                        tracing::info!("shutdown signal - closing connection");
+                                        let _ = ws.close(None).await;
+                                        return Ok(());
+                                    }
+                                }
+                                Some((symbol, snapshot)) = books.snapshot() => {
+                                    for line in books.publish(&symbol, snapshot) {
+                                        if tx.send(line).await.is_err() {
+                                            return Ok(());
+                                        }
+                                    }
+                                }
+                                _ = &mut refresh => {
+                                    tracing::info!("refreshing websocket token");
+                                    let _ = ws.close(None).await;
+                                    break;
+                                }
+                                _ = ping.tick() => {
+                                    request_id += 1;
+                                    let msg = serde_json::json!({"id": request_id.to_string(), "type": "ping"});
+                                    if let Err(e) = ws.send(Message::Text(msg.to_string())).await {
+                                        tracing::error!(error=%e, "failed to send ping");
+                                        break;
+                                    }
+                                }
+                                idle = watchdog.tick() => {
+                                    if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                        break;
+                                    }
+                                }
+                                msg = ws.next() => {
+                                    watchdog.feed();
+                                    match msg {
+                                        Some(Ok(Message::Text(txt))) => {
+                                            let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                                Ok(v) => v,
+                                                Err(e) => {
+                                                    dead_letter::record("kucoin", &txt, &e);
+                                                    continue;
+                                                }
+                                            };
+                                            match v.get("type").and_then(|t| t.as_str()) {
+                                                Some("message") => {
+                                                    let gap = sequence_gap(&v, &mut sequences);
+                                                    if gap {
+                                                        tracing::warn!(topic=?v.get("topic"), "level2 sequence gap; re-snapshotting");
+                                                    }
+                                                    let Some(mut line) = parse_event(&v, &mut last_trade_ids) else {
+                                                        dead_letter::record_no_event("kucoin", &txt);
+                                                        continue;
+                                                    };
+                                                    if let Some((symbol, first, last)) = level2_range(&v) {
+                                                        match books.diff(symbol, (first, last), gap, line) {
+                                                            Some(diff) => line = diff,
+                                                            None => {
+                                                                let (client, rest_url) = (client.clone(), self.rest_url.clone());
+                                                                let task_symbol = symbol.to_string();
+                                                                books.resync(symbol, async move {
+                                                                    fetch_snapshot(&client, &rest_url, &task_symbol).await
+                                                                });
+                                                                continue;
+                                                            }
+                                                        }
+                                                    }
+                                                    if tx.send(line).await.is_err() {
+                                                        return Ok(());
+                                                    }
+                                                }
+                                                Some("error") => {
+                                                    tracing::error!(code=?v.get("code"), data=?v.get("data"), "kucoin request rejected");
+                                                }
+                                                _ => {}
+                                            }
+                                        }
+                                        Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                        Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                        Some(Ok(_)) => { }
+                                        Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                        None => { tracing::warn!("stream ended"); break; }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!(error=%e, "connect failed");
+                    }
+                },
+            }
+
+            attempt = attempt.saturating_add(1);
+            ingest_stats::record("kucoin", Counter::Reconnect);
+            let exp: u32 = attempt.saturating_sub(1).min(4);
+            let delay = (1u64 << exp).min(self.max_reconnect_delay_secs);
+            let sleep = Duration::from_secs(delay);
+
+            tracing::info!(?sleep, "reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        tracing::info!("shutdown during backoff");
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct KucoinFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for KucoinFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let symbols = if spec.is_empty() {
+            vec!["BTC-USDT".to_string()]
+        } else if spec.eq_ignore_ascii_case("all") {
+            match fetch_all_symbols(&cfg.kucoin_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch kucoin symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(KucoinAgent::new(symbols, cfg)))
+    }
+}
+
+async fn send_subscribe(
+    ws: &mut WsStream,
+    request_id: &mut u64,
+    symbols: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    for batch in symbols.chunks(SUBSCRIBE_BATCH) {
+        for topic in TOPICS {
+            *request_id += 1;
+            let msg = serde_json::json!({
+                "id": request_id.to_string(),
+                "type": "subscribe",
+                "topic": format!("{topic}:{}", batch.join(",")),
+                "privateChannel": false,
+                "response": true,
+            });
+            ws.send(Message::Text(msg.to_string())).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Symbol and `sequenceStart`/`sequenceEnd` of a level2 update.
+pub fn level2_range(v: &serde_json::Value) -> Option<(&str, u64, u64)> {
+    if v.get("subject").and_then(|s| s.as_str()) != Some("trade.l2update") {
+        return None;
+    }
+    let data = v.get("data")?;
+    Some((
+        data.get("symbol")?.as_str()?,
+        data.get("sequenceStart")?.as_u64()?,
+        data.get("sequenceEnd")?.as_u64()?,
+    ))
+}
+
+/// Track `sequenceStart`/`sequenceEnd` of level2 updates per symbol, returning
+/// `true` when updates were skipped. Gaps are counted in [`STREAM_SEQ_GAPS`].
+pub fn sequence_gap(v: &serde_json::Value, sequences: &mut HashMap<String, u64>) -> bool {
+    let Some((symbol, start, end)) = level2_range(v) else {
+        return false;
+    };
+    match sequences.insert(symbol.to_string(), end) {
+        Some(prev) if start > prev + 1 => {
+            STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+            ingest_stats::record("kucoin", Counter::Gap);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Fetch the top 100 levels of the REST book for `symbol` as a `snapshot`
+/// event.
+async fn fetch_snapshot(
+    client: &reqwest::Client,
+    rest_url: &str,
+    symbol: &str,
+) -> Option<BookSnapshot> {
+    let url = format!("{rest_url}/api/v1/market/orderbook/level2_100?symbol={symbol}");
+    let limiter = rate_limit::limiter(Exchange::Kucoin);
+    // The 100-level book weighs 2 on the public pool.
+    limiter.acquire(2, Priority::High).await;
+    let resp = match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot failed");
+            return None;
+        }
+    };
+    match resp.json::<serde_json::Value>().await {
+        Ok(v) => snapshot_event(symbol, &v),
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot parse failed");
+            None
+        }
+    }
+}
+
+/// Sequence and canonical `snapshot` line of a REST order book response.
+pub fn snapshot_event(symbol: &str, resp: &serde_json::Value) -> Option<BookSnapshot> {
+    let data = resp.get("data")?;
+    let sequence = data.get("sequence")?.as_str()?.parse::<u64>().ok()?;
+    let line = serde_json::json!({
+        "agent": "kucoin",
+        "type": "snapshot",
+        "s": symbol_or_raw("kucoin", symbol),
+        "bids": levels(data.get("bids")),
+        "asks": levels(data.get("asks")),
+        "ts": data
+            .get("time")
+            .and_then(|t| t.as_i64())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis())
+    });
+    Some((sequence, line.to_string()))
+}
+
+/// Convert a KuCoin topic message into a canonical event line.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Option<String> {
+    let subject = v.get("subject").and_then(|s| s.as_str()).unwrap_or("");
+    let data = v.get("data")?;
+    let raw = match data.get("symbol").and_then(|s| s.as_str()) {
+        Some(s) => s,
+        // Ticker messages name the symbol only in the topic.
+        None => v.get("topic")?.as_str()?.rsplit(':').next()?,
+    };
+    let sym = symbol_or_raw("kucoin", raw);
+    let dec = |k: &str| {
+        data.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    // Match times are nanosecond strings, everything else milliseconds.
+    let time_ms = || match data.get("time") {
+        Some(serde_json::Value::String(ns)) => ns.parse::<i64>().ok().map(|n| n / 1_000_000),
+        Some(t) => t.as_i64(),
+        None => None,
+    };
+
+    let line = match subject {
+        "trade.l3match" => {
+            let trade_id = data
+                .get("tradeId")
+                .and_then(|id| id.as_str())
+                .and_then(|id| id.parse::<i64>().ok())
+                .filter(|id| *id > 0);
+            if let Some(id) = trade_id {
+                last_trade_ids.insert(sym.clone(), id);
+            }
+            serde_json::json!({
+                "agent": "kucoin",
+                "type": "trade",
+                "s": sym,
+                "t": trade_id,
+                "p": dec("price"),
+                "q": dec("size"),
+                "ts": time_ms().unwrap_or_default(),
+                "skew": clock::current_skew_ms()
+            })
+        }
+        "trade.l2update" => {
+            let changes = data.get("changes");
+            serde_json::json!({
+                "agent": "kucoin",
+                "type": "l2_diff",
+                "s": sym,
+                "bids": levels(changes.and_then(|c| c.get("bids"))),
+                "asks": levels(changes.and_then(|c| c.get("asks"))),
+                "ts": time_ms().unwrap_or_default()
+            })
+        }
+        "trade.ticker" => serde_json::json!({
+            "agent": "kucoin",
+            "type": "book_ticker",
+            "s": sym,
+            "bp": dec("bestBid"),
+            "bq": dec("bestBidSize"),
+            "ap": dec("bestAsk"),
+            "aq": dec("bestAskSize"),
+            "ts": time_ms().unwrap_or_default()
+        }),
+        _ => return None,
+    };
+    Some(line.to_string())
+}
+
+/// `[price, qty]` pairs from level2 changes, which also carry a sequence.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bullet_response_yields_tokenised_endpoint() {
+        let bullet = serde_json::json!({
+            "code": "200000",
+            "data": {
+                "token": "abc",
+                "instanceServers": [{
+                    "endpoint": "wss://ws-api-spot.kucoin.com/",
+                    "encrypt": true,
+                    "protocol": "websocket",
+                    "pingInterval": 18000,
+                    "pingTimeout": 10000
+                }]
+            }
+        });
+        let (url, ping) = ws_endpoint(&bullet, 7).unwrap();
+        assert_eq!(url, "wss://ws-api-spot.kucoin.com/?token=abc&connectId=7");
+        assert_eq!(ping, Duration::from_secs(18));
+        assert!(ws_endpoint(&serde_json::json!({"code": "400100"}), 7).is_none());
+    }
+
+    #[test]
+    fn rest_book_snapshot_carries_its_sequence() {
+        let resp = serde_json::json!({
+            "code": "200000",
+            "data": {
+                "time": 1700000000000i64,
+                "sequence": "3262786978",
+                "bids": [["6500.12", "0.45054140"]],
+                "asks": [["6500.16", "0.57753524"]]
+            }
+        });
+        let (sequence, line) = snapshot_event("BTC-USDT", &resp).unwrap();
+        assert_eq!(sequence, 3262786978);
+        let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["type"], "snapshot");
+        assert_eq!(v["s"], "BTC-USDT");
+        assert_eq!(v["bids"][0][0], "6500.12");
+        assert_eq!(v["ts"], 1700000000000i64);
+    }
+}
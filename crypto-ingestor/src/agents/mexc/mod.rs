@@ -0,0 +1,464 @@
+//! MEXC spot over the v3 JSON WebSocket.
+//!
+//! Each symbol is subscribed to the `deals` and `increase.depth` streams.
+//! MEXC accepts at most 30 subscriptions per connection, so symbols are
+//! spread over several connections. Depth updates carry a version `r` that
+//! increases by one per update; skipped versions are reported as gaps. Each
+//! book starts with a REST snapshot: a symbol's first depth updates, and
+//! those after a gap, are held back until the snapshot has been published
+//! (see [`BookSync`]).
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{symbol_or_raw, AgentFactory, BookSnapshot, BookSync, STREAM_SEQ_GAPS};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::Symbol;
+
+/// MEXC closes connections that send nothing for 60 seconds.
+const PING_INTERVAL_SECS: u64 = 20;
+const MAX_SUBSCRIPTIONS_PER_CONN: usize = 30;
+const DEALS: &str = "spot@public.deals.v3.api";
+const DEPTH: &str = "spot@public.increase.depth.v3.api";
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Fetch every online USDT spot pair.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "mexc",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Mexc);
+    limiter.acquire(10, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/api/v3/exchangeInfo"))
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .get("symbols")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| IngestorError::Other("mexc unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        // status "1" is online; newer responses use "ENABLED"
+        .filter(|s| {
+            matches!(
+                s.get("status").and_then(|st| st.as_str()),
+                Some("1" | "ENABLED")
+            )
+        })
+        .filter(|s| s.get("isSpotTradingAllowed").and_then(|a| a.as_bool()) != Some(false))
+        .filter(|s| s.get("quoteAsset").and_then(|q| q.as_str()) == Some("USDT"))
+        .filter_map(|s| s.get("symbol").and_then(|s| s.as_str()))
+        .map(str::to_string)
+        .collect())
+}
+
+pub struct MexcAgent {
+    symbols: Vec<String>,
+    ws_url: String,
+    rest_url: String,
+    max_reconnect_delay_secs: u64,
+}
+
+impl MexcAgent {
+    pub fn new(symbols: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            ws_url: cfg.mexc_ws_url.clone(),
+            rest_url: cfg.mexc_rest_url.clone(),
+            max_reconnect_delay_secs: cfg.mexc_max_reconnect_delay_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for MexcAgent {
+    fn name(&self) -> &'static str {
+        "mexc"
+    }
+
+    async fn run(
+        &mut self,
+        shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let per_conn = MAX_SUBSCRIPTIONS_PER_CONN / 2;
+        let handles: Vec<_> = self
+            .symbols
+            .chunks(per_conn)
+            .map(|chunk| {
+                tokio::spawn(connection_task(
+                    chunk.to_vec(),
+                    self.ws_url.clone(),
+                    self.rest_url.clone(),
+                    shutdown.clone(),
+                    tx.clone(),
+                    self.max_reconnect_delay_secs,
+                ))
+            })
+            .collect();
+        for h in handles {
+            let _ = h.await;
+        }
+        Ok(())
+    }
+}
+
+pub struct MexcFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for MexcFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let symbols = if spec.is_empty() {
+            vec!["BTCUSDT".to_string()]
+        } else if spec.eq_ignore_ascii_case("all") {
+            match fetch_all_symbols(&cfg.mexc_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch mexc symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(MexcAgent::new(symbols, cfg)))
+    }
+}
+
+async fn connection_task(
+    symbols: Vec<String>,
+    ws_url: String,
+    rest_url: String,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    max_reconnect_delay_secs: u64,
+) {
+    let mut attempt: u32 = 0;
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+    let client = http_client::builder().build().ok();
+
+    loop {
+        if *shutdown.borrow() {
+            break;
+        }
+
+        tracing::info!(url = %ws_url, "connecting");
+        match http_client::connect_ws(&ws_url).await {
+            Ok((mut ws, _)) => {
+                tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                attempt = 0;
+                // Depth versions are only comparable within one subscription.
+                let mut versions: HashMap<String, u64> = HashMap::new();
+                let mut books = BookSync::default();
+
+                if let Err(e) = send_subscribe(&mut ws, &symbols).await {
+                    tracing::error!(error=%e, "failed to send subscription");
+                    continue;
+                }
+
+                let ping_period = Duration::from_secs(PING_INTERVAL_SECS);
+                let mut ping = tokio::time::interval_at(
+                    tokio::time::Instant::now() + ping_period,
+                    ping_period,
+                );
+
+                loop {
+                    tokio::select! {
+                        _ = shutdown.changed() => {
+                            if *shutdown.borrow() {
+                                tracing::info!("shutdown signal - closing connection");
+                                let _ = ws.close(None).await;
+                                return;
+                            }
+                        }
+                        Some((symbol, snapshot)) = books.snapshot() => {
+                            for line in books.publish(&symbol, snapshot) {
+                                if tx.send(line).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        _ = ping.tick() => {
+                            let msg = serde_json::json!({"method": "PING"});
+                            if let Err(e) = ws.send(Message::Text(msg.to_string())).await {
+                                tracing::error!(error=%e, "failed to send ping");
+                                break;
+                            }
+                        }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
+                        msg = ws.next() => {
+                            watchdog.feed();
+                            match msg {
+                                Some(Ok(Message::Text(txt))) => {
+                                    let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                        Ok(v) => v,
+                                        Err(e) => {
+                                            dead_letter::record("mexc", &txt, &e);
+                                            continue;
+                                        }
+                                    };
+                                    if let Some(msg) = v.get("msg").and_then(|m| m.as_str()) {
+                                        if msg.starts_with("Not Subscribed") {
+                                            tracing::error!(%msg, "mexc subscription rejected");
+                                        }
+                                        continue;
+                                    }
+                                    let gap = depth_gap(&v, &mut versions);
+                                    if gap {
+                                        tracing::warn!(channel=?v.get("c"), "depth version gap");
+                                    }
+                                    let version = client.as_ref().and(depth_version(&v));
+                                    let lines = parse_event(&v, &mut last_trade_ids);
+                                    if lines.is_empty() && v.get("c").is_some() {
+                                        dead_letter::record_no_event("mexc", &txt);
+                                    }
+                                    for line in lines {
+                                        let line = match (version, &client) {
+                                            (Some((symbol, r)), Some(client)) => match books.diff(symbol, (r, r), gap, line) {
+                                                Some(diff) => diff,
+                                                None => {
+                                                    let (client, rest_url, task_symbol) = (client.clone(), rest_url.clone(), symbol.to_string());
+                                                    books.resync(symbol, async move {
+                                                        fetch_snapshot(&client, &rest_url, &task_symbol).await
+                                                    });
+                                                    continue;
+                                                }
+                                            },
+                                            _ => line,
+                                        };
+                                        if tx.send(line).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                Some(Ok(_)) => { }
+                                Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                None => { tracing::warn!("stream ended"); break; }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!(error=%e, "connect failed");
+            }
+        }
+
+        attempt = attempt.saturating_add(1);
+        ingest_stats::record("mexc", Counter::Reconnect);
+        let exp: u32 = attempt.saturating_sub(1).min(4);
+        let delay = (1u64 << exp).min(max_reconnect_delay_secs);
+        let sleep = Duration::from_secs(delay);
+
+        tracing::info!(?sleep, "reconnecting");
+        tokio::select! {
+            _ = tokio::time::sleep(sleep) => {},
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    tracing::info!("shutdown during backoff");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn send_subscribe(
+    ws: &mut WsStream,
+    symbols: &[String],
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let params: Vec<String> = symbols
+        .iter()
+        .flat_map(|s| [format!("{DEALS}@{s}"), format!("{DEPTH}@{s}")])
+        .collect();
+    let msg = serde_json::json!({"method": "SUBSCRIPTION", "params": params});
+    ws.send(Message::Text(msg.to_string())).await
+}
+
+/// Symbol and version `r` of a depth update.
+pub fn depth_version(v: &serde_json::Value) -> Option<(&str, u64)> {
+    let channel = v.get("c").and_then(|c| c.as_str()).unwrap_or("");
+    if !channel.starts_with(DEPTH) {
+        return None;
+    }
+    let version = v.get("d")?.get("r")?.as_str()?.parse::<u64>().ok()?;
+    Some((v.get("s")?.as_str()?, version))
+}
+
+/// Track the version `r` of depth updates per symbol, returning `true` when
+/// updates were skipped. Gaps are counted in [`STREAM_SEQ_GAPS`].
+pub fn depth_gap(v: &serde_json::Value, versions: &mut HashMap<String, u64>) -> bool {
+    let Some((symbol, version)) = depth_version(v) else {
+        return false;
+    };
+    match versions.insert(symbol.to_string(), version) {
+        Some(prev) if version > prev + 1 => {
+            STREAM_SEQ_GAPS.fetch_add(1, Ordering::Relaxed);
+            ingest_stats::record("mexc", Counter::Gap);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Fetch the REST depth of `symbol` as a `snapshot` event.
+async fn fetch_snapshot(
+    client: &reqwest::Client,
+    rest_url: &str,
+    symbol: &str,
+) -> Option<BookSnapshot> {
+    let url = format!("{rest_url}/api/v3/depth?symbol={symbol}&limit=1000");
+    let limiter = rate_limit::limiter(Exchange::Mexc);
+    // Depth requests weigh 1 at any limit.
+    limiter.acquire(1, Priority::High).await;
+    let resp = match client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot failed");
+            return None;
+        }
+    };
+    match resp.json::<serde_json::Value>().await {
+        Ok(v) => snapshot_event(symbol, &v),
+        Err(e) => {
+            tracing::error!(error=%e, %symbol, "snapshot parse failed");
+            None
+        }
+    }
+}
+
+/// `lastUpdateId` and canonical `snapshot` line of a REST depth response,
+/// whose levels are `[price, qty]` arrays rather than the stream's objects.
+pub fn snapshot_event(symbol: &str, resp: &serde_json::Value) -> Option<BookSnapshot> {
+    let last_update_id = resp.get("lastUpdateId")?.as_u64()?;
+    let side = |k: &str| -> Vec<[String; 2]> {
+        resp.get(k)
+            .and_then(|b| b.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|lvl| {
+                let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+                let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+                Some([p, q])
+            })
+            .collect()
+    };
+    let line = serde_json::json!({
+        "agent": "mexc",
+        "type": "snapshot",
+        "s": symbol_or_raw("mexc", symbol),
+        "bids": side("bids"),
+        "asks": side("asks"),
+        "ts": resp
+            .get("timestamp")
+            .and_then(|t| t.as_i64())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis())
+    });
+    Some((last_update_id, line.to_string()))
+}
+
+/// Convert a MEXC push into canonical event lines. Deal pushes may carry
+/// several trades; MEXC does not publish trade ids.
+pub fn parse_event(
+    v: &serde_json::Value,
+    _last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Vec<String> {
+    let channel = v.get("c").and_then(|c| c.as_str()).unwrap_or("");
+    let (Some(raw), Some(data)) = (v.get("s").and_then(|s| s.as_str()), v.get("d")) else {
+        return Vec::new();
+    };
+    let sym = symbol_or_raw("mexc", raw);
+    let dec = |src: &serde_json::Value, k: &str| {
+        src.get(k)
+            .and_then(|p| p.as_str())
+            .and_then(parse_decimal_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+
+    let events: Vec<serde_json::Value> = if channel.starts_with(DEALS) {
+        data.get("deals")
+            .and_then(|d| d.as_array())
+            .into_iter()
+            .flatten()
+            .map(|t| {
+                serde_json::json!({
+                    "agent": "mexc",
+                    "type": "trade",
+                    "s": sym,
+                    "t": null,
+                    "p": dec(t, "p"),
+                    "q": dec(t, "v"),
+                    "ts": t.get("t").and_then(|x| x.as_i64()).unwrap_or_default(),
+                    "skew": clock::current_skew_ms()
+                })
+            })
+            .collect()
+    } else if channel.starts_with(DEPTH) {
+        vec![serde_json::json!({
+            "agent": "mexc",
+            "type": "l2_diff",
+            "s": sym,
+            "bids": levels(data.get("bids")),
+            "asks": levels(data.get("asks")),
+            "ts": v.get("t").and_then(|x| x.as_i64()).unwrap_or_default()
+        })]
+    } else {
+        Vec::new()
+    };
+    events.into_iter().map(|e| e.to_string()).collect()
+}
+
+/// `[price, qty]` pairs from `{"p", "v"}` levels; zero quantities remove
+/// the level.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get("p")?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get("v")?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
@@ -1,33 +1,73 @@
 pub mod binance;
+pub mod bybit;
 pub mod coinbase;
+pub mod deribit;
+pub mod gate;
+pub mod hyperliquid;
+pub mod kucoin;
+pub mod mexc;
+pub mod okx;
+pub mod settlement;
+pub mod universe;
 
+use crate::ingest_stats::{self, Counter};
 use crate::{agent::Agent, config::Settings, error::IngestorError};
-use canonicalizer::CanonicalService;
+use canonicalizer::{CanonicalService, Delisting, DerivativeSymbol, Listing, Symbol};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
 #[async_trait::async_trait]
 pub trait AgentFactory: Send + Sync {
     async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>>;
 }
 
-pub static AGENT_FACTORIES: Lazy<Mutex<HashMap<&'static str, Box<dyn AgentFactory>>>> =
+/// Number of gaps detected in exchange stream sequence numbers.
+pub static STREAM_SEQ_GAPS: AtomicU64 = AtomicU64::new(0);
+
+pub static AGENT_FACTORIES: Lazy<Mutex<HashMap<&'static str, Arc<dyn AgentFactory>>>> =
     Lazy::new(|| {
-        let mut m: HashMap<&'static str, Box<dyn AgentFactory>> = HashMap::new();
-        m.insert("binance", Box::new(binance::BinanceFactory));
+        let mut m: HashMap<&'static str, Arc<dyn AgentFactory>> = HashMap::new();
+        m.insert("binance", Arc::new(binance::BinanceFactory));
         m.insert(
             "binance_options",
-            Box::new(binance::options::BinanceOptionsFactory),
+            Arc::new(binance::options::BinanceOptionsFactory),
+        );
+        m.insert(
+            "binance_coinm",
+            Arc::new(binance::coinm::BinanceCoinmFactory),
+        );
+        m.insert(
+            "binance_futures",
+            Arc::new(binance::futures::BinanceFuturesFactory),
         );
         m.insert(
             "binance_ohlcv",
-            Box::new(binance::ohlcv::BinanceOhlcvFactory),
+            Arc::new(binance::ohlcv::BinanceOhlcvFactory),
         );
-        m.insert("coinbase", Box::new(coinbase::CoinbaseFactory));
+        m.insert("bybit", Arc::new(bybit::BybitFactory));
+        m.insert("coinbase", Arc::new(coinbase::CoinbaseFactory));
         m.insert(
             "coinbase_ohlcv",
-            Box::new(coinbase::ohlcv::CoinbaseOhlcvFactory),
+            Arc::new(coinbase::ohlcv::CoinbaseOhlcvFactory),
+        );
+        m.insert("deribit", Arc::new(deribit::futures::DeribitFactory));
+        m.insert("gate", Arc::new(gate::GateFactory));
+        m.insert("hyperliquid", Arc::new(hyperliquid::HyperliquidFactory));
+        m.insert("kucoin", Arc::new(kucoin::KucoinFactory));
+        m.insert("mexc", Arc::new(mexc::MexcFactory));
+        m.insert("okx", Arc::new(okx::OkxFactory));
+        m.insert("okx_options", Arc::new(okx::options::OkxOptionsFactory));
+        m.insert("settlement", Arc::new(settlement::SettlementFactory));
+        m.insert(
+            "deribit_options_backfill",
+            Arc::new(deribit::DeribitOptionsBackfillFactory),
         );
         Mutex::new(m)
     });
@@ -66,6 +106,305 @@ async fn shared_symbols() -> Result<(Vec<String>, Vec<String>), IngestorError> {
     Ok((b_syms, c_syms))
 }
 
+/// Canonical symbol for `raw` on `exchange`. Symbols that cannot be mapped
+/// pass through unchanged and are counted in `ingest_stats` by reason.
+pub fn symbol_or_raw(exchange: &str, raw: &str) -> Symbol {
+    if let Some(sym) = CanonicalService::canonical_symbol(exchange, raw) {
+        return sym;
+    }
+    if let Err(e) = CanonicalService::try_canonical_pair(exchange, raw) {
+        tracing::debug!(%exchange, symbol=%raw, error=%e, "unmapped symbol");
+        ingest_stats::record(exchange, Counter::Unmapped(e.kind()));
+    }
+    Symbol::intern(raw)
+}
+
+/// `id` of a perpetual on the canonical pair `sym`, e.g. `BTC-USDT-PERP`.
+/// Symbols that are not a plain pair are returned unchanged.
+pub fn perp_id(sym: &str) -> String {
+    DerivativeSymbol::perp(sym).map_or_else(|| sym.to_string(), |d| d.to_string())
+}
+
+/// Interval between REST order book snapshots of a symbol until its
+/// volatility is known.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+/// Snapshot interval while a symbol is within its new-listing window.
+const NEW_LISTING_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+/// Snapshot interval of volatile or wide markets.
+const MIN_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+/// Snapshot interval of quiet markets.
+const MAX_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+/// Realized volatility, in basis points per minute, polled at the fastest rate.
+const HOT_VOLATILITY_BPS: f64 = 20.0;
+/// Bid-ask spread, in basis points, polled at the fastest rate.
+const HOT_SPREAD_BPS: f64 = 10.0;
+/// Weight of the newest return in the rolling variance.
+const VOLATILITY_ALPHA: f64 = 0.2;
+
+/// Paces the REST book snapshots of one symbol by how fast its market moves.
+///
+/// Each snapshot updates the spread and an exponentially weighted realized
+/// volatility of the mid price. Markets at [`HOT_VOLATILITY_BPS`] or
+/// [`HOT_SPREAD_BPS`] are polled every [`MIN_SNAPSHOT_INTERVAL`], quiet ones
+/// every [`MAX_SNAPSHOT_INTERVAL`], scaling geometrically in between.
+#[derive(Debug, Default)]
+pub struct SnapshotPacer {
+    last_mid: Option<(f64, Instant)>,
+    /// Squared log returns of the mid per minute.
+    variance: Option<f64>,
+    spread_bps: Option<f64>,
+}
+
+impl SnapshotPacer {
+    /// Record the top of book of a `snapshot` event line fetched at `at`.
+    pub fn observe(&mut self, line: &str, at: Instant) {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        let best = |side: &str| v.get(side)?.get(0)?.get(0)?.as_str()?.parse::<f64>().ok();
+        let (Some(bid), Some(ask)) = (best("bids"), best("asks")) else {
+            return;
+        };
+        if bid <= 0.0 || ask < bid {
+            return;
+        }
+        let mid = (bid + ask) / 2.0;
+        self.spread_bps = Some((ask - bid) / mid * 1e4);
+        if let Some((prev, prev_at)) = self.last_mid {
+            let minutes = at.saturating_duration_since(prev_at).as_secs_f64() / 60.0;
+            if minutes > 0.0 {
+                let r2 = (mid / prev).ln().powi(2) / minutes;
+                self.variance = Some(
+                    self.variance
+                        .map_or(r2, |v| v + VOLATILITY_ALPHA * (r2 - v)),
+                );
+            }
+        }
+        self.last_mid = Some((mid, at));
+    }
+
+    /// Rolling realized volatility of the mid in basis points per minute.
+    pub fn volatility_bps(&self) -> Option<f64> {
+        self.variance.map(|v| v.sqrt() * 1e4)
+    }
+
+    /// Delay before the next snapshot of a symbol whose accelerated
+    /// new-listing window ends at `boost_until`. Until two snapshots gave a
+    /// volatility, quiet markets are not slowed below [`SNAPSHOT_INTERVAL`].
+    pub fn delay(&self, boost_until: Instant) -> Duration {
+        if Instant::now() < boost_until {
+            return NEW_LISTING_SNAPSHOT_INTERVAL;
+        }
+        let Some(spread) = self.spread_bps else {
+            return SNAPSHOT_INTERVAL;
+        };
+        let vol = self.volatility_bps().unwrap_or(0.0);
+        let heat = (spread / HOT_SPREAD_BPS)
+            .max(vol / HOT_VOLATILITY_BPS)
+            .clamp(0.0, 1.0);
+        let ratio = MIN_SNAPSHOT_INTERVAL.as_secs_f64() / MAX_SNAPSHOT_INTERVAL.as_secs_f64();
+        let delay = MAX_SNAPSHOT_INTERVAL
+            .mul_f64(ratio.powf(heat))
+            .max(MIN_SNAPSHOT_INTERVAL);
+        if self.variance.is_none() {
+            delay.min(SNAPSHOT_INTERVAL)
+        } else {
+            delay
+        }
+    }
+}
+
+/// REST book resyncs started by stream sequence gaps, at most one in flight
+/// per symbol: gaps reported while a symbol's snapshot is still being fetched
+/// are already covered by it. A connection task owns its `Resyncs`, so
+/// dropping it when the task returns on shutdown aborts the pending fetches.
+#[derive(Debug, Default)]
+pub struct Resyncs {
+    inflight: HashMap<String, JoinHandle<()>>,
+}
+
+impl Resyncs {
+    /// Run `resync` for `symbol` in the background unless a resync of that
+    /// symbol is still running. Returns whether it was started.
+    pub fn start<F>(&mut self, symbol: &str, resync: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.inflight.get(symbol).is_some_and(|h| !h.is_finished()) {
+            return false;
+        }
+        self.inflight
+            .insert(symbol.to_string(), tokio::spawn(resync));
+        true
+    }
+}
+
+impl Drop for Resyncs {
+    fn drop(&mut self) {
+        for handle in self.inflight.values() {
+            handle.abort();
+        }
+    }
+}
+
+/// Sequence number and line of a REST order book snapshot.
+pub type BookSnapshot = (u64, String);
+
+/// Diffs held back per symbol until a snapshot covers them.
+const MAX_HELD_DIFFS: usize = 1000;
+
+enum Book {
+    /// Waiting for a snapshot, holding the diffs received since.
+    Pending(VecDeque<(u64, u64, String)>),
+    /// Publishing diffs; holds the snapshot sequence until the first diff
+    /// after it was checked against it.
+    Live(Option<u64>),
+}
+
+/// Level2 books of one connection that only publish diffs on top of a REST
+/// snapshot. The first diffs of a symbol, and those after a sequence gap,
+/// are held back while its snapshot is fetched. The snapshot is then
+/// published, followed by the held diffs it does not already cover. A
+/// snapshot older than the held diffs is discarded and fetched again.
+/// Resyncs run through [`Resyncs`], so they end with the connection.
+pub struct BookSync {
+    books: HashMap<String, Book>,
+    resyncs: Resyncs,
+    snapshots_tx: mpsc::UnboundedSender<(String, BookSnapshot)>,
+    snapshots_rx: mpsc::UnboundedReceiver<(String, BookSnapshot)>,
+}
+
+impl Default for BookSync {
+    fn default() -> Self {
+        let (snapshots_tx, snapshots_rx) = mpsc::unbounded_channel();
+        Self {
+            books: HashMap::new(),
+            resyncs: Resyncs::default(),
+            snapshots_tx,
+            snapshots_rx,
+        }
+    }
+}
+
+impl BookSync {
+    /// Diff `line` of `symbol` spanning sequences `first..=last`, following a
+    /// sequence gap when `gap` is set. Returns the line if it can be
+    /// published now; otherwise it is held and the symbol needs a
+    /// [`resync`](Self::resync).
+    pub fn diff(
+        &mut self,
+        symbol: &str,
+        (first, last): (u64, u64),
+        gap: bool,
+        line: String,
+    ) -> Option<String> {
+        let book = self
+            .books
+            .entry(symbol.to_string())
+            .or_insert_with(|| Book::Pending(VecDeque::new()));
+        if let Book::Live(snapshot) = book {
+            match *snapshot {
+                // Already contained in the snapshot.
+                Some(seq) if last <= seq => return None,
+                Some(seq) if first > seq + 1 => {}
+                _ if gap => {}
+                _ => {
+                    *snapshot = None;
+                    return Some(line);
+                }
+            }
+            *book = Book::Pending(VecDeque::new());
+        }
+        if let Book::Pending(held) = book {
+            if gap {
+                held.clear();
+            }
+            if held.len() == MAX_HELD_DIFFS {
+                held.pop_front();
+            }
+            held.push_back((first, last, line));
+        }
+        None
+    }
+
+    /// Fetch a snapshot of `symbol` in the background if it is waiting for
+    /// one and no fetch is in flight.
+    pub fn resync<F>(&mut self, symbol: &str, fetch: F)
+    where
+        F: Future<Output = Option<BookSnapshot>> + Send + 'static,
+    {
+        if !matches!(self.books.get(symbol), Some(Book::Pending(_))) {
+            return;
+        }
+        let (tx, key) = (self.snapshots_tx.clone(), symbol.to_string());
+        self.resyncs.start(symbol, async move {
+            if let Some(snapshot) = fetch.await {
+                let _ = tx.send((key, snapshot));
+            }
+        });
+    }
+
+    /// Next fetched snapshot, to be passed to [`publish`](Self::publish).
+    pub async fn snapshot(&mut self) -> Option<(String, BookSnapshot)> {
+        self.snapshots_rx.recv().await
+    }
+
+    /// Lines to publish for a fetched snapshot of `symbol`: the snapshot and
+    /// the held diffs after it, or nothing if it is stale.
+    pub fn publish(&mut self, symbol: &str, (seq, line): BookSnapshot) -> Vec<String> {
+        let Some(Book::Pending(held)) = self.books.get_mut(symbol) else {
+            return Vec::new();
+        };
+        while held.front().is_some_and(|(_, last, _)| *last <= seq) {
+            held.pop_front();
+        }
+        if held.front().is_some_and(|(first, _, _)| *first > seq + 1) {
+            tracing::debug!(%symbol, "snapshot predates held diffs; refetching");
+            return Vec::new();
+        }
+        let lines = std::iter::once(line)
+            .chain(held.drain(..).map(|(_, _, diff)| diff))
+            .collect::<Vec<_>>();
+        let snapshot = (lines.len() == 1).then_some(seq);
+        self.books.insert(symbol.to_string(), Book::Live(snapshot));
+        lines
+    }
+}
+
+/// Canonical `listing` and `delisting` events for the symbols a refresh of
+/// `agent`'s symbol list added and removed.
+pub fn listing_events(agent: &str, added: &[String], removed: &[String]) -> Vec<String> {
+    let ts = chrono::Utc::now().timestamp_millis();
+    let canonical = |raw: &String| {
+        CanonicalService::canonical_pair(agent, raw).unwrap_or_else(|| raw.to_uppercase())
+    };
+    let listings = added.iter().map(|raw| {
+        let symbol = canonical(raw);
+        let (base, quote) = symbol.split_once('-').unwrap_or((&symbol, ""));
+        let listing = Listing {
+            agent: agent.to_string(),
+            r#type: "listing".into(),
+            base: base.to_string(),
+            quote: quote.to_string(),
+            symbol: symbol.clone(),
+            lot_size: None,
+            tick_size: None,
+            timestamp: ts,
+        };
+        serde_json::to_string(&listing).unwrap()
+    });
+    let delistings = removed.iter().map(|raw| {
+        let delisting = Delisting {
+            agent: agent.to_string(),
+            r#type: "delisting".into(),
+            symbol: canonical(raw),
+            timestamp: ts,
+        };
+        serde_json::to_string(&delisting).unwrap()
+    });
+    listings.chain(delistings).collect()
+}
+
 /// Factory: "<agent>:<comma-separated-args>"
 /// e.g., "binance:btcusdt,ethusdt" or "binance:all"
 pub async fn make_agent(spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
@@ -74,7 +413,8 @@ pub async fn make_agent(spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
         None => (spec.trim().to_lowercase(), String::new()),
     };
 
-    if let Some(factory) = AGENT_FACTORIES.lock().unwrap().get(name.as_str()) {
+    let factory = AGENT_FACTORIES.lock().unwrap().get(name.as_str()).cloned();
+    if let Some(factory) = factory {
         factory.create(&args, cfg).await
     } else {
         None
@@ -84,3 +424,98 @@ pub async fn make_agent(spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
 pub fn available_agents() -> Vec<&'static str> {
     AGENT_FACTORIES.lock().unwrap().keys().copied().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_listings_are_snapshotted_faster_until_their_window_ends() {
+        let now = Instant::now();
+        let pacer = SnapshotPacer::default();
+        assert_eq!(
+            pacer.delay(now + Duration::from_secs(600)),
+            NEW_LISTING_SNAPSHOT_INTERVAL
+        );
+        assert_eq!(pacer.delay(now), SNAPSHOT_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn one_resync_per_symbol_runs_until_its_owner_is_dropped() {
+        let mut resyncs = Resyncs::default();
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel::<()>();
+        let (fetched_tx, mut fetched_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let pending = fetched_tx.clone();
+        assert!(resyncs.start("BTC-USD", async move {
+            let _ = done_rx.await;
+            let _ = pending.send("BTC-USD");
+        }));
+        // Further gaps while the first snapshot is in flight start nothing.
+        let dup = fetched_tx.clone();
+        assert!(!resyncs.start("BTC-USD", async move {
+            let _ = dup.send("duplicate");
+        }));
+        let other = fetched_tx.clone();
+        assert!(resyncs.start("ETH-USD", async move {
+            let _ = other.send("ETH-USD");
+        }));
+        assert_eq!(fetched_rx.recv().await, Some("ETH-USD"));
+
+        // A finished resync no longer blocks the next one.
+        let again = fetched_tx.clone();
+        assert!(resyncs.start("ETH-USD", async move {
+            let _ = again.send("ETH-USD again");
+        }));
+        assert_eq!(fetched_rx.recv().await, Some("ETH-USD again"));
+
+        // Dropping the owner, as a connection task does on shutdown, aborts
+        // the resync still waiting on its fetch.
+        drop(resyncs);
+        drop(fetched_tx);
+        assert_eq!(fetched_rx.recv().await, None);
+        assert!(done_tx.send(()).is_err());
+    }
+
+    #[test]
+    fn book_diffs_are_published_on_top_of_a_covering_snapshot() {
+        let mut books = BookSync::default();
+        let diff = |n: u64| format!("diff {n}");
+
+        // Diffs before the first snapshot are held.
+        assert_eq!(books.diff("BTC-USDT", (10, 11), false, diff(11)), None);
+        assert_eq!(books.diff("BTC-USDT", (12, 13), false, diff(13)), None);
+        // A snapshot older than the held diffs is refetched.
+        assert!(books.publish("BTC-USDT", (8, "old".into())).is_empty());
+        // A covering snapshot goes first and drops the diffs it contains.
+        assert_eq!(
+            books.publish("BTC-USDT", (11, "snapshot 11".into())),
+            ["snapshot 11", "diff 13"]
+        );
+        assert_eq!(
+            books.diff("BTC-USDT", (14, 14), false, diff(14)),
+            Some(diff(14))
+        );
+        // Late snapshots of a live book are ignored.
+        assert!(books.publish("BTC-USDT", (14, "late".into())).is_empty());
+
+        // A gap holds the book again, discarding diffs before it.
+        assert_eq!(books.diff("BTC-USDT", (20, 20), true, diff(20)), None);
+        assert_eq!(
+            books.publish("BTC-USDT", (21, "snapshot 21".into())),
+            ["snapshot 21"]
+        );
+        // The first diff after a snapshot that covered every held diff is
+        // checked against it.
+        assert_eq!(books.diff("BTC-USDT", (21, 21), false, diff(21)), None);
+        assert_eq!(
+            books.diff("BTC-USDT", (22, 22), false, diff(22)),
+            Some(diff(22))
+        );
+        // Diffs skipping past the snapshot hold the book again.
+        assert!(books.publish("ETH-USDT", (5, "eth".into())).is_empty());
+        assert_eq!(books.diff("ETH-USDT", (1, 1), false, diff(1)), None);
+        assert_eq!(books.publish("ETH-USDT", (5, "eth 5".into())), ["eth 5"]);
+        assert_eq!(books.diff("ETH-USDT", (7, 7), false, diff(7)), None);
+    }
+}
@@ -0,0 +1,446 @@
+//! OKX spot market data over the v5 public WebSocket.
+//!
+//! Each instrument is subscribed to the `trades`, `tickers` and a configurable
+//! order book channel: `books5` pushes the top five levels as snapshots, while
+//! `books-l2-tbt` sends an initial snapshot followed by tick-by-tick updates.
+
+pub mod options;
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{listing_events, symbol_or_raw, AgentFactory};
+use crate::clock;
+use crate::{
+    agent::Agent,
+    config::Settings,
+    dead_letter,
+    error::IngestorError,
+    http_client,
+    ingest_stats::{self, Counter},
+    parse::parse_decimal_str,
+    rate_limit::{self, Exchange, Priority},
+    watchdog::{self, Watchdog},
+};
+use canonicalizer::Symbol;
+
+/// Instruments per subscribe request, keeping frames well below OKX's limit.
+const SUBSCRIBE_BATCH: usize = 100;
+/// OKX drops connections that stay silent for 30 seconds.
+const PING_INTERVAL_SECS: u64 = 25;
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Fetch all live spot instrument ids quoted in USDT or USDC.
+pub async fn fetch_all_symbols(rest_url: &str) -> Result<Vec<String>, IngestorError> {
+    let http_err = |e| IngestorError::Http {
+        source: e,
+        exchange: "okx",
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http_err)?;
+    let limiter = rate_limit::limiter(Exchange::Okx);
+    limiter.acquire(1, Priority::High).await;
+    let resp: serde_json::Value = client
+        .get(format!("{rest_url}/api/v5/public/instruments"))
+        .query(&[("instType", "SPOT")])
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .map_err(http_err)?
+        .json()
+        .await
+        .map_err(http_err)?;
+
+    let arr = resp
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| IngestorError::Other("okx unexpected response".into()))?;
+    Ok(arr
+        .iter()
+        .filter(|i| i.get("state").and_then(|s| s.as_str()) == Some("live"))
+        .filter(|i| {
+            matches!(
+                i.get("quoteCcy").and_then(|q| q.as_str()),
+                Some("USDT" | "USDC")
+            )
+        })
+        .filter_map(|i| i.get("instId").and_then(|s| s.as_str()).map(str::to_string))
+        .collect())
+}
+
+pub struct OkxAgent {
+    symbols: Vec<String>,
+    /// Follow the exchange's instrument list instead of a fixed set.
+    all: bool,
+    ws_url: String,
+    rest_url: String,
+    book_channel: String,
+    max_reconnect_delay_secs: u64,
+    refresh_interval_mins: u64,
+}
+
+impl OkxAgent {
+    pub fn new(symbols: Vec<String>, all: bool, cfg: &Settings) -> Self {
+        Self {
+            symbols,
+            all,
+            ws_url: cfg.okx_ws_url.clone(),
+            rest_url: cfg.okx_rest_url.clone(),
+            book_channel: cfg.okx_book_channel.clone(),
+            max_reconnect_delay_secs: cfg.okx_max_reconnect_delay_secs,
+            refresh_interval_mins: cfg.okx_refresh_interval_mins,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for OkxAgent {
+    fn name(&self) -> &'static str {
+        "okx"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let (sym_tx, sym_rx) = tokio::sync::watch::channel(self.symbols.clone());
+        let conn = ConnectionConfig {
+            ws_url: self.ws_url.clone(),
+            book_channel: self.book_channel.clone(),
+            max_reconnect_delay_secs: self.max_reconnect_delay_secs,
+        };
+        let handle = tokio::spawn(connection_task(sym_rx, shutdown.clone(), tx.clone(), conn));
+
+        let mut refresh = tokio::time::interval(std::time::Duration::from_secs(
+            60 * self.refresh_interval_mins.max(1),
+        ));
+        refresh.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        refresh.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() { break; }
+                }
+                _ = refresh.tick(), if self.all => {
+                    match fetch_all_symbols(&self.rest_url).await {
+                        Ok(new_symbols) => {
+                            let new_set: HashSet<_> = new_symbols.iter().cloned().collect();
+                            let old_set: HashSet<_> = self.symbols.iter().cloned().collect();
+                            let added: Vec<_> = new_set.difference(&old_set).cloned().collect();
+                            let removed: Vec<_> = old_set.difference(&new_set).cloned().collect();
+                            if added.is_empty() && removed.is_empty() {
+                                tracing::info!("symbol refresh: no changes");
+                                continue;
+                            }
+                            tracing::info!(?added, ?removed, total=new_symbols.len(), "symbol refresh");
+                            for line in listing_events("okx", &added, &removed) {
+                                let _ = tx.send(line).await;
+                            }
+                            self.symbols = new_symbols;
+                            let _ = sym_tx.send(self.symbols.clone());
+                        }
+                        Err(e) => tracing::error!(error=%e, "failed to refresh symbols"),
+                    }
+                }
+            }
+        }
+
+        drop(sym_tx);
+        let _ = handle.await;
+        Ok(())
+    }
+}
+
+pub struct OkxFactory;
+
+#[async_trait::async_trait]
+impl AgentFactory for OkxFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let all = spec.eq_ignore_ascii_case("all");
+        let symbols = if spec.is_empty() {
+            vec!["BTC-USDT".to_string()]
+        } else if all {
+            match fetch_all_symbols(&cfg.okx_rest_url).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!(error=%e, "failed to fetch okx symbols");
+                    return None;
+                }
+            }
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Box::new(OkxAgent::new(symbols, all, cfg)))
+    }
+}
+
+struct ConnectionConfig {
+    ws_url: String,
+    book_channel: String,
+    max_reconnect_delay_secs: u64,
+}
+
+async fn connection_task(
+    mut symbols_rx: tokio::sync::watch::Receiver<Vec<String>>,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+    conn: ConnectionConfig,
+) {
+    let mut attempt: u32 = 0;
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+
+    loop {
+        if *shutdown.borrow() {
+            break;
+        }
+
+        tracing::info!(url = %conn.ws_url, "connecting");
+        let mut current_symbols = symbols_rx.borrow().clone();
+        match http_client::connect_ws(&conn.ws_url).await {
+            Ok((mut ws, _)) => {
+                tracing::info!("connected");
+                let mut watchdog = Watchdog::new(http_client::ws_idle_timeout());
+                attempt = 0;
+
+                if let Err(e) =
+                    send_op(&mut ws, "subscribe", &current_symbols, &conn.book_channel).await
+                {
+                    tracing::error!(error=%e, "failed to send subscription");
+                    continue;
+                }
+
+                let ping_period = std::time::Duration::from_secs(PING_INTERVAL_SECS);
+                let mut ping = tokio::time::interval_at(
+                    tokio::time::Instant::now() + ping_period,
+                    ping_period,
+                );
+
+                loop {
+                    tokio::select! {
+                        _ = shutdown.changed() => {
+                            if *shutdown.borrow() {
+                                tracing::info!("shutdown signal - closing connection");
+                                let _ = ws.close(None).await;
+                                return;
+                            }
+                        }
+                        _ = ping.tick() => {
+                            if let Err(e) = ws.send(Message::Text("ping".into())).await {
+                                tracing::error!(error=%e, "failed to send ping");
+                                break;
+                            }
+                        }
+                        changed = symbols_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            let new_syms = symbols_rx.borrow().clone();
+                            let new_set: HashSet<_> = new_syms.iter().cloned().collect();
+                            let old_set: HashSet<_> = current_symbols.iter().cloned().collect();
+                            let to_sub: Vec<_> = new_set.difference(&old_set).cloned().collect();
+                            let to_unsub: Vec<_> = old_set.difference(&new_set).cloned().collect();
+                            let _ = send_op(&mut ws, "unsubscribe", &to_unsub, &conn.book_channel).await;
+                            if let Err(e) = send_op(&mut ws, "subscribe", &to_sub, &conn.book_channel).await {
+                                tracing::error!(error=%e, "failed to update subscription");
+                                break;
+                            }
+                            current_symbols = new_syms;
+                        }
+                        idle = watchdog.tick() => {
+                            if !watchdog::keep_alive(&mut ws, idle, watchdog.window()).await {
+                                break;
+                            }
+                        }
+                        msg = ws.next() => {
+                            watchdog.feed();
+                            match msg {
+                                Some(Ok(Message::Text(txt))) => {
+                                    if txt == "pong" {
+                                        continue;
+                                    }
+                                    let v = match serde_json::from_str::<serde_json::Value>(&txt) {
+                                        Ok(v) => v,
+                                        Err(e) => {
+                                            dead_letter::record("okx", &txt, &e);
+                                            continue;
+                                        }
+                                    };
+                                    if let Some(event) = v.get("event").and_then(|e| e.as_str()) {
+                                        if event == "error" {
+                                            tracing::error!(code=?v.get("code"), msg=?v.get("msg"), "okx request rejected");
+                                        }
+                                        continue;
+                                    }
+                                    let lines = parse_event(&v, &mut last_trade_ids);
+                                    if lines.is_empty() && v.get("data").is_some() {
+                                        dead_letter::record_no_event("okx", &txt);
+                                    }
+                                    for line in lines {
+                                        if tx.send(line).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Some(Ok(Message::Ping(p))) => { let _ = ws.send(Message::Pong(p)).await; }
+                                Some(Ok(Message::Close(frame))) => { tracing::warn!(?frame, "server closed connection"); break; }
+                                Some(Ok(_)) => { }
+                                Some(Err(e)) => { tracing::error!(error=%e, "ws error"); break; }
+                                None => { tracing::warn!("stream ended"); break; }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!(error=%e, "connect failed");
+            }
+        }
+
+        attempt = attempt.saturating_add(1);
+        ingest_stats::record("okx", Counter::Reconnect);
+        let exp: u32 = attempt.saturating_sub(1).min(4);
+        let delay = (1u64 << exp).min(conn.max_reconnect_delay_secs);
+        let sleep = std::time::Duration::from_secs(delay);
+
+        tracing::info!(?sleep, "reconnecting");
+        tokio::select! {
+            _ = tokio::time::sleep(sleep) => {},
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    tracing::info!("shutdown during backoff");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Send a `subscribe` or `unsubscribe` request for the trade, ticker and book
+/// channels of `symbols`, batched to keep frames small.
+async fn send_op(
+    ws: &mut WsStream,
+    op: &str,
+    symbols: &[String],
+    book_channel: &str,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    for batch in symbols.chunks(SUBSCRIBE_BATCH) {
+        let args: Vec<_> = batch
+            .iter()
+            .flat_map(|s| {
+                ["trades", "tickers", book_channel]
+                    .map(|ch| serde_json::json!({"channel": ch, "instId": s}))
+            })
+            .collect();
+        let msg = serde_json::json!({"op": op, "args": args});
+        ws.send(Message::Text(msg.to_string())).await?;
+    }
+    Ok(())
+}
+
+/// Convert an OKX channel push into canonical event lines. A push may carry
+/// several entries, e.g. multiple trades.
+pub fn parse_event(
+    v: &serde_json::Value,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+) -> Vec<String> {
+    let arg = v.get("arg");
+    let channel = arg
+        .and_then(|a| a.get("channel"))
+        .and_then(|c| c.as_str())
+        .unwrap_or("");
+    let inst = arg
+        .and_then(|a| a.get("instId"))
+        .and_then(|i| i.as_str())
+        .unwrap_or("?");
+    let sym = symbol_or_raw("okx", inst);
+    let action = v.get("action").and_then(|a| a.as_str());
+
+    v.get("data")
+        .and_then(|d| d.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|d| {
+            let dec = |k: &str| {
+                d.get(k)
+                    .and_then(|p| p.as_str())
+                    .and_then(parse_decimal_str)
+                    .unwrap_or_else(|| "?".to_string())
+            };
+            let ts = d
+                .get("ts")
+                .and_then(|t| t.as_str())
+                .and_then(|t| t.parse::<i64>().ok())
+                .unwrap_or_default();
+            let line = match channel {
+                "trades" => {
+                    let trade_id = d
+                        .get("tradeId")
+                        .and_then(|id| id.as_str())
+                        .and_then(|id| id.parse::<i64>().ok())
+                        .filter(|id| *id > 0);
+                    if let Some(id) = trade_id {
+                        last_trade_ids.insert(sym.clone(), id);
+                    }
+                    serde_json::json!({
+                        "agent": "okx",
+                        "type": "trade",
+                        "s": sym,
+                        "t": trade_id,
+                        "p": dec("px"),
+                        "q": dec("sz"),
+                        "ts": ts,
+                        "skew": clock::current_skew_ms()
+                    })
+                }
+                "tickers" => serde_json::json!({
+                    "agent": "okx",
+                    "type": "book_ticker",
+                    "s": sym,
+                    "bp": dec("bidPx"),
+                    "bq": dec("bidSz"),
+                    "ap": dec("askPx"),
+                    "aq": dec("askSz"),
+                    "ts": ts
+                }),
+                "books5" | "books" | "books-l2-tbt" | "books50-l2-tbt" => {
+                    let typ = match action {
+                        Some("update") => "l2_diff",
+                        _ => "snapshot",
+                    };
+                    serde_json::json!({
+                        "agent": "okx",
+                        "type": typ,
+                        "s": sym,
+                        "bids": levels(d.get("bids")),
+                        "asks": levels(d.get("asks")),
+                        "ts": ts
+                    })
+                }
+                _ => return None,
+            };
+            Some(line.to_string())
+        })
+        .collect()
+}
+
+/// `[price, qty]` pairs from an OKX book side, whose levels also carry
+/// liquidated-order and order counts.
+fn levels(v: Option<&serde_json::Value>) -> Vec<[String; 2]> {
+    v.and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let p = parse_decimal_str(lvl.get(0)?.as_str()?)?;
+            let q = parse_decimal_str(lvl.get(1)?.as_str()?)?;
+            Some([p, q])
+        })
+        .collect()
+}
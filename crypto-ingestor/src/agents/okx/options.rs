@@ -0,0 +1,309 @@
+//! OKX option chains polled from the v5 REST API.
+//!
+//! For each underlying family, e.g. `BTC-USD`, option tickers supply bid, ask
+//! and last prices and the option summary supplies mark volatility and
+//! Black-Scholes greeks. Instruments are grouped into one chain per expiry.
+//! OKX quotes options in the underlying coin, so prices are converted to USD
+//! with the family's index price, matching the Binance chains.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use canonicalizer::{
+    AssetClass, CanonicalService, OptionChain, OptionGreeks, OptionQuote, OptionSurfacePoint,
+};
+use chrono::{NaiveDate, TimeZone, Utc};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+};
+
+pub struct OkxOptionsAgent {
+    families: Vec<String>,
+    rest_url: String,
+    poll_interval_secs: u64,
+}
+
+impl OkxOptionsAgent {
+    pub fn new(families: Vec<String>, cfg: &Settings) -> Self {
+        Self {
+            families,
+            rest_url: cfg.okx_rest_url.clone(),
+            poll_interval_secs: cfg.okx_options_poll_interval_secs,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Agent for OkxOptionsAgent {
+    fn name(&self) -> &'static str {
+        "okx_options"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let client = http_client::builder()
+            .build()
+            .map_err(|e| IngestorError::Http {
+                source: e,
+                exchange: "okx",
+                symbol: None,
+            })?;
+
+        let mut last: HashMap<(String, i64), OptionChain> = HashMap::new();
+
+        loop {
+            for family in &self.families {
+                let base = &self.rest_url;
+                let (tickers, summary, index) = tokio::join!(
+                    fetch_data(
+                        &client,
+                        format!("{base}/api/v5/market/tickers?instType=OPTION&instFamily={family}")
+                    ),
+                    fetch_data(
+                        &client,
+                        format!("{base}/api/v5/public/opt-summary?instFamily={family}")
+                    ),
+                    fetch_data(
+                        &client,
+                        format!("{base}/api/v5/market/index-tickers?instId={family}")
+                    ),
+                );
+                let Some(tickers) = tickers else {
+                    tracing::error!(%family, "okx option tickers request failed");
+                    continue;
+                };
+                let index = index
+                    .as_ref()
+                    .and_then(|v| v.as_array())
+                    .and_then(|a| a.first())
+                    .and_then(|i| as_f64(i, "idxPx"));
+                for chain in parse_chains(family, &tickers, summary.as_ref(), index) {
+                    let key = (family.clone(), chain.expiry);
+                    if last.get(&key) != Some(&chain) {
+                        if tx
+                            .send(serde_json::to_string(&chain).unwrap())
+                            .await
+                            .is_err()
+                        {
+                            return Ok(());
+                        }
+                        last.insert(key, chain);
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(self.poll_interval_secs)) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() { break; }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// GET `url` and return the `data` array of a successful OKX response.
+async fn fetch_data(client: &reqwest::Client, url: String) -> Option<Value> {
+    let limiter = rate_limit::limiter(Exchange::Okx);
+    limiter.acquire(1, Priority::Low).await;
+    let resp = client
+        .get(&url)
+        .send()
+        .await
+        .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+        .ok()?;
+    let v = resp.json::<Value>().await.ok()?;
+    if v.get("code").and_then(|c| c.as_str()) != Some("0") {
+        tracing::warn!(%url, msg=?v.get("msg"), "okx request rejected");
+        return None;
+    }
+    v.get("data").cloned()
+}
+
+/// Split an instrument id such as `BTC-USD-241227-60000-C` into expiry
+/// (seconds, at OKX's 08:00 UTC expiry time), strike and contract type.
+fn parse_instrument(inst_id: &str) -> Option<(i64, f64, &'static str)> {
+    let mut parts = inst_id.rsplitn(4, '-');
+    let kind = match parts.next()? {
+        "C" => "CALL",
+        "P" => "PUT",
+        _ => return None,
+    };
+    let strike = parts.next()?.parse().ok()?;
+    let date = NaiveDate::parse_from_str(parts.next()?, "%y%m%d").ok()?;
+    let expiry = Utc
+        .from_utc_datetime(&date.and_hms_opt(8, 0, 0)?)
+        .timestamp();
+    Some((expiry, strike, kind))
+}
+
+/// Build one chain per expiry for `family` from the option `tickers` and
+/// `summary` data arrays. Without an `index` price, bid, ask and last are
+/// left out rather than reported in coin terms.
+fn parse_chains(
+    family: &str,
+    tickers: &Value,
+    summary: Option<&Value>,
+    index: Option<f64>,
+) -> Vec<OptionChain> {
+    let Some(canon) = CanonicalService::canonical_pair("okx", family) else {
+        return Vec::new();
+    };
+    let Some(settle) = canon.split_once('-').map(|(b, _)| b.to_string()) else {
+        return Vec::new();
+    };
+    let summaries: HashMap<&str, &Value> = summary
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|s| Some((s.get("instId")?.as_str()?, s)))
+        .collect();
+    let usd = |v: &Value, k: &str| as_f64(v, k).zip(index).map(|(p, i)| p * i);
+
+    let mut by_expiry: BTreeMap<i64, Vec<OptionQuote>> = BTreeMap::new();
+    for t in tickers.as_array().into_iter().flatten() {
+        let Some(inst_id) = t.get("instId").and_then(|i| i.as_str()) else {
+            continue;
+        };
+        let Some((expiry, strike, kind)) = parse_instrument(inst_id) else {
+            continue;
+        };
+        let sum = summaries.get(inst_id).copied();
+        let greeks = sum.and_then(|s| {
+            let greeks = OptionGreeks {
+                delta: as_f64(s, "deltaBS"),
+                gamma: as_f64(s, "gammaBS"),
+                theta: as_f64(s, "thetaBS"),
+                vega: as_f64(s, "vegaBS"),
+            };
+            (greeks.delta.is_some()
+                || greeks.gamma.is_some()
+                || greeks.theta.is_some()
+                || greeks.vega.is_some())
+            .then_some(greeks)
+        });
+        by_expiry.entry(expiry).or_default().push(OptionQuote {
+            strike,
+            kind: kind.to_string(),
+            bid: usd(t, "bidPx"),
+            ask: usd(t, "askPx"),
+            last: usd(t, "last"),
+            iv: sum.and_then(|s| as_f64(s, "markVol")),
+            greeks,
+        });
+    }
+
+    by_expiry
+        .into_iter()
+        .map(|(expiry, mut options)| {
+            options.sort_by(|a, b| {
+                a.strike
+                    .total_cmp(&b.strike)
+                    .then_with(|| a.kind.cmp(&b.kind))
+            });
+            let surface = options
+                .iter()
+                .filter_map(|q| {
+                    q.iv.map(|iv| OptionSurfacePoint {
+                        strike: q.strike,
+                        expiry,
+                        iv,
+                    })
+                })
+                .collect();
+            OptionChain {
+                agent: "okx".to_string(),
+                r#type: "option_chain".to_string(),
+                s: canon.clone(),
+                ac: AssetClass::Option,
+                settle: settle.clone(),
+                expiry,
+                options,
+                surface,
+                ts: None,
+                settlement_price: None,
+            }
+        })
+        .collect()
+}
+
+/// OKX sends numbers as strings, with `""` for missing values.
+fn as_f64(v: &Value, key: &str) -> Option<f64> {
+    v.get(key)
+        .and_then(|x| x.as_str())
+        .and_then(|s| s.parse().ok())
+}
+
+pub struct OkxOptionsFactory;
+
+#[async_trait::async_trait]
+impl crate::agents::AgentFactory for OkxOptionsFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let families: Vec<String> = if spec.trim().is_empty() {
+            vec!["BTC-USD".to_string()]
+        } else {
+            spec.split(',')
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        Some(Box::new(OkxOptionsAgent::new(families, cfg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_instrument_reads_expiry_strike_and_kind() {
+        let (expiry, strike, kind) = parse_instrument("ETH-USD-240705-3500-P").unwrap();
+        assert_eq!(expiry, 1_720_166_400);
+        assert_eq!(strike, 3500.0);
+        assert_eq!(kind, "PUT");
+        assert!(parse_instrument("BTC-USD-SWAP").is_none());
+    }
+
+    #[test]
+    fn parse_chains_groups_by_expiry_in_usd() {
+        let tickers = serde_json::json!([
+            {"instId": "BTC-USD-240705-60000-C", "bidPx": "0.01", "askPx": "0.012", "last": "0.011"},
+            {"instId": "BTC-USD-240705-60000-P", "bidPx": "", "askPx": "0.02", "last": ""},
+            {"instId": "BTC-USD-240712-65000-C", "bidPx": "0.005", "askPx": "0.006", "last": ""}
+        ]);
+        let summary = serde_json::json!([
+            {"instId": "BTC-USD-240705-60000-C", "markVol": "0.55", "deltaBS": "0.4",
+                "gammaBS": "0.0001", "thetaBS": "-50", "vegaBS": "20"}
+        ]);
+        let chains = parse_chains("BTC-USD", &tickers, Some(&summary), Some(60_000.0));
+        assert_eq!(chains.len(), 2);
+        let near = &chains[0];
+        assert_eq!(near.s, "BTC-USD");
+        assert_eq!(near.settle, "BTC");
+        assert_eq!(near.options.len(), 2);
+        let call = &near.options[0];
+        assert_eq!(call.kind, "CALL");
+        assert!((call.bid.unwrap() - 600.0).abs() < 1e-6);
+        assert_eq!(call.greeks.as_ref().unwrap().delta, Some(0.4));
+        assert_eq!(near.surface.len(), 1);
+        assert_eq!(near.options[1].bid, None);
+
+        let unpriced = parse_chains("BTC-USD", &tickers, None, None);
+        assert!(unpriced
+            .iter()
+            .flat_map(|c| &c.options)
+            .all(|q| q.ask.is_none()));
+    }
+}
@@ -0,0 +1,291 @@
+//! Official end-of-day prices for daily marking and benchmarks.
+//!
+//! The agent polls each configured source a few times an hour and emits a
+//! [`SettlementPrice`] once per source and trading day:
+//!
+//! - `binance:<symbol>` and `coinbase:<product>` give the close of the last
+//!   completed UTC daily candle (`kind` `close`)
+//! - `deribit:<currency>` gives the index delivery price Deribit settles its
+//!   futures and options at, fixed at 08:00 UTC (`kind` `delivery`)
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use canonicalizer::{AssetClass, Bar, SettlementPrice};
+use chrono::{NaiveDate, TimeZone, Utc};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::{binance, coinbase, deribit};
+use crate::{
+    agent::Agent,
+    config::Settings,
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Exchange, Priority},
+};
+
+/// Time between polls of every source.
+const POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const DAY_MS: i64 = 86_400_000;
+
+/// Where one settlement price is read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Source {
+    Binance(String),
+    Coinbase(String),
+    Deribit(String),
+}
+
+impl Source {
+    /// Parse a `venue:symbol` entry.
+    pub fn parse(entry: &str) -> Option<Self> {
+        let (venue, symbol) = entry.trim().split_once(':')?;
+        let symbol = symbol.trim();
+        if symbol.is_empty() {
+            return None;
+        }
+        match venue.trim().to_lowercase().as_str() {
+            "binance" => Some(Self::Binance(symbol.to_uppercase())),
+            "coinbase" => Some(Self::Coinbase(symbol.to_uppercase())),
+            "deribit" => Some(Self::Deribit(symbol.to_lowercase())),
+            _ => None,
+        }
+    }
+}
+
+pub struct SettlementAgent {
+    sources: Vec<Source>,
+    deribit_rest_url: String,
+}
+
+impl SettlementAgent {
+    pub fn new(sources: Vec<Source>, cfg: &Settings) -> Self {
+        Self {
+            sources,
+            deribit_rest_url: cfg.deribit_rest_url.clone(),
+        }
+    }
+
+    async fn fetch(&self, client: &reqwest::Client, source: &Source) -> Option<SettlementPrice> {
+        let now = Utc::now().timestamp_millis();
+        match source {
+            Source::Binance(symbol) => {
+                let url = format!(
+                    "https://api.binance.us/api/v3/klines?symbol={symbol}&interval=1d&limit=2"
+                );
+                let limiter = rate_limit::binance();
+                limiter
+                    .acquire(binance::ohlcv::KLINES_WEIGHT, Priority::Low)
+                    .await;
+                let resp = client.get(&url).send().await.ok()?;
+                limiter.observe(resp.status(), resp.headers());
+                let v = resp.json::<Value>().await.ok()?;
+                daily_close(&binance::ohlcv::parse_bars(symbol, 86_400, &v), now)
+            }
+            Source::Coinbase(product) => {
+                let url = format!(
+                    "https://api.exchange.coinbase.com/products/{product}/candles?granularity=86400"
+                );
+                let limiter = rate_limit::coinbase();
+                limiter
+                    .acquire(coinbase::ohlcv::CANDLES_WEIGHT, Priority::Low)
+                    .await;
+                let resp = client.get(&url).send().await.ok()?;
+                limiter.observe(resp.status(), resp.headers());
+                let v = resp.json::<Value>().await.ok()?;
+                daily_close(&coinbase::ohlcv::parse_bars(product, 86_400, &v), now)
+            }
+            Source::Deribit(currency) => {
+                let url = format!(
+                    "{}/public/get_delivery_prices?index_name={currency}_usd&offset=0&count=1",
+                    self.deribit_rest_url
+                );
+                let limiter = rate_limit::limiter(Exchange::Deribit);
+                limiter.acquire(1, Priority::Low).await;
+                let v = client
+                    .get(&url)
+                    .send()
+                    .await
+                    .inspect(|resp| limiter.observe(resp.status(), resp.headers()))
+                    .ok()?
+                    .json::<Value>()
+                    .await
+                    .ok()?;
+                delivery_price(currency, &v)
+            }
+        }
+    }
+}
+
+/// Close of the newest daily bar completed by `now` (ms).
+pub fn daily_close(bars: &[Bar], now: i64) -> Option<SettlementPrice> {
+    let bar = bars
+        .iter()
+        .filter(|b| b.timestamp + DAY_MS <= now)
+        .max_by_key(|b| b.timestamp)?;
+    Some(SettlementPrice {
+        agent: bar.agent.clone(),
+        r#type: "settlement_price".into(),
+        symbol: bar.symbol.clone(),
+        ac: AssetClass::Spot,
+        kind: "close".into(),
+        price: bar.close.parse().ok()?,
+        date: Utc
+            .timestamp_millis_opt(bar.timestamp)
+            .single()?
+            .format("%Y-%m-%d")
+            .to_string(),
+        timestamp: bar.timestamp + DAY_MS,
+    })
+}
+
+/// Latest index delivery price of a `get_delivery_prices` response.
+pub fn delivery_price(currency: &str, v: &Value) -> Option<SettlementPrice> {
+    let (date, price) = deribit::parse_delivery_prices(v)
+        .into_iter()
+        .max_by_key(|(date, _)| *date)?;
+    Some(SettlementPrice {
+        agent: "deribit".into(),
+        r#type: "settlement_price".into(),
+        symbol: format!("{}-USD", currency.to_uppercase()),
+        ac: AssetClass::Index,
+        kind: "delivery".into(),
+        price,
+        date: date.format("%Y-%m-%d").to_string(),
+        timestamp: delivery_time(date),
+    })
+}
+
+/// Deribit fixes delivery prices at 08:00 UTC.
+fn delivery_time(date: NaiveDate) -> i64 {
+    date.and_hms_opt(8, 0, 0)
+        .map_or(0, |t| t.and_utc().timestamp_millis())
+}
+
+#[async_trait::async_trait]
+impl Agent for SettlementAgent {
+    fn name(&self) -> &'static str {
+        "settlement"
+    }
+
+    async fn run(
+        &mut self,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<(), IngestorError> {
+        let client = http_client::builder()
+            .build()
+            .map_err(|e| IngestorError::Http {
+                source: e,
+                exchange: "settlement",
+                symbol: None,
+            })?;
+        // Trading day last emitted per source.
+        let mut last: HashMap<Source, String> = HashMap::new();
+
+        loop {
+            for source in &self.sources {
+                let Some(price) = self.fetch(&client, source).await else {
+                    tracing::warn!(?source, "no settlement price available");
+                    continue;
+                };
+                if last.get(source) == Some(&price.date) {
+                    continue;
+                }
+                last.insert(source.clone(), price.date.clone());
+                if tx
+                    .send(serde_json::to_string(&price).unwrap())
+                    .await
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {},
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() { break; }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct SettlementFactory;
+
+#[async_trait::async_trait]
+impl super::AgentFactory for SettlementFactory {
+    async fn create(&self, spec: &str, cfg: &Settings) -> Option<Box<dyn Agent>> {
+        let spec = if spec.trim().is_empty() {
+            "binance:BTCUSDT,coinbase:BTC-USD,deribit:btc"
+        } else {
+            spec
+        };
+        let mut sources = Vec::new();
+        for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+            match Source::parse(entry) {
+                Some(s) => sources.push(s),
+                None => {
+                    tracing::error!(%entry, "invalid settlement source, expected venue:symbol");
+                    return None;
+                }
+            }
+        }
+        Some(Box::new(SettlementAgent::new(sources, cfg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn daily_close_skips_the_open_candle() {
+        let bar = |ts: i64, close: &str| Bar {
+            agent: "binance".into(),
+            r#type: "ohlcv".into(),
+            symbol: "BTC-USDT".into(),
+            interval: 86_400,
+            open: "1".into(),
+            high: "1".into(),
+            low: "1".into(),
+            close: close.into(),
+            volume: "1".into(),
+            ac: None,
+            settle: None,
+            id: None,
+            timestamp: ts,
+        };
+        // 2024-01-01 and 2024-01-02, queried midway through the 2nd.
+        let day = 1_704_067_200_000;
+        let bars = [bar(day, "42000.5"), bar(day + DAY_MS, "43000")];
+        let p = daily_close(&bars, day + DAY_MS + 3_600_000).unwrap();
+        assert_eq!(p.date, "2024-01-01");
+        assert_eq!(p.price, 42000.5);
+        assert_eq!(p.timestamp, day + DAY_MS);
+        assert_eq!(p.kind, "close");
+        assert!(daily_close(&bars[..1], day + 1).is_none());
+    }
+
+    #[test]
+    fn delivery_price_takes_the_latest_day() {
+        let v = json!({"result": {"data": [
+            {"date": "2024-01-02", "delivery_price": 45000.25},
+            {"date": "2024-01-01", "delivery_price": 42000.0}
+        ]}});
+        let p = delivery_price("btc", &v).unwrap();
+        assert_eq!(p.symbol, "BTC-USD");
+        assert_eq!(p.ac, AssetClass::Index);
+        assert_eq!(p.date, "2024-01-02");
+        assert_eq!(p.price, 45000.25);
+        assert_eq!(p.timestamp, 1_704_182_400_000);
+        assert_eq!(
+            Source::parse("deribit:ETH"),
+            Some(Source::Deribit("eth".into()))
+        );
+        assert!(Source::parse("kraken:XBTUSD").is_none());
+    }
+}
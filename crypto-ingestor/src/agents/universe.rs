@@ -0,0 +1,357 @@
+//! Symbol universes of the spot agents.
+//!
+//! Besides `all` and explicit symbol lists, the `binance` and `coinbase`
+//! agents accept selectors, applied in the order given:
+//!
+//! - `quote:USD` keeps markets quoted in `USD`
+//! - `meta:category=defi` keeps markets whose base asset carries that tag in
+//!   the `universe_categories` file
+//! - `top:50` keeps the 50 markets with the most 24h quote volume, read from
+//!   the exchange's tickers
+//!
+//! e.g. `binance:quote:USDT,top:20`. A [`Universe`] is resolved when the
+//! agent starts and again on every symbol refresh, so the markets entering
+//! and leaving it are announced as `listing` and `delisting` events. Markets
+//! the instrument registry lists as halted or delisted are left out of every
+//! universe, explicit symbol lists included, until they trade again.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{binance, coinbase};
+use crate::{
+    error::IngestorError,
+    http_client,
+    rate_limit::{self, Priority},
+};
+use canonicalizer::CanonicalService;
+
+/// Request weight of a 24h ticker call for every symbol.
+const TICKER_24HR_WEIGHT: i64 = 40;
+
+/// Tags per base asset, e.g. `{"UNI": {"category": "defi"}}`.
+pub type Categories = HashMap<String, HashMap<String, String>>;
+
+/// One step narrowing a universe.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Quote(String),
+    Meta { key: String, value: String },
+    Top(usize),
+}
+
+impl Filter {
+    /// Parse one `kind:arg` selector. `None` if `item` is not a selector.
+    pub fn parse(item: &str) -> Option<Result<Self, String>> {
+        let (kind, arg) = item.trim().split_once(':')?;
+        let arg = arg.trim();
+        Some(match kind.trim().to_lowercase().as_str() {
+            "top" => arg
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Filter::Top)
+                .ok_or_else(|| format!("invalid top count: {arg}")),
+            "quote" if !arg.is_empty() => Ok(Filter::Quote(arg.to_uppercase())),
+            "meta" => match arg.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => Ok(Filter::Meta {
+                    key: key.trim().to_lowercase(),
+                    value: value.trim().to_string(),
+                }),
+                _ => Err(format!("invalid meta selector, expected key=value: {arg}")),
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Symbols an agent subscribes to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Universe {
+    /// Every tradable USD-quoted market.
+    All,
+    /// A fixed list of native symbols.
+    Symbols(Vec<String>),
+    /// Every market that passes `filters`.
+    Select {
+        filters: Vec<Filter>,
+        categories: Option<String>,
+    },
+}
+
+impl Universe {
+    /// Selector universe of an agent spec such as `quote:USD,top:20`. `None`
+    /// if the spec lists symbols instead.
+    pub fn parse(spec: &str, categories: Option<&str>) -> Option<Result<Self, String>> {
+        let items: Vec<&str> = spec.split(',').filter(|s| !s.trim().is_empty()).collect();
+        let first = Filter::parse(items.first()?)?;
+        let filters = std::iter::once(Some(first))
+            .chain(items[1..].iter().map(|i| Filter::parse(i)))
+            .map(|f| f.unwrap_or_else(|| Err("cannot mix selectors and symbols".into())))
+            .collect::<Result<Vec<_>, _>>();
+        Some(filters.and_then(|filters| {
+            let tagged = filters.iter().any(|f| matches!(f, Filter::Meta { .. }));
+            if tagged && categories.is_none() {
+                return Err("meta selectors need a universe_categories file".into());
+            }
+            Ok(Universe::Select {
+                filters,
+                categories: categories.map(str::to_string),
+            })
+        }))
+    }
+
+    /// Native symbols of the universe on `exchange` right now.
+    pub async fn resolve(&self, exchange: &'static str) -> Result<Vec<String>, IngestorError> {
+        let (filters, categories) = match self {
+            Universe::Symbols(v) => return Ok(tradable(exchange, v.clone())),
+            Universe::All => return Ok(tradable(exchange, fetch_all_symbols(exchange).await?)),
+            Universe::Select {
+                filters,
+                categories,
+            } => (filters, categories),
+        };
+        let candidates = fetch_all_symbols(exchange).await?;
+        let volumes = if filters.iter().any(|f| matches!(f, Filter::Top(_))) {
+            fetch_volumes(exchange).await?
+        } else {
+            HashMap::new()
+        };
+        let categories = match categories {
+            Some(path) => load_categories(path)?,
+            None => Categories::new(),
+        };
+        Ok(tradable(
+            exchange,
+            select(exchange, candidates, filters, &volumes, &categories),
+        ))
+    }
+}
+
+/// `symbols` without the markets the instrument registry lists as not
+/// trading. Markets it has no rules for are kept.
+pub fn tradable(exchange: &str, mut symbols: Vec<String>) -> Vec<String> {
+    symbols.retain(|raw| {
+        let inactive = CanonicalService::canonical_pair(exchange, raw)
+            .and_then(|canon| CanonicalService::instrument(exchange, &canon))
+            .is_some_and(|info| !info.is_trading());
+        if inactive {
+            tracing::info!(%exchange, symbol=%raw, "skipping market that is not trading");
+        }
+        !inactive
+    });
+    symbols
+}
+
+async fn fetch_all_symbols(exchange: &str) -> Result<Vec<String>, IngestorError> {
+    match exchange {
+        "binance" => binance::fetch_all_symbols().await,
+        "coinbase" => coinbase::fetch_all_symbols().await,
+        _ => Err(IngestorError::Other(format!(
+            "no symbol universe for {exchange}"
+        ))),
+    }
+}
+
+/// Apply `filters` in order to the native `candidates` of `exchange`.
+pub fn select(
+    exchange: &str,
+    mut candidates: Vec<String>,
+    filters: &[Filter],
+    volumes: &HashMap<String, f64>,
+    categories: &Categories,
+) -> Vec<String> {
+    let pair = |raw: &str| {
+        let canon = CanonicalService::canonical_pair(exchange, raw)?;
+        let (base, quote) = canon.split_once('-')?;
+        Some((base.to_string(), quote.to_string()))
+    };
+    for filter in filters {
+        match filter {
+            Filter::Quote(q) => {
+                candidates.retain(|raw| pair(raw).is_some_and(|(_, quote)| quote == *q))
+            }
+            Filter::Meta { key, value } => candidates.retain(|raw| {
+                pair(raw)
+                    .and_then(|(base, _)| categories.get(&base)?.get(key))
+                    .is_some_and(|v| v.eq_ignore_ascii_case(value))
+            }),
+            Filter::Top(n) => {
+                let volume = |raw: &String| volumes.get(raw).copied().unwrap_or(0.0);
+                candidates.sort_by(|a, b| volume(b).total_cmp(&volume(a)).then(a.cmp(b)));
+                candidates.truncate(*n);
+            }
+        }
+    }
+    candidates
+}
+
+/// Read a categories file, keyed by uppercase base asset and lowercase tag.
+pub fn load_categories(path: &str) -> Result<Categories, IngestorError> {
+    let text = std::fs::read_to_string(path)?;
+    let raw: Categories = serde_json::from_str(&text)
+        .map_err(|e| IngestorError::Other(format!("invalid universe categories {path}: {e}")))?;
+    Ok(raw
+        .into_iter()
+        .map(|(asset, tags)| {
+            let tags = tags
+                .into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v))
+                .collect();
+            (asset.to_uppercase(), tags)
+        })
+        .collect())
+}
+
+/// 24h quote volume per native symbol.
+async fn fetch_volumes(exchange: &'static str) -> Result<HashMap<String, f64>, IngestorError> {
+    let http = |source| IngestorError::Http {
+        source,
+        exchange,
+        symbol: None,
+    };
+    let client = http_client::builder().build().map_err(http)?;
+    let (url, limiter, weight) = match exchange {
+        "binance" => (
+            "https://api.binance.us/api/v3/ticker/24hr",
+            rate_limit::binance(),
+            TICKER_24HR_WEIGHT,
+        ),
+        _ => (
+            "https://api.exchange.coinbase.com/products/stats",
+            rate_limit::coinbase(),
+            1,
+        ),
+    };
+    limiter.acquire(weight, Priority::Low).await;
+    let resp = client.get(url).send().await.map_err(http)?;
+    limiter.observe(resp.status(), resp.headers());
+    let v: Value = resp.json().await.map_err(http)?;
+    Ok(match exchange {
+        "binance" => parse_binance_volumes(&v),
+        _ => parse_coinbase_volumes(&v),
+    })
+}
+
+/// Quote volume per lowercase symbol of a Binance `ticker/24hr` response.
+pub fn parse_binance_volumes(v: &Value) -> HashMap<String, f64> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|t| {
+            let symbol = t.get("symbol")?.as_str()?.to_lowercase();
+            let volume = t.get("quoteVolume")?.as_str()?.parse().ok()?;
+            Some((symbol, volume))
+        })
+        .collect()
+}
+
+/// Quote volume per product of a Coinbase `/products/stats` response, from
+/// the base volume and last price of the past 24 hours.
+pub fn parse_coinbase_volumes(v: &Value) -> HashMap<String, f64> {
+    v.as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(id, stats)| {
+            let day = stats.get("stats_24hour")?;
+            let num = |k: &str| day.get(k)?.as_str()?.parse::<f64>().ok();
+            Some((id.clone(), num("volume")? * num("last")?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canonicalizer::{InstrumentInfo, InstrumentStatus};
+    use serde_json::json;
+
+    #[test]
+    fn selectors_parse_and_reject_mixed_specs() {
+        assert_eq!(Universe::parse("btcusdt,ethusdt", None), None);
+        assert_eq!(
+            Universe::parse("quote:usd, top:2", None),
+            Some(Ok(Universe::Select {
+                filters: vec![Filter::Quote("USD".into()), Filter::Top(2)],
+                categories: None,
+            }))
+        );
+        assert!(matches!(
+            Universe::parse("top:5,btcusdt", None),
+            Some(Err(_))
+        ));
+        assert!(matches!(Universe::parse("top:0", None), Some(Err(_))));
+        assert!(matches!(
+            Universe::parse("meta:category=defi", None),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn markets_that_stopped_trading_are_left_out() {
+        CanonicalService::update_instruments(|r| {
+            for (symbol, status) in [
+                ("HALTX-USD", InstrumentStatus::Halted),
+                ("LIVEX-USD", InstrumentStatus::Trading),
+            ] {
+                r.insert(InstrumentInfo {
+                    exchange: "coinbase".into(),
+                    symbol: symbol.into(),
+                    native: symbol.into(),
+                    status,
+                    tick_size: None,
+                    lot_size: None,
+                    min_notional: None,
+                });
+            }
+        });
+        let symbols = ["HALTX-USD", "LIVEX-USD", "NEWX-USD"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(tradable("coinbase", symbols), ["LIVEX-USD", "NEWX-USD"]);
+    }
+
+    #[test]
+    fn filters_apply_in_order() {
+        let candidates = ["BTC-USD", "ETH-USD", "UNI-USD", "AAVE-EUR", "AAVE-USD"]
+            .map(String::from)
+            .to_vec();
+        let volumes = parse_coinbase_volumes(&json!({
+            "BTC-USD": {"stats_24hour": {"volume": "10", "last": "60000"}},
+            "ETH-USD": {"stats_24hour": {"volume": "100", "last": "3000"}},
+            "UNI-USD": {"stats_24hour": {"volume": "1000", "last": "8"}},
+            "AAVE-USD": {"stats_24hour": {"volume": "50", "last": "100"}}
+        }));
+        let categories: Categories = [("UNI", "defi"), ("AAVE", "defi"), ("BTC", "currency")]
+            .into_iter()
+            .map(|(a, c)| (a.into(), HashMap::from([("category".into(), c.into())])))
+            .collect();
+        let run = |filters: &[Filter]| {
+            select(
+                "coinbase",
+                candidates.clone(),
+                filters,
+                &volumes,
+                &categories,
+            )
+        };
+
+        assert_eq!(run(&[Filter::Top(2)]), ["BTC-USD", "ETH-USD"]);
+        let defi = Filter::Meta {
+            key: "category".into(),
+            value: "DeFi".into(),
+        };
+        assert_eq!(
+            run(&[defi.clone(), Filter::Quote("USD".into())]),
+            ["UNI-USD", "AAVE-USD"]
+        );
+        assert_eq!(run(&[defi, Filter::Top(1)]), ["UNI-USD"]);
+
+        let binance = parse_binance_volumes(&json!([
+            {"symbol": "BTCUSDT", "quoteVolume": "123.5"},
+            {"symbol": "BAD"}
+        ]));
+        assert_eq!(binance, HashMap::from([("btcusdt".into(), 123.5)]));
+    }
+}
@@ -0,0 +1,106 @@
+//! Report gaps in captured trade and bar history.
+//!
+//! Reads event files written by the file sink, in capture order, and prints
+//! one `gap` record per trade id skip, silent trade stream or run of missing
+//! bars. With `--backfill`, missing Binance bars are fetched from the klines
+//! endpoint and appended to the given file so the dataset can be repaired.
+
+use std::collections::HashMap;
+
+use canonicalizer::CanonicalService;
+use clap::Parser;
+use ingestor::error::IngestorError;
+use ingestor::gaps::{self, Gap, GapKind, GapScanner};
+use ingestor::http_client;
+use ingestor::sink::{FileSink, OutputSink};
+use tokio::io::AsyncBufReadExt;
+use tracing_subscriber::FmtSubscriber;
+
+/// Command line arguments
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Find sequence and time gaps in captured events"
+)]
+struct Args {
+    /// Input files (JSON lines), in capture order
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Report trade streams silent for longer than this; 0 disables
+    #[arg(long, default_value_t = 60)]
+    max_silence_secs: u64,
+
+    /// Fetch missing Binance bars over REST and append them to this file
+    #[arg(long)]
+    backfill: Option<String>,
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), IngestorError> {
+    let subscriber = FmtSubscriber::builder()
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .finish();
+    let _ = tracing::subscriber::set_global_default(subscriber);
+
+    let args = Args::parse();
+    let mut scanner = GapScanner::new(args.max_silence_secs as i64 * 1000);
+    let mut found: Vec<Gap> = Vec::new();
+    for input in &args.inputs {
+        let file = tokio::fs::File::open(input).await?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            for gap in scanner.observe(&line) {
+                println!("{}", serde_json::to_string(&gap).unwrap());
+                found.push(gap);
+            }
+        }
+    }
+
+    let mut counts: HashMap<GapKind, u64> = HashMap::new();
+    for gap in &found {
+        *counts.entry(gap.kind).or_default() += 1;
+    }
+    tracing::info!(
+        seq = counts.get(&GapKind::Seq).copied().unwrap_or_default(),
+        time = counts.get(&GapKind::Time).copied().unwrap_or_default(),
+        bars = counts.get(&GapKind::Bars).copied().unwrap_or_default(),
+        "scan complete"
+    );
+
+    if let Some(path) = &args.backfill {
+        CanonicalService::init().await;
+        let sink = FileSink::new(path).await.map_err(IngestorError::Io)?;
+        let client = http_client::builder()
+            .build()
+            .map_err(|e| IngestorError::Http {
+                source: e,
+                exchange: "binance",
+                symbol: None,
+            })?;
+        let mut repaired = 0usize;
+        for gap in found.iter().filter(|g| g.kind == GapKind::Bars) {
+            let spot = gap.ac.as_deref().is_none_or(|ac| ac == "spot");
+            let Some(interval) = gap.interval.filter(|_| gap.agent == "binance" && spot) else {
+                tracing::warn!(agent=%gap.agent, s=%gap.symbol, "no backfill source");
+                continue;
+            };
+            let Some(symbol) = CanonicalService::native_pair("binance", &gap.symbol) else {
+                tracing::warn!(s=%gap.symbol, "no binance symbol");
+                continue;
+            };
+            let bars = gaps::fetch_binance_bars(&client, &symbol, interval, gap.from, gap.to).await;
+            if (bars.len() as i64) < gap.missing {
+                tracing::warn!(s=%gap.symbol, missing=gap.missing, fetched=bars.len(), "partial backfill");
+            }
+            for bar in &bars {
+                sink.send(&serde_json::to_string(bar).unwrap()).await?;
+            }
+            repaired += bars.len();
+        }
+        tracing::info!(bars = repaired, %path, "backfill complete");
+    }
+    Ok(())
+}
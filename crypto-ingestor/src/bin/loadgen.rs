@@ -0,0 +1,220 @@
+//! Synthetic Binance-style websocket server for soak testing the ingestor.
+//!
+//! Point the ingestor at it with `INGESTOR_BINANCE_WS_URL` (or a config file)
+//! and every connection receives trade, depth and book ticker frames for the
+//! symbols it subscribes to at the configured rate. Trade ids increase by one
+//! per symbol and `T`/`E` carry the send time, so gaps and end-to-end latency
+//! can be measured downstream.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tracing_subscriber::FmtSubscriber;
+
+/// Command line arguments
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about = "Synthetic exchange websocket load generator")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:9443")]
+    listen: String,
+
+    /// Steady-state messages per second per connection
+    #[arg(long, default_value_t = 1000)]
+    rate: u64,
+
+    /// Print an agent spec with this many synthetic symbols and exit
+    #[arg(long)]
+    symbols: Option<usize>,
+
+    /// Start a burst every N seconds (0 disables bursts)
+    #[arg(long, default_value_t = 0)]
+    burst_every_secs: u64,
+
+    /// Length of each burst in seconds
+    #[arg(long, default_value_t = 1)]
+    burst_secs: u64,
+
+    /// Rate multiplier applied during a burst
+    #[arg(long, default_value_t = 10)]
+    burst_factor: u64,
+}
+
+const TICK_MS: u64 = 10;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> std::io::Result<()> {
+    let subscriber = FmtSubscriber::builder().with_target(false).finish();
+    let _ = tracing::subscriber::set_global_default(subscriber);
+
+    let args = Args::parse();
+    if let Some(n) = args.symbols {
+        let syms: Vec<String> = (0..n).map(|i| format!("lg{i}usdt")).collect();
+        println!("binance:{}", syms.join(","));
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind(&args.listen).await?;
+    tracing::info!(addr = %listener.local_addr()?, rate = args.rate, "loadgen listening");
+
+    let sent = Arc::new(AtomicU64::new(0));
+    let counter = sent.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        let mut last = 0;
+        loop {
+            ticker.tick().await;
+            let now = counter.load(Ordering::Relaxed);
+            tracing::info!(msgs_per_sec = now - last, total = now, "loadgen");
+            last = now;
+        }
+    });
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let args = args.clone();
+        let sent = sent.clone();
+        tokio::spawn(async move {
+            tracing::info!(%peer, "client connected");
+            serve(stream, args, sent).await;
+            tracing::info!(%peer, "client disconnected");
+        });
+    }
+}
+
+async fn serve(stream: TcpStream, args: Args, sent: Arc<AtomicU64>) {
+    let ws = match accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            tracing::error!(error=%e, "handshake failed");
+            return;
+        }
+    };
+    let (mut write, mut read) = ws.split();
+
+    let mut symbols: Vec<String> = Vec::new();
+    let mut trade_ids: HashMap<String, i64> = HashMap::new();
+    let mut ticker = tokio::time::interval(Duration::from_millis(TICK_MS));
+    let started = tokio::time::Instant::now();
+    let mut carry: u64 = 0;
+    let mut seq: u64 = 0;
+
+    loop {
+        tokio::select! {
+            msg = read.next() => {
+                match msg {
+                    Some(Ok(Message::Text(txt))) => {
+                        if let Some(ack) = handle_request(&txt, &mut symbols) {
+                            if write.send(Message::Text(ack)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Ping(p))) => {
+                        let _ = write.send(Message::Pong(p)).await;
+                    }
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return,
+                    Some(Ok(_)) => {}
+                }
+            }
+            _ = ticker.tick() => {
+                if symbols.is_empty() {
+                    continue;
+                }
+                let rate = current_rate(&args, started.elapsed());
+                let budget = rate * TICK_MS + carry;
+                let n = budget / 1000;
+                carry = budget % 1000;
+                for _ in 0..n {
+                    // Symbols take turns, so each one's frame type advances
+                    // with its own round rather than with `seq`, which would
+                    // pin a symbol to one type when their counts share a factor.
+                    let n_symbols = symbols.len() as u64;
+                    let sym = &symbols[(seq % n_symbols) as usize];
+                    let frame = synthetic_frame(sym, seq / n_symbols, &mut trade_ids);
+                    seq += 1;
+                    if write.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                    sent.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// Apply SUBSCRIBE/UNSUBSCRIBE requests to `symbols`, returning the ack frame.
+fn handle_request(txt: &str, symbols: &mut Vec<String>) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(txt).ok()?;
+    let method = v.get("method")?.as_str()?;
+    let params = v.get("params")?.as_array()?;
+    for p in params.iter().filter_map(|p| p.as_str()) {
+        let Some(sym) = p.strip_suffix("@trade") else {
+            continue;
+        };
+        match method {
+            "SUBSCRIBE" if !symbols.iter().any(|s| s == sym) => symbols.push(sym.to_string()),
+            "UNSUBSCRIBE" => symbols.retain(|s| s != sym),
+            _ => {}
+        }
+    }
+    Some(serde_json::json!({"result": null, "id": v.get("id").cloned()}).to_string())
+}
+
+fn current_rate(args: &Args, elapsed: Duration) -> u64 {
+    if args.burst_every_secs == 0 {
+        return args.rate;
+    }
+    let pos = elapsed.as_secs() % args.burst_every_secs;
+    if elapsed.as_secs() >= args.burst_every_secs && pos < args.burst_secs {
+        args.rate * args.burst_factor
+    } else {
+        args.rate
+    }
+}
+
+/// Frame number `round` of `symbol`, cycling through trade, depth update and
+/// book ticker frames.
+fn synthetic_frame(symbol: &str, round: u64, trade_ids: &mut HashMap<String, i64>) -> String {
+    let now = chrono::Utc::now().timestamp_millis();
+    let price = 100.0 + (round % 200) as f64 * 0.01;
+    let upper = symbol.to_uppercase();
+    match round % 3 {
+        0 => {
+            let id = trade_ids.entry(symbol.to_string()).or_insert(0);
+            *id += 1;
+            serde_json::json!({
+                "e": "trade",
+                "E": now,
+                "s": upper,
+                "t": *id,
+                "p": format!("{price:.2}"),
+                "q": "0.010",
+                "T": now
+            })
+        }
+        1 => serde_json::json!({
+            "e": "depthUpdate",
+            "E": now,
+            "s": upper,
+            "b": [[format!("{:.2}", price - 0.01), "1.5"]],
+            "a": [[format!("{:.2}", price + 0.01), "2.0"]]
+        }),
+        _ => serde_json::json!({
+            "e": "bookTicker",
+            "E": now,
+            "s": upper,
+            "b": format!("{:.2}", price - 0.01),
+            "B": "1.5",
+            "a": format!("{:.2}", price + 0.01),
+            "A": "2.0"
+        }),
+    }
+    .to_string()
+}
@@ -0,0 +1,176 @@
+//! Re-run the current parsers over dead-letter or raw capture files.
+//!
+//! Each input line is either a `dead_letter` record written by the ingestor
+//! (`{"type":"dead_letter","agent":..,"raw":..}`) or a raw exchange message, in
+//! which case `--agent` names the exchange it came from, with the stream after
+//! a colon where an exchange's streams parse differently, e.g. `bybit:linear`. Messages the current
+//! parsers understand are emitted as canonical events to the chosen sink;
+//! the rest are counted and reported, so parsers can be fixed and the same
+//! files backfilled again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use canonicalizer::{CanonicalService, Symbol};
+use clap::Parser;
+use ingestor::agents::{binance, bybit, coinbase, deribit, gate, hyperliquid, kucoin, mexc, okx};
+use ingestor::error::IngestorError;
+use ingestor::sink::{DynSink, FileSink, StdoutSink};
+use serde_json::Value;
+use tokio::io::AsyncBufReadExt;
+use tracing_subscriber::FmtSubscriber;
+
+/// Command line arguments
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Reprocess dead-lettered or captured exchange messages"
+)]
+struct Args {
+    /// Input files (JSON lines)
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Exchange for raw capture lines that are not dead-letter records, e.g.
+    /// `coinbase` or `bybit:linear`
+    #[arg(long)]
+    agent: Option<String>,
+
+    /// Output sink type (stdout or file)
+    #[arg(long, default_value = "stdout")]
+    sink: String,
+
+    /// File path for the file sink
+    #[arg(long)]
+    file_path: Option<String>,
+}
+
+#[derive(Default)]
+struct Stats {
+    recovered: u64,
+    ignored: u64,
+    failed: u64,
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), IngestorError> {
+    let subscriber = FmtSubscriber::builder()
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .finish();
+    let _ = tracing::subscriber::set_global_default(subscriber);
+
+    let args = Args::parse();
+    let sink: DynSink = match args.sink.as_str() {
+        "stdout" => Arc::new(StdoutSink::new()),
+        "file" => {
+            let path = args
+                .file_path
+                .as_ref()
+                .ok_or_else(|| IngestorError::Other("file_path not set".into()))?;
+            Arc::new(FileSink::new(path).await.map_err(IngestorError::Io)?)
+        }
+        other => {
+            return Err(IngestorError::Other(format!(
+                "unknown sink type: {}",
+                other
+            )));
+        }
+    };
+
+    CanonicalService::init().await;
+
+    let mut stats = Stats::default();
+    let mut last_trade_ids: HashMap<Symbol, i64> = HashMap::new();
+    let mut tickers: HashMap<Symbol, bybit::TickerQuote> = HashMap::new();
+    for input in &args.inputs {
+        let file = tokio::fs::File::open(input).await?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match reprocess_line(
+                &line,
+                args.agent.as_deref(),
+                &mut last_trade_ids,
+                &mut tickers,
+            ) {
+                Ok(events) if events.is_empty() => stats.ignored += 1,
+                Ok(events) => {
+                    for event in events {
+                        sink.send(&event).await?;
+                    }
+                    stats.recovered += 1;
+                }
+                Err(e) => {
+                    tracing::debug!(%input, error=%e, "still unparseable");
+                    stats.failed += 1;
+                }
+            }
+        }
+    }
+
+    tracing::info!(
+        recovered = stats.recovered,
+        ignored = stats.ignored,
+        failed = stats.failed,
+        "reprocessing complete"
+    );
+    Ok(())
+}
+
+/// Parse one input line, returning the canonical events the current parsers
+/// produce, which is none for messages they deliberately skip.
+fn reprocess_line(
+    line: &str,
+    default_agent: Option<&str>,
+    last_trade_ids: &mut HashMap<Symbol, i64>,
+    tickers: &mut HashMap<Symbol, bybit::TickerQuote>,
+) -> Result<Vec<String>, String> {
+    let outer: Option<Value> = serde_json::from_str(line).ok();
+    let (agent, stream, raw) = match &outer {
+        Some(v) if v.get("type").and_then(|t| t.as_str()) == Some("dead_letter") => (
+            v.get("agent").and_then(|a| a.as_str()),
+            v.get("stream").and_then(|s| s.as_str()),
+            v.get("raw").and_then(|r| r.as_str()).unwrap_or_default(),
+        ),
+        _ => {
+            let agent = default_agent.map(|a| a.split_once(':').unwrap_or((a, "")));
+            (
+                agent.map(|a| a.0),
+                agent.map(|a| a.1).filter(|s| !s.is_empty()),
+                line,
+            )
+        }
+    };
+    let agent = agent.ok_or("no agent for raw line; pass --agent")?;
+    let v: Value = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    let events = match agent {
+        "binance" => binance::parse_event(&v, last_trade_ids)
+            .into_iter()
+            .collect(),
+        "binance_futures" | "binance_coinm" => binance::futures::parse_event(&v, last_trade_ids),
+        "coinbase" => coinbase::parse_event(&v, last_trade_ids)
+            .into_iter()
+            .collect(),
+        "deribit" => deribit::futures::parse_event(&v, last_trade_ids),
+        "gate" => gate::parse_event(&v, last_trade_ids).into_iter().collect(),
+        "hyperliquid" => hyperliquid::parse_event(&v, last_trade_ids),
+        "kucoin" => kucoin::parse_event(&v, last_trade_ids)
+            .into_iter()
+            .collect(),
+        "mexc" => mexc::parse_event(&v, last_trade_ids),
+        "okx" => okx::parse_event(&v, last_trade_ids),
+        "bybit" => {
+            let category = match stream {
+                Some("linear") => bybit::Category::Linear,
+                _ => bybit::Category::Spot,
+            };
+            bybit::parse_event(&v, category, last_trade_ids, tickers)
+        }
+        other => return Err(format!("no parser for agent {other}")),
+    };
+    Ok(events)
+}
@@ -0,0 +1,84 @@
+//! Build and configuration identity of the running ingestor.
+//!
+//! The git SHA, build time and enabled features are captured by `build.rs`.
+//! Together with a hash of the effective [`Settings`] they are emitted as a
+//! `build_info` event at startup, so drift between instances of a fleet shows
+//! up in the output stream.
+
+use sha2::{Digest, Sha256};
+
+use crate::config::Settings;
+
+pub const GIT_SHA: &str = env!("INGESTOR_GIT_SHA");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Build time in milliseconds since the Unix epoch.
+pub fn built_at() -> i64 {
+    env!("INGESTOR_BUILT_AT").parse().unwrap_or_default()
+}
+
+/// Cargo features the ingestor was compiled with.
+pub fn features() -> Vec<&'static str> {
+    env!("INGESTOR_FEATURES")
+        .split(',')
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+/// SHA-256 of the effective configuration, excluding API credentials. Map
+/// fields of [`Settings`] are ordered, so equal configurations hash equally.
+pub fn config_hash(settings: &Settings) -> String {
+    let json = serde_json::to_vec(settings).unwrap_or_default();
+    hex::encode(Sha256::digest(json))
+}
+
+/// The `build_info` event for `settings`.
+pub fn event(settings: &Settings) -> serde_json::Value {
+    serde_json::json!({
+        "agent": "ingestor",
+        "type": "build_info",
+        "version": VERSION,
+        "git_sha": GIT_SHA,
+        "built_at": built_at(),
+        "features": features(),
+        "config_hash": config_hash(settings),
+        "ts": chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_hash_ignores_credentials() {
+        let base = Settings::default();
+        let mut with_key = base.clone();
+        with_key.binance_api_secret = Some("secret".into());
+        assert_eq!(config_hash(&base), config_hash(&with_key));
+
+        let mut other = base.clone();
+        other.subscription_resync_secs += 1;
+        assert_ne!(config_hash(&base), config_hash(&other));
+    }
+
+    #[test]
+    fn config_hash_is_stable_across_map_order() {
+        let build = |slos: &[(&str, u64)]| {
+            let groups = [("USD", vec!["USD", "USDT"]), ("EUR", vec!["EUR", "EURC"])];
+            config_hash(&Settings {
+                freshness_slos_ms: slos.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+                quote_groups: groups
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+                    .collect(),
+                ..Settings::default()
+            })
+        };
+        let slos = [("trade", 5_000), ("l2_diff", 2_000), ("book_ticker", 1_000)];
+        let mut reversed = slos;
+        reversed.reverse();
+        assert_eq!(build(&slos), build(&slos));
+        assert_eq!(build(&slos), build(&reversed));
+    }
+}
@@ -2,7 +2,6 @@ use once_cell::sync::Lazy;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::Duration;
 
-
 pub static CLOCK_SKEW_MS: Lazy<AtomicI64> = Lazy::new(|| AtomicI64::new(0));
 
 pub fn spawn_clock_sync() {
@@ -28,4 +27,3 @@ pub fn spawn_clock_sync() {
 pub fn current_skew_ms() -> i64 {
     CLOCK_SKEW_MS.load(Ordering::Relaxed)
 }
-
@@ -1,5 +1,11 @@
+use std::collections::BTreeMap;
+
 use clap::Parser;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::fanout::SinkConfig;
+use crate::sink::Encoding;
+use crate::transform::TransformConfig;
 
 /// Default refresh interval for the Coinbase websocket connection.
 pub const DEFAULT_COINBASE_REFRESH_INTERVAL_MINS: u64 = 60;
@@ -20,6 +26,65 @@ pub struct Cli {
     #[arg(long)]
     pub file_path: Option<String>,
 
+    /// Deployment label stamped into every emitted event (e.g. prod, staging)
+    #[arg(long)]
+    pub deployment: Option<String>,
+
+    /// Tenant label stamped into every emitted event
+    #[arg(long)]
+    pub tenant: Option<String>,
+
+    /// Also print 1-in-N events per type/symbol to stdout, or stderr when
+    /// stdout carries protobuf (development aid)
+    #[arg(long)]
+    pub sample_every: Option<u64>,
+
+    /// JSON, TOML or YAML file pinning exchange symbols and asset aliases to canonical forms
+    #[arg(long)]
+    pub symbol_overrides: Option<String>,
+
+    /// JSON file tagging base assets for `meta:key=value` symbol universes
+    #[arg(long)]
+    pub universe_categories: Option<String>,
+
+    /// Write unparseable exchange messages to this file (JSON lines)
+    #[arg(long)]
+    pub dead_letter_path: Option<String>,
+
+    /// Report venue lead/lag for book ticker moves matched within this window
+    #[arg(long)]
+    pub lead_lag_window_ms: Option<u64>,
+
+    /// Score venues for wash-trade patterns over windows of this many seconds
+    #[arg(long)]
+    pub wash_trade_window_secs: Option<u64>,
+
+    /// Emit book depth profiles around the mid at most this often per symbol
+    #[arg(long)]
+    pub depth_profile_secs: Option<u64>,
+
+    /// Flag trades and bursts above this percentile of recent trade sizes
+    #[arg(long)]
+    pub large_print_percentile: Option<f64>,
+
+    /// Publish a fair mid for book tickers whose thinner top-of-book side is
+    /// worth less than this notional
+    #[arg(long)]
+    pub fair_mid_min_notional: Option<f64>,
+
+    /// Merge order book diffs of the same symbol arriving within this many
+    /// milliseconds into one
+    #[arg(long)]
+    pub conflate_ms: Option<u64>,
+
+    /// Numeric encoding of prices and quantities (decimal, fixed)
+    #[arg(long)]
+    pub numeric_format: Option<String>,
+
+    /// Wire format of the output sink (json, proto)
+    #[arg(long, value_enum)]
+    pub encoding: Option<Encoding>,
+
     /// Enable trade feeds
     #[arg(long)]
     pub trades: bool,
@@ -72,12 +137,27 @@ pub struct Cli {
     #[arg(long)]
     pub telemetry: bool,
 
+    /// Canonicalize symbols inside the ingestor instead of piping events
+    /// through a `canonicalizer` child process
+    #[arg(long)]
+    pub in_process_canonicalizer: bool,
+
+    /// Check canonicalized pairs against exchange listings and report those
+    /// that fail as unmapped
+    #[arg(long)]
+    pub validate_symbols: bool,
+
+    /// Stamp schema version, event id, ingest time and source sequence on
+    /// every event
+    #[arg(long)]
+    pub envelope: bool,
+
     /// Agent specifications (e.g. binance:btcusdt)
     pub specs: Vec<String>,
 }
 
 /// Application configuration loaded from file and environment
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Settings {
     pub binance_ws_url: String,
     pub binance_refresh_interval_mins: u64,
@@ -87,6 +167,10 @@ pub struct Settings {
     #[serde(default)]
     pub binance_futures_ws_url: Option<String>,
     #[serde(default)]
+    pub binance_coinm_rest_url: Option<String>,
+    #[serde(default)]
+    pub binance_coinm_ws_url: Option<String>,
+    #[serde(default)]
     pub binance_options_rest_url: String,
     #[serde(default)]
     pub binance_options_symbols: Vec<String>,
@@ -104,17 +188,137 @@ pub struct Settings {
     #[serde(default = "default_coinbase_ohlcv_poll_interval_secs")]
     pub coinbase_ohlcv_poll_interval_secs: u64,
     #[serde(default)]
-    pub binance_api_key: Option<String>,
+    pub bybit_ws_url: String,
     #[serde(default)]
-    pub binance_api_secret: Option<String>,
+    pub bybit_rest_url: String,
+    #[serde(default = "default_bybit_orderbook_depth")]
+    pub bybit_orderbook_depth: u32,
+    #[serde(default = "default_bybit_max_reconnect_delay_secs")]
+    pub bybit_max_reconnect_delay_secs: u64,
     #[serde(default)]
-    pub coinbase_api_key: Option<String>,
+    pub gate_ws_url: String,
+    #[serde(default)]
+    pub gate_rest_url: String,
+    #[serde(default = "default_gate_max_reconnect_delay_secs")]
+    pub gate_max_reconnect_delay_secs: u64,
+    #[serde(default = "default_gate_refresh_interval_mins")]
+    pub gate_refresh_interval_mins: u64,
+    #[serde(default)]
+    pub hyperliquid_ws_url: String,
+    #[serde(default)]
+    pub hyperliquid_rest_url: String,
+    #[serde(default = "default_hyperliquid_max_reconnect_delay_secs")]
+    pub hyperliquid_max_reconnect_delay_secs: u64,
+    #[serde(default)]
+    pub kucoin_rest_url: String,
+    #[serde(default = "default_kucoin_max_reconnect_delay_secs")]
+    pub kucoin_max_reconnect_delay_secs: u64,
+    #[serde(default)]
+    pub mexc_ws_url: String,
+    #[serde(default)]
+    pub mexc_rest_url: String,
+    #[serde(default = "default_mexc_max_reconnect_delay_secs")]
+    pub mexc_max_reconnect_delay_secs: u64,
+    #[serde(default)]
+    pub okx_ws_url: String,
+    #[serde(default)]
+    pub okx_rest_url: String,
+    #[serde(default = "default_okx_book_channel")]
+    pub okx_book_channel: String,
+    #[serde(default = "default_okx_max_reconnect_delay_secs")]
+    pub okx_max_reconnect_delay_secs: u64,
+    #[serde(default = "default_okx_refresh_interval_mins")]
+    pub okx_refresh_interval_mins: u64,
+    #[serde(default = "default_okx_options_poll_interval_secs")]
+    pub okx_options_poll_interval_secs: u64,
+    #[serde(default = "default_subscription_resync_secs")]
+    pub subscription_resync_secs: u64,
+    #[serde(default = "default_funding_window_mins")]
+    pub funding_window_mins: u64,
     #[serde(default)]
+    pub funding_hours: BTreeMap<String, Vec<u32>>,
+    #[serde(default = "default_new_listing_window_mins")]
+    pub new_listing_window_mins: u64,
+    #[serde(default)]
+    pub deribit_ws_url: String,
+    #[serde(default)]
+    pub deribit_rest_url: String,
+    #[serde(default = "default_deribit_max_reconnect_delay_secs")]
+    pub deribit_max_reconnect_delay_secs: u64,
+    #[serde(default)]
+    pub deribit_history_url: String,
+    #[serde(default = "default_deribit_backfill_days")]
+    pub deribit_backfill_days: u64,
+    #[serde(default, skip_serializing)]
+    pub binance_api_key: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub binance_api_secret: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub coinbase_api_key: Option<String>,
+    #[serde(default, skip_serializing)]
     pub coinbase_api_secret: Option<String>,
     #[serde(default = "default_sink")]
     pub sink: String,
     #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    #[serde(default)]
     pub file_path: Option<String>,
+    #[serde(default)]
+    pub deployment: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub sample_every: Option<u64>,
+    #[serde(default)]
+    pub symbol_overrides: Option<String>,
+    #[serde(default)]
+    pub universe_categories: Option<String>,
+    #[serde(default)]
+    pub dead_letter_path: Option<String>,
+    #[serde(default = "default_dead_letter_sample_every")]
+    pub dead_letter_sample_every: u64,
+    #[serde(default)]
+    pub lead_lag_window_ms: Option<u64>,
+    #[serde(default = "default_lead_lag_report_secs")]
+    pub lead_lag_report_secs: u64,
+    #[serde(default)]
+    pub wash_trade_window_secs: Option<u64>,
+    #[serde(default)]
+    pub depth_profile_secs: Option<u64>,
+    #[serde(default = "default_depth_profile_bands_bps")]
+    pub depth_profile_bands_bps: Vec<f64>,
+    #[serde(default)]
+    pub large_print_percentile: Option<f64>,
+    #[serde(default = "default_large_print_window")]
+    pub large_print_window: usize,
+    #[serde(default = "default_large_print_burst_ms")]
+    pub large_print_burst_ms: i64,
+    #[serde(default)]
+    pub fair_mid_min_notional: Option<f64>,
+    #[serde(default = "default_fair_mid_window_ms")]
+    pub fair_mid_window_ms: i64,
+    #[serde(default)]
+    pub conflate_ms: Option<u64>,
+    #[serde(default = "default_numeric_format")]
+    pub numeric_format: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default)]
+    pub freshness_slos_ms: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub quote_groups: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub transforms: Vec<TransformConfig>,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default = "default_http_timeout_secs")]
+    pub http_timeout_secs: u64,
+    #[serde(default = "default_ws_idle_timeout_secs")]
+    pub ws_idle_timeout_secs: u64,
+    #[serde(default)]
+    pub tls_ca_file: Option<String>,
+    #[serde(default)]
+    pub tls_server_names: Vec<TlsServerName>,
 
     #[serde(default)]
     pub trades: bool,
@@ -142,12 +346,29 @@ pub struct Settings {
     pub news_headlines: bool,
     #[serde(default)]
     pub telemetry: bool,
+    #[serde(default)]
+    pub in_process_canonicalizer: bool,
+    #[serde(default)]
+    pub validate_symbols: bool,
+    #[serde(default)]
+    pub envelope: bool,
+}
+
+/// TLS server name (SNI) to present when connecting to `host`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TlsServerName {
+    pub host: String,
+    pub server_name: String,
 }
 
 fn default_sink() -> String {
     "stdout".into()
 }
 
+fn default_numeric_format() -> String {
+    "decimal".into()
+}
+
 fn default_binance_options_poll_interval_secs() -> u64 {
     60
 }
@@ -160,6 +381,106 @@ fn default_coinbase_ohlcv_poll_interval_secs() -> u64 {
     60
 }
 
+fn default_bybit_orderbook_depth() -> u32 {
+    50
+}
+
+fn default_bybit_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_gate_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_gate_refresh_interval_mins() -> u64 {
+    60
+}
+
+fn default_hyperliquid_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_kucoin_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_mexc_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_okx_book_channel() -> String {
+    "books5".into()
+}
+
+fn default_okx_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_okx_refresh_interval_mins() -> u64 {
+    60
+}
+
+fn default_okx_options_poll_interval_secs() -> u64 {
+    60
+}
+
+fn default_subscription_resync_secs() -> u64 {
+    300
+}
+
+fn default_funding_window_mins() -> u64 {
+    10
+}
+
+fn default_new_listing_window_mins() -> u64 {
+    30
+}
+
+fn default_deribit_max_reconnect_delay_secs() -> u64 {
+    30
+}
+
+fn default_deribit_backfill_days() -> u64 {
+    30
+}
+
+fn default_dead_letter_sample_every() -> u64 {
+    1
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_http_timeout_secs() -> u64 {
+    30
+}
+
+fn default_ws_idle_timeout_secs() -> u64 {
+    60
+}
+
+fn default_lead_lag_report_secs() -> u64 {
+    60
+}
+
+fn default_depth_profile_bands_bps() -> Vec<f64> {
+    vec![5.0, 10.0, 25.0, 50.0]
+}
+
+fn default_large_print_window() -> usize {
+    1000
+}
+
+fn default_large_print_burst_ms() -> i64 {
+    50
+}
+
+fn default_fair_mid_window_ms() -> i64 {
+    5000
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -168,6 +489,8 @@ impl Default for Settings {
             binance_max_reconnect_delay_secs: 30,
             binance_futures_rest_url: None,
             binance_futures_ws_url: None,
+            binance_coinm_rest_url: None,
+            binance_coinm_ws_url: None,
             binance_options_rest_url: String::new(),
             binance_options_symbols: Vec::new(),
             binance_options_poll_interval_secs: 60,
@@ -178,12 +501,72 @@ impl Default for Settings {
             coinbase_max_reconnect_delay_secs: 30,
             coinbase_ohlcv_intervals: Vec::new(),
             coinbase_ohlcv_poll_interval_secs: 60,
+            bybit_ws_url: String::new(),
+            bybit_rest_url: String::new(),
+            bybit_orderbook_depth: default_bybit_orderbook_depth(),
+            bybit_max_reconnect_delay_secs: default_bybit_max_reconnect_delay_secs(),
+            gate_ws_url: String::new(),
+            gate_rest_url: String::new(),
+            gate_max_reconnect_delay_secs: default_gate_max_reconnect_delay_secs(),
+            gate_refresh_interval_mins: default_gate_refresh_interval_mins(),
+            hyperliquid_ws_url: String::new(),
+            hyperliquid_rest_url: String::new(),
+            hyperliquid_max_reconnect_delay_secs: default_hyperliquid_max_reconnect_delay_secs(),
+            kucoin_rest_url: String::new(),
+            kucoin_max_reconnect_delay_secs: default_kucoin_max_reconnect_delay_secs(),
+            mexc_ws_url: String::new(),
+            mexc_rest_url: String::new(),
+            mexc_max_reconnect_delay_secs: default_mexc_max_reconnect_delay_secs(),
+            okx_ws_url: String::new(),
+            okx_rest_url: String::new(),
+            okx_book_channel: default_okx_book_channel(),
+            okx_max_reconnect_delay_secs: default_okx_max_reconnect_delay_secs(),
+            okx_refresh_interval_mins: default_okx_refresh_interval_mins(),
+            okx_options_poll_interval_secs: default_okx_options_poll_interval_secs(),
+            subscription_resync_secs: default_subscription_resync_secs(),
+            funding_window_mins: default_funding_window_mins(),
+            funding_hours: BTreeMap::new(),
+            new_listing_window_mins: default_new_listing_window_mins(),
+            deribit_ws_url: String::new(),
+            deribit_rest_url: String::new(),
+            deribit_max_reconnect_delay_secs: default_deribit_max_reconnect_delay_secs(),
+            deribit_history_url: String::new(),
+            deribit_backfill_days: default_deribit_backfill_days(),
             binance_api_key: None,
             binance_api_secret: None,
             coinbase_api_key: None,
             coinbase_api_secret: None,
             sink: default_sink(),
+            sinks: Vec::new(),
             file_path: None,
+            deployment: None,
+            tenant: None,
+            sample_every: None,
+            symbol_overrides: None,
+            universe_categories: None,
+            dead_letter_path: None,
+            dead_letter_sample_every: default_dead_letter_sample_every(),
+            lead_lag_window_ms: None,
+            lead_lag_report_secs: default_lead_lag_report_secs(),
+            wash_trade_window_secs: None,
+            depth_profile_secs: None,
+            depth_profile_bands_bps: default_depth_profile_bands_bps(),
+            large_print_percentile: None,
+            large_print_window: default_large_print_window(),
+            large_print_burst_ms: default_large_print_burst_ms(),
+            fair_mid_min_notional: None,
+            fair_mid_window_ms: default_fair_mid_window_ms(),
+            conflate_ms: None,
+            numeric_format: default_numeric_format(),
+            encoding: Encoding::default(),
+            freshness_slos_ms: BTreeMap::new(),
+            quote_groups: BTreeMap::new(),
+            transforms: Vec::new(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            http_timeout_secs: default_http_timeout_secs(),
+            ws_idle_timeout_secs: default_ws_idle_timeout_secs(),
+            tls_ca_file: None,
+            tls_server_names: Vec::new(),
             trades: false,
             l2_diffs: false,
             l2_snapshots: false,
@@ -197,6 +580,9 @@ impl Default for Settings {
             top_dex_pools: false,
             news_headlines: false,
             telemetry: false,
+            in_process_canonicalizer: false,
+            validate_symbols: false,
+            envelope: false,
         }
     }
 }
@@ -209,6 +595,8 @@ impl Settings {
             .set_default("binance_max_reconnect_delay_secs", 30)?
             .set_default("binance_futures_rest_url", "https://fapi.binance.com")?
             .set_default("binance_futures_ws_url", "wss://fstream.binance.com")?
+            .set_default("binance_coinm_rest_url", "https://dapi.binance.com")?
+            .set_default("binance_coinm_ws_url", "wss://dstream.binance.com")?
             .set_default(
                 "binance_options_rest_url",
                 "https://eapi.binance.us/eapi/v1",
@@ -224,7 +612,48 @@ impl Settings {
             .set_default("coinbase_max_reconnect_delay_secs", 30)?
             .set_default("coinbase_ohlcv_poll_interval_secs", 60)?
             .set_default("coinbase_ohlcv_intervals", vec![60])?
+            .set_default("bybit_ws_url", "wss://stream.bybit.com/v5/public")?
+            .set_default("bybit_rest_url", "https://api.bybit.com")?
+            .set_default("bybit_orderbook_depth", 50)?
+            .set_default("bybit_max_reconnect_delay_secs", 30)?
+            .set_default("gate_ws_url", "wss://api.gateio.ws/ws/v4/")?
+            .set_default("gate_rest_url", "https://api.gateio.ws")?
+            .set_default("gate_max_reconnect_delay_secs", 30)?
+            .set_default("gate_refresh_interval_mins", 60)?
+            .set_default("hyperliquid_ws_url", "wss://api.hyperliquid.xyz/ws")?
+            .set_default("hyperliquid_rest_url", "https://api.hyperliquid.xyz")?
+            .set_default("hyperliquid_max_reconnect_delay_secs", 30)?
+            .set_default("kucoin_rest_url", "https://api.kucoin.com")?
+            .set_default("kucoin_max_reconnect_delay_secs", 30)?
+            .set_default("mexc_ws_url", "wss://wbs.mexc.com/ws")?
+            .set_default("mexc_rest_url", "https://api.mexc.com")?
+            .set_default("mexc_max_reconnect_delay_secs", 30)?
+            .set_default("okx_ws_url", "wss://ws.okx.com:8443/ws/v5/public")?
+            .set_default("okx_rest_url", "https://www.okx.com")?
+            .set_default("okx_book_channel", "books5")?
+            .set_default("okx_max_reconnect_delay_secs", 30)?
+            .set_default("okx_refresh_interval_mins", 60)?
+            .set_default("okx_options_poll_interval_secs", 60)?
+            .set_default("subscription_resync_secs", 300)?
+            .set_default("funding_window_mins", 10)?
+            .set_default("new_listing_window_mins", 30)?
+            .set_default("deribit_ws_url", "wss://www.deribit.com/ws/api/v2")?
+            .set_default("deribit_rest_url", "https://www.deribit.com/api/v2")?
+            .set_default("deribit_max_reconnect_delay_secs", 30)?
+            .set_default("deribit_history_url", "https://history.deribit.com/api/v2")?
+            .set_default("deribit_backfill_days", 30)?
             .set_default("sink", "stdout")?
+            .set_default("dead_letter_sample_every", 1)?
+            .set_default("lead_lag_report_secs", 60)?
+            .set_default("depth_profile_bands_bps", default_depth_profile_bands_bps())?
+            .set_default("large_print_window", 1000)?
+            .set_default("large_print_burst_ms", 50)?
+            .set_default("fair_mid_window_ms", 5000)?
+            .set_default("numeric_format", "decimal")?
+            .set_default("encoding", "json")?
+            .set_default("connect_timeout_secs", 10)?
+            .set_default("http_timeout_secs", 30)?
+            .set_default("ws_idle_timeout_secs", 60)?
             .set_default("trades", false)?
             .set_default("l2_diffs", false)?
             .set_default("l2_snapshots", false)?
@@ -238,6 +667,9 @@ impl Settings {
             .set_default("top_dex_pools", false)?
             .set_default("news_headlines", false)?
             .set_default("telemetry", false)?
+            .set_default("in_process_canonicalizer", false)?
+            .set_default("validate_symbols", false)?
+            .set_default("envelope", false)?
             .add_source(config::Environment::with_prefix("INGESTOR").separator("_"));
         if let Some(path) = &cli.config {
             builder = builder.add_source(config::File::with_name(path));
@@ -249,6 +681,48 @@ impl Settings {
         if let Some(p) = &cli.file_path {
             settings.file_path = Some(p.clone());
         }
+        if let Some(d) = &cli.deployment {
+            settings.deployment = Some(d.clone());
+        }
+        if let Some(t) = &cli.tenant {
+            settings.tenant = Some(t.clone());
+        }
+        if let Some(n) = cli.sample_every {
+            settings.sample_every = Some(n);
+        }
+        if let Some(p) = &cli.symbol_overrides {
+            settings.symbol_overrides = Some(p.clone());
+        }
+        if let Some(p) = &cli.universe_categories {
+            settings.universe_categories = Some(p.clone());
+        }
+        if let Some(p) = &cli.dead_letter_path {
+            settings.dead_letter_path = Some(p.clone());
+        }
+        if let Some(w) = cli.lead_lag_window_ms {
+            settings.lead_lag_window_ms = Some(w);
+        }
+        if let Some(w) = cli.wash_trade_window_secs {
+            settings.wash_trade_window_secs = Some(w);
+        }
+        if let Some(s) = cli.depth_profile_secs {
+            settings.depth_profile_secs = Some(s);
+        }
+        if let Some(p) = cli.large_print_percentile {
+            settings.large_print_percentile = Some(p);
+        }
+        if let Some(n) = cli.fair_mid_min_notional {
+            settings.fair_mid_min_notional = Some(n);
+        }
+        if let Some(ms) = cli.conflate_ms {
+            settings.conflate_ms = Some(ms);
+        }
+        if let Some(f) = &cli.numeric_format {
+            settings.numeric_format = f.clone();
+        }
+        if let Some(e) = cli.encoding {
+            settings.encoding = e;
+        }
         // populate API keys from environment if not set in config
         settings.binance_api_key = settings
             .binance_api_key
@@ -275,9 +749,15 @@ impl Settings {
         settings.top_dex_pools = settings.top_dex_pools || cli.top_dex_pools;
         settings.news_headlines = settings.news_headlines || cli.news_headlines;
         settings.telemetry = settings.telemetry || cli.telemetry;
+        settings.in_process_canonicalizer =
+            settings.in_process_canonicalizer || cli.in_process_canonicalizer;
+        settings.validate_symbols = settings.validate_symbols || cli.validate_symbols;
+        settings.envelope = settings.envelope || cli.envelope;
         settings.binance_futures_rest_url =
             settings.binance_futures_rest_url.filter(|s| !s.is_empty());
         settings.binance_futures_ws_url = settings.binance_futures_ws_url.filter(|s| !s.is_empty());
+        settings.binance_coinm_rest_url = settings.binance_coinm_rest_url.filter(|s| !s.is_empty());
+        settings.binance_coinm_ws_url = settings.binance_coinm_ws_url.filter(|s| !s.is_empty());
         Ok(settings)
     }
 }
@@ -0,0 +1,187 @@
+//! Conflation of order book diffs for slow consumers.
+//!
+//! [`ConflationSink`] holds back `l2_diff` events and merges every diff of
+//! the same exchange and instrument arriving within the window into one: each
+//! price level keeps the quantity of its latest update, and the merged event
+//! carries the fields of the newest diff plus a `conflated` count of the diffs
+//! it replaces. Books are keyed by [`InstrumentKey`], so the spot and perp
+//! books of one symbol on a venue stay apart. Consumers then see at most one
+//! diff per book and window however fast the exchange updates. A book
+//! snapshot flushes the pending diff first so the two stay in order. Diffs
+//! merged away are counted as `conflated` in `ingest_stats`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::InstrumentKey;
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::error::IngestorError;
+use crate::ingest_stats::{self, Counter};
+use crate::sink::{DynSink, OutputSink};
+
+/// Diffs of one book merged so far.
+struct Pending {
+    since: Instant,
+    /// Newest diff, whose levels are replaced by the merged ones.
+    event: Map<String, Value>,
+    bids: Vec<(String, Value)>,
+    asks: Vec<(String, Value)>,
+    count: u64,
+}
+
+impl Pending {
+    fn merge(&mut self, event: Map<String, Value>) {
+        for (side, levels) in [("bids", &mut self.bids), ("asks", &mut self.asks)] {
+            for lvl in event
+                .get(side)
+                .and_then(|l| l.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let Some(price) = lvl.get(0).map(price_key) else {
+                    continue;
+                };
+                match levels.iter_mut().find(|(p, _)| *p == price) {
+                    Some(slot) => slot.1 = lvl.clone(),
+                    None => levels.push((price, lvl.clone())),
+                }
+            }
+        }
+        self.event = event;
+        self.count += 1;
+    }
+
+    fn into_line(mut self) -> String {
+        self.event.insert(
+            "bids".into(),
+            Value::Array(self.bids.into_iter().map(|l| l.1).collect()),
+        );
+        self.event.insert(
+            "asks".into(),
+            Value::Array(self.asks.into_iter().map(|l| l.1).collect()),
+        );
+        if self.count > 1 {
+            self.event.insert("conflated".into(), self.count.into());
+        }
+        Value::Object(self.event).to_string()
+    }
+}
+
+/// Price of a level as a map key, so `"100.0"` and `100.0` match.
+fn price_key(v: &Value) -> String {
+    match v {
+        Value::String(s) => s
+            .parse::<f64>()
+            .map_or_else(|_| s.clone(), |p| p.to_string()),
+        other => other.to_string(),
+    }
+}
+
+/// Sink wrapper merging order book diffs per book and window.
+pub struct ConflationSink {
+    inner: DynSink,
+    window: Duration,
+    pending: Mutex<HashMap<(String, InstrumentKey), Pending>>,
+}
+
+impl ConflationSink {
+    pub fn new(inner: DynSink, window: Duration) -> Self {
+        Self {
+            inner,
+            window,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Emit every merged diff older than the window, or all of them with
+    /// `all`.
+    pub async fn flush(&self, all: bool) -> Result<(), IngestorError> {
+        let due: Vec<Pending> = {
+            let mut pending = self.pending.lock().await;
+            let keys: Vec<_> = pending
+                .iter()
+                .filter(|(_, p)| all || p.since.elapsed() >= self.window)
+                .map(|(k, _)| k.clone())
+                .collect();
+            keys.iter().filter_map(|k| pending.remove(k)).collect()
+        };
+        for p in due {
+            self.inner.send(&p.into_line()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutputSink for ConflationSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        let Ok(v) = serde_json::from_str::<Value>(line) else {
+            return self.inner.send(line).await;
+        };
+        let field = |k: &str| v.get(k).and_then(|v| v.as_str()).map(str::to_string);
+        let (Some(kind), Some(agent), Some(instrument)) =
+            (field("type"), field("agent"), InstrumentKey::from_event(&v))
+        else {
+            return self.inner.send(line).await;
+        };
+        let Value::Object(event) = v else {
+            return self.inner.send(line).await;
+        };
+        let key = (agent, instrument);
+        match kind.as_str() {
+            "l2_diff" => {
+                let mut pending = self.pending.lock().await;
+                match pending.get_mut(&key) {
+                    Some(p) => {
+                        p.merge(event);
+                        ingest_stats::record(&key.0, Counter::Conflated);
+                    }
+                    None => {
+                        let mut p = Pending {
+                            since: Instant::now(),
+                            event: Map::new(),
+                            bids: Vec::new(),
+                            asks: Vec::new(),
+                            count: 0,
+                        };
+                        p.merge(event);
+                        pending.insert(key, p);
+                    }
+                }
+                Ok(())
+            }
+            "snapshot" => {
+                let held = self.pending.lock().await.remove(&key);
+                if let Some(p) = held {
+                    self.inner.send(&p.into_line()).await?;
+                }
+                self.inner.send(line).await
+            }
+            _ => self.inner.send(line).await,
+        }
+    }
+}
+
+/// Flush merged diffs as their windows close until shutdown. Diffs still held
+/// then are flushed by the caller once the canonicalizer has drained.
+pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: Arc<ConflationSink>) {
+    let every = (sink.window / 2).max(Duration::from_millis(1));
+    let mut interval = tokio::time::interval(every);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(e) = sink.flush(false).await {
+                    tracing::error!(error=%e, "failed to flush conflated diffs");
+                }
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
+    }
+}
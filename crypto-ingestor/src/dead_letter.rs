@@ -0,0 +1,95 @@
+//! Dead-letter queue for exchange messages that could not be parsed.
+//!
+//! Agents call [`record`] with the raw payload and the parse error, and
+//! [`record_no_event`] for well-formed data messages their parser could not
+//! turn into an event, e.g. an unknown event type or a missing symbol. Every
+//! failure is counted; when a queue has been installed with [`init`], a sample
+//! of them is written to its sink as JSON lines so exchange schema changes can
+//! be diagnosed and the payloads reprocessed later.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use tokio::sync::mpsc;
+
+use crate::ingest_stats::{self, Counter};
+use crate::sink::DynSink;
+
+/// Dead letters buffered for the writer before new ones are dropped.
+const QUEUE_CAPACITY: usize = 1024;
+
+static QUEUE: OnceLock<DeadLetterQueue> = OnceLock::new();
+static FAILURES: AtomicU64 = AtomicU64::new(0);
+
+struct DeadLetterQueue {
+    tx: mpsc::Sender<String>,
+    sample_every: u64,
+}
+
+/// Install the dead-letter queue, writing one in `sample_every` failures to
+/// `sink`. Must be called from within a Tokio runtime.
+pub fn init(sink: DynSink, sample_every: u64) {
+    let (tx, mut rx) = mpsc::channel::<String>(QUEUE_CAPACITY);
+    let queue = DeadLetterQueue {
+        tx,
+        sample_every: sample_every.max(1),
+    };
+    if QUEUE.set(queue).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if let Err(e) = sink.send(&line).await {
+                tracing::error!(error=%e, "dead letter sink error");
+            }
+        }
+    });
+}
+
+/// Record a message from `agent` that failed to parse.
+pub fn record(agent: &str, raw: &str, error: &dyn std::fmt::Display) {
+    record_in(agent, None, raw, error);
+}
+
+/// Like [`record`], for agents whose streams need different parsers, such as
+/// Bybit's spot and linear categories. `stream` is kept on the dead letter so
+/// it can be reprocessed with the right one.
+pub fn record_in(agent: &str, stream: Option<&str>, raw: &str, error: &dyn std::fmt::Display) {
+    let n = FAILURES.fetch_add(1, Ordering::Relaxed) + 1;
+    ingest_stats::record(agent, Counter::Unparseable);
+    tracing::debug!(%agent, %error, "unparseable message");
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    if !(n - 1).is_multiple_of(queue.sample_every) {
+        return;
+    }
+    let mut line = serde_json::json!({
+        "agent": agent,
+        "type": "dead_letter",
+        "error": error.to_string(),
+        "raw": raw,
+        "ts": chrono::Utc::now().timestamp_millis(),
+    });
+    if let Some(stream) = stream {
+        line["stream"] = stream.into();
+    }
+    if queue.tx.try_send(line.to_string()).is_err() {
+        tracing::warn!(%agent, "dead letter queue full; dropping");
+    }
+}
+
+/// Record a well-formed data message from `agent` that yielded no event.
+pub fn record_no_event(agent: &str, raw: &str) {
+    record_no_event_in(agent, None, raw);
+}
+
+/// Like [`record_no_event`], keeping the `stream` as [`record_in`] does.
+pub fn record_no_event_in(agent: &str, stream: Option<&str>, raw: &str) {
+    record_in(agent, stream, raw, &"data message yielded no event");
+}
+
+/// Total number of unparseable messages seen by this process.
+pub fn failures() -> u64 {
+    FAILURES.load(Ordering::Relaxed)
+}
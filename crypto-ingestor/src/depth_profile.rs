@@ -0,0 +1,134 @@
+//! Order book depth profiles for liquidity heatmaps.
+//!
+//! [`DepthProfileSink`] watches `snapshot` events on their way to the output
+//! sink. At most once per interval for each exchange and instrument it sums the
+//! resting quantity within each configured distance of the mid, e.g. 5, 10, 25
+//! and 50 bps, and emits a [`DepthProfile`] event. Stored over time, the
+//! profiles chart how liquidity near the price thickens and thins.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::{AssetClass, DepthBand, DepthProfile, InstrumentKey};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Sink wrapper emitting periodic depth profiles of book snapshots.
+pub struct DepthProfileSink {
+    inner: DynSink,
+    bands_bps: Vec<f64>,
+    every_ms: i64,
+    /// Book timestamp of the last profile per (exchange, instrument).
+    last: Mutex<HashMap<(String, InstrumentKey), i64>>,
+}
+
+impl DepthProfileSink {
+    pub fn new(inner: DynSink, mut bands_bps: Vec<f64>, every: Duration) -> Self {
+        bands_bps.retain(|b| *b > 0.0);
+        bands_bps.sort_by(f64::total_cmp);
+        bands_bps.dedup();
+        Self {
+            inner,
+            bands_bps,
+            every_ms: every.as_millis() as i64,
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The profile of `line` if it is a snapshot due for one.
+    async fn observe(&self, line: &str) -> Option<DepthProfile> {
+        let v = serde_json::from_str::<Value>(line).ok()?;
+        if v.get("type").and_then(|t| t.as_str()) != Some("snapshot") {
+            return None;
+        }
+        let agent = v.get("agent")?.as_str()?;
+        let instrument = InstrumentKey::from_event(&v)?;
+        let ts = v.get("ts")?.as_i64()?;
+        {
+            let mut last = self.last.lock().await;
+            let key = (agent.to_string(), instrument.clone());
+            if last.get(&key).is_some_and(|t| ts - t < self.every_ms) {
+                return None;
+            }
+            last.insert(key, ts);
+        }
+        let (mid, bands) = profile(&levels(&v, "bids"), &levels(&v, "asks"), &self.bands_bps)?;
+        Some(DepthProfile {
+            agent: agent.to_string(),
+            r#type: "depth_profile".to_string(),
+            symbol: instrument.symbol.to_string(),
+            mid,
+            bands,
+            ac: (instrument.class != AssetClass::Spot).then_some(instrument.class),
+            settle: v.get("settle").and_then(|s| s.as_str()).map(str::to_string),
+            id: v.get("id").and_then(|s| s.as_str()).map(str::to_string),
+            timestamp: ts,
+        })
+    }
+}
+
+/// `(price, qty)` pairs of one book side.
+fn levels(v: &Value, side: &str) -> Vec<(f64, f64)> {
+    v.get(side)
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|lvl| {
+            let num = |i: usize| lvl.get(i)?.as_str()?.parse::<f64>().ok();
+            Some((num(0)?, num(1)?))
+        })
+        .collect()
+}
+
+/// Mid price and cumulative depth per band. `None` for a one-sided or
+/// crossed book.
+fn profile(
+    bids: &[(f64, f64)],
+    asks: &[(f64, f64)],
+    bands_bps: &[f64],
+) -> Option<(f64, Vec<DepthBand>)> {
+    let best_bid = bids.iter().map(|l| l.0).fold(f64::NEG_INFINITY, f64::max);
+    let best_ask = asks.iter().map(|l| l.0).fold(f64::INFINITY, f64::min);
+    if !best_bid.is_finite() || !best_ask.is_finite() || best_bid > best_ask {
+        return None;
+    }
+    let mid = (best_bid + best_ask) / 2.0;
+    let within = |side: &[(f64, f64)], bps: f64| {
+        side.iter()
+            .filter(|(px, _)| (px - mid).abs() / mid * 1e4 <= bps)
+            .fold((0.0, 0.0), |(qty, notional), (px, q)| {
+                (qty + q, notional + px * q)
+            })
+    };
+    let bands = bands_bps
+        .iter()
+        .map(|&bps| {
+            let (bid_qty, bid_notional) = within(bids, bps);
+            let (ask_qty, ask_notional) = within(asks, bps);
+            DepthBand {
+                bps,
+                bid_qty,
+                ask_qty,
+                bid_notional,
+                ask_notional,
+            }
+        })
+        .collect();
+    Some((mid, bands))
+}
+
+#[async_trait]
+impl OutputSink for DepthProfileSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        let profile = self.observe(line).await;
+        self.inner.send(line).await?;
+        if let Some(p) = profile {
+            self.inner.send(&serde_json::to_string(&p).unwrap()).await?;
+        }
+        Ok(())
+    }
+}
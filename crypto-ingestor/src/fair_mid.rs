@@ -0,0 +1,169 @@
+//! Liquidity-adjusted mid prices for thin books.
+//!
+//! [`FairMidSink`] adds a `mid` to every `book_ticker` event. On a liquid
+//! book it is the plain top-of-book mid, tagged `"mid_kind": "top"`. When the
+//! smaller side of the top of book is worth less than the configured notional,
+//! a single lot quoted in or pulled out moves that mid around, so the sink
+//! instead publishes a fair mid tagged `"mid_kind": "fair"`: the median of the
+//! last trade price, the size-weighted micro-price and the consolidated mid
+//! across venues, as sampled over the last `window_ms`. Spread detectors
+//! reading `mid` then stop firing on quote flicker in the long tail.
+//!
+//! With [`QuoteGroups`], the consolidated mid spans every venue quoting an
+//! equivalent asset, e.g. Coinbase `BTC-USD` and Binance `BTC-USDT`, and the
+//! event records the group as `quote_group`. Quotes and trades are kept per
+//! instrument, so a perpetual only consolidates with other perpetuals and its
+//! trades never stand in for the spot market's last trade.
+
+use std::collections::{HashMap, VecDeque};
+
+use async_trait::async_trait;
+use canonicalizer::{AssetClass, InstrumentKey, QuoteGroups};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Top of book of one venue.
+struct Quote {
+    bid: f64,
+    ask: f64,
+    ts: i64,
+}
+
+/// Fair mid inputs of one venue and instrument.
+#[derive(Default)]
+struct Market {
+    last_trade: Option<(i64, f64)>,
+    /// `(ts, price)` of every input sampled while the book was thin.
+    samples: VecDeque<(i64, f64)>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Latest quote per instrument, keyed by quote group and asset class,
+    /// and venue and original instrument, as one venue can list several
+    /// symbols of a group.
+    quotes: HashMap<(String, AssetClass), HashMap<(String, InstrumentKey), Quote>>,
+    markets: HashMap<(String, InstrumentKey), Market>,
+}
+
+/// Sink wrapper adding top-of-book or fair mids to `book_ticker` events.
+pub struct FairMidSink {
+    inner: DynSink,
+    min_notional: f64,
+    window_ms: i64,
+    groups: QuoteGroups,
+    state: Mutex<State>,
+}
+
+impl FairMidSink {
+    pub fn new(inner: DynSink, min_notional: f64, window_ms: i64) -> Self {
+        Self {
+            inner,
+            min_notional,
+            window_ms: window_ms.max(1),
+            groups: QuoteGroups::default(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Consolidate quotes across venues by their [`QuoteGroups`] key.
+    pub fn with_quote_groups(mut self, groups: QuoteGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// `line` with its mid added, if it is a book ticker.
+    async fn observe(&self, line: &str) -> Option<String> {
+        let mut v = serde_json::from_str::<Value>(line).ok()?;
+        let kind = v.get("type")?.as_str()?;
+        if kind != "trade" && kind != "book_ticker" {
+            return None;
+        }
+        let num = |k: &str| match v.get(k)? {
+            Value::String(s) => s.parse::<f64>().ok(),
+            n => n.as_f64(),
+        };
+        let agent = v.get("agent")?.as_str()?.to_string();
+        let instrument = InstrumentKey::from_event(&v)?;
+        let ts = v.get("ts")?.as_i64()?;
+
+        let mut state = self.state.lock().await;
+        let State { quotes, markets } = &mut *state;
+        let market = markets
+            .entry((agent.clone(), instrument.clone()))
+            .or_default();
+        if kind == "trade" {
+            market.last_trade = Some((ts, num("p")?));
+            return None;
+        }
+
+        let (bid, ask) = (num("bp")?, num("ap")?);
+        let (bid_qty, ask_qty) = (num("bq")?, num("aq")?);
+        if bid <= 0.0 || ask < bid {
+            return None;
+        }
+        let (key, group) = self.groups.key(&instrument.symbol);
+        let venues = quotes.entry((key, instrument.class)).or_default();
+        venues.insert((agent, instrument), Quote { bid, ask, ts });
+
+        let thin = (bid * bid_qty).min(ask * ask_qty) < self.min_notional;
+        let (mid, mid_kind) = if thin {
+            let since = ts - self.window_ms;
+            let fresh = || venues.values().filter(|q| q.ts >= since);
+            let best_bid = fresh().map(|q| q.bid).fold(f64::NEG_INFINITY, f64::max);
+            let best_ask = fresh().map(|q| q.ask).fold(f64::INFINITY, f64::min);
+            if best_bid <= best_ask {
+                market.samples.push_back((ts, (best_bid + best_ask) / 2.0));
+            }
+            if bid_qty + ask_qty > 0.0 {
+                let micro = (bid * ask_qty + ask * bid_qty) / (bid_qty + ask_qty);
+                market.samples.push_back((ts, micro));
+            }
+            if let Some((_, price)) = market.last_trade.filter(|(t, _)| *t >= since) {
+                market.samples.push_back((ts, price));
+            }
+            while market.samples.front().is_some_and(|(t, _)| *t < since) {
+                market.samples.pop_front();
+            }
+            (
+                median(market.samples.iter().map(|s| s.1).collect())?,
+                "fair",
+            )
+        } else {
+            market.samples.clear();
+            ((bid + ask) / 2.0, "top")
+        };
+        v["mid"] = mid.into();
+        v["mid_kind"] = mid_kind.into();
+        if let Some(g) = group {
+            v["quote_group"] = g.name.as_str().into();
+        }
+        Some(v.to_string())
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    Some(if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    })
+}
+
+#[async_trait]
+impl OutputSink for FairMidSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        match self.observe(line).await {
+            Some(annotated) => self.inner.send(&annotated).await,
+            None => self.inner.send(line).await,
+        }
+    }
+}
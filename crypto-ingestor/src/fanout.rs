@@ -0,0 +1,316 @@
+//! Fan-out to several sinks with independent failure domains.
+//!
+//! Each configured sink gets its own bounded queue and writer task, so a slow
+//! or failing sink neither blocks the others nor the agents: events it cannot
+//! keep up with are dropped and counted. Failed writes are retried with
+//! backoff before being given up on. Configured in the config file, e.g.:
+//!
+//! ```toml
+//! [[sinks]]
+//! name = "archive"
+//! kind = "file"
+//! path = "events.jsonl"
+//! buffer = 100000
+//!
+//! [[sinks]]
+//! kind = "stdout"
+//! sample_every = 100
+//! ```
+//!
+//! `encoding = "proto"` writes a sink's events as length-delimited protobuf
+//! instead of JSON lines.
+//!
+//! `types`, `agents` and `symbols` route events to a sink: it only receives
+//! events matching every list given. Symbol patterns may use `*` wildcards,
+//! e.g. `BTC-*`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::error::IngestorError;
+use crate::ingest_stats;
+use crate::sink::{DynSink, Encoding, FileSink, OutputSink, Sampler, StdoutSink};
+
+/// One entry of the `sinks` config list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SinkConfig {
+    /// Name used in logs and stats; defaults to `kind`.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `stdout` or `file`.
+    pub kind: String,
+    /// Output path for `file` sinks.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Events queued for this sink before new ones are dropped.
+    #[serde(default = "default_buffer")]
+    pub buffer: usize,
+    /// Retries of a failed write before the event is dropped.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubling for each further one.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Only forward 1-in-N events per event type and symbol.
+    #[serde(default)]
+    pub sample_every: Option<u64>,
+    /// Wire format, `json` or `proto`.
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(flatten)]
+    pub route: Route,
+}
+
+/// Routing rules selecting the events a sink receives.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Route {
+    /// Event types routed to this sink; all when empty.
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Agents routed to this sink; all when empty.
+    #[serde(default)]
+    pub agents: Vec<String>,
+    /// Symbol patterns routed to this sink; all when empty.
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+impl Route {
+    /// Whether the routing rules send `event` to this sink. Events without
+    /// the field a rule filters on are not routed by that rule.
+    fn matches(&self, event: Option<&serde_json::Value>) -> bool {
+        let field = |k: &str| event.and_then(|v| v.get(k)).and_then(|v| v.as_str());
+        let allowed = |rules: &[String], k: &str, matches: fn(&str, &str) -> bool| {
+            rules.is_empty() || field(k).is_some_and(|v| rules.iter().any(|r| matches(r, v)))
+        };
+        allowed(&self.types, "type", |r, v| r == v)
+            && allowed(&self.agents, "agent", |r, v| r == v)
+            && allowed(&self.symbols, "s", glob_match)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.agents.is_empty() && self.symbols.is_empty()
+    }
+}
+
+/// Match `s` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = s.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for p in parts {
+        match rest.find(p) {
+            Some(i) => rest = &rest[i + p.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn default_buffer() -> usize {
+    10_000
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+/// Delivery counters of one fan-out branch.
+#[derive(Debug, Default)]
+pub struct BranchStats {
+    /// Events accepted but not yet written or given up on.
+    pub queued: AtomicU64,
+    pub sent: AtomicU64,
+    /// Events dropped because the queue was full.
+    pub dropped: AtomicU64,
+    pub retries: AtomicU64,
+    /// Events given up on after exhausting retries.
+    pub failed: AtomicU64,
+}
+
+struct Branch {
+    name: String,
+    tx: mpsc::Sender<String>,
+    sampler: Option<Sampler>,
+    /// Routing rules, kept only when the sink has any.
+    route: Option<Route>,
+    stats: Arc<BranchStats>,
+}
+
+/// Sink forwarding every event to a set of independently buffered sinks.
+pub struct FanoutSink {
+    branches: Vec<Branch>,
+}
+
+impl FanoutSink {
+    /// Open the sinks described by `configs` and start their writer tasks.
+    /// Must be called from within a Tokio runtime.
+    pub async fn from_config(configs: &[SinkConfig]) -> Result<Self, IngestorError> {
+        let mut sinks = Vec::with_capacity(configs.len());
+        for c in configs {
+            let sink: DynSink = match c.kind.as_str() {
+                "stdout" => Arc::new(StdoutSink::new().with_encoding(c.encoding)),
+                "file" => {
+                    let path = c.path.as_ref().ok_or_else(|| {
+                        IngestorError::Other(format!("file sink {:?} has no path", c.name))
+                    })?;
+                    Arc::new(
+                        FileSink::new(path)
+                            .await
+                            .map_err(IngestorError::Io)?
+                            .with_encoding(c.encoding),
+                    )
+                }
+                other => {
+                    return Err(IngestorError::Other(format!(
+                        "unknown sink type: {}",
+                        other
+                    )));
+                }
+            };
+            sinks.push((c.clone(), sink));
+        }
+        Ok(Self::new(sinks))
+    }
+
+    /// Fan out to already constructed sinks, using each config's name, queue
+    /// size, retry policy and sampling.
+    pub fn new(sinks: Vec<(SinkConfig, DynSink)>) -> Self {
+        let branches = sinks
+            .into_iter()
+            .map(|(c, sink)| {
+                let name = c.name.clone().unwrap_or_else(|| c.kind.clone());
+                let (tx, rx) = mpsc::channel(c.buffer.max(1));
+                let stats = Arc::new(BranchStats::default());
+                tokio::spawn(write_loop(name.clone(), sink, rx, c.clone(), stats.clone()));
+                Branch {
+                    name,
+                    tx,
+                    sampler: c.sample_every.map(Sampler::new),
+                    route: (!c.route.is_empty()).then(|| c.route.clone()),
+                    stats,
+                }
+            })
+            .collect();
+        Self { branches }
+    }
+
+    /// Counters per sink name.
+    pub fn stats(&self) -> Vec<(&str, &BranchStats)> {
+        self.branches
+            .iter()
+            .map(|b| (b.name.as_str(), &*b.stats))
+            .collect()
+    }
+
+    /// Wait up to `timeout` for all queued events to be written.
+    pub async fn flush(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self
+            .branches
+            .iter()
+            .any(|b| b.stats.queued.load(Ordering::Relaxed) > 0)
+        {
+            if tokio::time::Instant::now() >= deadline {
+                tracing::warn!("timed out flushing sinks");
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for FanoutSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        let mut event = None;
+        for b in &self.branches {
+            if let Some(route) = &b.route {
+                let event = event.get_or_insert_with(|| serde_json::from_str(line).ok());
+                if !route.matches(event.as_ref()) {
+                    continue;
+                }
+            }
+            if let Some(sampler) = &b.sampler {
+                if !sampler.hit(line).await {
+                    continue;
+                }
+            }
+            b.stats.queued.fetch_add(1, Ordering::Relaxed);
+            if b.tx.try_send(line.to_string()).is_err() {
+                b.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                let dropped = b.stats.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                ingest_stats::record_drop(line);
+                if dropped.is_power_of_two() {
+                    tracing::warn!(sink=%b.name, dropped, "sink queue full; dropping");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn write_loop(
+    name: String,
+    sink: DynSink,
+    mut rx: mpsc::Receiver<String>,
+    cfg: SinkConfig,
+    stats: Arc<BranchStats>,
+) {
+    while let Some(line) = rx.recv().await {
+        let mut attempt = 0;
+        loop {
+            match sink.send(&line).await {
+                Ok(()) => {
+                    stats.sent.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                Err(e) if attempt < cfg.max_retries => {
+                    stats.retries.fetch_add(1, Ordering::Relaxed);
+                    let delay = cfg.retry_backoff_ms << attempt.min(10);
+                    tracing::warn!(sink=%name, error=%e, attempt, "sink write failed; retrying");
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    stats.failed.fetch_add(1, Ordering::Relaxed);
+                    ingest_stats::record_drop(&line);
+                    tracing::error!(sink=%name, error=%e, "sink write failed; dropping event");
+                    break;
+                }
+            }
+        }
+        stats.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn symbol_patterns_match_wildcards() {
+        assert!(glob_match("BTC-USDT", "BTC-USDT"));
+        assert!(!glob_match("BTC-USDT", "BTC-USDC"));
+        assert!(glob_match("BTC-*", "BTC-USDT"));
+        assert!(glob_match("*-USD*", "ETH-USDC"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("*-EUR", "BTC-USD"));
+        assert!(!glob_match("B*T*X", "BTC"));
+    }
+}
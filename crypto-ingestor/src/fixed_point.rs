@@ -0,0 +1,160 @@
+//! Fixed-point numeric encoding of emitted events.
+//!
+//! By default prices and quantities are decimal strings. [`FixedPointSink`]
+//! rewrites them as integers scaled by a per-event exponent: `"p": "101.25"`
+//! becomes `"p": 10125` with `"pe": -2`, and quantities likewise use `"qe"`.
+//! Book levels share the event's exponents. The exponent follows the tick and
+//! lot sizes from `listing` events where known, widened when a value carries
+//! more decimals, so the encoding is always lossless.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+const PRICE_FIELDS: [&str; 3] = ["p", "bp", "ap"];
+const QTY_FIELDS: [&str; 3] = ["q", "bq", "aq"];
+const LEVEL_FIELDS: [&str; 2] = ["bids", "asks"];
+
+/// Decimal places of the tick and lot size of one market.
+#[derive(Default, Clone, Copy)]
+struct Scales {
+    price: u32,
+    qty: u32,
+}
+
+/// Sink wrapper encoding prices and quantities as scaled integers.
+pub struct FixedPointSink {
+    inner: DynSink,
+    scales: Mutex<HashMap<(String, String), Scales>>,
+}
+
+impl FixedPointSink {
+    pub fn new(inner: DynSink) -> Self {
+        Self {
+            inner,
+            scales: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn encode(&self, map: &mut Map<String, Value>) -> Option<()> {
+        let agent = map.get("agent")?.as_str()?.to_string();
+        let sym = map.get("s")?.as_str()?.to_string();
+        let key = (agent, sym);
+
+        if map.get("type").and_then(|t| t.as_str()) == Some("listing") {
+            let scale = |k: &str| {
+                map.get(k)
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Decimal::from_str(s).ok())
+                    .map_or(0, |d| d.normalize().scale())
+            };
+            let scales = Scales {
+                price: scale("tick_size"),
+                qty: scale("lot_size"),
+            };
+            self.scales.lock().await.insert(key, scales);
+            return None;
+        }
+
+        let mut prices = Vec::new();
+        let mut qtys = Vec::new();
+        for f in PRICE_FIELDS {
+            if let Some(v) = map.get(f) {
+                prices.push(decimal(v)?);
+            }
+        }
+        for f in QTY_FIELDS {
+            if let Some(v) = map.get(f) {
+                qtys.push(decimal(v)?);
+            }
+        }
+        for f in LEVEL_FIELDS {
+            for lvl in map.get(f).and_then(|l| l.as_array()).into_iter().flatten() {
+                prices.push(decimal(lvl.get(0)?)?);
+                qtys.push(decimal(lvl.get(1)?)?);
+            }
+        }
+        if prices.is_empty() && qtys.is_empty() {
+            return None;
+        }
+
+        let known = self
+            .scales
+            .lock()
+            .await
+            .get(&key)
+            .copied()
+            .unwrap_or_default();
+        let pscale = prices.iter().map(|d| d.scale()).fold(known.price, u32::max);
+        let qscale = qtys.iter().map(|d| d.scale()).fold(known.qty, u32::max);
+
+        // Check every value fits before touching the event, so a failure
+        // leaves it intact.
+        for d in &prices {
+            scaled(*d, pscale)?;
+        }
+        for d in &qtys {
+            scaled(*d, qscale)?;
+        }
+        for f in PRICE_FIELDS {
+            if let Some(v) = map.get_mut(f) {
+                *v = scaled(decimal(v)?, pscale)?.into();
+            }
+        }
+        for f in QTY_FIELDS {
+            if let Some(v) = map.get_mut(f) {
+                *v = scaled(decimal(v)?, qscale)?.into();
+            }
+        }
+        for f in LEVEL_FIELDS {
+            if let Some(levels) = map.get_mut(f).and_then(|l| l.as_array_mut()) {
+                for lvl in levels {
+                    let p = scaled(decimal(lvl.get(0)?)?, pscale)?;
+                    let q = scaled(decimal(lvl.get(1)?)?, qscale)?;
+                    *lvl = serde_json::json!([p, q]);
+                }
+            }
+        }
+        if !prices.is_empty() {
+            map.insert("pe".into(), (-(pscale as i64)).into());
+        }
+        if !qtys.is_empty() {
+            map.insert("qe".into(), (-(qscale as i64)).into());
+        }
+        Some(())
+    }
+}
+
+/// Parse a decimal string (or JSON number) without trailing zeros.
+fn decimal(v: &Value) -> Option<Decimal> {
+    canonicalizer::decimal::from_value(v).map(|d| d.normalize())
+}
+
+/// Mantissa of `d` at `scale` decimal places, if it fits in an `i64`.
+fn scaled(mut d: Decimal, scale: u32) -> Option<i64> {
+    d.rescale(scale);
+    if d.scale() != scale {
+        return None;
+    }
+    i64::try_from(d.mantissa()).ok()
+}
+
+#[async_trait]
+impl OutputSink for FixedPointSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        // Events that cannot be encoded losslessly keep their strings.
+        if let Ok(Value::Object(mut map)) = serde_json::from_str::<Value>(line) {
+            if self.encode(&mut map).await.is_some() {
+                return self.inner.send(&Value::Object(map).to_string()).await;
+            }
+        }
+        self.inner.send(line).await
+    }
+}
@@ -0,0 +1,158 @@
+//! Per-event-type freshness SLOs.
+//!
+//! [`FreshnessSink`] keeps the newest timestamp seen per exchange and event
+//! type. [`run`] compares them against the configured SLOs, e.g. trades no
+//! older than 2 seconds, and emits a `firing` [`SloAlert`] when an exchange's
+//! stream falls behind and a `resolved` one when it catches up. Breaches are
+//! also counted in [`SLO_VIOLATIONS`]. Configured in milliseconds per type:
+//!
+//! ```toml
+//! [freshness_slos_ms]
+//! trade = 2000
+//! funding = 90000
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::SloAlert;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Number of SLO breaches since startup.
+pub static SLO_VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Newest event timestamps per exchange and type, checked against the SLOs.
+pub struct Freshness {
+    slos: HashMap<String, u64>,
+    /// Newest timestamp in milliseconds per (agent, event type).
+    last: Mutex<HashMap<(String, String), i64>>,
+    firing: Mutex<HashSet<(String, String)>>,
+}
+
+impl Freshness {
+    /// Track the event types in `slos`, mapping type to allowed lag in ms.
+    pub fn new(slos: HashMap<String, u64>) -> Self {
+        Self {
+            slos,
+            last: Mutex::new(HashMap::new()),
+            firing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Record an event received at `now` (ms). Its own `ts` is used when it
+    /// is newer, so exchange-side delays count towards the lag as well.
+    fn observe(&self, line: &str, now: i64) {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        let (Some(agent), Some(typ)) = (
+            v.get("agent").and_then(|a| a.as_str()),
+            v.get("type").and_then(|t| t.as_str()),
+        ) else {
+            return;
+        };
+        if !self.slos.contains_key(typ) {
+            return;
+        }
+        let ts = v
+            .get("ts")
+            .and_then(|t| t.as_i64())
+            .filter(|ts| *ts > 0)
+            .unwrap_or(now);
+        let mut last = self.last.lock().unwrap();
+        let entry = last
+            .entry((agent.to_string(), typ.to_string()))
+            .or_insert(ts);
+        *entry = (*entry).max(ts);
+    }
+
+    /// Compare every tracked stream with its SLO at `now` (ms), returning
+    /// alerts for streams that started or stopped breaching it. Streams are
+    /// only tracked once they produced an event.
+    pub fn check(&self, now: i64) -> Vec<SloAlert> {
+        let last = self.last.lock().unwrap();
+        let mut firing = self.firing.lock().unwrap();
+        let mut alerts = Vec::new();
+        for (key, ts) in last.iter() {
+            let Some(&slo_ms) = self.slos.get(&key.1) else {
+                continue;
+            };
+            let lag_ms = now - ts;
+            let breached = lag_ms > slo_ms as i64;
+            let state = match (breached, firing.contains(key)) {
+                (true, false) => {
+                    firing.insert(key.clone());
+                    SLO_VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+                    "firing"
+                }
+                (false, true) => {
+                    firing.remove(key);
+                    "resolved"
+                }
+                _ => continue,
+            };
+            alerts.push(SloAlert {
+                agent: key.0.clone(),
+                r#type: "slo_alert".to_string(),
+                event_type: key.1.clone(),
+                state: state.to_string(),
+                lag_ms,
+                slo_ms,
+                timestamp: now,
+            });
+        }
+        alerts.sort_by(|a, b| (&a.agent, &a.event_type).cmp(&(&b.agent, &b.event_type)));
+        alerts
+    }
+}
+
+/// Check the SLOs every `every` and emit alerts to `sink` until shutdown.
+pub async fn run(
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    freshness: Arc<Freshness>,
+    sink: DynSink,
+    every: Duration,
+) {
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                for alert in freshness.check(chrono::Utc::now().timestamp_millis()) {
+                    tracing::warn!(agent=%alert.agent, event_type=%alert.event_type, state=%alert.state, lag_ms=alert.lag_ms, "freshness SLO");
+                    if let Err(e) = sink.send(&serde_json::to_string(&alert).unwrap()).await {
+                        tracing::error!(error=%e, "failed to emit SLO alert");
+                    }
+                }
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
+    }
+}
+
+/// Sink wrapper feeding event timestamps into a [`Freshness`] tracker.
+pub struct FreshnessSink {
+    inner: DynSink,
+    freshness: Arc<Freshness>,
+}
+
+impl FreshnessSink {
+    pub fn new(inner: DynSink, freshness: Arc<Freshness>) -> Self {
+        Self { inner, freshness }
+    }
+}
+
+#[async_trait]
+impl OutputSink for FreshnessSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        self.freshness
+            .observe(line, chrono::Utc::now().timestamp_millis());
+        self.inner.send(line).await
+    }
+}
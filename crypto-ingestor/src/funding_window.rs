@@ -0,0 +1,164 @@
+//! Scheduling around perpetual funding settlements.
+//!
+//! Venues settle funding at fixed UTC hours, which differ by venue and can be
+//! overridden per agent with `funding_hours`. Open interest and basis move
+//! sharply around those times, so pollers ask [`FundingSchedule::phase`]
+//! whether a settlement is near and speed up, and [`run`] announces every
+//! change of phase as a [`FundingWindow`] event.
+
+use std::time::Duration;
+
+use canonicalizer::FundingWindow;
+use tokio::sync::mpsc;
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// Binance USDⓈ-M perpetuals settle every eight hours from midnight UTC.
+pub const BINANCE_FUNDING_HOURS: [u32; 3] = [0, 8, 16];
+
+/// Position relative to the nearest funding settlement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FundingPhase {
+    Idle,
+    /// Within the window before a settlement.
+    Pre,
+    /// Within the window after a settlement.
+    Post,
+}
+
+impl FundingPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FundingPhase::Idle => "idle",
+            FundingPhase::Pre => "pre",
+            FundingPhase::Post => "post",
+        }
+    }
+}
+
+/// Funding settlement times of one venue.
+#[derive(Debug, Clone)]
+pub struct FundingSchedule {
+    agent: &'static str,
+    /// Settlement hours of the day, UTC.
+    hours: Vec<i64>,
+    window_ms: i64,
+}
+
+impl FundingSchedule {
+    pub fn new(agent: &'static str, hours: &[u32], window: Duration) -> Self {
+        let mut hours: Vec<i64> = hours.iter().map(|h| i64::from(*h % 24)).collect();
+        hours.sort_unstable();
+        hours.dedup();
+        Self {
+            agent,
+            hours,
+            window_ms: window.as_millis() as i64,
+        }
+    }
+
+    /// Settlement times from the day before `now_ms` to the day after.
+    fn settlements(&self, now_ms: i64) -> impl Iterator<Item = i64> + '_ {
+        let day = now_ms.div_euclid(DAY_MS) * DAY_MS;
+        [day - DAY_MS, day, day + DAY_MS]
+            .into_iter()
+            .flat_map(move |d| self.hours.iter().map(move |h| d + h * HOUR_MS))
+    }
+
+    /// Phase at `now_ms` and the settlement it refers to: the one being
+    /// approached or just passed, or the next one when idle.
+    pub fn phase(&self, now_ms: i64) -> (FundingPhase, i64) {
+        let mut next = i64::MAX;
+        for s in self.settlements(now_ms) {
+            if now_ms < s && s - now_ms <= self.window_ms {
+                return (FundingPhase::Pre, s);
+            }
+            if now_ms >= s && now_ms - s < self.window_ms {
+                return (FundingPhase::Post, s);
+            }
+            if s > now_ms {
+                next = next.min(s);
+            }
+        }
+        (FundingPhase::Idle, next)
+    }
+
+    /// Time of the first phase change after `now_ms`.
+    fn next_change(&self, now_ms: i64) -> i64 {
+        self.settlements(now_ms)
+            .flat_map(|s| [s - self.window_ms, s, s + self.window_ms])
+            .filter(|t| *t > now_ms)
+            .min()
+            .unwrap_or(now_ms + HOUR_MS)
+    }
+}
+
+/// Emit a [`FundingWindow`] event now and on every phase change until shutdown.
+pub async fn run(
+    schedule: FundingSchedule,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    tx: mpsc::Sender<String>,
+) {
+    loop {
+        let now = chrono::Utc::now().timestamp_millis();
+        let (phase, settlement_ts) = schedule.phase(now);
+        let event = FundingWindow {
+            agent: schedule.agent.to_string(),
+            r#type: "funding_window".to_string(),
+            state: phase.as_str().to_string(),
+            settlement_ts,
+            timestamp: now,
+        };
+        if tx
+            .send(serde_json::to_string(&event).unwrap())
+            .await
+            .is_err()
+        {
+            break;
+        }
+        let wait = Duration::from_millis((schedule.next_change(now) - now).max(1) as u64);
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_surround_each_settlement() {
+        let schedule =
+            FundingSchedule::new("binance", &BINANCE_FUNDING_HOURS, Duration::from_secs(600));
+        let eight = 8 * HOUR_MS;
+        let min = 60_000;
+
+        assert_eq!(
+            schedule.phase(eight - 11 * min),
+            (FundingPhase::Idle, eight)
+        );
+        assert_eq!(schedule.phase(eight - 5 * min), (FundingPhase::Pre, eight));
+        assert_eq!(schedule.phase(eight), (FundingPhase::Post, eight));
+        assert_eq!(schedule.phase(eight + 10 * min).0, FundingPhase::Idle);
+        assert_eq!(schedule.next_change(eight - 11 * min), eight - 10 * min);
+        assert_eq!(schedule.next_change(eight + 3 * min), eight + 10 * min);
+
+        // Midnight settlements roll over to the next day.
+        let late = DAY_MS - 2 * min;
+        assert_eq!(schedule.phase(late), (FundingPhase::Pre, DAY_MS));
+
+        // Venues settling at other hours configure their own.
+        let four_hourly =
+            FundingSchedule::new("binance", &[0, 4, 8, 12, 16, 20], Duration::from_secs(600));
+        assert_eq!(
+            four_hourly.phase(4 * HOUR_MS - 5 * min),
+            (FundingPhase::Pre, 4 * HOUR_MS)
+        );
+        assert_eq!(schedule.phase(4 * HOUR_MS).0, FundingPhase::Idle);
+    }
+}
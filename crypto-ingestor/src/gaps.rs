@@ -0,0 +1,206 @@
+//! Gap detection over captured event history.
+//!
+//! [`GapScanner`] reads canonical event lines in capture order and tracks the
+//! last trade id and timestamp per exchange and instrument, and the last bar
+//! per interval. Spot and derivative streams of one symbol, e.g. Binance
+//! `BTC-USDT` spot and its perpetual, count their trade ids apart. A trade id
+//! that skips ahead is a sequence gap, a trade arriving after more than the
+//! allowed silence is a time gap, and a bar more than one interval after the
+//! previous one means bars are missing. Each finding is a [`Gap`] record, so
+//! the `gaps` binary can report them and backfill missing bars over REST.
+
+use std::collections::HashMap;
+
+use canonicalizer::Bar;
+use serde::Serialize;
+
+use crate::agents::binance::ohlcv::{interval_str, parse_bars, KLINES_WEIGHT};
+use crate::rate_limit::{self, Priority};
+
+/// What a [`Gap`] measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// Trade ids `from..=to` were never seen.
+    Seq,
+    /// No trades between the `from` and `to` timestamps (ms).
+    Time,
+    /// Bars opening from `from` to `to` (ms) are missing.
+    Bars,
+}
+
+/// One hole in the captured history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Gap {
+    pub agent: String,
+    #[serde(rename = "type")]
+    pub r#type: &'static str,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Asset class of a derivative stream, as on its events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ac: Option<String>,
+    /// Contract id of a derivative stream, as on its events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub kind: GapKind,
+    pub from: i64,
+    pub to: i64,
+    /// Missing trades or bars; zero for time gaps.
+    pub missing: i64,
+    /// Bar interval in seconds, for bar gaps.
+    #[serde(rename = "i", skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+}
+
+#[derive(Default)]
+struct Last {
+    id: Option<i64>,
+    ts: Option<i64>,
+}
+
+/// Finds sequence and time gaps in trade and bar events.
+pub struct GapScanner {
+    max_silence_ms: i64,
+    last: HashMap<StreamKey, Last>,
+}
+
+/// Agent, symbol, asset class, contract id and bar interval (zero for
+/// trades) of one event stream.
+type StreamKey = (String, String, Option<String>, Option<String>, u64);
+
+impl GapScanner {
+    /// Report trade streams silent for longer than `max_silence_ms`; zero
+    /// only reports trade id and bar gaps.
+    pub fn new(max_silence_ms: i64) -> Self {
+        Self {
+            max_silence_ms,
+            last: HashMap::new(),
+        }
+    }
+
+    /// Feed one event line, returning the gaps it reveals. Lines other than
+    /// `trade` and `ohlcv` events are ignored, as are out-of-order ids and
+    /// timestamps, which cannot open a gap.
+    pub fn observe(&mut self, line: &str) -> Vec<Gap> {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return Vec::new();
+        };
+        let str_of = |k: &str| v.get(k).and_then(|x| x.as_str());
+        let (Some(agent), Some(typ), Some(sym)) = (str_of("agent"), str_of("type"), str_of("s"))
+        else {
+            return Vec::new();
+        };
+        let ac = str_of("ac").map(str::to_string);
+        let id = str_of("id").map(str::to_string);
+        let ts = v.get("ts").and_then(|t| t.as_i64()).filter(|ts| *ts > 0);
+        let key = |interval| {
+            (
+                agent.to_string(),
+                sym.to_string(),
+                ac.clone(),
+                id.clone(),
+                interval,
+            )
+        };
+        let gap = |kind, from, to, missing, interval| Gap {
+            agent: agent.to_string(),
+            r#type: "gap",
+            symbol: sym.to_string(),
+            ac: ac.clone(),
+            id: id.clone(),
+            kind,
+            from,
+            to,
+            missing,
+            interval,
+        };
+        let mut gaps = Vec::new();
+        match typ {
+            "trade" => {
+                let last = self.last.entry(key(0)).or_default();
+                if let Some(id) = v.get("t").and_then(|t| t.as_i64()) {
+                    if let Some(prev) = last.id.filter(|prev| id > prev + 1) {
+                        gaps.push(gap(GapKind::Seq, prev + 1, id - 1, id - prev - 1, None));
+                    }
+                    last.id = Some(last.id.map_or(id, |prev| prev.max(id)));
+                }
+                if let Some(ts) = ts {
+                    let silence = self.max_silence_ms;
+                    if let Some(prev) = last.ts.filter(|prev| silence > 0 && ts - prev > silence) {
+                        gaps.push(gap(GapKind::Time, prev, ts, 0, None));
+                    }
+                    last.ts = Some(last.ts.map_or(ts, |prev| prev.max(ts)));
+                }
+            }
+            "ohlcv" => {
+                let (Some(ts), Some(interval)) = (ts, v.get("i").and_then(|i| i.as_u64())) else {
+                    return gaps;
+                };
+                let step = interval as i64 * 1000;
+                let last = self.last.entry(key(interval)).or_default();
+                if let Some(prev) = last.ts.filter(|prev| step > 0 && ts - prev > step) {
+                    let missing = (ts - prev) / step - 1;
+                    gaps.push(gap(
+                        GapKind::Bars,
+                        prev + step,
+                        ts - step,
+                        missing,
+                        Some(interval),
+                    ));
+                }
+                last.ts = Some(last.ts.map_or(ts, |prev| prev.max(ts)));
+            }
+            _ => {}
+        }
+        gaps
+    }
+}
+
+/// Fetch the bars opening between `start` and `end` (ms, inclusive), paging
+/// through the klines endpoint. Stops at the first failed request, returning
+/// what was fetched so far.
+pub async fn fetch_binance_bars(
+    client: &reqwest::Client,
+    symbol: &str,
+    interval: u64,
+    start: i64,
+    end: i64,
+) -> Vec<Bar> {
+    const PAGE: usize = 1000;
+    let limiter = rate_limit::binance();
+    let mut bars = Vec::new();
+    let mut from = start;
+    while from <= end {
+        let url = format!(
+            "https://api.binance.us/api/v3/klines?symbol={}&interval={}&startTime={}&endTime={}&limit={}",
+            symbol.to_uppercase(),
+            interval_str(interval),
+            from,
+            end,
+            PAGE
+        );
+        limiter.acquire(KLINES_WEIGHT, Priority::Low).await;
+        let Ok(resp) = client.get(&url).send().await else {
+            break;
+        };
+        limiter.observe(resp.status(), resp.headers());
+        if !resp.status().is_success() {
+            break;
+        }
+        let Ok(v) = resp.json::<serde_json::Value>().await else {
+            break;
+        };
+        let page = parse_bars(symbol, interval, &v);
+        let Some(last) = page.last().map(|b| b.timestamp) else {
+            break;
+        };
+        let full = page.len() == PAGE;
+        bars.extend(page);
+        if !full {
+            break;
+        }
+        from = last + interval as i64 * 1000;
+    }
+    bars
+}
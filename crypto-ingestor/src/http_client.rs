@@ -1,11 +1,184 @@
+//! Shared network settings for REST clients and websocket connections.
+//!
+//! [`init`] installs the connect timeout, request timeout, websocket idle
+//! window and extra trusted roots from the settings once at startup;
+//! [`builder`] and [`connect_ws`] apply them to every HTTP client and
+//! websocket connection made afterwards.
+//! Without `init` the defaults below are used.
+
+use std::io;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
 use reqwest::ClientBuilder;
+use rustls::pki_types::{CertificateDer, ServerName};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+
+use crate::config::Settings;
+use crate::error::IngestorError;
 
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_WS_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct NetConfig {
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    ws_idle_timeout: Duration,
+    /// DER encoded certificates trusted in addition to the bundled roots.
+    ca_certs: Vec<Vec<u8>>,
+    /// TLS server name to present instead of the URL host, by host.
+    server_names: Vec<(String, String)>,
+    /// Websocket TLS config, built only when roots or names are customised.
+    tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            ws_idle_timeout: DEFAULT_WS_IDLE_TIMEOUT,
+            ca_certs: Vec::new(),
+            server_names: Vec::new(),
+            tls: None,
+        }
+    }
+}
+
+static NET: OnceLock<NetConfig> = OnceLock::new();
+
+fn net() -> &'static NetConfig {
+    NET.get_or_init(NetConfig::default)
+}
 
-/// Build a `reqwest::ClientBuilder` configured for the current runtime.
+/// Install the network settings. Only the first call has an effect, and it
+/// must happen before any client or connection is created.
+pub fn init(settings: &Settings) -> Result<(), IngestorError> {
+    let mut ca_certs = Vec::new();
+    if let Some(path) = &settings.tls_ca_file {
+        let pem = std::fs::read(path)?;
+        ca_certs = rustls_pemfile::certs(&mut pem.as_slice())?;
+        if ca_certs.is_empty() {
+            return Err(IngestorError::Other(format!(
+                "no certificates found in {path}"
+            )));
+        }
+    }
+    let server_names: Vec<(String, String)> = settings
+        .tls_server_names
+        .iter()
+        .map(|n| (n.host.to_ascii_lowercase(), n.server_name.clone()))
+        .collect();
+    let tls = if ca_certs.is_empty() && server_names.is_empty() {
+        None
+    } else {
+        Some(Arc::new(tls_config(&ca_certs)?))
+    };
+    let _ = NET.set(NetConfig {
+        connect_timeout: Duration::from_secs(settings.connect_timeout_secs.max(1)),
+        request_timeout: Duration::from_secs(settings.http_timeout_secs.max(1)),
+        ws_idle_timeout: Duration::from_secs(settings.ws_idle_timeout_secs),
+        ca_certs,
+        server_names,
+        tls,
+    });
+    Ok(())
+}
+
+fn tls_config(ca_certs: &[Vec<u8>]) -> Result<rustls::ClientConfig, IngestorError> {
+    let mut roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    for der in ca_certs {
+        roots
+            .add(CertificateDer::from(der.clone()))
+            .map_err(|e| IngestorError::Other(format!("invalid CA certificate: {e}")))?;
+    }
+    Ok(rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Silence after which a websocket connection is considered stalled; zero
+/// when the [`watchdog`](crate::watchdog) is disabled.
+pub fn ws_idle_timeout() -> Duration {
+    net().ws_idle_timeout
+}
+
+/// Build a `reqwest::ClientBuilder` configured for the current runtime, with
+/// the shared connect and request timeouts and any extra trusted roots.
 ///
-/// Certificate verification is disabled in this environment to allow
-/// connections to hosts with self-signed or otherwise untrusted certificates.
-/// **Do not enable this behaviour in production.**
+/// Without extra roots, certificate verification is disabled in this
+/// environment to allow connections to hosts with self-signed or otherwise
+/// untrusted certificates. **Do not enable this behaviour in production.**
+/// With `tls_ca_file` set, certificates are verified against the bundled
+/// roots plus the configured ones, as for websocket connections.
 pub fn builder() -> ClientBuilder {
-    reqwest::Client::builder().danger_accept_invalid_certs(true)
+    let net = net();
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(net.connect_timeout)
+        .timeout(net.request_timeout);
+    if net.ca_certs.is_empty() {
+        return builder.danger_accept_invalid_certs(true);
+    }
+    for der in &net.ca_certs {
+        if let Ok(cert) = reqwest::Certificate::from_der(der) {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    builder
+}
+
+/// Open a websocket connection, giving up once the TCP, TLS and websocket
+/// handshakes together take longer than the connect timeout.
+pub async fn connect_ws(url: &str) -> Result<(WsStream, Response), WsError> {
+    let net = net();
+    match tokio::time::timeout(net.connect_timeout, connect(net, url)).await {
+        Ok(res) => res,
+        Err(_) => Err(WsError::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "websocket connect timed out after {:?}",
+                net.connect_timeout
+            ),
+        ))),
+    }
+}
+
+async fn connect(net: &NetConfig, url: &str) -> Result<(WsStream, Response), WsError> {
+    let Some(tls) = &net.tls else {
+        return tokio_tungstenite::connect_async(url).await;
+    };
+    let request = url.into_client_request()?;
+    let uri = request.uri();
+    let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+    let server_name = net
+        .server_names
+        .iter()
+        .find(|(h, _)| *h == host)
+        .map(|(_, n)| n.clone());
+    let Some(server_name) = server_name.filter(|_| uri.scheme_str() == Some("wss")) else {
+        return tokio_tungstenite::connect_async_tls_with_config(
+            request,
+            None,
+            false,
+            Some(Connector::Rustls(tls.clone())),
+        )
+        .await;
+    };
+    let port = uri.port_u16().unwrap_or(443);
+    let server_name = ServerName::try_from(server_name)
+        .map_err(|e| WsError::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+    let tcp = TcpStream::connect((host.as_str(), port)).await?;
+    let stream = tokio_rustls::TlsConnector::from(tls.clone())
+        .connect(server_name, tcp)
+        .await?;
+    tokio_tungstenite::client_async(request, MaybeTlsStream::Rustls(stream)).await
 }
@@ -0,0 +1,155 @@
+//! Per-minute ingestion statistics written into the data stream.
+//!
+//! [`IngestStatsSink`] counts emitted events per exchange and type, while
+//! agents report unparseable messages, sequence gaps, reconnects and symbols
+//! that could not be canonicalized through [`record`], and sinks report the
+//! events they drop through [`record_drop`]. [`run`] periodically turns the
+//! counters into one [`IngestStats`] event per exchange, so stored datasets
+//! describe their own completeness.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::IngestStats;
+use once_cell::sync::Lazy;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Stream health counters reported by agents.
+#[derive(Debug, Clone, Copy)]
+pub enum Counter {
+    Unparseable,
+    Gap,
+    Reconnect,
+    /// A symbol could not be canonicalized, for the given
+    /// [`CanonicalError::kind`](canonicalizer::CanonicalError::kind).
+    Unmapped(&'static str),
+    /// An order book diff was merged into a later one.
+    Conflated,
+    /// An event was dropped on its way to a sink.
+    Dropped,
+}
+
+#[derive(Default)]
+struct AgentStats {
+    messages: BTreeMap<String, u64>,
+    unparseable: u64,
+    gaps: u64,
+    reconnects: u64,
+    unmapped: BTreeMap<String, u64>,
+    conflated: u64,
+    drops: u64,
+}
+
+static STATS: Lazy<Mutex<HashMap<String, AgentStats>>> = Lazy::new(Default::default);
+
+/// Count one occurrence of `counter` for `agent`.
+pub fn record(agent: &str, counter: Counter) {
+    let mut stats = STATS.lock().unwrap();
+    let entry = stats.entry(agent.to_string()).or_default();
+    match counter {
+        Counter::Unparseable => entry.unparseable += 1,
+        Counter::Gap => entry.gaps += 1,
+        Counter::Reconnect => entry.reconnects += 1,
+        Counter::Unmapped(kind) => *entry.unmapped.entry(kind.to_string()).or_insert(0) += 1,
+        Counter::Conflated => entry.conflated += 1,
+        Counter::Dropped => entry.drops += 1,
+    }
+}
+
+/// Count the event `line` as dropped for the agent that emitted it.
+pub fn record_drop(line: &str) {
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+        return;
+    };
+    if let Some(agent) = v.get("agent").and_then(|a| a.as_str()) {
+        record(agent, Counter::Dropped);
+    }
+}
+
+fn observe(line: &str) {
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+        return;
+    };
+    let (Some(agent), Some(typ)) = (
+        v.get("agent").and_then(|a| a.as_str()),
+        v.get("type").and_then(|t| t.as_str()),
+    ) else {
+        return;
+    };
+    if typ == "ingest_stats" {
+        return;
+    }
+    let mut stats = STATS.lock().unwrap();
+    *stats
+        .entry(agent.to_string())
+        .or_default()
+        .messages
+        .entry(typ.to_string())
+        .or_insert(0) += 1;
+}
+
+/// Take the counters accumulated since the previous call as events.
+pub fn drain(window: Duration) -> Vec<IngestStats> {
+    let stats = std::mem::take(&mut *STATS.lock().unwrap());
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut events: Vec<_> = stats
+        .into_iter()
+        .map(|(agent, s)| IngestStats {
+            agent,
+            r#type: "ingest_stats".to_string(),
+            window_secs: window.as_secs(),
+            messages: s.messages,
+            unparseable: s.unparseable,
+            gaps: s.gaps,
+            reconnects: s.reconnects,
+            unmapped: s.unmapped,
+            conflated: s.conflated,
+            drops: s.drops,
+            timestamp: now,
+        })
+        .collect();
+    events.sort_by(|a, b| a.agent.cmp(&b.agent));
+    events
+}
+
+/// Emit [`IngestStats`] events to `sink` every `every` until shutdown.
+pub async fn run(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink, every: Duration) {
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                for event in drain(every) {
+                    if let Err(e) = sink.send(&serde_json::to_string(&event).unwrap()).await {
+                        tracing::error!(error=%e, "failed to emit ingest stats");
+                    }
+                }
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
+    }
+}
+
+/// Sink wrapper counting the events passing through per exchange and type.
+pub struct IngestStatsSink {
+    inner: DynSink,
+}
+
+impl IngestStatsSink {
+    pub fn new(inner: DynSink) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl OutputSink for IngestStatsSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        observe(line);
+        self.inner.send(line).await
+    }
+}
@@ -0,0 +1,215 @@
+//! Detection of trades that are large for their market.
+//!
+//! [`LargePrintSink`] keeps the sizes of the last `window` trades of every
+//! venue and instrument, so spot and perpetual prints of one symbol are
+//! judged apart. A trade bigger than the configured percentile of that
+//! distribution emits a [`LargePrint`] of kind `trade`; a burst of same-side
+//! trades arriving within `burst_ms` of each other whose combined size is
+//! emits one of kind `burst`, whether or not one of its trades was reported
+//! on its own. The event counts the distinct prices traded at, so a print that
+//! swept several book levels stands out from one filled at the touch, and
+//! carries the aggressor side inferred by the tick rule for signed volume
+//! measures.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use canonicalizer::{AssetClass, InstrumentKey, LargePrint};
+use tokio::sync::Mutex;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Trades seen before a market's size threshold is trusted.
+const MIN_SAMPLES: usize = 100;
+
+/// Same-side trades printed back to back.
+struct Burst {
+    side: Option<&'static str>,
+    last_ts: i64,
+    qty: f64,
+    notional: f64,
+    trades: u64,
+    prices: Vec<u64>,
+    /// Already reported as a burst.
+    reported: bool,
+}
+
+/// Sizes of the last `window` trades, in arrival order and sorted, so the
+/// percentile is a lookup rather than a selection over the window.
+#[derive(Default)]
+struct Sizes {
+    arrival: VecDeque<f64>,
+    sorted: Vec<f64>,
+}
+
+impl Sizes {
+    fn len(&self) -> usize {
+        self.arrival.len()
+    }
+
+    fn push(&mut self, qty: f64, window: usize) {
+        let at = self.sorted.partition_point(|s| s.total_cmp(&qty).is_lt());
+        self.sorted.insert(at, qty);
+        self.arrival.push_back(qty);
+        if self.arrival.len() > window {
+            if let Some(old) = self.arrival.pop_front() {
+                let at = self.sorted.partition_point(|s| s.total_cmp(&old).is_lt());
+                self.sorted.remove(at);
+            }
+        }
+    }
+
+    /// Nearest-rank percentile `p` (0 to 100).
+    fn percentile(&self, p: f64) -> f64 {
+        let n = self.sorted.len();
+        let rank = ((p / 100.0 * n as f64).ceil() as usize).clamp(1, n);
+        self.sorted[rank - 1]
+    }
+}
+
+#[derive(Default)]
+struct Tape {
+    sizes: Sizes,
+    last_price: Option<f64>,
+    side: Option<&'static str>,
+    burst: Option<Burst>,
+}
+
+type SharedTape = Arc<Mutex<Tape>>;
+
+/// Sink wrapper emitting [`LargePrint`] events for outsized trades.
+pub struct LargePrintSink {
+    inner: DynSink,
+    percentile: f64,
+    window: usize,
+    burst_ms: i64,
+    /// Tape per venue and instrument, each behind its own lock so markets
+    /// are observed concurrently.
+    tapes: Mutex<HashMap<(String, InstrumentKey), SharedTape>>,
+}
+
+impl LargePrintSink {
+    pub fn new(inner: DynSink, percentile: f64, window: usize, burst_ms: i64) -> Self {
+        Self {
+            inner,
+            percentile: percentile.clamp(0.0, 100.0),
+            window: window.max(MIN_SAMPLES),
+            burst_ms,
+            tapes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The large prints completed by `line`, if it is such a trade.
+    async fn observe(&self, line: &str) -> Vec<LargePrint> {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return Vec::new();
+        };
+        if v.get("type").and_then(|t| t.as_str()) != Some("trade") {
+            return Vec::new();
+        }
+        let num = |k: &str| match v.get(k)? {
+            serde_json::Value::String(s) => s.parse::<f64>().ok(),
+            n => n.as_f64(),
+        };
+        let (Some(agent), Some(instrument), Some(ts), Some(price), Some(qty)) = (
+            v.get("agent").and_then(|a| a.as_str()),
+            InstrumentKey::from_event(&v),
+            v.get("ts").and_then(|t| t.as_i64()),
+            num("p"),
+            num("q"),
+        ) else {
+            return Vec::new();
+        };
+
+        let tape = self
+            .tapes
+            .lock()
+            .await
+            .entry((agent.to_string(), instrument.clone()))
+            .or_default()
+            .clone();
+        let mut tape = tape.lock().await;
+        let tape = &mut *tape;
+        let threshold =
+            (tape.sizes.len() >= MIN_SAMPLES).then(|| tape.sizes.percentile(self.percentile));
+        tape.sizes.push(qty, self.window);
+
+        match tape.last_price {
+            Some(last) if price > last => tape.side = Some("buy"),
+            Some(last) if price < last => tape.side = Some("sell"),
+            _ => {}
+        }
+        tape.last_price = Some(price);
+        let side = tape.side;
+        let burst = match &mut tape.burst {
+            Some(b) if ts - b.last_ts <= self.burst_ms && b.side == side => b,
+            slot => slot.insert(Burst {
+                side,
+                last_ts: ts,
+                qty: 0.0,
+                notional: 0.0,
+                trades: 0,
+                prices: Vec::new(),
+                reported: false,
+            }),
+        };
+        burst.last_ts = ts;
+        burst.qty += qty;
+        burst.notional += price * qty;
+        burst.trades += 1;
+        if !burst.prices.contains(&price.to_bits()) {
+            burst.prices.push(price.to_bits());
+        }
+
+        let Some(threshold) = threshold else {
+            return Vec::new();
+        };
+        let event = |kind: &str, qty: f64, notional: f64, trades: u64, levels: u64| LargePrint {
+            agent: agent.to_string(),
+            r#type: "large_print".to_string(),
+            symbol: instrument.symbol.to_string(),
+            kind: kind.to_string(),
+            side: side.map(str::to_string),
+            qty,
+            notional,
+            trades,
+            levels,
+            swept: levels > 1,
+            threshold,
+            percentile: self.percentile,
+            ac: (instrument.class != AssetClass::Spot).then_some(instrument.class),
+            settle: v.get("settle").and_then(|s| s.as_str()).map(str::to_string),
+            id: v.get("id").and_then(|s| s.as_str()).map(str::to_string),
+            timestamp: ts,
+        };
+        let mut prints = Vec::new();
+        if qty > threshold {
+            prints.push(event("trade", qty, price * qty, 1, 1));
+        }
+        if !burst.reported && burst.trades > 1 && burst.qty > threshold {
+            burst.reported = true;
+            prints.push(event(
+                "burst",
+                burst.qty,
+                burst.notional,
+                burst.trades,
+                burst.prices.len() as u64,
+            ));
+        }
+        prints
+    }
+}
+
+#[async_trait]
+impl OutputSink for LargePrintSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        let prints = self.observe(line).await;
+        self.inner.send(line).await?;
+        for p in prints {
+            self.inner.send(&serde_json::to_string(&p).unwrap()).await?;
+        }
+        Ok(())
+    }
+}
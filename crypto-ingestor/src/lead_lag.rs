@@ -0,0 +1,267 @@
+//! Lead/lag detection between venues quoting the same canonical symbol.
+//!
+//! [`LeadLagSink`] watches `book_ticker` events on their way to the output
+//! sink. Every change in a venue's mid price is a move; when another venue's
+//! mid moves in the same direction within the window, the first venue is
+//! credited as leader with the delay between the two exchange timestamps.
+//!
+//! Alongside these counts, the sink cross-correlates the venues' mid log
+//! returns on a millisecond grid for every lag up to the window. Returns are
+//! zero between moves, so each pair of moves `lag` ms apart adds the product
+//! of their returns to that lag; normalised by both venues' return energy,
+//! the lag with the highest correlation is reported with its value.
+//! Per-pair statistics are periodically emitted as [`LeadLag`] events.
+//! With [`QuoteGroups`], venues quoting equivalent assets, such as `BTC-USD`
+//! and `BTC-USDT`, are compared as one symbol. Mids are still tracked per
+//! original instrument, so a venue listing several of them moves with each.
+//! Venues are only compared within one asset class: spot books with spot
+//! books and perpetuals with perpetuals, so the basis between a venue's spot
+//! and perp markets never shows up as moves.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::{AssetClass, InstrumentKey, LeadLag, QuoteGroups};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+struct Move {
+    venue: String,
+    ts: i64,
+    up: bool,
+    /// Log return of the mid.
+    ret: f64,
+    /// Venues that already matched this move as followers.
+    followed_by: Vec<String>,
+}
+
+/// Symbol, or quote group key, and asset class venues are compared on.
+type Book = (String, AssetClass);
+
+#[derive(Default)]
+struct SymbolState {
+    /// Latest mid per venue and original instrument, as one venue can list
+    /// several symbols of a quote group.
+    mids: HashMap<(String, InstrumentKey), f64>,
+    moves: VecDeque<Move>,
+}
+
+#[derive(Default)]
+struct State {
+    symbols: HashMap<Book, SymbolState>,
+    /// Leader moves per (book, venue) since the last report.
+    moves: HashMap<(Book, String), u64>,
+    /// Observed lags per (book, leader, follower) since the last report.
+    lags: HashMap<(Book, String, String), Vec<i64>>,
+    /// Sum of squared returns per (book, venue) since the last report.
+    energy: HashMap<(Book, String), f64>,
+    /// Summed return products per (book, leader, follower) and lag in ms
+    /// since the last report.
+    xcorr: HashMap<(Book, String, String), HashMap<i64, f64>>,
+    /// Quote group of every grouped symbol key.
+    quote_groups: HashMap<String, String>,
+    last_report: Option<Instant>,
+}
+
+/// Sink wrapper measuring which venue's price moves precede the others'.
+pub struct LeadLagSink {
+    inner: DynSink,
+    window_ms: i64,
+    report_every: Duration,
+    groups: QuoteGroups,
+    state: Mutex<State>,
+}
+
+impl LeadLagSink {
+    pub fn new(inner: DynSink, window_ms: u64, report_every: Duration) -> Self {
+        Self {
+            inner,
+            window_ms: window_ms as i64,
+            report_every,
+            groups: QuoteGroups::default(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Compare symbols by their [`QuoteGroups`] key.
+    pub fn with_quote_groups(mut self, groups: QuoteGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    async fn observe(&self, line: &str) {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        if v.get("type").and_then(|t| t.as_str()) != Some("book_ticker") {
+            return;
+        }
+        let (Some(venue), Some(instrument), Some(ts)) = (
+            v.get("agent").and_then(|a| a.as_str()),
+            InstrumentKey::from_event(&v),
+            v.get("ts").and_then(|t| t.as_i64()),
+        ) else {
+            return;
+        };
+        let px = |k: &str| v.get(k)?.as_str()?.parse::<f64>().ok();
+        let (Some(bid), Some(ask)) = (px("bp"), px("ap")) else {
+            return;
+        };
+        let mid = (bid + ask) / 2.0;
+        let (key, group) = self.groups.key(&instrument.symbol);
+        let book: Book = (key.clone(), instrument.class);
+        let market = (venue.to_string(), instrument);
+
+        let mut state = self.state.lock().await;
+        let State {
+            symbols,
+            moves,
+            lags,
+            energy,
+            xcorr,
+            quote_groups,
+            ..
+        } = &mut *state;
+        if let Some(g) = group {
+            quote_groups
+                .entry(key.clone())
+                .or_insert_with(|| g.name.clone());
+        }
+        let entry = symbols.entry(book.clone()).or_default();
+        let prev = entry.mids.insert(market, mid);
+        let (up, ret) = match prev {
+            Some(p) if mid != p && p > 0.0 && mid > 0.0 => (mid > p, (mid / p).ln()),
+            _ => return,
+        };
+
+        while entry
+            .moves
+            .front()
+            .is_some_and(|m| m.ts < ts - self.window_ms)
+        {
+            entry.moves.pop_front();
+        }
+        for m in entry.moves.iter_mut().filter(|m| m.venue != venue) {
+            // Exchange timestamps can arrive out of order, so the earlier
+            // move of the two leads; simultaneous moves count both ways.
+            if m.ts <= ts {
+                *xcorr
+                    .entry((book.clone(), m.venue.clone(), venue.to_string()))
+                    .or_default()
+                    .entry(ts - m.ts)
+                    .or_insert(0.0) += m.ret * ret;
+            }
+            if m.ts >= ts && m.ts - ts <= self.window_ms {
+                *xcorr
+                    .entry((book.clone(), venue.to_string(), m.venue.clone()))
+                    .or_default()
+                    .entry(m.ts - ts)
+                    .or_insert(0.0) += m.ret * ret;
+            }
+            if m.up == up && m.ts <= ts && !m.followed_by.iter().any(|f| f == venue) {
+                m.followed_by.push(venue.to_string());
+                lags.entry((book.clone(), m.venue.clone(), venue.to_string()))
+                    .or_default()
+                    .push(ts - m.ts);
+            }
+        }
+        entry.moves.push_back(Move {
+            venue: venue.to_string(),
+            ts,
+            up,
+            ret,
+            followed_by: Vec::new(),
+        });
+        *energy
+            .entry((book.clone(), venue.to_string()))
+            .or_insert(0.0) += ret * ret;
+        *moves.entry((book, venue.to_string())).or_insert(0) += 1;
+    }
+
+    /// Emit a [`LeadLag`] report for every venue pair seen since the last
+    /// report and reset the statistics.
+    pub async fn report(&self) -> Result<(), IngestorError> {
+        let (moves, lags, energy, mut xcorr, quote_groups) = {
+            let mut state = self.state.lock().await;
+            state.last_report = Some(Instant::now());
+            (
+                std::mem::take(&mut state.moves),
+                std::mem::take(&mut state.lags),
+                std::mem::take(&mut state.energy),
+                std::mem::take(&mut state.xcorr),
+                state.quote_groups.clone(),
+            )
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        for ((book, leader, follower), mut lag) in lags {
+            lag.sort_unstable();
+            let norm = [&leader, &follower]
+                .map(|v| {
+                    energy
+                        .get(&(book.clone(), v.clone()))
+                        .copied()
+                        .unwrap_or_default()
+                })
+                .iter()
+                .product::<f64>()
+                .sqrt();
+            // Highest correlation, the shortest lag on ties.
+            let (xcorr_lag_ms, xcorr) = xcorr
+                .remove(&(book.clone(), leader.clone(), follower.clone()))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(lag, sum)| (lag, if norm > 0.0 { sum / norm } else { 0.0 }))
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+                .unwrap_or_default();
+            let report = LeadLag {
+                r#type: "lead_lag".to_string(),
+                leader_moves: moves
+                    .get(&(book.clone(), leader.clone()))
+                    .copied()
+                    .unwrap_or_default(),
+                followed: lag.len() as u64,
+                mean_lag_ms: lag.iter().sum::<i64>() as f64 / lag.len() as f64,
+                median_lag_ms: lag[lag.len() / 2],
+                xcorr_lag_ms,
+                xcorr,
+                quote_group: quote_groups.get(&book.0).cloned(),
+                ac: (book.1 != AssetClass::Spot).then_some(book.1),
+                symbol: book.0,
+                leader,
+                follower,
+                timestamp: now,
+            };
+            self.inner
+                .send(&serde_json::to_string(&report).unwrap())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn report_due(&self) -> bool {
+        let mut state = self.state.lock().await;
+        match state.last_report {
+            Some(t) => t.elapsed() >= self.report_every,
+            None => {
+                state.last_report = Some(Instant::now());
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for LeadLagSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        self.observe(line).await;
+        self.inner.send(line).await?;
+        if self.report_due().await {
+            self.report().await?;
+        }
+        Ok(())
+    }
+}
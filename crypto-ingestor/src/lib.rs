@@ -1,9 +1,27 @@
 pub mod agent;
 pub mod agents;
+pub mod build_info;
 pub mod clock;
 pub mod config;
+pub mod conflate;
+pub mod dead_letter;
+pub mod depth_profile;
 pub mod error;
+pub mod fair_mid;
+pub mod fanout;
+pub mod fixed_point;
+pub mod freshness;
+pub mod funding_window;
+pub mod gaps;
 pub mod http_client;
+pub mod ingest_stats;
+pub mod large_print;
+pub mod lead_lag;
 pub mod metadata;
 pub mod parse;
+pub mod rate_limit;
+pub mod redact;
 pub mod sink;
+pub mod transform;
+pub mod wash_trade;
+pub mod watchdog;
@@ -1,31 +1,56 @@
 mod agent;
 mod agents;
+mod build_info;
 mod clock;
 mod config;
+mod conflate;
+mod dead_letter;
+mod depth_profile;
 mod error;
+mod fair_mid;
+mod fanout;
+mod fixed_point;
+mod freshness;
+mod funding_window;
 mod http_client;
+mod ingest_stats;
+mod large_print;
+mod lead_lag;
 mod metadata;
 mod parse;
+mod rate_limit;
+mod redact;
 mod sink;
+mod transform;
+mod wash_trade;
+mod watchdog;
 
 use agents::{available_agents, make_agent};
-use canonicalizer::CanonicalService;
+use canonicalizer::{CanonicalService, QuoteGroups};
 use clap::Parser;
 use config::{Cli, Settings};
+use conflate::ConflationSink;
+use depth_profile::DepthProfileSink;
 use error::IngestorError;
-use sink::{DynSink, FileSink, StdoutSink};
+use fair_mid::FairMidSink;
+use fanout::FanoutSink;
+use fixed_point::FixedPointSink;
+use freshness::{Freshness, FreshnessSink};
+use ingest_stats::IngestStatsSink;
+use large_print::LargePrintSink;
+use lead_lag::LeadLagSink;
+use sink::{DynSink, Encoding, EnvelopeSink, FileSink, LabelSink, SamplingSink, StdoutSink};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio::process::Command;
 use tokio::sync::mpsc;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::FmtSubscriber;
+use transform::TransformSink;
+use wash_trade::WashTradeSink;
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<(), IngestorError> {
-    // logger
-    let subscriber = FmtSubscriber::builder().with_target(false).finish();
-    let _ = tracing::subscriber::set_global_default(subscriber);
-
     // parse CLI and configuration
     let cli = Cli::parse();
     let mut specs = cli.specs.clone();
@@ -42,19 +67,65 @@ async fn main() -> Result<(), IngestorError> {
     }
     let settings = Settings::load(&cli)?;
 
+    // logger, with credentials scrubbed from every line; protobuf frames on
+    // stdout would be corrupted by log lines, so those go to stderr then
+    let proto_on_stdout = if settings.sinks.is_empty() {
+        settings.sink == "stdout" && settings.encoding == Encoding::Proto
+    } else {
+        settings
+            .sinks
+            .iter()
+            .any(|c| c.kind == "stdout" && c.encoding == Encoding::Proto)
+    };
+    let log_writer = if proto_on_stdout {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let subscriber = FmtSubscriber::builder()
+        .with_target(false)
+        .with_writer(redact::RedactingWriter::new(log_writer))
+        .finish();
+    let _ = tracing::subscriber::set_global_default(subscriber);
+
+    for secret in [
+        &settings.binance_api_key,
+        &settings.binance_api_secret,
+        &settings.coinbase_api_key,
+        &settings.coinbase_api_secret,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        redact::register_secret(secret);
+    }
+
+    http_client::init(&settings)?;
     clock::spawn_clock_sync();
 
-    // initialise output sink
-    let sink: DynSink = match settings.sink.as_str() {
-        "stdout" => Arc::new(StdoutSink::new()),
-        "file" => {
+    // initialise output sink; a `sinks` list replaces the single `--sink`
+    let fanout = if settings.sinks.is_empty() {
+        None
+    } else {
+        Some(Arc::new(FanoutSink::from_config(&settings.sinks).await?))
+    };
+    let encoding = settings.encoding;
+    let sink: DynSink = match (&fanout, settings.sink.as_str()) {
+        (Some(fanout), _) => fanout.clone(),
+        (None, "stdout") => Arc::new(StdoutSink::new().with_encoding(encoding)),
+        (None, "file") => {
             let path = settings
                 .file_path
                 .as_ref()
                 .ok_or_else(|| IngestorError::Other("file_path not set".into()))?;
-            Arc::new(FileSink::new(path).await.map_err(IngestorError::Io)?)
+            Arc::new(
+                FileSink::new(path)
+                    .await
+                    .map_err(IngestorError::Io)?
+                    .with_encoding(encoding),
+            )
         }
-        other => {
+        (None, other) => {
             return Err(IngestorError::Other(format!(
                 "unknown sink type: {}",
                 other
@@ -62,93 +133,267 @@ async fn main() -> Result<(), IngestorError> {
         }
     };
 
+    // Stamped last so derived and telemetry events carry the envelope too.
+    let sink: DynSink = if settings.envelope {
+        Arc::new(EnvelopeSink::new(sink))
+    } else {
+        sink
+    };
+    let sink: DynSink = if settings.telemetry {
+        Arc::new(IngestStatsSink::new(sink))
+    } else {
+        sink
+    };
+    let freshness = (!settings.freshness_slos_ms.is_empty()).then(|| {
+        let slos = settings.freshness_slos_ms.clone().into_iter().collect();
+        Arc::new(Freshness::new(slos))
+    });
+    let sink: DynSink = match &freshness {
+        Some(f) => Arc::new(FreshnessSink::new(sink, f.clone())),
+        None => sink,
+    };
+    let sink: DynSink = match settings.numeric_format.as_str() {
+        "decimal" => sink,
+        "fixed" => Arc::new(FixedPointSink::new(sink)),
+        other => {
+            return Err(IngestorError::Other(format!(
+                "unknown numeric format: {}",
+                other
+            )));
+        }
+    };
+    let sink: DynSink = if settings.deployment.is_some() || settings.tenant.is_some() {
+        Arc::new(LabelSink::new(
+            sink,
+            settings.deployment.clone(),
+            settings.tenant.clone(),
+        ))
+    } else {
+        sink
+    };
+    let quote_groups = QuoteGroups::new(&settings.quote_groups);
+    let sink: DynSink = match settings.lead_lag_window_ms {
+        Some(w) if w > 0 => Arc::new(
+            LeadLagSink::new(
+                sink,
+                w,
+                std::time::Duration::from_secs(settings.lead_lag_report_secs),
+            )
+            .with_quote_groups(quote_groups.clone()),
+        ),
+        _ => sink,
+    };
+    let sink: DynSink = match settings.wash_trade_window_secs {
+        Some(w) if w > 0 => Arc::new(WashTradeSink::new(sink, std::time::Duration::from_secs(w))),
+        _ => sink,
+    };
+    let sink: DynSink = match settings.depth_profile_secs {
+        Some(s) if s > 0 => Arc::new(DepthProfileSink::new(
+            sink,
+            settings.depth_profile_bands_bps.clone(),
+            std::time::Duration::from_secs(s),
+        )),
+        _ => sink,
+    };
+    let sink: DynSink = match settings.large_print_percentile {
+        Some(p) if p > 0.0 => Arc::new(LargePrintSink::new(
+            sink,
+            p,
+            settings.large_print_window,
+            settings.large_print_burst_ms,
+        )),
+        _ => sink,
+    };
+    let sink: DynSink = match settings.fair_mid_min_notional {
+        Some(n) if n > 0.0 => Arc::new(
+            FairMidSink::new(sink, n, settings.fair_mid_window_ms).with_quote_groups(quote_groups),
+        ),
+        _ => sink,
+    };
+    let conflation = settings.conflate_ms.filter(|ms| *ms > 0).map(|ms| {
+        Arc::new(ConflationSink::new(
+            sink.clone(),
+            std::time::Duration::from_millis(ms),
+        ))
+    });
+    let sink: DynSink = match &conflation {
+        Some(c) => c.clone(),
+        None => sink,
+    };
+    let sink: DynSink = match settings.sample_every {
+        Some(n) if n > 0 => {
+            // JSON samples would corrupt a protobuf stream on stdout
+            let samples = if proto_on_stdout {
+                StdoutSink::stderr()
+            } else {
+                StdoutSink::new()
+            };
+            Arc::new(SamplingSink::new(sink, Arc::new(samples), n))
+        }
+        _ => sink,
+    };
+    let sink: DynSink = if settings.transforms.is_empty() {
+        sink
+    } else {
+        Arc::new(TransformSink::new(
+            sink,
+            transform::from_config(&settings.transforms)?,
+        ))
+    };
+
+    if let Some(path) = &settings.dead_letter_path {
+        let dlq: DynSink = Arc::new(FileSink::new(path).await.map_err(IngestorError::Io)?);
+        dead_letter::init(dlq, settings.dead_letter_sample_every);
+    }
+
+    let info = build_info::event(&settings);
+    tracing::info!(%info, "build info");
+    sink.send(&info.to_string()).await?;
+
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
     // periodically refresh reference data
-    tokio::spawn(metadata::run(shutdown_rx.clone(), sink.clone()));
-
-    // spawn canonicalizer process
-    let exe = std::env::current_exe()?;
-    let canon_path = exe.with_file_name("canonicalizer");
-    if !canon_path.exists() {
-        let mut build = Command::new("cargo");
-        build
-            .arg("build")
-            .arg("-p")
-            .arg("canonicalizer")
-            .arg("--bin")
-            .arg("canonicalizer");
-        if !cfg!(debug_assertions) {
-            build.arg("--release");
-        }
-        let status = build.status().await?;
-        if !status.success() {
-            return Err(IngestorError::Other("failed to build canonicalizer".into()));
-        }
+    tokio::spawn(metadata::run(
+        shutdown_rx.clone(),
+        sink.clone(),
+        settings.clone(),
+    ));
+    if let Some(f) = &freshness {
+        tokio::spawn(freshness::run(
+            shutdown_rx.clone(),
+            f.clone(),
+            sink.clone(),
+            std::time::Duration::from_secs(1),
+        ));
+    }
+    if let Some(c) = &conflation {
+        tokio::spawn(conflate::run(shutdown_rx.clone(), c.clone()));
+    }
+    if settings.telemetry {
+        tokio::spawn(rate_limit::run_gauges(shutdown_rx.clone(), sink.clone()));
+        tokio::spawn(ingest_stats::run(
+            shutdown_rx.clone(),
+            sink.clone(),
+            std::time::Duration::from_secs(60),
+        ));
+    }
+
+    if let Some(path) = &settings.symbol_overrides {
+        CanonicalService::load_overrides(path).map_err(|e| {
+            IngestorError::Other(format!("failed to load symbol overrides {path}: {e}"))
+        })?;
+    }
+    if settings.validate_symbols {
+        CanonicalService::set_validation(true);
     }
+    let overrides_path = settings.symbol_overrides.clone();
+    let validate_symbols = settings.validate_symbols;
     let (tx, rx) = mpsc::channel::<String>(100);
 
-    // spawn watchdog for canonicalizer process
-    let canon_path_clone = canon_path.clone();
-    let sink_clone = sink.clone();
-    let canon_watchdog = tokio::spawn(async move {
-        let mut rx = rx;
-        loop {
-            let mut canon_child = match Command::new(&canon_path_clone)
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(e) => {
-                    tracing::error!(error=%e, "failed to spawn canonicalizer");
-                    return;
+    let canon_watchdog = if settings.in_process_canonicalizer {
+        // Canonicalize on the ingestor's own runtime; overrides were loaded
+        // into the process above.
+        let sink = sink.clone();
+        tokio::spawn(async move {
+            let mut rx = rx;
+            while let Some(line) = rx.recv().await {
+                let Some(line) = canonicalizer::process_line(&line) else {
+                    continue;
+                };
+                if let Err(e) = sink.send(&line).await {
+                    tracing::error!(error=%e, "sink error");
                 }
-            };
-
-            let mut canon_stdin = canon_child.stdin.take().expect("canonicalizer stdin");
-            let canon_stdout = canon_child.stdout.take().expect("canonicalizer stdout");
-            let mut reader = tokio::io::BufReader::new(canon_stdout).lines();
-            let sink = sink_clone.clone();
+            }
+        })
+    } else {
+        // spawn canonicalizer process
+        let exe = std::env::current_exe()?;
+        let canon_path = exe.with_file_name("canonicalizer");
+        if !canon_path.exists() {
+            let mut build = Command::new("cargo");
+            build
+                .arg("build")
+                .arg("-p")
+                .arg("canonicalizer")
+                .arg("--bin")
+                .arg("canonicalizer");
+            if !cfg!(debug_assertions) {
+                build.arg("--release");
+            }
+            let status = build.status().await?;
+            if !status.success() {
+                return Err(IngestorError::Other("failed to build canonicalizer".into()));
+            }
+        }
 
+        // spawn watchdog for canonicalizer process
+        let canon_path_clone = canon_path.clone();
+        let sink_clone = sink.clone();
+        tokio::spawn(async move {
+            let mut rx = rx;
             loop {
-                tokio::select! {
-                    line = rx.recv() => {
-                        match line {
-                            Some(line) => {
-                                if canon_stdin.write_all(line.as_bytes()).await.is_err() {
-                                    break;
+                let mut cmd = Command::new(&canon_path_clone);
+                cmd.arg("--json")
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped());
+                if let Some(path) = &overrides_path {
+                    cmd.env("CANONICAL_OVERRIDES", path);
+                }
+                if validate_symbols {
+                    cmd.env("CANONICAL_VALIDATE", "1");
+                }
+                let mut canon_child = match cmd.spawn() {
+                    Ok(child) => child,
+                    Err(e) => {
+                        tracing::error!(error=%e, "failed to spawn canonicalizer");
+                        return;
+                    }
+                };
+
+                let mut canon_stdin = canon_child.stdin.take().expect("canonicalizer stdin");
+                let canon_stdout = canon_child.stdout.take().expect("canonicalizer stdout");
+                let mut reader = tokio::io::BufReader::new(canon_stdout).lines();
+                let sink = sink_clone.clone();
+
+                loop {
+                    tokio::select! {
+                        line = rx.recv() => {
+                            match line {
+                                Some(line) => {
+                                    if canon_stdin.write_all(line.as_bytes()).await.is_err() {
+                                        break;
+                                    }
+                                    if canon_stdin.write_all(b"\n").await.is_err() {
+                                        break;
+                                    }
                                 }
-                                if canon_stdin.write_all(b"\n").await.is_err() {
-                                    break;
+                                None => {
+                                    let _ = canon_child.kill().await;
+                                    return;
                                 }
                             }
-                            None => {
-                                let _ = canon_child.kill().await;
-                                return;
-                            }
                         }
-                    }
-                    res = reader.next_line() => {
-                        match res {
-                            Ok(Some(line)) => {
-                                if let Err(e) = sink.send(&line).await {
-                                    tracing::error!(error=%e, "sink error");
+                        res = reader.next_line() => {
+                            match res {
+                                Ok(Some(line)) => {
+                                    if let Err(e) = sink.send(&line).await {
+                                        tracing::error!(error=%e, "sink error");
+                                    }
                                 }
+                                _ => break,
                             }
-                            _ => break,
                         }
-                    }
-                    status = canon_child.wait() => {
-                        tracing::warn!(?status, "canonicalizer exited; restarting");
-                        break;
+                        status = canon_child.wait() => {
+                            tracing::warn!(?status, "canonicalizer exited; restarting");
+                            break;
+                        }
                     }
                 }
-            }
 
-            let _ = canon_child.kill().await;
-        }
-    });
+                let _ = canon_child.kill().await;
+            }
+        })
+    };
 
     // Initialise the canonical service before any agents are created so that
     // the required quote asset list is available for symbol comparisons.
@@ -194,6 +439,28 @@ async fn main() -> Result<(), IngestorError> {
 
     drop(tx);
     let _ = canon_watchdog.await;
+    if let Some(c) = &conflation {
+        if let Err(e) = c.flush(true).await {
+            tracing::error!(error=%e, "failed to flush conflated diffs");
+        }
+    }
+    tracing::info!(failures = dead_letter::failures(), "unparseable messages");
+    tracing::info!(
+        gaps = agents::STREAM_SEQ_GAPS.load(std::sync::atomic::Ordering::Relaxed),
+        "stream sequence gaps"
+    );
+    if let Some(fanout) = &fanout {
+        fanout.flush(std::time::Duration::from_secs(5)).await;
+        for (name, stats) in fanout.stats() {
+            tracing::info!(
+                sink = %name,
+                sent = stats.sent.load(std::sync::atomic::Ordering::Relaxed),
+                dropped = stats.dropped.load(std::sync::atomic::Ordering::Relaxed),
+                failed = stats.failed.load(std::sync::atomic::Ordering::Relaxed),
+                "sink delivery"
+            );
+        }
+    }
 
     Ok(())
 }
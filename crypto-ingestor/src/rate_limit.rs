@@ -0,0 +1,529 @@
+//! Adaptive REST rate limiting driven by exchange-reported usage headers.
+//!
+//! Each exchange has a process-wide [`RateLimiter`]. Callers `acquire` the
+//! weight of a request before sending it and `observe` every response; the
+//! limiter reads the exchange's usage headers, keeps the latest values as
+//! gauges and pauses further requests when the budget is nearly spent or the
+//! exchange answered `429`.
+//!
+//! Acquired weight is also kept in a ledger of the last minute, shared by every
+//! poller and backfill of the exchange. Before a request is let through the
+//! limiter forecasts whether it fits the minute's budget and, if not, delays it
+//! until enough weight has aged out. [`Priority::Low`] work such as bar polls
+//! and gap backfills stops at a lower watermark than [`Priority::High`] work,
+//! so it yields to book snapshots well before the exchange would answer `429`.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use tokio::time::Instant;
+
+use crate::sink::DynSink;
+
+/// Fraction of the budget low priority requests may use.
+const LOW_PRIORITY_WATERMARK: f64 = 0.6;
+/// Span of the weight ledger.
+const LEDGER_WINDOW: Duration = Duration::from_secs(60);
+/// Pause applied on `429` when the exchange gives no `Retry-After`.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// A REST API with its own request budget. Binance's USD-M (`fapi`), COIN-M
+/// (`dapi`) and options (`eapi`) APIs weigh requests separately from spot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exchange {
+    Binance,
+    BinanceFutures,
+    BinanceCoinm,
+    BinanceOptions,
+    Coinbase,
+    Okx,
+    Bybit,
+    Gate,
+    Kucoin,
+    Mexc,
+    Deribit,
+    Hyperliquid,
+}
+
+/// How an exchange reports its usage in response headers.
+enum Usage {
+    /// Weight used in the current minute.
+    UsedWeight(&'static str),
+    /// Requests left in the current window, and when the window resets.
+    Remaining {
+        remaining: &'static [&'static str],
+        reset: &'static [&'static str],
+        reset_in: Reset,
+    },
+    /// No usage headers; only the ledger and `429`s apply.
+    None,
+}
+
+/// Unit of a window reset header.
+enum Reset {
+    EpochSecs,
+    EpochMillis,
+    /// Milliseconds from now.
+    Millis,
+}
+
+impl Exchange {
+    /// Every exchange, in declaration order.
+    pub const ALL: [Exchange; 12] = [
+        Exchange::Binance,
+        Exchange::BinanceFutures,
+        Exchange::BinanceCoinm,
+        Exchange::BinanceOptions,
+        Exchange::Coinbase,
+        Exchange::Okx,
+        Exchange::Bybit,
+        Exchange::Gate,
+        Exchange::Kucoin,
+        Exchange::Mexc,
+        Exchange::Deribit,
+        Exchange::Hyperliquid,
+    ];
+
+    /// Agent name the limiter's telemetry is reported under.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exchange::Binance => "binance",
+            Exchange::BinanceFutures => "binance_futures",
+            Exchange::BinanceCoinm => "binance_coinm",
+            Exchange::BinanceOptions => "binance_options",
+            Exchange::Coinbase => "coinbase",
+            Exchange::Okx => "okx",
+            Exchange::Bybit => "bybit",
+            Exchange::Gate => "gate",
+            Exchange::Kucoin => "kucoin",
+            Exchange::Mexc => "mexc",
+            Exchange::Deribit => "deribit",
+            Exchange::Hyperliquid => "hyperliquid",
+        }
+    }
+
+    /// Published per-IP request weight allowed per minute. Limits given per
+    /// second or per 10 seconds are scaled to a minute.
+    pub fn budget(&self) -> i64 {
+        match self {
+            Exchange::Binance => 1200,
+            Exchange::BinanceFutures | Exchange::BinanceCoinm => 2400,
+            Exchange::BinanceOptions => 400,
+            // 10 public requests per second
+            Exchange::Coinbase => 600,
+            // 20 requests per 2 seconds per market data endpoint
+            Exchange::Okx => 600,
+            // 600 requests per 5 seconds
+            Exchange::Bybit => 7200,
+            // 200 requests per 10 seconds per endpoint
+            Exchange::Gate => 1200,
+            // 2000 weight per 30 seconds on the public pool
+            Exchange::Kucoin => 4000,
+            // 500 weight per 10 seconds
+            Exchange::Mexc => 3000,
+            // 20 non-matching-engine requests per second
+            Exchange::Deribit => 1200,
+            Exchange::Hyperliquid => 1200,
+        }
+    }
+
+    /// Fraction of the budget after which high priority requests are held
+    /// back. Venues that report no usage keep more headroom, as the ledger
+    /// only sees this process's own requests.
+    fn high_watermark(&self) -> f64 {
+        match self.usage() {
+            Usage::None => 0.8,
+            _ => 0.9,
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        match self {
+            Exchange::Binance
+            | Exchange::BinanceFutures
+            | Exchange::BinanceCoinm
+            | Exchange::BinanceOptions => Usage::UsedWeight("x-mbx-used-weight-1m"),
+            Exchange::Coinbase => Usage::Remaining {
+                remaining: &["cb-ratelimit-remaining", "x-ratelimit-remaining"],
+                reset: &["cb-ratelimit-reset", "x-ratelimit-reset"],
+                reset_in: Reset::EpochSecs,
+            },
+            Exchange::Bybit => Usage::Remaining {
+                remaining: &["x-bapi-limit-status"],
+                reset: &["x-bapi-limit-reset-timestamp"],
+                reset_in: Reset::EpochMillis,
+            },
+            Exchange::Gate => Usage::Remaining {
+                remaining: &["x-gate-ratelimit-requests-remain"],
+                reset: &["x-gate-ratelimit-reset-timestamp"],
+                reset_in: Reset::EpochMillis,
+            },
+            Exchange::Kucoin => Usage::Remaining {
+                remaining: &["gw-ratelimit-remaining"],
+                reset: &["gw-ratelimit-reset"],
+                reset_in: Reset::Millis,
+            },
+            Exchange::Okx | Exchange::Mexc | Exchange::Deribit | Exchange::Hyperliquid => {
+                Usage::None
+            }
+        }
+    }
+}
+
+/// How urgently a request must be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Needed to keep live data correct, e.g. order book snapshots.
+    High,
+    /// Polls and backfills that can wait, e.g. bars.
+    Low,
+}
+
+/// Shared limiter for one exchange's REST API.
+pub struct RateLimiter {
+    exchange: Exchange,
+    budget: i64,
+    used_weight: AtomicI64,
+    remaining: AtomicI64,
+    paused_until: Mutex<Option<Instant>>,
+    /// Weight acquired during the last [`LEDGER_WINDOW`], oldest first.
+    ledger: Mutex<VecDeque<(Instant, i64)>>,
+}
+
+static LIMITERS: Lazy<[RateLimiter; 12]> = Lazy::new(|| Exchange::ALL.map(RateLimiter::new));
+
+/// Limiter shared by all REST callers of `exchange`.
+pub fn limiter(exchange: Exchange) -> &'static RateLimiter {
+    // `Exchange::ALL` lists the variants in declaration order.
+    &LIMITERS[exchange as usize]
+}
+
+/// Limiter shared by all Binance REST callers.
+pub fn binance() -> &'static RateLimiter {
+    limiter(Exchange::Binance)
+}
+
+/// Limiter shared by all Coinbase REST callers.
+pub fn coinbase() -> &'static RateLimiter {
+    limiter(Exchange::Coinbase)
+}
+
+impl RateLimiter {
+    pub fn new(exchange: Exchange) -> Self {
+        Self {
+            exchange,
+            budget: exchange.budget(),
+            used_weight: AtomicI64::new(-1),
+            remaining: AtomicI64::new(-1),
+            paused_until: Mutex::new(None),
+            ledger: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Weight the exchange allows per minute.
+    pub fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    /// Weight acquired during the last minute.
+    pub fn spent_weight(&self) -> i64 {
+        let mut ledger = self.ledger.lock().unwrap();
+        expire(&mut ledger, Instant::now());
+        ledger.iter().map(|(_, w)| w).sum()
+    }
+
+    /// Last `X-MBX-USED-WEIGHT-1M` reported by a Binance API, or `-1` if
+    /// unknown.
+    pub fn used_weight(&self) -> i64 {
+        self.used_weight.load(Ordering::Relaxed)
+    }
+
+    /// Last remaining request count reported by the exchange, or `-1` if
+    /// unknown.
+    pub fn remaining(&self) -> i64 {
+        self.remaining.load(Ordering::Relaxed)
+    }
+
+    /// Time left before requests may be sent again.
+    pub fn pause_remaining(&self) -> Duration {
+        self.paused_until
+            .lock()
+            .unwrap()
+            .map(|t| t.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Time until a request of `weight` fits within the budget share of
+    /// `priority`, given the weight spent during the last minute.
+    pub fn forecast(&self, weight: i64, priority: Priority) -> Duration {
+        let now = Instant::now();
+        let mut ledger = self.ledger.lock().unwrap();
+        expire(&mut ledger, now);
+        self.wait_for(&ledger, weight, priority, now)
+    }
+
+    fn wait_for(
+        &self,
+        ledger: &VecDeque<(Instant, i64)>,
+        weight: i64,
+        priority: Priority,
+        now: Instant,
+    ) -> Duration {
+        let cap = (self.budget as f64
+            * match priority {
+                Priority::High => self.exchange.high_watermark(),
+                Priority::Low => LOW_PRIORITY_WATERMARK,
+            }) as i64;
+        let mut spent: i64 = ledger.iter().map(|(_, w)| w).sum();
+        if spent + weight <= cap || ledger.is_empty() {
+            return Duration::ZERO;
+        }
+        // Work larger than the cap goes through once the ledger is empty.
+        let mut until = now;
+        for (at, w) in ledger {
+            spent -= w;
+            until = *at + LEDGER_WINDOW;
+            if spent + weight <= cap {
+                break;
+            }
+        }
+        until.saturating_duration_since(now)
+    }
+
+    /// Wait until the exchange budget allows a request of `weight`, then
+    /// record it in the ledger.
+    pub async fn acquire(&self, weight: i64, priority: Priority) {
+        let wait = self.pause_remaining();
+        if !wait.is_zero() {
+            tracing::debug!(exchange = ?self.exchange, ?wait, "rate limit pause");
+            tokio::time::sleep(wait).await;
+        }
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut ledger = self.ledger.lock().unwrap();
+                expire(&mut ledger, now);
+                let wait = self.wait_for(&ledger, weight, priority, now);
+                if wait.is_zero() {
+                    ledger.push_back((now, weight));
+                    return;
+                }
+                wait
+            };
+            tracing::debug!(exchange = ?self.exchange, ?wait, ?priority, weight, "deferring request to stay within budget");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Record the usage headers of a response and adjust the pause.
+    pub fn observe(&self, status: StatusCode, headers: &HeaderMap) {
+        let mut pause = None;
+        match self.exchange.usage() {
+            Usage::UsedWeight(header) => {
+                if let Some(used) = header_i64(headers, header) {
+                    self.used_weight.store(used, Ordering::Relaxed);
+                    if used as f64 >= self.exchange.high_watermark() * self.budget as f64 {
+                        pause = Some(until_next_minute());
+                    }
+                }
+            }
+            Usage::Remaining {
+                remaining,
+                reset,
+                reset_in,
+            } => {
+                if let Some(left) = remaining.iter().find_map(|h| header_i64(headers, h)) {
+                    self.remaining.store(left, Ordering::Relaxed);
+                    if left <= 0 {
+                        let reset = reset.iter().find_map(|h| header_i64(headers, h));
+                        pause = Some(
+                            reset
+                                .map(|r| match reset_in {
+                                    Reset::EpochSecs => until_epoch_ms(r * 1000),
+                                    Reset::EpochMillis => until_epoch_ms(r),
+                                    Reset::Millis => {
+                                        Duration::from_millis(r.clamp(0, 60_000) as u64)
+                                    }
+                                })
+                                .unwrap_or(DEFAULT_RETRY_AFTER),
+                        );
+                    }
+                }
+            }
+            Usage::None => {}
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS || status.as_u16() == 418 {
+            let retry_after = header_i64(headers, "retry-after")
+                .map(|s| Duration::from_secs(s.max(0) as u64))
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+            pause = Some(pause.map_or(retry_after, |p: Duration| p.max(retry_after)));
+        }
+        if let Some(pause) = pause {
+            tracing::warn!(exchange = ?self.exchange, ?pause, %status, "rate limit reached; pausing requests");
+            let until = Instant::now() + pause;
+            let mut guard = self.paused_until.lock().unwrap();
+            if guard.is_none_or(|t| t < until) {
+                *guard = Some(until);
+            }
+        }
+    }
+}
+
+/// Periodically emit the limiter gauges as `rate_limit` telemetry events.
+pub async fn run_gauges(mut shutdown: tokio::sync::watch::Receiver<bool>, sink: DynSink) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                for exchange in Exchange::ALL {
+                    let limiter = limiter(exchange);
+                    let line = serde_json::json!({
+                        "agent": exchange.name(),
+                        "type": "rate_limit",
+                        "used_weight": limiter.used_weight(),
+                        "spent_weight": limiter.spent_weight(),
+                        "budget": limiter.budget(),
+                        "remaining": limiter.remaining(),
+                        "paused_ms": limiter.pause_remaining().as_millis() as u64,
+                        "ts": chrono::Utc::now().timestamp_millis(),
+                    });
+                    if let Err(e) = sink.send(&line.to_string()).await {
+                        tracing::error!(error=%e, "failed to emit rate limit gauges");
+                    }
+                }
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() { break; }
+            }
+        }
+    }
+}
+
+/// Drop ledger entries older than [`LEDGER_WINDOW`].
+fn expire(ledger: &mut VecDeque<(Instant, i64)>, now: Instant) {
+    while ledger
+        .front()
+        .is_some_and(|(at, _)| now.duration_since(*at) >= LEDGER_WINDOW)
+    {
+        ledger.pop_front();
+    }
+}
+
+fn header_i64(headers: &HeaderMap, name: &str) -> Option<i64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn until_next_minute() -> Duration {
+    let ms = chrono::Utc::now().timestamp_millis().rem_euclid(60_000);
+    Duration::from_millis((60_000 - ms) as u64)
+}
+
+fn until_epoch_ms(reset: i64) -> Duration {
+    let now = chrono::Utc::now().timestamp_millis();
+    Duration::from_millis((reset - now).clamp(0, 60_000) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn binance_weight_near_limit_pauses() {
+        let limiter = RateLimiter::new(Exchange::Binance);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-mbx-used-weight-1m", HeaderValue::from_static("100"));
+        limiter.observe(StatusCode::OK, &headers);
+        assert_eq!(limiter.used_weight(), 100);
+        assert!(limiter.pause_remaining().is_zero());
+
+        headers.insert("x-mbx-used-weight-1m", HeaderValue::from_static("1150"));
+        limiter.observe(StatusCode::OK, &headers);
+        assert!(!limiter.pause_remaining().is_zero());
+    }
+
+    #[test]
+    fn too_many_requests_honours_retry_after() {
+        let limiter = RateLimiter::new(Exchange::Coinbase);
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("5"));
+        limiter.observe(StatusCode::TOO_MANY_REQUESTS, &headers);
+        let pause = limiter.pause_remaining();
+        assert!(pause > Duration::from_secs(4) && pause <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn venues_read_their_own_usage_headers() {
+        let bybit = RateLimiter::new(Exchange::Bybit);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-mbx-used-weight-1m", HeaderValue::from_static("5000"));
+        headers.insert("x-bapi-limit-status", HeaderValue::from_static("12"));
+        bybit.observe(StatusCode::OK, &headers);
+        assert_eq!(bybit.remaining(), 12);
+        assert_eq!(bybit.used_weight(), -1);
+        assert!(bybit.pause_remaining().is_zero());
+
+        let reset = chrono::Utc::now().timestamp_millis() + 3_000;
+        headers.insert("x-bapi-limit-status", HeaderValue::from_static("0"));
+        headers.insert(
+            "x-bapi-limit-reset-timestamp",
+            HeaderValue::from_str(&reset.to_string()).unwrap(),
+        );
+        bybit.observe(StatusCode::OK, &headers);
+        let pause = bybit.pause_remaining();
+        assert!(pause > Duration::from_secs(2) && pause <= Duration::from_secs(3));
+
+        let kucoin = RateLimiter::new(Exchange::Kucoin);
+        let mut headers = HeaderMap::new();
+        headers.insert("gw-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("gw-ratelimit-reset", HeaderValue::from_static("2000"));
+        kucoin.observe(StatusCode::OK, &headers);
+        let pause = kucoin.pause_remaining();
+        assert!(pause > Duration::from_secs(1) && pause <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn futures_apis_have_their_own_budget() {
+        assert!(!std::ptr::eq(
+            limiter(Exchange::Binance),
+            limiter(Exchange::BinanceFutures)
+        ));
+        for exchange in Exchange::ALL {
+            assert_eq!(limiter(exchange).exchange, exchange);
+        }
+
+        // USD-M weight is counted against 2400, so a reading that would pause
+        // spot still leaves room.
+        let fapi = RateLimiter::new(Exchange::BinanceFutures);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-mbx-used-weight-1m", HeaderValue::from_static("1150"));
+        fapi.observe(StatusCode::OK, &headers);
+        assert!(fapi.pause_remaining().is_zero());
+    }
+
+    #[tokio::test]
+    async fn low_priority_work_is_deferred_before_the_budget_runs_out() {
+        let limiter = RateLimiter::new(Exchange::Binance);
+        let now = Instant::now();
+        limiter
+            .ledger
+            .lock()
+            .unwrap()
+            .extend([(now - Duration::from_secs(40), 600), (now, 100)]);
+        limiter.acquire(10, Priority::Low).await;
+        assert_eq!(limiter.spent_weight(), 710);
+
+        // Low priority work is capped at 720 of 1200 and must wait for the
+        // oldest request to age out; snapshots still fit under 1080.
+        let wait = limiter.forecast(50, Priority::Low);
+        assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20));
+        assert!(limiter.forecast(50, Priority::High).is_zero());
+        assert!(!limiter.forecast(400, Priority::High).is_zero());
+        assert!(limiter.forecast(5_000, Priority::High) > Duration::from_secs(59));
+    }
+}
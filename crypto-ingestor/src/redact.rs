@@ -0,0 +1,175 @@
+//! Credential scrubbing for log output.
+//!
+//! Error paths often interpolate full request URLs, and with them API keys,
+//! listen keys, tokens and signatures. [`RedactingWriter`] wraps the log
+//! writer and passes every formatted line through [`scrub`], which blanks the
+//! values of sensitive query parameters and headers as well as any secret
+//! registered with [`register_secret`], such as the configured API keys.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::RwLock;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Parameter and header names whose values are always scrubbed, lowercase.
+const SENSITIVE_NAMES: [&str; 11] = [
+    "access_token",
+    "api_key",
+    "api-key",
+    "api_secret",
+    "apikey",
+    "listenkey",
+    "passphrase",
+    "secret",
+    "signature",
+    "token",
+    "x-mbx-apikey",
+];
+
+/// Secrets shorter than this are not registered, so that short config values
+/// cannot blank out unrelated text.
+const MIN_SECRET_LEN: usize = 8;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Scrub every occurrence of `secret` from future log lines.
+pub fn register_secret(secret: &str) {
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Replace credentials in `line` with [`REDACTED`].
+pub fn scrub(line: &str) -> Cow<'_, str> {
+    let mut out = Cow::Borrowed(line);
+    for secret in SECRETS.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        if out.contains(secret.as_str()) {
+            out = Cow::Owned(out.replace(secret.as_str(), REDACTED));
+        }
+    }
+    let lower = out.to_ascii_lowercase();
+    let mut values = Vec::new();
+    for name in SENSITIVE_NAMES {
+        for (start, _) in lower.match_indices(name) {
+            if let Some(range) = value_after(&lower, start, name.len()) {
+                values.push(range);
+            }
+        }
+    }
+    if values.is_empty() {
+        return out;
+    }
+    values.sort_unstable();
+    let mut scrubbed = String::with_capacity(out.len());
+    let mut pos = 0;
+    for (start, end) in values {
+        if start < pos {
+            continue;
+        }
+        scrubbed.push_str(&out[pos..start]);
+        scrubbed.push_str(REDACTED);
+        pos = end;
+    }
+    scrubbed.push_str(&out[pos..]);
+    Cow::Owned(scrubbed)
+}
+
+/// Byte range of the value assigned to the name at `start..start + len`, for
+/// `name=value`, `name: value` and `"name":"value"` forms. Names must stand
+/// alone, so `token` does not match inside `tokens` or `csrftoken`.
+fn value_after(s: &str, start: usize, len: usize) -> Option<(usize, usize)> {
+    let bytes = s.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'-';
+    if start > 0 && is_word(bytes[start - 1]) {
+        return None;
+    }
+    let mut i = start + len;
+    if bytes.get(i).is_some_and(|b| is_word(*b)) {
+        return None;
+    }
+    if bytes.get(i) == Some(&b'"') {
+        i += 1;
+    }
+    if !matches!(bytes.get(i), Some(b'=' | b':')) {
+        return None;
+    }
+    i += 1;
+    while bytes.get(i).is_some_and(|b| *b == b' ' || *b == b'"') {
+        i += 1;
+    }
+    let value_start = i;
+    let delimiter = |b: u8| {
+        matches!(b, b'&' | b'"' | b'\'' | b',' | b';' | b')' | b'}' | b']')
+            || b.is_ascii_whitespace()
+    };
+    while bytes.get(i).is_some_and(|b| !delimiter(*b)) {
+        i += 1;
+    }
+    (i > value_start && !s[value_start..i].eq_ignore_ascii_case(REDACTED))
+        .then_some((value_start, i))
+}
+
+/// [`MakeWriter`] wrapper scrubbing each log line before it is written.
+pub struct RedactingWriter<M> {
+    inner: M,
+}
+
+impl<M> RedactingWriter<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingWriter<M> {
+    type Writer = ScrubbingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ScrubbingWriter {
+            inner: self.inner.make_writer(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Buffers one formatted event and writes it scrubbed when flushed or dropped.
+pub struct ScrubbingWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ScrubbingWriter<W> {
+    fn emit(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let line = String::from_utf8_lossy(&self.buf);
+        let res = self.inner.write_all(scrub(&line).as_bytes());
+        self.buf.clear();
+        res
+    }
+}
+
+impl<W: Write> Write for ScrubbingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.emit()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ScrubbingWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.emit();
+    }
+}
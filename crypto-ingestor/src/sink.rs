@@ -1,6 +1,9 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio::sync::Mutex;
 
 use crate::error::IngestorError;
@@ -12,30 +15,80 @@ pub trait OutputSink: Send + Sync {
 
 pub type DynSink = Arc<dyn OutputSink>;
 
+/// Wire format written by [`StdoutSink`] and [`FileSink`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// One JSON event per line.
+    #[default]
+    Json,
+    /// Length-delimited protobuf events, see `canonicalizer/proto/events.proto`.
+    Proto,
+}
+
+impl Encoding {
+    async fn write<W: AsyncWrite + Unpin>(self, out: &mut W, line: &str) -> std::io::Result<()> {
+        match self {
+            Self::Json => {
+                out.write_all(line.as_bytes()).await?;
+                out.write_all(b"\n").await
+            }
+            Self::Proto => {
+                out.write_all(&canonicalizer::proto::encode_line(line))
+                    .await
+            }
+        }
+    }
+}
+
 pub struct StdoutSink {
-    stdout: Mutex<tokio::io::Stdout>,
+    out: Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    encoding: Encoding,
 }
 
 impl StdoutSink {
     pub fn new() -> Self {
+        Self::to_writer(tokio::io::stdout())
+    }
+
+    /// Console sink writing to stderr, for side output such as samples when
+    /// stdout carries protobuf frames.
+    pub fn stderr() -> Self {
+        Self::to_writer(tokio::io::stderr())
+    }
+
+    /// Console sink writing to `out` instead of a standard stream.
+    pub fn to_writer(out: impl AsyncWrite + Send + Unpin + 'static) -> Self {
         Self {
-            stdout: Mutex::new(tokio::io::stdout()),
+            out: Mutex::new(Box::new(out)),
+            encoding: Encoding::Json,
         }
     }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl OutputSink for StdoutSink {
     async fn send(&self, line: &str) -> Result<(), IngestorError> {
-        let mut stdout = self.stdout.lock().await;
-        stdout.write_all(line.as_bytes()).await?;
-        stdout.write_all(b"\n").await?;
+        let mut out = self.out.lock().await;
+        self.encoding.write(&mut *out, line).await?;
         Ok(())
     }
 }
 
 pub struct FileSink {
     file: Mutex<tokio::fs::File>,
+    encoding: Encoding,
 }
 
 impl FileSink {
@@ -47,16 +100,193 @@ impl FileSink {
             .await?;
         Ok(Self {
             file: Mutex::new(file),
+            encoding: Encoding::Json,
         })
     }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
 }
 
 #[async_trait]
 impl OutputSink for FileSink {
     async fn send(&self, line: &str) -> Result<(), IngestorError> {
         let mut file = self.file.lock().await;
-        file.write_all(line.as_bytes()).await?;
-        file.write_all(b"\n").await?;
+        self.encoding.write(&mut *file, line).await?;
         Ok(())
     }
 }
+
+/// Sink wrapper stamping `deployment`/`tenant` labels into every JSON event so
+/// several ingestor clusters can share downstream storage without collisions.
+///
+/// Lines that are not JSON objects are forwarded unchanged.
+pub struct LabelSink {
+    inner: DynSink,
+    labels: Vec<(&'static str, String)>,
+}
+
+impl LabelSink {
+    pub fn new(inner: DynSink, deployment: Option<String>, tenant: Option<String>) -> Self {
+        let mut labels = Vec::new();
+        if let Some(d) = deployment {
+            labels.push(("deployment", d));
+        }
+        if let Some(t) = tenant {
+            labels.push(("tenant", t));
+        }
+        Self { inner, labels }
+    }
+}
+
+#[async_trait]
+impl OutputSink for LabelSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        if self.labels.is_empty() {
+            return self.inner.send(line).await;
+        }
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(mut map)) => {
+                for (k, v) in &self.labels {
+                    map.insert((*k).to_string(), serde_json::Value::String(v.clone()));
+                }
+                let out = serde_json::Value::Object(map).to_string();
+                self.inner.send(&out).await
+            }
+            _ => self.inner.send(line).await,
+        }
+    }
+}
+
+/// Stamps the [`canonicalizer::Envelope`] fields on every JSON event:
+/// `schema_version`, a ULID `event_id`, `ingest_ts` and a per-agent
+/// `source_seq`. The fields are appended to the line as written, so the
+/// event's own fields keep their order and the event is not re-serialized.
+/// Events that already carry an `event_id`, e.g. replayed ones, pass through
+/// unchanged.
+///
+/// `source_seq` counts every event of an agent before any routing, so a sink
+/// whose routes filter events sees gaps for the events it did not take.
+pub struct EnvelopeSink {
+    inner: DynSink,
+    seqs: Mutex<HashMap<String, u64>>,
+}
+
+/// The fields of an event [`EnvelopeSink`] reads.
+#[derive(Deserialize)]
+struct EnvelopeHead<'a> {
+    #[serde(borrow, default)]
+    agent: Option<Cow<'a, str>>,
+    #[serde(default)]
+    event_id: Option<serde::de::IgnoredAny>,
+}
+
+impl EnvelopeSink {
+    pub fn new(inner: DynSink) -> Self {
+        Self {
+            inner,
+            seqs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for EnvelopeSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        let Some(body) = line
+            .trim_end()
+            .strip_suffix('}')
+            .filter(|_| line.trim_start().starts_with('{'))
+        else {
+            return self.inner.send(line).await;
+        };
+        let head = match serde_json::from_str::<EnvelopeHead>(line) {
+            Ok(h) if h.event_id.is_none() => h,
+            _ => return self.inner.send(line).await,
+        };
+        let agent = head.agent.unwrap_or_default();
+        let seq = {
+            let mut seqs = self.seqs.lock().await;
+            let seq = seqs.entry(agent.into_owned()).or_default();
+            *seq += 1;
+            *seq
+        };
+        let sep = if body.trim_end().ends_with('{') {
+            ""
+        } else {
+            ","
+        };
+        let stamped = format!(
+            r#"{body}{sep}"schema_version":{},"event_id":"{}","ingest_ts":{},"source_seq":{seq}}}"#,
+            canonicalizer::SCHEMA_VERSION,
+            ulid::Ulid::new(),
+            chrono::Utc::now().timestamp_millis(),
+        );
+        self.inner.send(&stamped).await
+    }
+}
+
+/// Picks 1-in-`every` events per event type and symbol.
+pub struct Sampler {
+    every: u64,
+    counts: Mutex<HashMap<(String, String), u64>>,
+}
+
+impl Sampler {
+    pub fn new(every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `line` is the next sample of its type and symbol.
+    pub async fn hit(&self, line: &str) -> bool {
+        let v: serde_json::Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+        let typ = v.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let sym = v.get("s").and_then(|s| s.as_str()).unwrap_or("");
+        let mut counts = self.counts.lock().await;
+        let n = counts
+            .entry((typ.to_string(), sym.to_string()))
+            .or_insert(0);
+        let hit = *n % self.every == 0;
+        *n += 1;
+        hit
+    }
+}
+
+/// Sink wrapper forwarding every event to `inner` and 1-in-`every` events per
+/// event type and symbol to `sample`, giving developers a readable tail of the
+/// stream without the full firehose.
+pub struct SamplingSink {
+    inner: DynSink,
+    sample: DynSink,
+    sampler: Sampler,
+}
+
+impl SamplingSink {
+    pub fn new(inner: DynSink, sample: DynSink, every: u64) -> Self {
+        Self {
+            inner,
+            sample,
+            sampler: Sampler::new(every),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for SamplingSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        if self.sampler.hit(line).await {
+            if let Err(e) = self.sample.send(line).await {
+                tracing::warn!(error=%e, "sample sink error");
+            }
+        }
+        self.inner.send(line).await
+    }
+}
@@ -0,0 +1,194 @@
+//! User-configured transforms applied to events before they reach the sink.
+//!
+//! Transforms run in the order given and each may be limited to some agents,
+//! event types or asset classes. They are declared in the config file, e.g.
+//! in TOML:
+//!
+//! ```toml
+//! [[transforms]]
+//! kind = "symbol_rewrite"
+//! from = "XBT-USD"
+//! to = "BTC-USD"
+//!
+//! [[transforms]]
+//! kind = "scale"
+//! types = ["trade"]
+//! agents = ["bybit"]
+//! asset_classes = ["perp"]
+//! field = "q"
+//! factor = "0.001"
+//! ```
+//!
+//! Code embedding the ingestor can also implement [`Transform`] directly and
+//! hand its own transforms to [`TransformSink::new`].
+
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// One step of the transform pipeline.
+pub trait Transform: Send + Sync {
+    /// Rewrite `event` in place.
+    fn apply(&self, event: &mut Map<String, Value>);
+}
+
+/// Declarative transform as it appears in the `transforms` config list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TransformConfig {
+    /// Only apply to events from these agents; all agents when empty.
+    #[serde(default)]
+    pub agents: Vec<String>,
+    /// Only apply to these event types; all types when empty.
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Only apply to events of these asset classes, e.g. `perp`; events
+    /// without an `ac` are `spot`. All classes when empty.
+    #[serde(default)]
+    pub asset_classes: Vec<String>,
+    #[serde(flatten)]
+    pub op: TransformOp,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TransformOp {
+    /// Replace the canonical symbol `from` with `to`.
+    SymbolRewrite { from: String, to: String },
+    /// Set `field` to a constant value.
+    Enrich { field: String, value: String },
+    /// Remove `fields` from the event.
+    Redact { fields: Vec<String> },
+    /// Multiply the decimal string in `field` by `factor`, e.g. to convert
+    /// contract quantities into base units.
+    Scale { field: String, factor: String },
+}
+
+struct Configured {
+    agents: Vec<String>,
+    types: Vec<String>,
+    asset_classes: Vec<String>,
+    op: Op,
+}
+
+enum Op {
+    SymbolRewrite { from: String, to: String },
+    Enrich { field: String, value: Value },
+    Redact { fields: Vec<String> },
+    Scale { field: String, factor: Decimal },
+}
+
+impl Configured {
+    fn matches(&self, event: &Map<String, Value>) -> bool {
+        let field_in = |key: &str, default: Option<&str>, allowed: &[String]| {
+            allowed.is_empty()
+                || event
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .or(default)
+                    .is_some_and(|v| allowed.iter().any(|a| a == v))
+        };
+        field_in("agent", None, &self.agents)
+            && field_in("type", None, &self.types)
+            && field_in("ac", Some("spot"), &self.asset_classes)
+    }
+}
+
+impl Transform for Configured {
+    fn apply(&self, event: &mut Map<String, Value>) {
+        if !self.matches(event) {
+            return;
+        }
+        match &self.op {
+            Op::SymbolRewrite { from, to } => {
+                if event.get("s").and_then(|s| s.as_str()) == Some(from) {
+                    event.insert("s".into(), Value::String(to.clone()));
+                }
+            }
+            Op::Enrich { field, value } => {
+                event.insert(field.clone(), value.clone());
+            }
+            Op::Redact { fields } => {
+                for f in fields {
+                    event.remove(f);
+                }
+            }
+            Op::Scale { field, factor } => {
+                let scaled = event
+                    .get(field)
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Decimal::from_str(s).ok())
+                    .and_then(|d| d.checked_mul(*factor));
+                if let Some(d) = scaled {
+                    event.insert(field.clone(), Value::String(d.normalize().to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// Build the transforms declared in the config, in order.
+pub fn from_config(configs: &[TransformConfig]) -> Result<Vec<Box<dyn Transform>>, IngestorError> {
+    configs
+        .iter()
+        .map(|c| {
+            let op = match &c.op {
+                TransformOp::SymbolRewrite { from, to } => Op::SymbolRewrite {
+                    from: from.clone(),
+                    to: to.clone(),
+                },
+                TransformOp::Enrich { field, value } => Op::Enrich {
+                    field: field.clone(),
+                    value: Value::String(value.clone()),
+                },
+                TransformOp::Redact { fields } => Op::Redact {
+                    fields: fields.clone(),
+                },
+                TransformOp::Scale { field, factor } => Op::Scale {
+                    field: field.clone(),
+                    factor: Decimal::from_str(factor).map_err(|e| {
+                        IngestorError::Other(format!("invalid scale factor {factor}: {e}"))
+                    })?,
+                },
+            };
+            Ok(Box::new(Configured {
+                agents: c.agents.clone(),
+                types: c.types.clone(),
+                asset_classes: c.asset_classes.clone(),
+                op,
+            }) as Box<dyn Transform>)
+        })
+        .collect()
+}
+
+/// Sink wrapper running every event through the transform pipeline.
+pub struct TransformSink {
+    inner: DynSink,
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl TransformSink {
+    pub fn new(inner: DynSink, transforms: Vec<Box<dyn Transform>>) -> Self {
+        Self { inner, transforms }
+    }
+}
+
+#[async_trait]
+impl OutputSink for TransformSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        match serde_json::from_str::<Value>(line) {
+            Ok(Value::Object(mut map)) => {
+                for t in &self.transforms {
+                    t.apply(&mut map);
+                }
+                self.inner.send(&Value::Object(map).to_string()).await
+            }
+            _ => self.inner.send(line).await,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Detection of trade prints that look like wash trading.
+//!
+//! [`WashTradeSink`] collects the `trade` events of every venue and instrument
+//! over a window and flags two patterns:
+//!
+//! - identical price and size prints repeating at a regular cadence, and
+//! - same-size ping-pong, where consecutive prints bounce between two prices
+//!   and return to where they started.
+//!
+//! At the end of each window a [`WashTradeSuspect`] event is emitted for every
+//! venue and instrument with flagged trades, scored by the flagged share of the
+//! traded quantity, so consumers can discount that venue's volume. Spot and
+//! perp prints of one symbol are kept on separate tapes, as two markets trading
+//! a basis apart would otherwise look like ping-pong.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use canonicalizer::{AssetClass, InstrumentKey, WashTradeSuspect};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::error::IngestorError;
+use crate::sink::{DynSink, OutputSink};
+
+/// Identical prints needed before their cadence is considered.
+const MIN_REPEATS: usize = 3;
+/// Maximum coefficient of variation of the gaps between repeated prints.
+const CADENCE_TOLERANCE: f64 = 0.2;
+
+#[derive(Clone, Copy)]
+struct Print {
+    ts: i64,
+    price: f64,
+    qty: f64,
+}
+
+#[derive(Default)]
+struct State {
+    prints: HashMap<(String, InstrumentKey), Vec<Print>>,
+    window_start: Option<Instant>,
+}
+
+/// Sink wrapper scoring venues for suspicious trade prints.
+pub struct WashTradeSink {
+    inner: DynSink,
+    window: Duration,
+    state: Mutex<State>,
+}
+
+impl WashTradeSink {
+    pub fn new(inner: DynSink, window: Duration) -> Self {
+        Self {
+            inner,
+            window,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    async fn observe(&self, line: &str) {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        if v.get("type").and_then(|t| t.as_str()) != Some("trade") {
+            return;
+        }
+        let num = |k: &str| match v.get(k)? {
+            serde_json::Value::String(s) => s.parse::<f64>().ok(),
+            n => n.as_f64(),
+        };
+        let (Some(agent), Some(instrument), Some(ts), Some(price), Some(qty)) = (
+            v.get("agent").and_then(|a| a.as_str()),
+            InstrumentKey::from_event(&v),
+            v.get("ts").and_then(|t| t.as_i64()),
+            num("p"),
+            num("q"),
+        ) else {
+            return;
+        };
+        self.state
+            .lock()
+            .await
+            .prints
+            .entry((agent.to_string(), instrument))
+            .or_default()
+            .push(Print { ts, price, qty });
+    }
+
+    /// Emit a [`WashTradeSuspect`] event for every venue and instrument with
+    /// flagged trades since the last report and start a new window.
+    pub async fn report(&self) -> Result<(), IngestorError> {
+        let prints = {
+            let mut state = self.state.lock().await;
+            state.window_start = Some(Instant::now());
+            std::mem::take(&mut state.prints)
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        for ((agent, instrument), mut prints) in prints {
+            prints.sort_by_key(|p| p.ts);
+            let flags = flag(&prints);
+            let flagged = flags.iter().filter(|f| **f).count();
+            if flagged == 0 {
+                continue;
+            }
+            let total: f64 = prints.iter().map(|p| p.qty).sum();
+            let flagged_qty: f64 = prints
+                .iter()
+                .zip(&flags)
+                .filter(|(_, f)| **f)
+                .map(|(p, _)| p.qty)
+                .sum();
+            let event = WashTradeSuspect {
+                agent,
+                r#type: "wash_trade_suspect".to_string(),
+                symbol: instrument.symbol.to_string(),
+                trades: prints.len() as u64,
+                flagged: flagged as u64,
+                score: if total > 0.0 {
+                    flagged_qty / total
+                } else {
+                    0.0
+                },
+                ac: (instrument.class != AssetClass::Spot).then_some(instrument.class),
+                settle: (instrument.class != AssetClass::Spot)
+                    .then(|| instrument.settle.to_string()),
+                id: instrument.id().map(|id| id.to_string()),
+                timestamp: now,
+            };
+            self.inner
+                .send(&serde_json::to_string(&event).unwrap())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn report_due(&self) -> bool {
+        let mut state = self.state.lock().await;
+        match state.window_start {
+            Some(t) => t.elapsed() >= self.window,
+            None => {
+                state.window_start = Some(Instant::now());
+                false
+            }
+        }
+    }
+}
+
+/// Flag the prints (in time order) matching either wash-trade pattern.
+fn flag(prints: &[Print]) -> Vec<bool> {
+    let mut flags = vec![false; prints.len()];
+
+    let mut repeats: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (i, p) in prints.iter().enumerate() {
+        repeats
+            .entry((p.price.to_bits(), p.qty.to_bits()))
+            .or_default()
+            .push(i);
+    }
+    for idx in repeats.values().filter(|idx| idx.len() >= MIN_REPEATS) {
+        let gaps: Vec<f64> = idx
+            .windows(2)
+            .map(|w| (prints[w[1]].ts - prints[w[0]].ts) as f64)
+            .collect();
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        let var = gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / gaps.len() as f64;
+        if mean == 0.0 || var.sqrt() / mean <= CADENCE_TOLERANCE {
+            for &i in idx {
+                flags[i] = true;
+            }
+        }
+    }
+
+    for i in 2..prints.len() {
+        let (a, b, c) = (prints[i - 2], prints[i - 1], prints[i]);
+        if a.qty == b.qty && b.qty == c.qty && a.price == c.price && a.price != b.price {
+            flags[i - 2] = true;
+            flags[i - 1] = true;
+            flags[i] = true;
+        }
+    }
+    flags
+}
+
+#[async_trait]
+impl OutputSink for WashTradeSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        self.observe(line).await;
+        self.inner.send(line).await?;
+        if self.report_due().await {
+            self.report().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(ts: i64, price: f64, qty: f64) -> Print {
+        Print { ts, price, qty }
+    }
+
+    #[test]
+    fn flags_regular_repeats_and_ping_pong_only() {
+        let prints = [
+            print(0, 100.0, 1.0),
+            print(1_000, 100.0, 1.0),
+            print(2_000, 100.0, 1.0),
+            print(2_500, 99.0, 0.3),
+            print(3_000, 100.0, 1.0),
+            print(3_700, 101.0, 0.7),
+        ];
+        let flags = flag(&prints);
+        assert_eq!(flags, [true, true, true, false, true, false]);
+
+        let ping_pong = [
+            print(0, 50.0, 2.0),
+            print(10, 50.5, 2.0),
+            print(25, 50.0, 2.0),
+            print(90, 51.0, 1.0),
+        ];
+        assert_eq!(flag(&ping_pong), [true, true, true, false]);
+
+        let irregular = [
+            print(0, 10.0, 1.0),
+            print(100, 10.0, 1.0),
+            print(5_000, 10.0, 1.0),
+        ];
+        assert_eq!(flag(&irregular), [false, false, false]);
+    }
+
+    #[tokio::test]
+    async fn spot_and_perp_prints_keep_separate_tapes() {
+        let sink = WashTradeSink::new(
+            std::sync::Arc::new(crate::sink::StdoutSink::new()),
+            Duration::from_secs(60),
+        );
+        // Same-size prints alternating between a spot and a perp market a
+        // basis apart.
+        for (ts, perp) in [(0, false), (10, true), (20, false), (30, true)] {
+            let mut v = serde_json::json!({"agent": "bybit", "type": "trade", "s": "BTC-USDT",
+                "ts": ts, "p": if perp { "100.2" } else { "100" }, "q": "1"});
+            if perp {
+                v["ac"] = "perp".into();
+                v["settle"] = "USDT".into();
+            }
+            sink.observe(&v.to_string()).await;
+        }
+
+        let state = sink.state.lock().await;
+        assert_eq!(state.prints.len(), 2);
+        for prints in state.prints.values() {
+            assert_eq!(flag(prints), [false, false]);
+        }
+    }
+}
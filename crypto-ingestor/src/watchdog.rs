@@ -0,0 +1,93 @@
+//! Idle watchdog for websocket connections.
+//!
+//! A half-open TCP connection looks healthy to the reader: no error, no close,
+//! just silence. Agents [`feed`](Watchdog::feed) the watchdog on every frame
+//! received. After half the idle window without one it asks for a ping probe,
+//! which a live server answers with a pong, and after the full window it
+//! reports the connection as stalled so the agent reconnects.
+
+use std::time::Duration;
+
+use futures_util::SinkExt;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::http_client::WsStream;
+
+/// What a silent connection needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idle {
+    /// Silent for half the window: send a ping.
+    Probe,
+    /// Silent for the whole window: reconnect.
+    Stalled,
+}
+
+pub struct Watchdog {
+    window: Duration,
+    last_seen: Instant,
+    probed: bool,
+    check: Interval,
+}
+
+impl Watchdog {
+    /// Watch for `window` of silence; a zero window disables the watchdog.
+    pub fn new(window: Duration) -> Self {
+        let period = (window / 4).max(Duration::from_millis(10));
+        let mut check = tokio::time::interval_at(Instant::now() + period, period);
+        check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            window,
+            last_seen: Instant::now(),
+            probed: false,
+            check,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Record that a frame was received.
+    pub fn feed(&mut self) {
+        self.last_seen = Instant::now();
+        self.probed = false;
+    }
+
+    /// Resolve when the connection needs a probe or has stalled. Cancel safe,
+    /// so it can be polled in a `select!` loop alongside the stream.
+    pub async fn tick(&mut self) -> Idle {
+        if self.window.is_zero() {
+            return std::future::pending().await;
+        }
+        loop {
+            self.check.tick().await;
+            let silent = self.last_seen.elapsed();
+            if silent >= self.window {
+                return Idle::Stalled;
+            }
+            if silent >= self.window / 2 && !self.probed {
+                self.probed = true;
+                return Idle::Probe;
+            }
+        }
+    }
+}
+
+/// Act on a [`Watchdog::tick`] result: ping on [`Idle::Probe`], log on
+/// [`Idle::Stalled`]. Returns `false` when the connection should be dropped.
+pub async fn keep_alive(ws: &mut WsStream, idle: Idle, window: Duration) -> bool {
+    match idle {
+        Idle::Probe => match ws.send(Message::Ping(Vec::new())).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!(error=%e, "failed to send ping");
+                false
+            }
+        },
+        Idle::Stalled => {
+            tracing::warn!(?window, "no data or pong received; reconnecting");
+            false
+        }
+    }
+}
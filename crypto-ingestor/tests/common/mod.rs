@@ -0,0 +1,21 @@
+//! Helpers shared by the integration tests.
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use ingestor::error::IngestorError;
+use ingestor::sink::OutputSink;
+
+/// Sink collecting every line it is sent.
+#[derive(Default)]
+pub struct VecSink {
+    pub lines: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl OutputSink for VecSink {
+    async fn send(&self, line: &str) -> Result<(), IngestorError> {
+        self.lines.lock().await.push(line.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use ingestor::dead_letter;
+use ingestor::sink::DynSink;
+
+mod common;
+use common::VecSink;
+
+#[tokio::test]
+async fn unparseable_messages_are_sampled_to_the_sink() {
+    let sink = Arc::new(VecSink::default());
+    dead_letter::init(sink.clone() as DynSink, 2);
+
+    for i in 0..4 {
+        let raw = format!("{{\"e\":\"trade\",{i}");
+        let err = serde_json::from_str::<serde_json::Value>(&raw).unwrap_err();
+        dead_letter::record("binance", &raw, &err);
+    }
+    assert_eq!(dead_letter::failures(), 4);
+
+    for _ in 0..50 {
+        if sink.lines.lock().await.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let lines = sink.lines.lock().await;
+    assert_eq!(lines.len(), 2);
+    let v: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(v["type"], "dead_letter");
+    assert_eq!(v["agent"], "binance");
+    assert_eq!(v["raw"], "{\"e\":\"trade\",0");
+    assert!(v["error"].as_str().unwrap().contains("line 1"));
+    assert!(v.get("stream").is_none());
+    drop(lines);
+
+    // Streams parsed differently keep their name on the dead letter.
+    dead_letter::record_no_event_in("bybit", Some("linear"), "{\"topic\":\"x\"}");
+    for _ in 0..50 {
+        if sink.lines.lock().await.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let lines = sink.lines.lock().await;
+    let v: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+    assert_eq!(v["agent"], "bybit");
+    assert_eq!(v["stream"], "linear");
+}
@@ -0,0 +1,288 @@
+//! Exchange outages replayed against agents by scripted mock servers.
+//!
+//! Each test scripts the faults a mock exchange injects into its stream and
+//! checks that the agent recovers and counts the fault in `ingest_stats`.
+//! The counters are process-wide, so tests hold [`SERIAL`] while they run.
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+use ingestor::agent::Agent;
+use ingestor::agents::{coinbase::CoinbaseAgent, STREAM_SEQ_GAPS};
+use ingestor::config::Settings;
+use ingestor::dead_letter;
+use ingestor::gaps::{GapKind, GapScanner};
+use ingestor::ingest_stats;
+use ingestor::rate_limit::{Exchange, Priority, RateLimiter};
+
+static SERIAL: Mutex<()> = Mutex::const_new(());
+
+/// One step of a scripted mock exchange connection.
+enum Fault {
+    /// Send a message unchanged.
+    Send(Value),
+    /// Send a message twice in a row.
+    Duplicate(Value),
+    /// Send a text frame that is not valid JSON.
+    Malformed(&'static str),
+    /// Drop the connection without a close frame.
+    Disconnect,
+}
+
+/// Serve one scripted connection per entry of `scripts`, in order. Each
+/// connection waits for the client's subscription before its script runs and,
+/// unless the script disconnects, stays open until the client closes it.
+async fn mock_exchange(scripts: Vec<Vec<Fault>>) -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        for script in scripts {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            let _ = ws.next().await;
+            let mut open = true;
+            for fault in script {
+                match fault {
+                    Fault::Send(v) => ws.send(Message::Text(v.to_string())).await.unwrap(),
+                    Fault::Duplicate(v) => {
+                        for _ in 0..2 {
+                            ws.send(Message::Text(v.to_string())).await.unwrap();
+                        }
+                    }
+                    Fault::Malformed(raw) => ws.send(Message::Text(raw.into())).await.unwrap(),
+                    Fault::Disconnect => {
+                        open = false;
+                        break;
+                    }
+                }
+            }
+            if open {
+                while let Some(Ok(msg)) = ws.next().await {
+                    if msg.is_close() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    (url, server)
+}
+
+fn coinbase_match(trade_id: i64) -> Value {
+    json!({
+        "type": "match",
+        "product_id": "BTC-USD",
+        "trade_id": trade_id,
+        "price": "100.00",
+        "size": "0.5",
+        "time": "2024-01-01T00:00:00Z"
+    })
+}
+
+fn coinbase_l2update(sequence: u64) -> Value {
+    json!({
+        "type": "l2update",
+        "product_id": "BTC-USD",
+        "sequence": sequence,
+        "changes": [["buy", "100.00", "1"]],
+        "time": "2024-01-01T00:00:00Z"
+    })
+}
+
+/// Run a Coinbase agent against `url` until `count` lines were emitted.
+async fn run_coinbase(url: String, count: usize) -> Vec<Value> {
+    let cfg = Settings {
+        coinbase_ws_url: url,
+        coinbase_max_reconnect_delay_secs: 1,
+        ..Default::default()
+    };
+    let mut agent = CoinbaseAgent::new(vec!["BTC-USD".into()], &cfg);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (tx, mut rx) = mpsc::channel::<String>(16);
+    let handle = tokio::spawn(async move {
+        agent.run(shutdown_rx, tx).await.unwrap();
+    });
+
+    let mut lines = Vec::new();
+    while lines.len() < count {
+        let line = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("agent stalled")
+            .expect("agent stopped");
+        let v: Value = serde_json::from_str(&line).unwrap();
+        // Book snapshots are fetched from the live REST API, if reachable.
+        if v["type"] != "snapshot" {
+            lines.push(v);
+        }
+    }
+
+    shutdown_tx.send(true).unwrap();
+    handle.await.unwrap();
+    lines
+}
+
+fn coinbase_stats() -> Option<canonicalizer::IngestStats> {
+    ingest_stats::drain(Duration::from_secs(60))
+        .into_iter()
+        .find(|s| s.agent == "coinbase")
+}
+
+#[tokio::test]
+async fn disconnect_mid_stream_reconnects_and_resumes() {
+    let _serial = SERIAL.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let (url, server) = mock_exchange(vec![
+        vec![Fault::Send(coinbase_match(1)), Fault::Disconnect],
+        vec![Fault::Send(coinbase_match(2))],
+    ])
+    .await;
+
+    let lines = run_coinbase(url, 2).await;
+    assert_eq!(lines[0]["t"], 1);
+    assert_eq!(lines[1]["t"], 2);
+    server.await.unwrap();
+
+    let stats = coinbase_stats().unwrap();
+    assert_eq!(stats.reconnects, 1);
+}
+
+#[tokio::test]
+async fn malformed_json_is_dead_lettered_and_the_stream_continues() {
+    let _serial = SERIAL.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let failures = dead_letter::failures();
+    let (url, server) = mock_exchange(vec![vec![
+        Fault::Malformed(r#"{"type":"match","product_id":"#),
+        Fault::Send(coinbase_match(7)),
+    ]])
+    .await;
+
+    let lines = run_coinbase(url, 1).await;
+    assert_eq!(lines[0]["t"], 7);
+    server.await.unwrap();
+
+    assert_eq!(dead_letter::failures() - failures, 1);
+    let stats = coinbase_stats().unwrap();
+    assert_eq!(stats.unparseable, 1);
+    assert_eq!(stats.reconnects, 0);
+}
+
+#[tokio::test]
+async fn data_messages_yielding_no_event_are_dead_lettered() {
+    let _serial = SERIAL.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let failures = dead_letter::failures();
+    let (url, server) = mock_exchange(vec![vec![
+        Fault::Send(json!({"type": "heartbeat", "product_id": "BTC-USD", "sequence": 1})),
+        Fault::Send(json!({"type": "auction", "product_id": "BTC-USD", "sequence": 2})),
+        Fault::Send(coinbase_match(7)),
+    ]])
+    .await;
+
+    let lines = run_coinbase(url, 1).await;
+    assert_eq!(lines[0]["t"], 7);
+    server.await.unwrap();
+
+    // The heartbeat is a control message; the unknown type is not.
+    assert_eq!(dead_letter::failures() - failures, 1);
+    assert_eq!(coinbase_stats().unwrap().unparseable, 1);
+}
+
+#[tokio::test]
+async fn out_of_order_sequences_count_one_gap() {
+    let _serial = SERIAL.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let gaps = STREAM_SEQ_GAPS.load(Ordering::Relaxed);
+    let (url, server) = mock_exchange(vec![[10, 12, 11, 13]
+        .into_iter()
+        .map(|seq| Fault::Send(coinbase_l2update(seq)))
+        .collect()])
+    .await;
+
+    let lines = run_coinbase(url, 4).await;
+    assert!(lines.iter().all(|v| v["type"] == "l2_diff"));
+    server.await.unwrap();
+
+    // 12 skips 11; the late 11 neither counts nor makes 13 look like a gap.
+    assert_eq!(STREAM_SEQ_GAPS.load(Ordering::Relaxed) - gaps, 1);
+    assert_eq!(coinbase_stats().unwrap().gaps, 1);
+}
+
+#[tokio::test]
+async fn duplicate_and_reordered_trades_pass_through_for_downstream_checks() {
+    let _serial = SERIAL.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let (url, server) = mock_exchange(vec![vec![
+        Fault::Send(coinbase_match(1)),
+        Fault::Duplicate(coinbase_match(2)),
+        Fault::Send(coinbase_match(4)),
+        Fault::Send(coinbase_match(3)),
+    ]])
+    .await;
+
+    let lines = run_coinbase(url, 5).await;
+    let ids: Vec<_> = lines.iter().map(|v| v["t"].as_i64().unwrap()).collect();
+    assert_eq!(ids, [1, 2, 2, 4, 3]);
+    server.await.unwrap();
+
+    // The gap scanner ignores the repeat and the late trade, reporting only
+    // the hole left when 4 arrived before 3.
+    let mut scanner = GapScanner::new(0);
+    let found: Vec<_> = lines
+        .iter()
+        .flat_map(|v| scanner.observe(&v.to_string()))
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, GapKind::Seq);
+    assert_eq!((found[0].from, found[0].to), (3, 3));
+    assert!(coinbase_stats().is_none_or(|s| s.gaps == 0 && s.reconnects == 0));
+}
+
+#[tokio::test]
+async fn rate_limit_storm_pauses_requests_until_retry_after() {
+    let _serial = SERIAL.lock().await;
+    const STORM: usize = 2;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/v3/depth", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        for n in 0..=STORM {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let response = if n < STORM {
+                "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            } else {
+                "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}"
+            };
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    let limiter = RateLimiter::new(Exchange::Binance);
+    let client = reqwest::Client::new();
+    let start = tokio::time::Instant::now();
+    let mut attempts = 0;
+    loop {
+        limiter.acquire(1, Priority::High).await;
+        attempts += 1;
+        let resp = client.get(&url).send().await.unwrap();
+        limiter.observe(resp.status(), resp.headers());
+        if resp.status().is_success() {
+            break;
+        }
+        assert!(!limiter.pause_remaining().is_zero());
+    }
+    server.await.unwrap();
+
+    assert_eq!(attempts, STORM + 1);
+    assert!(start.elapsed() >= Duration::from_secs(STORM as u64));
+    assert!(limiter.pause_remaining().is_zero());
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use ingestor::agents::binance;
+use ingestor::gaps::{GapKind, GapScanner};
+use serde_json::json;
+
+#[test]
+fn trade_id_skips_and_silence_are_reported() {
+    let mut scanner = GapScanner::new(5_000);
+    let trade = |t: i64, ts: i64| {
+        json!({"agent": "binance", "type": "trade", "s": "BTC-USDT", "t": t, "p": "1", "q": "1", "ts": ts})
+            .to_string()
+    };
+    assert!(scanner.observe(&trade(10, 1_000)).is_empty());
+    assert!(scanner.observe(&trade(11, 2_000)).is_empty());
+    // replayed or out-of-order prints never open a gap
+    assert!(scanner.observe(&trade(11, 2_000)).is_empty());
+
+    let gaps = scanner.observe(&trade(15, 9_000));
+    assert_eq!(gaps.len(), 2);
+    assert_eq!(gaps[0].kind, GapKind::Seq);
+    assert_eq!((gaps[0].from, gaps[0].to, gaps[0].missing), (12, 14, 3));
+    assert_eq!(gaps[1].kind, GapKind::Time);
+    assert_eq!((gaps[1].from, gaps[1].to), (2_000, 9_000));
+
+    // streams are tracked per symbol
+    let other =
+        json!({"agent": "binance", "type": "trade", "s": "ETH-USDT", "t": 500, "ts": 9_500});
+    assert!(scanner.observe(&other.to_string()).is_empty());
+}
+
+#[test]
+fn missing_bars_are_counted_per_interval() {
+    let mut scanner = GapScanner::new(0);
+    let bar = |i: u64, ts: i64| {
+        json!({"agent": "binance", "type": "ohlcv", "s": "BTC-USDT", "i": i, "o": "1", "h": "1",
+            "l": "1", "c": "1", "v": "1", "ts": ts})
+        .to_string()
+    };
+    assert!(scanner.observe(&bar(60, 0)).is_empty());
+    assert!(scanner.observe(&bar(60, 60_000)).is_empty());
+    // the current bar is polled repeatedly
+    assert!(scanner.observe(&bar(60, 60_000)).is_empty());
+    assert!(scanner.observe(&bar(300, 0)).is_empty());
+
+    let gaps = scanner.observe(&bar(60, 300_000));
+    assert_eq!(gaps.len(), 1);
+    assert_eq!(gaps[0].kind, GapKind::Bars);
+    assert_eq!(gaps[0].interval, Some(60));
+    assert_eq!(
+        (gaps[0].from, gaps[0].to, gaps[0].missing),
+        (120_000, 240_000, 3)
+    );
+
+    let v: serde_json::Value = serde_json::to_value(&gaps[0]).unwrap();
+    assert_eq!(v["type"], "gap");
+    assert_eq!(v["kind"], "bars");
+    assert!(scanner.observe(&bar(300, 300_000)).is_empty());
+}
+
+#[test]
+fn spot_and_perp_trade_ids_are_tracked_apart() {
+    let mut scanner = GapScanner::new(0);
+    let (mut spot_ids, mut perp_ids) = (HashMap::new(), HashMap::new());
+    let mut spot = |t: i64| {
+        binance::parse_event(
+            &json!({"e": "trade", "E": t, "s": "BTCUSDT", "t": t, "p": "100", "q": "1", "T": t, "m": false}),
+            &mut spot_ids,
+        )
+        .unwrap()
+    };
+    let mut perp = |a: i64| {
+        binance::futures::parse_event(
+            &json!({"e": "aggTrade", "E": a, "s": "BTCUSDT", "a": a, "p": "100", "q": "1", "T": a, "m": false}),
+            &mut perp_ids,
+        )
+        .remove(0)
+    };
+
+    // Both streams are BTC-USDT; interleaving their ids is not a gap.
+    assert!(scanner.observe(&spot(10)).is_empty());
+    assert!(scanner.observe(&perp(5_000)).is_empty());
+    assert!(scanner.observe(&spot(11)).is_empty());
+    assert!(scanner.observe(&perp(5_001)).is_empty());
+
+    let gaps = scanner.observe(&perp(5_004));
+    assert_eq!(gaps.len(), 1);
+    assert_eq!((gaps[0].from, gaps[0].to), (5_002, 5_003));
+    assert_eq!(gaps[0].ac.as_deref(), Some("perp"));
+    assert_eq!(gaps[0].id.as_deref(), Some("BTC-USDT-PERP"));
+}
@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use ingestor::agents::SnapshotPacer;
+use ingestor::config::Settings;
+use ingestor::http_client;
+use ingestor::watchdog::{Idle, Watchdog};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Error as WsError;
+
+#[tokio::test]
+async fn hung_websocket_handshake_times_out() {
+    let settings = Settings {
+        connect_timeout_secs: 1,
+        ..Settings::default()
+    };
+    http_client::init(&settings).unwrap();
+
+    // accept the TCP connection but never answer the upgrade request
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let start = Instant::now();
+    let res = http_client::connect_ws(&format!("ws://{addr}")).await;
+    match res {
+        Err(WsError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn missing_ca_file_is_an_error() {
+    let settings = Settings {
+        tls_ca_file: Some("/nonexistent/ca.pem".into()),
+        ..Settings::default()
+    };
+    assert!(http_client::init(&settings).is_err());
+}
+
+#[tokio::test]
+async fn watchdog_probes_then_reports_a_stall() {
+    let window = Duration::from_millis(200);
+    let mut watchdog = Watchdog::new(window);
+    let start = Instant::now();
+    assert_eq!(watchdog.tick().await, Idle::Probe);
+    assert!(start.elapsed() >= window / 2);
+    assert_eq!(watchdog.tick().await, Idle::Stalled);
+    assert!(start.elapsed() >= window);
+
+    // frames push the deadline back and re-arm the probe
+    watchdog.feed();
+    let fed = Instant::now();
+    assert_eq!(watchdog.tick().await, Idle::Probe);
+    assert!(fed.elapsed() >= window / 2);
+}
+
+#[tokio::test]
+async fn zero_window_disables_the_watchdog() {
+    let mut watchdog = Watchdog::new(Duration::ZERO);
+    let tick = tokio::time::timeout(Duration::from_millis(100), watchdog.tick()).await;
+    assert!(tick.is_err());
+}
+
+#[test]
+fn snapshot_pacing_follows_spread_and_volatility() {
+    let book = |bid: &str, ask: &str| {
+        serde_json::json!({"type": "snapshot", "bids": [[bid, "1"]], "asks": [[ask, "1"]]})
+            .to_string()
+    };
+    let now = tokio::time::Instant::now();
+    let minute = Duration::from_secs(60);
+
+    let mut pacer = SnapshotPacer::default();
+    assert_eq!(pacer.delay(now), Duration::from_secs(60));
+    assert_eq!(pacer.delay(now + minute), Duration::from_secs(5));
+
+    // A tight, still book is slowed once its volatility is known.
+    pacer.observe(&book("99.995", "100.005"), now);
+    assert_eq!(pacer.delay(now), Duration::from_secs(60));
+    pacer.observe(&book("99.995", "100.005"), now + minute);
+    assert_eq!(pacer.volatility_bps(), Some(0.0));
+    let quiet = pacer.delay(now);
+    assert!(quiet > Duration::from_secs(200) && quiet < Duration::from_secs(300));
+
+    // A 0.5% move within a minute polls at the fastest rate.
+    pacer.observe(&book("100.495", "100.505"), now + minute * 2);
+    assert!(pacer.volatility_bps().unwrap() > 20.0);
+    assert_eq!(pacer.delay(now), Duration::from_secs(10));
+
+    let mut wide = SnapshotPacer::default();
+    wide.observe(&book("99.9", "100.1"), now);
+    assert_eq!(wide.delay(now), Duration::from_secs(10));
+}
@@ -70,3 +70,23 @@ async fn mev_signal_event_is_canonicalized() {
     });
     assert_eq!(event["s"], "ADA-USDT");
 }
+
+#[tokio::test]
+async fn symbol_refresh_changes_become_listing_events() {
+    canonicalize("binance", "btcusdt").await;
+    let lines = ingestor::agents::listing_events(
+        "binance",
+        &["ethusdt".to_string()],
+        &["btcusdt".to_string()],
+    );
+    let events: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(events[0]["type"], "listing");
+    assert_eq!(events[0]["s"], "ETH-USDT");
+    assert_eq!(events[0]["base"], "ETH");
+    assert_eq!(events[0]["quote"], "USDT");
+    assert_eq!(events[1]["type"], "delisting");
+    assert_eq!(events[1]["s"], "BTC-USDT");
+}
@@ -0,0 +1,700 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use ingestor::agents::{binance, bybit, coinbase, deribit, gate, hyperliquid, kucoin, mexc, okx};
+
+#[test]
+fn binance_parse_event_handles_stream_events() {
+    let mut ids = HashMap::new();
+    let line = binance::parse_event(
+        &json!({"e": "bookTicker", "s": "ethusdt", "b": "10.50", "B": "1", "a": "10.60", "A": "2", "E": 5}),
+        &mut ids,
+    )
+    .expect("book ticker");
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["bp"], "10.5");
+    assert_eq!(v["ts"], 5);
+
+    assert!(binance::parse_event(&json!({"result": null, "id": 1}), &mut ids).is_none());
+}
+
+#[test]
+fn coinbase_parse_event_handles_feed_messages() {
+    let mut ids = HashMap::new();
+    let line = coinbase::parse_event(
+        &json!({"type": "l2update", "product_id": "BTC-USD", "changes": [["buy", "100.0", "1"], ["sell", "101", "2"]], "time": "2024-01-01T00:00:00Z"}),
+        &mut ids,
+    )
+    .expect("l2 update");
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["s"], "BTC-USD");
+    assert_eq!(v["bids"], json!([["100", "1"]]));
+    assert_eq!(v["asks"], json!([["101", "2"]]));
+
+    assert!(coinbase::parse_event(&json!({"type": "heartbeat"}), &mut ids).is_none());
+}
+
+#[test]
+fn coinbase_sequence_gaps_are_detected_per_product() {
+    let mut seqs = HashMap::new();
+    let msg = |p: &str, seq: u64| json!({"type": "l2update", "product_id": p, "sequence": seq});
+    assert!(!coinbase::sequence_gap(&msg("BTC-USD", 10), &mut seqs));
+    assert!(!coinbase::sequence_gap(&msg("BTC-USD", 11), &mut seqs));
+    assert!(!coinbase::sequence_gap(&msg("ETH-USD", 50), &mut seqs));
+    assert!(coinbase::sequence_gap(&msg("BTC-USD", 14), &mut seqs));
+    // A late message neither counts as a gap nor rewinds the sequence.
+    assert!(!coinbase::sequence_gap(&msg("BTC-USD", 12), &mut seqs));
+    assert!(!coinbase::sequence_gap(&msg("BTC-USD", 15), &mut seqs));
+    assert!(!coinbase::sequence_gap(&msg("ETH-USD", 51), &mut seqs));
+    assert!(ingestor::agents::STREAM_SEQ_GAPS.load(std::sync::atomic::Ordering::Relaxed) >= 1);
+}
+
+#[test]
+fn okx_parse_event_handles_channel_pushes() {
+    let mut ids = HashMap::new();
+    let trades = okx::parse_event(
+        &json!({"arg": {"channel": "trades", "instId": "BTC-USDT"}, "data": [
+            {"instId": "BTC-USDT", "tradeId": "130639474", "px": "42219.90", "sz": "0.12", "side": "buy", "ts": "1630048897897"},
+            {"instId": "BTC-USDT", "tradeId": "130639475", "px": "42220", "sz": "1", "side": "sell", "ts": "1630048897898"}
+        ]}),
+        &mut ids,
+    );
+    assert_eq!(trades.len(), 2);
+    let v: serde_json::Value = serde_json::from_str(&trades[0]).unwrap();
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(v["t"], 130639474);
+    assert_eq!(v["p"], "42219.9");
+    assert_eq!(v["ts"], 1630048897897i64);
+
+    let update = okx::parse_event(
+        &json!({"arg": {"channel": "books-l2-tbt", "instId": "ETH-USDT"}, "action": "update", "data": [
+            {"asks": [["2500.5", "3", "0", "2"]], "bids": [], "ts": "5"}
+        ]}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&update[0]).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["asks"], json!([["2500.5", "3"]]));
+
+    let ticker = okx::parse_event(
+        &json!({"arg": {"channel": "tickers", "instId": "ETH-USDT"}, "data": [
+            {"bidPx": "2500.1", "bidSz": "4", "askPx": "2500.2", "askSz": "1.50", "ts": "6"}
+        ]}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&ticker[0]).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["aq"], "1.5");
+
+    assert!(okx::parse_event(&json!({"event": "subscribe"}), &mut ids).is_empty());
+}
+
+#[test]
+fn bybit_parse_event_handles_spot_and_linear_topics() {
+    let mut ids = HashMap::new();
+    let mut tickers = HashMap::new();
+    let trades = bybit::parse_event(
+        &json!({"topic": "publicTrade.BTCUSDT", "type": "snapshot", "ts": 1672304486868i64, "data": [
+            {"T": 1672304486865i64, "s": "BTCUSDT", "S": "Buy", "v": "0.001", "p": "16578.50", "i": "2290000000007764263", "BT": false}
+        ]}),
+        bybit::Category::Spot,
+        &mut ids,
+        &mut tickers,
+    );
+    let v: serde_json::Value = serde_json::from_str(&trades[0]).unwrap();
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["p"], "16578.5");
+    assert_eq!(v["ts"], 1672304486865i64);
+    assert!(v.get("ac").is_none());
+
+    let top = bybit::parse_event(
+        &json!({"topic": "orderbook.1.ETHUSDT", "type": "snapshot", "ts": 7, "data": {
+            "s": "ETHUSDT", "b": [["2000.1", "3"]], "a": [["2000.2", "1.0"]], "u": 1
+        }}),
+        bybit::Category::Spot,
+        &mut ids,
+        &mut tickers,
+    );
+    let v: serde_json::Value = serde_json::from_str(&top[0]).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["aq"], "1");
+
+    let delta = bybit::parse_event(
+        &json!({"topic": "orderbook.50.BTCUSDT", "type": "delta", "ts": 8, "data": {
+            "s": "BTCUSDT", "b": [["30000", "0"]], "a": [], "u": 2
+        }}),
+        bybit::Category::Linear,
+        &mut ids,
+        &mut tickers,
+    );
+    let v: serde_json::Value = serde_json::from_str(&delta[0]).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["ac"], "perp");
+    assert_eq!(v["settle"], "USDT");
+    assert_eq!(v["id"], "BTC-USDT-PERP");
+
+    let ticker = bybit::parse_event(
+        &json!({"topic": "tickers.BTCUSDT", "type": "delta", "ts": 9, "data": {
+            "symbol": "BTCUSDT", "fundingRate": "0.0001", "openInterest": "25000.5"
+        }}),
+        bybit::Category::Linear,
+        &mut ids,
+        &mut tickers,
+    );
+    let types: Vec<String> = ticker
+        .iter()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).unwrap()["type"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(types, ["funding", "open_interest"]);
+
+    // A partial delta updates the quote merged from the snapshot.
+    let quote = |msg: serde_json::Value, tickers: &mut HashMap<_, _>| {
+        bybit::parse_event(&msg, bybit::Category::Linear, &mut HashMap::new(), tickers)
+    };
+    let snapshot = quote(
+        json!({"topic": "tickers.ETHUSDT", "type": "snapshot", "ts": 10, "data": {
+            "symbol": "ETHUSDT", "bid1Price": "2000.1", "bid1Size": "3",
+            "ask1Price": "2000.2", "ask1Size": "1"
+        }}),
+        &mut tickers,
+    );
+    let v: serde_json::Value = serde_json::from_str(&snapshot[0]).unwrap();
+    assert_eq!(
+        (v["bp"].as_str(), v["aq"].as_str()),
+        (Some("2000.1"), Some("1"))
+    );
+    let delta = quote(
+        json!({"topic": "tickers.ETHUSDT", "type": "delta", "ts": 11, "data": {
+            "symbol": "ETHUSDT", "ask1Size": "4.5"
+        }}),
+        &mut tickers,
+    );
+    let v: serde_json::Value = serde_json::from_str(&delta[0]).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["bp"], "2000.1");
+    assert_eq!(v["ap"], "2000.2");
+    assert_eq!(v["aq"], "4.5");
+    assert_eq!(v["ts"], 11);
+}
+
+#[test]
+fn kucoin_parse_event_handles_topic_messages() {
+    let mut ids = HashMap::new();
+    let trade = kucoin::parse_event(
+        &json!({"type": "message", "topic": "/market/match:BTC-USDT", "subject": "trade.l3match", "data": {
+            "symbol": "BTC-USDT", "side": "buy", "type": "match", "sequence": "1545896669145",
+            "price": "0.08200", "size": "0.01022222", "tradeId": "5c24c5da03aa673885cd67aa",
+            "time": "1545913818099033203"
+        }}),
+        &mut ids,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&trade).unwrap();
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(v["p"], "0.082");
+    assert!(v["t"].is_null());
+    assert_eq!(v["ts"], 1545913818099i64);
+
+    let diff = json!({"type": "message", "topic": "/market/level2:BTC-USDT", "subject": "trade.l2update", "data": {
+        "changes": {"asks": [["18906", "0.00331", "14103845"]], "bids": []},
+        "sequenceStart": 14103845, "sequenceEnd": 14103845, "symbol": "BTC-USDT", "time": 1663747970273i64
+    }});
+    let v: serde_json::Value =
+        serde_json::from_str(&kucoin::parse_event(&diff, &mut ids).unwrap()).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["asks"], json!([["18906", "0.00331"]]));
+
+    let mut seqs = HashMap::new();
+    assert!(!kucoin::sequence_gap(&diff, &mut seqs));
+    let mut skipped = diff.clone();
+    skipped["data"]["sequenceStart"] = json!(14103850);
+    assert!(kucoin::sequence_gap(&skipped, &mut seqs));
+
+    let ticker = kucoin::parse_event(
+        &json!({"type": "message", "topic": "/market/ticker:ETH-USDT", "subject": "trade.ticker", "data": {
+            "sequence": "1545896668986", "price": "0.08", "size": "0.011",
+            "bestAsk": "0.08", "bestAskSize": "0.18", "bestBid": "0.049", "bestBidSize": "0.036",
+            "time": 1545896668986i64
+        }}),
+        &mut ids,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&ticker).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["s"], "ETH-USDT");
+    assert_eq!(v["bp"], "0.049");
+
+    assert!(kucoin::parse_event(
+        &json!({"type": "message", "subject": "other", "data": {}}),
+        &mut ids
+    )
+    .is_none());
+}
+
+#[test]
+fn gate_parse_event_handles_channel_updates() {
+    let mut ids = HashMap::new();
+    let trade = gate::parse_event(
+        &json!({"time": 1606292218, "time_ms": 1606292218231i64, "channel": "spot.trades", "event": "update", "result": {
+            "id": 309143071, "create_time": 1606292218, "create_time_ms": "1606292218213.4578", "side": "sell",
+            "currency_pair": "GT_USDT", "amount": "16.4700000000", "price": "0.4705000000"
+        }}),
+        &mut ids,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&trade).unwrap();
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "GT-USDT");
+    assert_eq!(v["t"], 309143071);
+    assert_eq!(v["p"], "0.4705");
+    assert_eq!(v["q"], "16.47");
+    assert_eq!(v["ts"], 1606292218213i64);
+
+    let ticker = gate::parse_event(
+        &json!({"time": 1606293275, "channel": "spot.book_ticker", "event": "update", "result": {
+            "t": 1606293275123i64, "u": 48733182, "s": "BTC_USDT", "b": "19177.79", "B": "0.0003341504", "a": "19179.38", "A": "0.09"
+        }}),
+        &mut ids,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&ticker).unwrap();
+    assert_eq!(v["type"], "book_ticker");
+    assert_eq!(v["ap"], "19179.38");
+
+    let diff = gate::parse_event(
+        &json!({"time": 1606294781, "channel": "spot.order_book_update", "event": "update", "result": {
+            "t": 1606294781123i64, "e": "depthUpdate", "s": "BTC_USDT", "U": 48776301, "u": 48776306,
+            "b": [["19137.74", "0.0001"]], "a": [["19137.75", "0.6135"]]
+        }}),
+        &mut ids,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&diff).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["bids"], json!([["19137.74", "0.0001"]]));
+
+    assert!(gate::parse_event(
+        &json!({"time": 1, "channel": "spot.trades", "event": "subscribe", "result": {"status": "success"}}),
+        &mut ids
+    )
+    .is_none());
+}
+
+#[test]
+fn gate_trades_replayed_after_a_reconnect_are_dropped() {
+    let trade = |id: i64| {
+        json!({"time": 1606292218, "channel": "spot.trades", "event": "update", "result": {
+            "id": id, "create_time_ms": "1606292218213.4578", "currency_pair": "GT_USDT",
+            "amount": "1", "price": "0.47"
+        }})
+    };
+    let mut ids = HashMap::new();
+    assert!(gate::parse_event(&trade(100), &mut ids).is_some());
+    assert!(gate::parse_event(&trade(101), &mut ids).is_some());
+
+    // The connection drops and the new one resends the last trades.
+    assert!(gate::replayed_trade(&trade(100), &ids));
+    assert!(gate::parse_event(&trade(100), &mut ids).is_none());
+    assert!(gate::parse_event(&trade(101), &mut ids).is_none());
+    assert!(!gate::replayed_trade(&trade(102), &ids));
+    let v: serde_json::Value =
+        serde_json::from_str(&gate::parse_event(&trade(102), &mut ids).unwrap()).unwrap();
+    assert_eq!(v["t"], 102);
+}
+
+#[test]
+fn gate_book_update_id_gaps_are_detected_and_snapshots_carry_their_id() {
+    let update = |first: u64, last: u64| {
+        json!({"time": 1, "channel": "spot.order_book_update", "event": "update", "result": {
+            "t": 1606294781123i64, "s": "BTC_USDT", "U": first, "u": last, "b": [], "a": []
+        }})
+    };
+    let mut ids = HashMap::new();
+    assert_eq!(
+        gate::book_update_range(&update(10, 12)),
+        Some(("BTC_USDT", 10, 12))
+    );
+    assert!(!gate::book_gap(&update(10, 12), &mut ids));
+    assert!(!gate::book_gap(&update(13, 15), &mut ids));
+    assert!(gate::book_gap(&update(18, 20), &mut ids));
+
+    let (id, line) = gate::snapshot_event(
+        "BTC_USDT",
+        &json!({"id": 48776305, "current": 1623898993123i64, "update": 1623898993121i64,
+            "asks": [["19137.75", "0.6135"]], "bids": [["19137.74", "0.0001"]]}),
+    )
+    .unwrap();
+    assert_eq!(id, 48776305);
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["type"], "snapshot");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(v["asks"], json!([["19137.75", "0.6135"]]));
+}
+
+#[test]
+fn binance_futures_parse_event_handles_combined_streams() {
+    let mut ids = HashMap::new();
+    let trade = binance::futures::parse_event(
+        &json!({"stream": "btcusdt@aggTrade", "data": {
+            "e": "aggTrade", "E": 123456789, "s": "BTCUSDT", "a": 5933014, "p": "30000.10",
+            "q": "0.500", "f": 100, "l": 105, "T": 123456785, "m": true
+        }}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&trade[0]).unwrap();
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["ac"], "perp");
+    assert_eq!(v["settle"], "USDT");
+    assert_eq!(v["id"], "BTC-USDT-PERP");
+    assert_eq!(v["t"], 5933014);
+    assert_eq!(v["p"], "30000.1");
+    assert!(v.get("q_unit").is_none());
+
+    let mark = binance::futures::parse_event(
+        &json!({"e": "markPriceUpdate", "E": 1562305380000i64, "s": "BTCUSDT", "p": "11794.15000000",
+            "i": "11784.62659091", "r": "0.00038167", "T": 1562306400000i64}),
+        &mut ids,
+    );
+    let types: Vec<String> = mark
+        .iter()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).unwrap()["type"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(types, ["mark_price", "funding"]);
+
+    let liq = binance::futures::parse_event(
+        &json!({"e": "forceOrder", "E": 1568014460893i64, "o": {
+            "s": "BTCUSDT", "S": "SELL", "o": "LIMIT", "q": "0.014", "p": "9910", "T": 1568014460893i64
+        }}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&liq[0]).unwrap();
+    assert_eq!(v["type"], "liquidation");
+    assert_eq!(v["side"], "SELL");
+
+    let depth = |pu: i64, u: i64| {
+        json!({"e": "depthUpdate", "E": 1, "T": 1, "s": "BTCUSDT", "U": pu + 1, "u": u, "pu": pu,
+            "b": [["30000", "1"]], "a": []})
+    };
+    let mut seqs = HashMap::new();
+    assert!(!binance::futures::depth_gap(&depth(10, 12), &mut seqs));
+    assert!(!binance::futures::depth_gap(&depth(12, 15), &mut seqs));
+    assert!(binance::futures::depth_gap(&depth(17, 20), &mut seqs));
+    let v: serde_json::Value =
+        serde_json::from_str(&binance::futures::parse_event(&depth(20, 21), &mut ids)[0]).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["bids"], json!([["30000", "1"]]));
+    assert_eq!(
+        binance::futures::depth_range(
+            &json!({"stream": "btcusdt@depth@100ms", "data": depth(20, 21)})
+        ),
+        Some(("BTCUSDT", 21, 21))
+    );
+
+    let (last_update_id, line) = binance::futures::snapshot_event(
+        "BTCUSDT",
+        &json!({"lastUpdateId": 1027024, "E": 1589436922972i64, "T": 1589436922959i64,
+            "bids": [["4.00000000", "431.00000000"]], "asks": [["4.00000200", "12.00000000"]]}),
+    )
+    .unwrap();
+    assert_eq!(last_update_id, 1027024);
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["type"], "snapshot");
+    assert_eq!(v["ac"], "perp");
+    assert_eq!(v["id"], "BTC-USDT-PERP");
+    assert_eq!(v["bids"], json!([["4", "431"]]));
+    assert_eq!(v["ts"], 1589436922972i64);
+}
+
+#[test]
+fn binance_coinm_events_settle_in_the_base_asset() {
+    let mut ids = HashMap::new();
+    let trade = binance::futures::parse_event(
+        &json!({"stream": "btcusd_perp@aggTrade", "data": {
+            "e": "aggTrade", "E": 1591261134288i64, "s": "BTCUSD_PERP", "a": 4231, "p": "9587.3",
+            "q": "12", "f": 20, "l": 21, "T": 1591261134288i64, "m": true
+        }}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&trade[0]).unwrap();
+    assert_eq!(v["s"], "BTC-USD");
+    assert_eq!(v["ac"], "perp");
+    assert_eq!(v["settle"], "BTC");
+    assert_eq!(v["id"], "BTC-USD-PERP");
+    assert_eq!(v["q"], "12");
+    assert_eq!(v["q_unit"], "contracts");
+    assert_eq!(v["contract_size"], "100");
+
+    let mark = binance::futures::parse_event(
+        &json!({"stream": "btcusd_perp@markPrice@1s", "data": {
+            "e": "markPriceUpdate", "E": 1596095725000i64, "s": "BTCUSD_PERP", "p": "11185.87786614",
+            "P": "11202.05370000", "r": "0.00030000", "T": 1596096000000i64
+        }}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&mark[1]).unwrap();
+    assert_eq!(v["type"], "funding");
+    assert_eq!(v["s"], "BTC-USD");
+    assert_eq!(v["r"], "0.0003");
+}
+
+#[test]
+fn hyperliquid_parse_event_handles_channels() {
+    let mut ids = HashMap::new();
+    let trades = hyperliquid::parse_event(
+        &json!({"channel": "trades", "data": [
+            {"coin": "BTC", "side": "B", "px": "67000.50", "sz": "0.0100", "time": 1700000000000i64, "hash": "0x00", "tid": 812345678901i64}
+        ]}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&trades[0]).unwrap();
+    assert_eq!(v["agent"], "hyperliquid");
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "BTC-USD");
+    assert_eq!(v["ac"], "perp");
+    assert_eq!(v["settle"], "USDC");
+    assert_eq!(v["id"], "BTC-USD-PERP");
+    assert_eq!(v["q"], "0.01");
+
+    let book = hyperliquid::parse_event(
+        &json!({"channel": "l2Book", "data": {"coin": "ETH", "time": 1700000000001i64, "levels": [
+            [{"px": "2000.1", "sz": "3", "n": 2}],
+            [{"px": "2000.2", "sz": "1.50", "n": 1}]
+        ]}}),
+        &mut ids,
+    );
+    let v: serde_json::Value = serde_json::from_str(&book[0]).unwrap();
+    assert_eq!(v["type"], "snapshot");
+    assert_eq!(v["asks"], json!([["2000.2", "1.5"]]));
+
+    let ctx = hyperliquid::parse_event(
+        &json!({"channel": "activeAssetCtx", "data": {"coin": "BTC", "ctx": {
+            "funding": "0.0000125", "openInterest": "25000.10", "markPx": "67001", "oraclePx": "67000"
+        }}}),
+        &mut ids,
+    );
+    let events: Vec<serde_json::Value> = ctx
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(events[0]["type"], "funding");
+    assert_eq!(events[0]["r"], "0.0000125");
+    assert_eq!(events[0]["funding_interval_h"], 1);
+    assert_eq!(events[1]["oi"], "25000.1");
+    assert_eq!(events[2]["p"], "67001");
+
+    assert!(hyperliquid::parse_event(&json!({"channel": "pong"}), &mut ids).is_empty());
+}
+
+#[test]
+fn deribit_parse_event_handles_subscriptions() {
+    let mut ids = HashMap::new();
+    let notification = |channel: &str, data: serde_json::Value| json!({"jsonrpc": "2.0", "method": "subscription", "params": {"channel": channel, "data": data}});
+    let parse = |v: &serde_json::Value, ids: &mut HashMap<_, _>| -> Vec<serde_json::Value> {
+        deribit::futures::parse_event(v, ids)
+            .iter()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    };
+
+    let trades = parse(
+        &notification(
+            "trades.ETH-27DEC24.100ms",
+            json!([
+                {"trade_id": "ETH-223344", "price": 3400.5, "amount": 120.0, "timestamp": 1700000000000i64,
+                 "instrument_name": "ETH-27DEC24", "direction": "buy"}
+            ]),
+        ),
+        &mut ids,
+    );
+    assert_eq!(trades[0]["type"], "trade");
+    assert_eq!(trades[0]["s"], "ETH-USD-20241227");
+    assert_eq!(trades[0]["ac"], "future");
+    assert_eq!(trades[0]["id"], "ETH-USD-20241227");
+    assert_eq!(trades[0]["settle"], "ETH");
+    assert_eq!(trades[0]["t"], 223344);
+    assert_eq!(trades[0]["p"], "3400.5");
+    assert_eq!(trades[0]["q"], "120");
+
+    let book = parse(
+        &notification(
+            "book.BTC-PERPETUAL.100ms",
+            json!({
+                "type": "change", "timestamp": 1700000000001i64, "change_id": 11, "prev_change_id": 10,
+                "bids": [["delete", 67000.0, 0.0], ["new", 66999.5, 2500.0]], "asks": [["change", 67001.0, 10.0]]
+            }),
+        ),
+        &mut ids,
+    );
+    assert_eq!(book[0]["type"], "l2_diff");
+    assert_eq!(book[0]["ac"], "perp");
+    assert_eq!(book[0]["settle"], "BTC");
+    assert_eq!(book[0]["id"], "BTC-USD-PERP");
+    assert_eq!(
+        book[0]["bids"],
+        json!([["67000", "0"], ["66999.5", "2500"]])
+    );
+
+    let ticker = parse(
+        &notification(
+            "ticker.ETH_USDC-PERPETUAL.100ms",
+            json!({
+                "timestamp": 1700000000002i64, "best_bid_price": 3400.1, "best_bid_amount": 1.5,
+                "best_ask_price": 3400.2, "best_ask_amount": 2.0, "mark_price": 3400.15, "open_interest": 5000.25
+            }),
+        ),
+        &mut ids,
+    );
+    assert_eq!(ticker[0]["type"], "book_ticker");
+    assert_eq!(ticker[0]["s"], "ETH-USDC");
+    assert_eq!(ticker[0]["settle"], "USDC");
+    assert_eq!(ticker[0]["ap"], "3400.2");
+    assert_eq!(ticker[1]["p"], "3400.15");
+    assert_eq!(ticker[2]["oi"], "5000.25");
+
+    let funding = parse(
+        &notification(
+            "perpetual.BTC-PERPETUAL.100ms",
+            json!({
+                "timestamp": 1700000000003i64, "interest": 1.2e-5, "index_price": 67000.1
+            }),
+        ),
+        &mut ids,
+    );
+    assert_eq!(funding[0]["type"], "funding");
+    assert_eq!(funding[0]["r"], "0.000012");
+}
+
+#[test]
+fn deribit_book_change_id_gaps_are_detected() {
+    let book = |typ: &str, prev: i64, id: i64| {
+        json!({"method": "subscription", "params": {"channel": "book.BTC-PERPETUAL.100ms", "data": {
+            "type": typ, "timestamp": 1, "prev_change_id": prev, "change_id": id, "bids": [], "asks": []
+        }}})
+    };
+    let mut change_ids = HashMap::new();
+    assert!(!deribit::futures::book_gap(
+        &book("snapshot", 0, 10),
+        &mut change_ids
+    ));
+    assert!(!deribit::futures::book_gap(
+        &book("change", 10, 11),
+        &mut change_ids
+    ));
+    assert!(deribit::futures::book_gap(
+        &book("change", 13, 14),
+        &mut change_ids
+    ));
+}
+
+#[test]
+fn mexc_parse_event_handles_deals_and_depth() {
+    let mut ids = HashMap::new();
+    let deals = mexc::parse_event(
+        &json!({"c": "spot@public.deals.v3.api@PEPEUSDT", "s": "PEPEUSDT", "t": 1678783345178i64,
+        "d": {"e": "spot@public.deals.v3.api", "deals": [
+            {"S": 2, "p": "0.00000812", "t": 1678783345173i64, "v": "1500000.00"},
+            {"S": 1, "p": "0.00000813", "t": 1678783345174i64, "v": "20000"}
+        ]}}),
+        &mut ids,
+    );
+    assert_eq!(deals.len(), 2);
+    let v: serde_json::Value = serde_json::from_str(&deals[0]).unwrap();
+    assert_eq!(v["agent"], "mexc");
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "PEPE-USDT");
+    assert_eq!(v["t"], serde_json::Value::Null);
+    assert_eq!(v["p"], "0.00000812");
+    assert_eq!(v["q"], "1500000");
+    assert_eq!(v["ts"], 1678783345173i64);
+
+    let depth = |r: &str| {
+        json!({"c": "spot@public.increase.depth.v3.api@BTCUSDT", "s": "BTCUSDT", "t": 1661932660144i64,
+            "d": {"e": "spot@public.increase.depth.v3.api", "r": r,
+                "asks": [{"p": "20290.89", "v": "0.00000000"}], "bids": [{"p": "20290.1", "v": "0.5"}]}})
+    };
+    let v: serde_json::Value =
+        serde_json::from_str(&mexc::parse_event(&depth("3407459756"), &mut ids)[0]).unwrap();
+    assert_eq!(v["type"], "l2_diff");
+    assert_eq!(v["asks"], json!([["20290.89", "0"]]));
+    assert_eq!(v["bids"], json!([["20290.1", "0.5"]]));
+
+    let mut versions = HashMap::new();
+    assert!(!mexc::depth_gap(&depth("10"), &mut versions));
+    assert!(!mexc::depth_gap(&depth("11"), &mut versions));
+    assert!(mexc::depth_gap(&depth("14"), &mut versions));
+    assert_eq!(mexc::depth_version(&depth("14")), Some(("BTCUSDT", 14)));
+
+    let (last_update_id, line) = mexc::snapshot_event(
+        "BTCUSDT",
+        &json!({"lastUpdateId": 3407459755u64, "bids": [["20290.1", "0.5"]], "asks": [["20290.89", "1.25"]]}),
+    )
+    .unwrap();
+    assert_eq!(last_update_id, 3407459755);
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["type"], "snapshot");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(v["asks"], json!([["20290.89", "1.25"]]));
+
+    assert!(mexc::parse_event(&json!({"id": 0, "code": 0, "msg": "PONG"}), &mut ids).is_empty());
+}
+
+#[test]
+fn derivative_parser_output_keeps_its_contract_as_typed_events() {
+    let mut ids = HashMap::new();
+    let mut lines = Vec::new();
+    for msg in [
+        json!({"e": "aggTrade", "E": 1, "s": "BTCUSDT", "a": 5, "p": "100.5", "q": "2", "T": 1}),
+        json!({"e": "depthUpdate", "E": 2, "s": "BTCUSDT", "b": [["100", "1"]], "a": []}),
+        json!({"e": "markPriceUpdate", "E": 3, "s": "BTCUSDT", "p": "100.2", "r": "0.0001"}),
+        json!({"e": "forceOrder", "E": 4, "o": {"s": "BTCUSDT", "S": "SELL", "p": "99", "q": "1"}}),
+    ] {
+        lines.extend(binance::futures::parse_event(&msg, &mut ids));
+    }
+    lines.extend(bybit::parse_event(
+        &json!({"topic": "tickers.ETHUSDT", "type": "snapshot", "ts": 5, "data": {
+            "symbol": "ETHUSDT", "bid1Price": "2000.1", "bid1Size": "3", "ask1Price": "2000.2",
+            "ask1Size": "1", "fundingRate": "0.0001", "openInterest": "25000.5"
+        }}),
+        bybit::Category::Linear,
+        &mut ids,
+        &mut HashMap::new(),
+    ));
+
+    let mut kinds = Vec::new();
+    for line in &lines {
+        let expected: serde_json::Value = serde_json::from_str(line).unwrap();
+        let event: canonicalizer::Event = serde_json::from_str(line).unwrap();
+        assert_eq!(serde_json::to_value(&event).unwrap(), expected, "{line}");
+        if !matches!(event, canonicalizer::Event::Other(_)) {
+            assert_eq!(expected["ac"], "perp", "{line}");
+            kinds.push(event.kind().to_string());
+        }
+    }
+    assert_eq!(
+        kinds,
+        [
+            "trade",
+            "l2_diff",
+            "funding",
+            "liquidation",
+            "book_ticker",
+            "funding",
+            "open_interest"
+        ]
+    );
+}
@@ -0,0 +1,66 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use ingestor::redact::{self, RedactingWriter, REDACTED};
+
+#[test]
+fn sensitive_parameters_and_headers_are_scrubbed() {
+    let url = "error sending request for url (https://api.stlouisfed.org/fred/series?series_id=DGS10&api_key=abcdef123456&file_type=json)";
+    let scrubbed = redact::scrub(url);
+    assert!(!scrubbed.contains("abcdef123456"));
+    assert!(scrubbed.contains(&format!("api_key={REDACTED}&file_type=json")));
+
+    let signed =
+        redact::scrub("GET /api/v3/order?symbol=BTCUSDT&timestamp=1&signature=9f86d08 failed");
+    assert_eq!(
+        signed,
+        format!("GET /api/v3/order?symbol=BTCUSDT&timestamp=1&signature={REDACTED} failed")
+    );
+    assert_eq!(
+        redact::scrub(r#"headers: {"X-MBX-APIKEY": "vmPUZE6mv9", "listenKey":"pqia91ma19"}"#),
+        format!(r#"headers: {{"X-MBX-APIKEY": "{REDACTED}", "listenKey":"{REDACTED}"}}"#)
+    );
+    assert_eq!(
+        redact::scrub("wss://ws-api-spot.kucoin.com/?token=2neAiuYv&connectId=7"),
+        format!("wss://ws-api-spot.kucoin.com/?token={REDACTED}&connectId=7")
+    );
+
+    let benign = "tokens=5 csrftoken=abc keys: 3";
+    assert_eq!(redact::scrub(benign), benign);
+}
+
+#[test]
+fn registered_secrets_are_scrubbed_from_log_lines() {
+    redact::register_secret("short");
+    redact::register_secret("sk-live-0123456789");
+
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+    impl Write for Buf {
+        fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(b);
+            Ok(b.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buf = Buf::default();
+    let out = buf.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(RedactingWriter::new(move || out.clone()))
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::error!(
+            error = "auth failed for key sk-live-0123456789",
+            "request rejected"
+        );
+        tracing::info!("short circuit");
+    });
+
+    let logged = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert!(!logged.contains("sk-live-0123456789"));
+    assert!(logged.contains(&format!("auth failed for key {REDACTED}")));
+    assert!(logged.contains("short circuit"));
+}
@@ -0,0 +1,960 @@
+use async_trait::async_trait;
+use canonicalizer::QuoteGroups;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use ingestor::agents;
+use ingestor::conflate::ConflationSink;
+use ingestor::depth_profile::DepthProfileSink;
+use ingestor::error::IngestorError;
+use ingestor::fair_mid::FairMidSink;
+use ingestor::fanout::{FanoutSink, Route, SinkConfig};
+use ingestor::fixed_point::FixedPointSink;
+use ingestor::freshness::{Freshness, FreshnessSink, SLO_VIOLATIONS};
+use ingestor::ingest_stats::{self, Counter, IngestStatsSink};
+use ingestor::large_print::LargePrintSink;
+use ingestor::lead_lag::LeadLagSink;
+use ingestor::sink::{
+    DynSink, Encoding, EnvelopeSink, LabelSink, OutputSink, SamplingSink, StdoutSink,
+};
+use ingestor::transform::{self, TransformConfig, TransformSink};
+
+mod common;
+use common::VecSink;
+
+/// Held by tests reading the process-wide `ingest_stats` counters.
+static STATS: Mutex<()> = Mutex::const_new(());
+
+#[tokio::test]
+async fn label_sink_stamps_json_events() {
+    let inner = Arc::new(VecSink::default());
+    let sink = LabelSink::new(
+        inner.clone() as DynSink,
+        Some("staging".into()),
+        Some("desk-a".into()),
+    );
+
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT"}"#)
+        .await
+        .unwrap();
+    sink.send("not json").await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let v: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(v["deployment"], "staging");
+    assert_eq!(v["tenant"], "desk-a");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(lines[1], "not json");
+}
+
+#[tokio::test]
+async fn envelope_sink_stamps_ids_versions_and_per_agent_sequences() {
+    let inner = Arc::new(VecSink::default());
+    let sink = EnvelopeSink::new(inner.clone() as DynSink);
+    let suspect = canonicalizer::WashTradeSuspect {
+        agent: "binance".into(),
+        r#type: "wash_trade_suspect".into(),
+        symbol: "BTC-USDT".into(),
+        trades: 10,
+        flagged: 3,
+        score: 0.25,
+        ac: None,
+        settle: None,
+        id: None,
+        timestamp: 1_000,
+    };
+
+    sink.send(&serde_json::to_string(&suspect).unwrap())
+        .await
+        .unwrap();
+    sink.send(r#"{"agent":"coinbase","type":"trade","s":"BTC-USD"}"#)
+        .await
+        .unwrap();
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT"}"#)
+        .await
+        .unwrap();
+    let replayed = r#"{"agent":"binance","type":"trade","event_id":"01J0000000000000000000000A","source_seq":7}"#;
+    sink.send(replayed).await.unwrap();
+    sink.send("not json").await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let first: canonicalizer::Envelope<canonicalizer::WashTradeSuspect> =
+        serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first.event, suspect);
+    assert_eq!(first.schema_version, canonicalizer::SCHEMA_VERSION);
+    assert_eq!(first.event_id.len(), 26);
+    assert!(first.ingest_ts > 0);
+    assert_eq!(first.source_seq, 1);
+
+    let v: Vec<serde_json::Value> = lines[1..3]
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(v[0]["source_seq"], 1);
+    assert_eq!(v[1]["source_seq"], 2);
+    // The envelope is appended; the event's own fields keep their order.
+    assert!(lines[1]
+        .starts_with(r#"{"agent":"coinbase","type":"trade","s":"BTC-USD","schema_version":"#));
+    assert_ne!(v[1]["event_id"], first.event_id.as_str());
+    assert_eq!(lines[3], replayed);
+    assert_eq!(lines[4], "not json");
+}
+
+#[tokio::test]
+async fn sampling_sink_forwards_one_in_n_per_type_and_symbol() {
+    let primary = Arc::new(VecSink::default());
+    let sample = Arc::new(VecSink::default());
+    let sink = SamplingSink::new(primary.clone() as DynSink, sample.clone() as DynSink, 3);
+
+    for _ in 0..6 {
+        sink.send(r#"{"type":"trade","s":"BTC-USDT"}"#)
+            .await
+            .unwrap();
+        sink.send(r#"{"type":"trade","s":"ETH-USDT"}"#)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(primary.lines.lock().await.len(), 12);
+    let sampled = sample.lines.lock().await;
+    assert_eq!(sampled.len(), 4);
+    assert_eq!(sampled.iter().filter(|l| l.contains("BTC-USDT")).count(), 2);
+}
+
+#[tokio::test]
+async fn sampling_over_proto_stdout_keeps_samples_out_of_the_frames() {
+    use tokio::io::AsyncReadExt;
+
+    let (stdout, mut stdout_rx) = tokio::io::duplex(1 << 16);
+    let (stderr, mut stderr_rx) = tokio::io::duplex(1 << 16);
+    let primary = StdoutSink::to_writer(stdout).with_encoding(Encoding::Proto);
+    let sink = SamplingSink::new(
+        Arc::new(primary) as DynSink,
+        Arc::new(StdoutSink::to_writer(stderr)) as DynSink,
+        2,
+    );
+    for _ in 0..4 {
+        sink.send(
+            r#"{"agent":"binance","type":"trade","s":"BTC-USDT","p":"1","q":"1","t":1,"ts":1}"#,
+        )
+        .await
+        .unwrap();
+    }
+    drop(sink);
+
+    let mut frames = Vec::new();
+    stdout_rx.read_to_end(&mut frames).await.unwrap();
+    let mut buf = frames.as_slice();
+    let mut events = 0;
+    while !buf.is_empty() {
+        let event = canonicalizer::proto::decode(buf).unwrap();
+        assert_eq!(event.agent, "binance");
+        // skip the varint length prefix and the message it announces
+        let (mut len, mut prefix) = (0usize, 0);
+        loop {
+            let b = buf[prefix];
+            len |= ((b & 0x7f) as usize) << (7 * prefix);
+            prefix += 1;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        buf = &buf[prefix + len..];
+        events += 1;
+    }
+    assert_eq!(events, 4);
+
+    let mut samples = String::new();
+    stderr_rx.read_to_string(&mut samples).await.unwrap();
+    assert_eq!(samples.lines().count(), 2);
+    assert!(samples.lines().all(|l| l.contains(r#""s":"BTC-USDT""#)));
+}
+
+#[tokio::test]
+async fn lead_lag_sink_credits_the_venue_that_moves_first() {
+    let inner = Arc::new(VecSink::default());
+    // Binance quotes USDT, Coinbase USD; the group compares them.
+    let groups = QuoteGroups::new(&BTreeMap::from([(
+        "USD".to_string(),
+        vec!["USD".to_string(), "USDT".to_string()],
+    )]));
+    let sink = LeadLagSink::new(inner.clone() as DynSink, 500, Duration::from_secs(3600))
+        .with_quote_groups(groups);
+
+    let tick = |agent: &str, mid: f64, ts: i64| {
+        json!({
+            "agent": agent,
+            "type": "book_ticker",
+            "s": if agent == "binance" { "BTC-USDT" } else { "BTC-USD" },
+            "bp": format!("{}", mid - 0.5),
+            "ap": format!("{}", mid + 0.5),
+            "ts": ts
+        })
+        .to_string()
+    };
+    for (agent, mid, ts) in [
+        ("binance", 100.0, 0),
+        ("coinbase", 100.0, 0),
+        ("binance", 101.0, 1_000),
+        ("coinbase", 101.0, 1_040),
+        ("binance", 100.0, 2_000),
+        ("coinbase", 100.0, 2_060),
+        ("binance", 101.0, 3_000),
+    ] {
+        sink.send(&tick(agent, mid, ts)).await.unwrap();
+    }
+    sink.report().await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    assert_eq!(lines.len(), 8);
+    let report: serde_json::Value = serde_json::from_str(&lines[7]).unwrap();
+    assert_eq!(report["type"], "lead_lag");
+    assert_eq!(report["s"], "BTC-USD");
+    assert_eq!(report["quote_group"], "USD");
+    assert_eq!(report["leader"], "binance");
+    assert_eq!(report["follower"], "coinbase");
+    assert_eq!(report["leader_moves"], 3);
+    assert_eq!(report["followed"], 2);
+    assert_eq!(report["mean_lag_ms"], 50.0);
+    assert_eq!(report["median_lag_ms"], 60);
+    // Both followed moves correlate equally, at 40 and 60 ms; the shorter
+    // lag wins. Binance made three moves of equal size, Coinbase two.
+    assert_eq!(report["xcorr_lag_ms"], 40);
+    assert!((report["xcorr"].as_f64().unwrap() - 1.0 / 6f64.sqrt()).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn grouped_symbols_of_one_venue_keep_their_own_mids() {
+    let groups = QuoteGroups::new(&BTreeMap::from([(
+        "USD".to_string(),
+        vec!["USD".to_string(), "USDT".to_string(), "USDC".to_string()],
+    )]));
+    let tick = |s: &str, mid: f64, ts: i64| {
+        json!({"agent": "binance", "type": "book_ticker", "s": s, "ts": ts,
+            "bp": format!("{}", mid - 2.0), "bq": "1", "ap": format!("{}", mid + 2.0), "aq": "1"})
+        .to_string()
+    };
+    // Binance.US quotes BTC in USD, USDT and USDC at slightly different mids.
+    let ticks = [
+        tick("BTC-USD", 100.0, 0),
+        tick("BTC-USDT", 100.6, 10),
+        tick("BTC-USDC", 99.8, 20),
+        tick("BTC-USD", 100.0, 1_000),
+        tick("BTC-USDT", 100.6, 1_010),
+        tick("BTC-USDC", 99.8, 1_020),
+    ];
+
+    let inner = Arc::new(VecSink::default());
+    let sink = LeadLagSink::new(inner.clone() as DynSink, 500, Duration::from_secs(3600))
+        .with_quote_groups(groups.clone());
+    for t in &ticks {
+        sink.send(t).await.unwrap();
+    }
+    sink.report().await.unwrap();
+    // Unchanged quotes are no moves, so nothing is reported.
+    assert_eq!(inner.lines.lock().await.len(), ticks.len());
+
+    let inner = Arc::new(VecSink::default());
+    let sink = FairMidSink::new(inner.clone() as DynSink, 1_000.0, 5_000).with_quote_groups(groups);
+    for t in &ticks {
+        sink.send(t).await.unwrap();
+    }
+    let lines = inner.lines.lock().await;
+    let mid: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+    // The consolidated mid spans all three quotes, best bid 98.6 and best ask
+    // 101.8, rather than only the last one: median of 100.2 and micro 99.8.
+    assert!((mid["mid"].as_f64().unwrap() - 100.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn lead_lag_compares_spot_and_perp_books_apart() {
+    let inner = Arc::new(VecSink::default());
+    let sink = LeadLagSink::new(inner.clone() as DynSink, 500, Duration::from_secs(3600));
+    let tick = |agent: &str, perp: bool, mid: f64, ts: i64| {
+        let mut v = json!({"agent": agent, "type": "book_ticker", "s": "BTC-USDT", "ts": ts,
+            "bp": format!("{}", mid - 0.5), "ap": format!("{}", mid + 0.5)});
+        if perp {
+            v["ac"] = json!("perp");
+            v["settle"] = json!("USDT");
+        }
+        v.to_string()
+    };
+    // Bybit spot and perp quote steady mids a basis apart, interleaved; a
+    // spot-only venue moves once.
+    for (agent, perp, mid, ts) in [
+        ("bybit", false, 100.0, 0),
+        ("bybit", true, 100.2, 10),
+        ("binance", false, 100.0, 20),
+        ("bybit", false, 100.0, 1_000),
+        ("bybit", true, 100.2, 1_010),
+        ("binance", false, 101.0, 1_020),
+        ("bybit", false, 100.0, 1_030),
+        ("bybit", true, 100.2, 1_040),
+    ] {
+        sink.send(&tick(agent, perp, mid, ts)).await.unwrap();
+    }
+    sink.report().await.unwrap();
+
+    // The alternating books are no moves, so Bybit leads nothing.
+    assert_eq!(inner.lines.lock().await.len(), 8);
+}
+
+#[tokio::test]
+async fn fixed_point_sink_scales_by_listing_tick_and_lot_size() {
+    let inner = Arc::new(VecSink::default());
+    let sink = FixedPointSink::new(inner.clone() as DynSink);
+
+    let listing = json!({"agent": "binance", "type": "listing", "s": "BTC-USDT",
+        "base": "BTC", "quote": "USDT", "lot_size": "0.00001000", "tick_size": "0.01000000"});
+    sink.send(&listing.to_string()).await.unwrap();
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT","p":"65000.1","q":"0.5"}"#)
+        .await
+        .unwrap();
+    sink.send(
+        r#"{"agent":"binance","type":"l2_diff","s":"BTC-USDT","bids":[["1.234","2"]],"asks":[]}"#,
+    )
+    .await
+    .unwrap();
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT","p":"?","q":"1"}"#)
+        .await
+        .unwrap();
+
+    let lines = inner.lines.lock().await;
+    let trade: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(trade["p"], 6500010);
+    assert_eq!(trade["pe"], -2);
+    assert_eq!(trade["q"], 50000);
+    assert_eq!(trade["qe"], -5);
+    // Values finer than the tick size widen the exponent instead of rounding.
+    let diff: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+    assert_eq!(diff["bids"], json!([[1234, 200000]]));
+    assert_eq!(diff["pe"], -3);
+    // Events that cannot be encoded are forwarded unchanged.
+    let bad: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+    assert_eq!(bad["p"], "?");
+    assert!(bad.get("pe").is_none());
+}
+
+#[tokio::test]
+async fn ingest_stats_count_messages_and_stream_health_per_exchange() {
+    let _stats = STATS.lock().await;
+    let inner = Arc::new(VecSink::default());
+    let sink = IngestStatsSink::new(inner.clone() as DynSink);
+
+    for line in [
+        r#"{"agent":"binance","type":"trade","s":"BTC-USDT"}"#,
+        r#"{"agent":"binance","type":"trade","s":"ETH-USDT"}"#,
+        r#"{"agent":"binance","type":"book_ticker","s":"BTC-USDT"}"#,
+        r#"{"agent":"coinbase","type":"l2_diff","s":"BTC-USD"}"#,
+        "not json",
+    ] {
+        sink.send(line).await.unwrap();
+    }
+    ingest_stats::record("coinbase", Counter::Gap);
+    ingest_stats::record("coinbase", Counter::Reconnect);
+    ingest_stats::record_drop(r#"{"agent":"coinbase","type":"trade","s":"BTC-USD"}"#);
+    assert_eq!(
+        agents::symbol_or_raw("binance", "btcxyz").as_str(),
+        "btcxyz"
+    );
+    assert_eq!(inner.lines.lock().await.len(), 5);
+
+    let stats = ingest_stats::drain(Duration::from_secs(60));
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].agent, "binance");
+    assert_eq!(stats[0].messages["trade"], 2);
+    assert_eq!(stats[0].messages["book_ticker"], 1);
+    assert_eq!(stats[0].unmapped["unknown_quote"], 1);
+    assert_eq!(stats[1].agent, "coinbase");
+    assert_eq!(stats[1].gaps, 1);
+    assert_eq!(stats[1].reconnects, 1);
+    assert_eq!(stats[1].drops, 1);
+    assert_eq!(stats[1].window_secs, 60);
+    assert!(stats[1].unmapped.is_empty());
+    assert!(ingest_stats::drain(Duration::from_secs(60)).is_empty());
+}
+
+#[tokio::test]
+async fn transform_sink_applies_configured_transforms_in_order() {
+    #[derive(serde::Deserialize)]
+    struct Cfg {
+        transforms: Vec<TransformConfig>,
+    }
+    let cfg: Cfg = config::Config::builder()
+        .add_source(config::File::from_str(
+            r#"
+[[transforms]]
+kind = "symbol_rewrite"
+from = "BTC-USDT"
+to = "XBT-USDT"
+
+[[transforms]]
+kind = "scale"
+types = ["trade"]
+agents = ["bybit"]
+asset_classes = ["perp"]
+field = "q"
+factor = "0.001"
+
+[[transforms]]
+kind = "enrich"
+field = "desk"
+value = "emea"
+
+[[transforms]]
+kind = "redact"
+fields = ["skew"]
+"#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+
+    let inner = Arc::new(VecSink::default());
+    let sink = TransformSink::new(
+        inner.clone() as DynSink,
+        transform::from_config(&cfg.transforms).unwrap(),
+    );
+    let trade = |category| {
+        agents::bybit::parse_event(
+            &json!({"topic": "publicTrade.BTCUSDT", "type": "snapshot", "ts": 1672304486868i64, "data": [
+                {"T": 1672304486865i64, "s": "BTCUSDT", "S": "Buy", "v": "2500", "p": "16578.50", "i": "2290000000007764263", "BT": false}
+            ]}),
+            category,
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+        )
+        .remove(0)
+    };
+    let perp = trade(agents::bybit::Category::Linear);
+    assert!(serde_json::from_str::<serde_json::Value>(&perp).unwrap()["skew"].is_i64());
+    sink.send(&perp).await.unwrap();
+    sink.send(&trade(agents::bybit::Category::Spot))
+        .await
+        .unwrap();
+
+    let lines = inner.lines.lock().await;
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first["s"], "XBT-USDT");
+    assert_eq!(first["ac"], "perp");
+    assert_eq!(first["q"], "2.5");
+    assert_eq!(first["desk"], "emea");
+    assert!(first.get("skew").is_none());
+    let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(second["s"], "XBT-USDT");
+    assert_eq!(second["q"], "2500");
+    assert_eq!(second["desk"], "emea");
+}
+
+struct FailingSink;
+
+#[async_trait]
+impl OutputSink for FailingSink {
+    async fn send(&self, _line: &str) -> Result<(), IngestorError> {
+        Err(IngestorError::Other("down".into()))
+    }
+}
+
+#[tokio::test]
+async fn fanout_sink_isolates_failing_sinks() {
+    let _stats = STATS.lock().await;
+    let cfg = |name: &str, retries: u32, sample_every: Option<u64>| SinkConfig {
+        name: Some(name.into()),
+        kind: "test".into(),
+        path: None,
+        buffer: 100,
+        max_retries: retries,
+        retry_backoff_ms: 1,
+        sample_every,
+        encoding: Default::default(),
+        route: Route::default(),
+    };
+    let good = Arc::new(VecSink::default());
+    let sampled = Arc::new(VecSink::default());
+    let sink = FanoutSink::new(vec![
+        (cfg("good", 3, None), good.clone() as DynSink),
+        (cfg("sampled", 3, Some(2)), sampled.clone() as DynSink),
+        (cfg("bad", 2, None), Arc::new(FailingSink) as DynSink),
+    ]);
+
+    for i in 0..4 {
+        sink.send(&json!({"agent": "okx", "type": "trade", "s": "BTC-USDT", "t": i}).to_string())
+            .await
+            .unwrap();
+    }
+    sink.flush(Duration::from_secs(5)).await;
+
+    assert_eq!(good.lines.lock().await.len(), 4);
+    assert_eq!(sampled.lines.lock().await.len(), 2);
+    let stats = sink.stats();
+    let bad = stats.iter().find(|(n, _)| *n == "bad").unwrap().1;
+    assert_eq!(bad.failed.load(std::sync::atomic::Ordering::Relaxed), 4);
+    assert_eq!(bad.retries.load(std::sync::atomic::Ordering::Relaxed), 8);
+    let stats = ingest_stats::drain(Duration::from_secs(60));
+    let okx = stats.iter().find(|s| s.agent == "okx").unwrap();
+    assert_eq!(okx.drops, 4);
+}
+
+#[tokio::test]
+async fn fanout_sink_routes_events_by_type_agent_and_symbol() {
+    #[derive(serde::Deserialize)]
+    struct Cfg {
+        sinks: Vec<SinkConfig>,
+    }
+    let cfg: Cfg = config::Config::builder()
+        .add_source(config::File::from_str(
+            r#"
+[[sinks]]
+name = "trades"
+kind = "test"
+types = ["trade"]
+
+[[sinks]]
+name = "btc_books"
+kind = "test"
+types = ["snapshot", "l2_diff"]
+agents = ["binance"]
+symbols = ["BTC-*"]
+"#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+    let trades = Arc::new(VecSink::default());
+    let books = Arc::new(VecSink::default());
+    let mut sinks = cfg.sinks.into_iter();
+    let sink = FanoutSink::new(vec![
+        (sinks.next().unwrap(), trades.clone() as DynSink),
+        (sinks.next().unwrap(), books.clone() as DynSink),
+    ]);
+
+    for event in [
+        json!({"agent": "binance", "type": "trade", "s": "BTC-USDT"}),
+        json!({"agent": "binance", "type": "l2_diff", "s": "BTC-USDT"}),
+        json!({"agent": "binance", "type": "l2_diff", "s": "ETH-USDT"}),
+        json!({"agent": "okx", "type": "snapshot", "s": "BTC-USDT"}),
+    ] {
+        sink.send(&event.to_string()).await.unwrap();
+    }
+    sink.send("not json").await.unwrap();
+    sink.flush(Duration::from_secs(5)).await;
+
+    assert_eq!(trades.lines.lock().await.len(), 1);
+    let books = books.lines.lock().await;
+    assert_eq!(books.len(), 1);
+    assert!(books[0].contains("\"l2_diff\""));
+}
+
+#[tokio::test]
+async fn freshness_slos_fire_and_resolve_per_stream() {
+    let inner = Arc::new(VecSink::default());
+    let freshness = Arc::new(Freshness::new(
+        [
+            ("trade".to_string(), 2_000),
+            ("funding".to_string(), 90_000),
+        ]
+        .into(),
+    ));
+    let sink = FreshnessSink::new(inner.clone() as DynSink, freshness.clone());
+    let event = |agent: &str, typ: &str, ts: i64| {
+        json!({"agent": agent, "type": typ, "s": "BTC-USDT", "ts": ts}).to_string()
+    };
+
+    sink.send(&event("binance", "trade", 10_000)).await.unwrap();
+    sink.send(&event("okx", "trade", 11_500)).await.unwrap();
+    sink.send(&event("okx", "funding", 1_000)).await.unwrap();
+    sink.send(&event("okx", "book_ticker", 1_000))
+        .await
+        .unwrap();
+    assert_eq!(inner.lines.lock().await.len(), 4);
+
+    let before = SLO_VIOLATIONS.load(std::sync::atomic::Ordering::Relaxed);
+    let alerts = freshness.check(13_000);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].agent, "binance");
+    assert_eq!(alerts[0].event_type, "trade");
+    assert_eq!(alerts[0].state, "firing");
+    assert_eq!(alerts[0].lag_ms, 3_000);
+    assert_eq!(alerts[0].slo_ms, 2_000);
+    assert_eq!(
+        SLO_VIOLATIONS.load(std::sync::atomic::Ordering::Relaxed),
+        before + 1
+    );
+    // a breach is reported once, not on every check
+    assert!(freshness.check(13_100).is_empty());
+
+    sink.send(&event("binance", "trade", 13_200)).await.unwrap();
+    let alerts = freshness.check(13_600);
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(
+        (alerts[0].agent.as_str(), alerts[0].state.as_str()),
+        ("binance", "resolved")
+    );
+    assert_eq!(
+        (alerts[1].agent.as_str(), alerts[1].state.as_str()),
+        ("okx", "firing")
+    );
+}
+
+#[tokio::test]
+async fn depth_profiles_sum_book_depth_per_band_at_most_once_per_interval() {
+    let inner = Arc::new(VecSink::default());
+    let sink = DepthProfileSink::new(
+        inner.clone() as DynSink,
+        vec![25.0, 5.0, 10.0],
+        Duration::from_secs(60),
+    );
+
+    let snapshot = |ts: i64| {
+        json!({"agent": "binance", "type": "snapshot", "s": "BTC-USDT", "ts": ts,
+            "bids": [["99.99", "1"], ["99.9", "2"], ["99.5", "4"]],
+            "asks": [["100.01", "3"], ["100.2", "5"], ["101", "6"]]})
+        .to_string()
+    };
+    sink.send(&snapshot(1_000)).await.unwrap();
+    sink.send(&snapshot(30_000)).await.unwrap();
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT","ts":40000}"#)
+        .await
+        .unwrap();
+    sink.send(&snapshot(61_000)).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let types: Vec<String> = lines
+        .iter()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["type"].to_string())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "\"snapshot\"",
+            "\"depth_profile\"",
+            "\"snapshot\"",
+            "\"trade\"",
+            "\"snapshot\"",
+            "\"depth_profile\""
+        ]
+    );
+
+    let profile: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(profile["s"], "BTC-USDT");
+    assert_eq!(profile["ts"], 1_000);
+    assert_eq!(profile["mid"], 100.0);
+    let bands = profile["bands"].as_array().unwrap();
+    assert_eq!(bands.len(), 3);
+    assert_eq!(bands[0]["bps"], 5.0);
+    assert_eq!(bands[0]["bid_qty"], 1.0);
+    assert_eq!(bands[0]["ask_qty"], 3.0);
+    assert_eq!(bands[1]["bid_qty"], 3.0);
+    assert_eq!(bands[1]["ask_qty"], 3.0);
+    assert_eq!(bands[2]["bps"], 25.0);
+    assert_eq!(bands[2]["ask_qty"], 8.0);
+    assert!((bands[2]["ask_notional"].as_f64().unwrap() - 801.03).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn depth_profiles_throttle_spot_and_perp_books_apart() {
+    let inner = Arc::new(VecSink::default());
+    let sink = DepthProfileSink::new(
+        inner.clone() as DynSink,
+        vec![10.0],
+        Duration::from_secs(60),
+    );
+
+    let book = json!({"agent": "bybit", "type": "snapshot", "s": "BTC-USDT", "ts": 1_000,
+        "bids": [["99.99", "1"]], "asks": [["100.01", "1"]]});
+    let mut perp = book.clone();
+    perp["ac"] = json!("perp");
+    perp["settle"] = json!("USDT");
+    perp["id"] = json!("BTC-USDT-PERP");
+    sink.send(&book.to_string()).await.unwrap();
+    sink.send(&perp.to_string()).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let profiles: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .filter(|v| v["type"] == "depth_profile")
+        .collect();
+    assert_eq!(profiles.len(), 2);
+    assert!(profiles[0].get("ac").is_none());
+    assert_eq!(profiles[1]["ac"], "perp");
+    assert_eq!(profiles[1]["settle"], "USDT");
+    assert_eq!(profiles[1]["id"], "BTC-USDT-PERP");
+}
+
+#[tokio::test]
+async fn large_prints_flag_outsized_trades_and_bursts_that_sweep_levels() {
+    let inner = Arc::new(VecSink::default());
+    let sink = LargePrintSink::new(inner.clone() as DynSink, 99.0, 1000, 50);
+    let trade = |ts: i64, p: &str, q: &str| {
+        json!({"agent": "binance", "type": "trade", "s": "BTC-USDT", "ts": ts, "p": p, "q": q})
+            .to_string()
+    };
+
+    for i in 0..100 {
+        sink.send(&trade(i * 1_000, "100", "1")).await.unwrap();
+    }
+    // One outsized trade, then three small buys lifting the offer together.
+    sink.send(&trade(200_000, "99.9", "10")).await.unwrap();
+    sink.send(&trade(300_000, "100.1", "0.6")).await.unwrap();
+    sink.send(&trade(300_010, "100.2", "0.6")).await.unwrap();
+    sink.send(&trade(300_020, "100.3", "0.6")).await.unwrap();
+    sink.send(&trade(400_000, "100.3", "0.5")).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let prints: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .filter(|v| v["type"] == "large_print")
+        .collect();
+    assert_eq!(lines.len(), 105 + 2);
+    assert_eq!(prints.len(), 2);
+
+    let single = &prints[0];
+    assert_eq!(single["kind"], "trade");
+    assert_eq!(single["side"], "sell");
+    assert_eq!(single["qty"], 10.0);
+    assert_eq!(single["threshold"], 1.0);
+    assert_eq!(single["swept"], false);
+
+    let burst = &prints[1];
+    assert_eq!(burst["kind"], "burst");
+    assert_eq!(burst["side"], "buy");
+    assert_eq!(burst["trades"], 2);
+    assert_eq!(burst["levels"], 2);
+    assert_eq!(burst["swept"], true);
+    assert_eq!(burst["ts"], 300_010);
+    assert!((burst["notional"].as_f64().unwrap() - 120.18).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn large_prints_judge_instruments_apart_and_report_bursts_led_by_a_large_fill() {
+    let inner = Arc::new(VecSink::default());
+    let sink = LargePrintSink::new(inner.clone() as DynSink, 99.0, 1000, 50);
+    let (mut spot_ids, mut perp_ids) = (HashMap::new(), HashMap::new());
+    let mut spot = |t: i64, ts: i64, p: &str, q: &str| {
+        agents::binance::parse_event(
+            &json!({"e": "trade", "E": ts, "s": "BTCUSDT", "t": t, "p": p, "q": q, "T": ts, "m": false}),
+            &mut spot_ids,
+        )
+        .unwrap()
+    };
+    let mut perp = |a: i64, ts: i64, q: &str| {
+        agents::binance::futures::parse_event(
+            &json!({"e": "aggTrade", "E": ts, "s": "BTCUSDT", "a": a, "p": "100", "q": q, "T": ts, "m": false}),
+            &mut perp_ids,
+        )
+        .remove(0)
+    };
+
+    // The perpetual trades far larger sizes than spot BTC-USDT.
+    for i in 1..=100 {
+        sink.send(&spot(i, i * 1_000, "100", "1")).await.unwrap();
+        sink.send(&perp(i, i * 1_000, "50")).await.unwrap();
+    }
+    // A large spot buy, then a small one in the same burst.
+    sink.send(&spot(101, 200_000, "100.1", "10")).await.unwrap();
+    sink.send(&spot(102, 200_010, "100.2", "0.5"))
+        .await
+        .unwrap();
+
+    let lines = inner.lines.lock().await;
+    let prints: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .filter(|v| v["type"] == "large_print")
+        .collect();
+    assert_eq!(prints.len(), 2);
+    assert_eq!(prints[0]["kind"], "trade");
+    assert_eq!(prints[0]["threshold"], 1.0);
+    assert!(prints[0].get("ac").is_none());
+    assert_eq!(prints[1]["kind"], "burst");
+    assert_eq!(prints[1]["trades"], 2);
+    assert_eq!(prints[1]["qty"], 10.5);
+}
+
+#[tokio::test]
+async fn fair_mid_smooths_thin_books_and_passes_liquid_ones() {
+    let inner = Arc::new(VecSink::default());
+    let sink = FairMidSink::new(inner.clone() as DynSink, 1_000.0, 5_000);
+    let ticker = |agent: &str, ts: i64, bp: &str, bq: &str, ap: &str, aq: &str| {
+        json!({"agent": agent, "type": "book_ticker", "s": "XYZ-USD", "ts": ts,
+            "bp": bp, "bq": bq, "ap": ap, "aq": aq})
+        .to_string()
+    };
+
+    // Deep enough: the plain top-of-book mid.
+    sink.send(&ticker("coinbase", 0, "9.9", "500", "10.1", "500"))
+        .await
+        .unwrap();
+    sink.send(
+        &json!({"agent": "binance", "type": "trade", "s": "XYZ-USD", "ts": 900, "p": "10.0", "q": "1"})
+            .to_string(),
+    )
+    .await
+    .unwrap();
+    // One lot on the bid: micro 10.0, consolidated mid 10.0, last trade 10.0.
+    sink.send(&ticker("binance", 1_000, "9.8", "1", "10.2", "1"))
+        .await
+        .unwrap();
+    // The bid flickers away; the median holds near the fair price.
+    sink.send(&ticker("binance", 1_100, "9.0", "1", "10.2", "1"))
+        .await
+        .unwrap();
+
+    let lines = inner.lines.lock().await;
+    let v: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(v[0]["mid_kind"], "top");
+    assert!((v[0]["mid"].as_f64().unwrap() - 10.0).abs() < 1e-9);
+    assert!(v[1].get("mid").is_none());
+    assert_eq!(v[2]["mid_kind"], "fair");
+    assert!((v[2]["mid"].as_f64().unwrap() - 10.0).abs() < 1e-9);
+    assert_eq!(v[3]["mid_kind"], "fair");
+    let raw_mid: f64 = (9.0 + 10.2) / 2.0;
+    let fair = v[3]["mid"].as_f64().unwrap();
+    assert!((fair - 10.0).abs() < (raw_mid - 10.0).abs());
+}
+
+#[tokio::test]
+async fn fair_mid_keeps_spot_and_perp_markets_apart() {
+    let inner = Arc::new(VecSink::default());
+    let sink = FairMidSink::new(inner.clone() as DynSink, 1_000.0, 5_000);
+
+    // A deep BTC-USDT perpetual quote just inside the spot spread, and a
+    // perpetual print far from it.
+    let perp_quote = agents::bybit::parse_event(
+        &json!({"topic": "tickers.BTCUSDT", "type": "snapshot", "ts": 1_000, "data": {
+            "symbol": "BTCUSDT", "bid1Price": "100.1", "bid1Size": "50",
+            "ask1Price": "100.15", "ask1Size": "50"
+        }}),
+        agents::bybit::Category::Linear,
+        &mut HashMap::new(),
+        &mut HashMap::new(),
+    );
+    let perp_trade = agents::binance::futures::parse_event(
+        &json!({"e": "aggTrade", "E": 1_000, "s": "BTCUSDT", "a": 1, "p": "120", "q": "1", "T": 1_000, "m": false}),
+        &mut HashMap::new(),
+    );
+    for line in perp_quote.iter().chain(&perp_trade) {
+        sink.send(line).await.unwrap();
+    }
+    // A thin spot book: its fair mid only uses spot inputs.
+    let spot = agents::binance::parse_event(
+        &json!({"e": "bookTicker", "s": "BTCUSDT", "b": "99.8", "B": "1", "a": "100.2", "A": "1", "E": 1_000}),
+        &mut HashMap::new(),
+    )
+    .unwrap();
+    sink.send(&spot).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let v: serde_json::Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+    assert_eq!(v["s"], "BTC-USDT");
+    assert!(v.get("ac").is_none());
+    assert_eq!(v["mid_kind"], "fair");
+    assert!((v["mid"].as_f64().unwrap() - 100.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn conflation_merges_diffs_per_book_until_the_window_closes() {
+    let _stats = STATS.lock().await;
+    ingest_stats::drain(Duration::from_secs(60));
+    let inner = Arc::new(VecSink::default());
+    let sink = ConflationSink::new(inner.clone() as DynSink, Duration::from_millis(20));
+    let diff = |s: &str, ts: i64, bids: serde_json::Value, asks: serde_json::Value| {
+        json!({"agent": "binance", "type": "l2_diff", "s": s, "ts": ts, "bids": bids, "asks": asks})
+            .to_string()
+    };
+
+    sink.send(&diff(
+        "BTC-USDT",
+        1,
+        json!([["100.0", "1"]]),
+        json!([["101", "2"]]),
+    ))
+    .await
+    .unwrap();
+    sink.send(&diff(
+        "BTC-USDT",
+        2,
+        json!([["100", "3"], ["99.5", "1"]]),
+        json!([]),
+    ))
+    .await
+    .unwrap();
+    sink.send(&diff("BTC-USDT", 3, json!([["99.5", "0"]]), json!([])))
+        .await
+        .unwrap();
+    sink.send(&diff("ETH-USDT", 4, json!([["10", "1"]]), json!([])))
+        .await
+        .unwrap();
+    sink.send(r#"{"agent":"binance","type":"trade","s":"BTC-USDT","p":"100","q":"1"}"#)
+        .await
+        .unwrap();
+    // Only the trade passes straight through while the window is open.
+    assert_eq!(inner.lines.lock().await.len(), 1);
+    sink.flush(false).await.unwrap();
+    assert_eq!(inner.lines.lock().await.len(), 1);
+
+    // A snapshot releases its book's pending diff ahead of itself.
+    sink.send(r#"{"agent":"binance","type":"snapshot","s":"ETH-USDT","bids":[],"asks":[]}"#)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    sink.flush(false).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let v: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(v.len(), 4);
+    assert_eq!(v[1]["s"], "ETH-USDT");
+    assert!(v[1].get("conflated").is_none());
+    assert_eq!(v[2]["type"], "snapshot");
+    assert_eq!(v[3]["s"], "BTC-USDT");
+    assert_eq!(v[3]["ts"], 3);
+    assert_eq!(v[3]["conflated"], 3);
+    assert_eq!(v[3]["bids"], json!([["100", "3"], ["99.5", "0"]]));
+    assert_eq!(v[3]["asks"], json!([["101", "2"]]));
+
+    let stats = ingest_stats::drain(Duration::from_secs(60));
+    assert_eq!(stats[0].agent, "binance");
+    assert_eq!(stats[0].conflated, 2);
+}
+
+#[tokio::test]
+async fn conflation_keeps_spot_and_perp_books_apart() {
+    let inner = Arc::new(VecSink::default());
+    let sink = ConflationSink::new(inner.clone() as DynSink, Duration::from_secs(60));
+    let depth =
+        |bid: &str| json!({"e": "depthUpdate", "E": 1, "s": "BTCUSDT", "b": [[bid, "1"]], "a": []});
+    let spot = agents::binance::parse_event(&depth("100"), &mut Default::default()).unwrap();
+    let perp = agents::binance::futures::parse_event(&depth("101"), &mut Default::default());
+    sink.send(&spot).await.unwrap();
+    sink.send(&perp[0]).await.unwrap();
+    sink.flush(true).await.unwrap();
+
+    let lines = inner.lines.lock().await;
+    let mut v: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    v.sort_by_key(|e| e["ac"].as_str().map(str::to_string));
+    assert_eq!(v.len(), 2);
+    assert!(v.iter().all(|e| e["s"] == "BTC-USDT"));
+    assert!(v[0].get("ac").is_none());
+    assert_eq!(v[0]["bids"], json!([["100", "1"]]));
+    assert_eq!(v[1]["ac"], "perp");
+    assert_eq!(v[1]["bids"], json!([["101", "1"]]));
+    assert!(v.iter().all(|e| e.get("conflated").is_none()));
+}
@@ -5,7 +5,7 @@ use tokio::sync::{mpsc, watch};
 use tokio_tungstenite::{accept_async, tungstenite::Message};
 
 use ingestor::agent::Agent;
-use ingestor::agents::{binance::BinanceAgent, coinbase::CoinbaseAgent};
+use ingestor::agents::{binance::BinanceAgent, coinbase::CoinbaseAgent, okx::OkxAgent};
 use ingestor::config::{Settings, DEFAULT_COINBASE_REFRESH_INTERVAL_MINS};
 
 #[tokio::test]
@@ -76,6 +76,7 @@ async fn binance_trade_messages_are_canonicalized_with_id() {
         let ack = json!({"id":1}).to_string();
         ws.send(Message::Text(ack)).await.unwrap();
         let msg = json!({
+            "e": "trade",
             "s": "btcusdt",
             "t": 7,
             "p": "50.00",
@@ -118,3 +119,185 @@ async fn binance_trade_messages_are_canonicalized_with_id() {
     handle.await.unwrap();
     server.await.unwrap();
 }
+
+#[tokio::test]
+async fn binance_subscription_drift_is_resynced() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept_async(stream).await.unwrap();
+        let mut requests = Vec::new();
+        while let Some(Ok(Message::Text(txt))) = ws.next().await {
+            let v: serde_json::Value = serde_json::from_str(&txt).unwrap();
+            match v["method"].as_str() {
+                // report one stream dropped and one stale stream
+                Some("LIST_SUBSCRIPTIONS") => {
+                    let resp = json!({
+                        "result": ["btcusdt@trade", "btcusdt@bookTicker", "ethusdt@trade"],
+                        "id": v["id"],
+                    });
+                    ws.send(Message::Text(resp.to_string())).await.unwrap();
+                }
+                _ => {
+                    requests.push(v.clone());
+                    let ack = json!({"result": null, "id": v["id"]});
+                    ws.send(Message::Text(ack.to_string())).await.unwrap();
+                }
+            }
+            if requests.len() == 3 {
+                break;
+            }
+        }
+        requests
+    });
+
+    let cfg = Settings {
+        binance_ws_url: format!("ws://{}", addr),
+        binance_refresh_interval_mins: 60,
+        binance_max_reconnect_delay_secs: 1,
+        subscription_resync_secs: 1,
+        ..Default::default()
+    };
+
+    let mut agent = BinanceAgent::new(Some(vec!["btcusdt".into()]), &cfg)
+        .await
+        .unwrap();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (tx, _rx) = mpsc::channel::<String>(16);
+
+    let handle = tokio::spawn(async move {
+        agent.run(shutdown_rx, tx).await.unwrap();
+    });
+
+    let requests = tokio::time::timeout(std::time::Duration::from_secs(10), server)
+        .await
+        .expect("no resync")
+        .unwrap();
+    assert_eq!(requests[0]["method"], "SUBSCRIBE");
+    assert_eq!(requests[1]["method"], "UNSUBSCRIBE");
+    assert_eq!(requests[1]["params"], json!(["ethusdt@trade"]));
+    assert_eq!(requests[2]["method"], "SUBSCRIBE");
+    assert_eq!(requests[2]["params"], json!(["btcusdt@depth@100ms"]));
+
+    shutdown_tx.send(true).unwrap();
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn binance_rejected_subscription_is_retried_with_new_id() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept_async(stream).await.unwrap();
+        let mut requests = Vec::new();
+        while let Some(Ok(Message::Text(txt))) = ws.next().await {
+            let v: serde_json::Value = serde_json::from_str(&txt).unwrap();
+            let resp = if requests.is_empty() {
+                json!({"error": {"code": 2, "msg": "Invalid request"}, "id": v["id"]})
+            } else {
+                json!({"result": null, "id": v["id"]})
+            };
+            ws.send(Message::Text(resp.to_string())).await.unwrap();
+            requests.push(v);
+            if requests.len() == 2 {
+                break;
+            }
+        }
+        requests
+    });
+
+    let cfg = Settings {
+        binance_ws_url: format!("ws://{}", addr),
+        binance_refresh_interval_mins: 60,
+        binance_max_reconnect_delay_secs: 1,
+        ..Default::default()
+    };
+
+    let mut agent = BinanceAgent::new(Some(vec!["btcusdt".into()]), &cfg)
+        .await
+        .unwrap();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (tx, _rx) = mpsc::channel::<String>(16);
+
+    let handle = tokio::spawn(async move {
+        agent.run(shutdown_rx, tx).await.unwrap();
+    });
+
+    let requests = tokio::time::timeout(std::time::Duration::from_secs(10), server)
+        .await
+        .expect("no retry")
+        .unwrap();
+    assert_eq!(requests[1]["method"], "SUBSCRIBE");
+    assert_eq!(requests[1]["params"], requests[0]["params"]);
+    assert_ne!(requests[1]["id"], requests[0]["id"]);
+
+    shutdown_tx.send(true).unwrap();
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn okx_subscribes_all_channels_and_emits_trades() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept_async(stream).await.unwrap();
+        let sub = match ws.next().await {
+            Some(Ok(Message::Text(t))) => serde_json::from_str::<serde_json::Value>(&t).unwrap(),
+            other => panic!("expected subscription, got {other:?}"),
+        };
+        ws.send(Message::Text(
+            json!({"event": "subscribe", "arg": {"channel": "trades", "instId": "BTC-USDT"}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+        let msg = json!({
+            "arg": {"channel": "trades", "instId": "BTC-USDT"},
+            "data": [{"instId": "BTC-USDT", "tradeId": "7", "px": "30000.10", "sz": "0.25", "side": "buy", "ts": "1700000000000"}]
+        })
+        .to_string();
+        ws.send(Message::Text(msg)).await.unwrap();
+        let _ = ws.next().await;
+        sub
+    });
+
+    let cfg = Settings {
+        okx_ws_url: format!("ws://{}", addr),
+        okx_book_channel: "books5".into(),
+        okx_max_reconnect_delay_secs: 1,
+        okx_refresh_interval_mins: 60,
+        ..Default::default()
+    };
+    let mut agent = OkxAgent::new(vec!["BTC-USDT".into()], false, &cfg);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (tx, mut rx) = mpsc::channel::<String>(1);
+    let handle = tokio::spawn(async move {
+        agent.run(shutdown_rx, tx).await.unwrap();
+    });
+
+    let line = rx.recv().await.expect("no message");
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["agent"], "okx");
+    assert_eq!(v["type"], "trade");
+    assert_eq!(v["s"], "BTC-USDT");
+    assert_eq!(v["t"], 7);
+    assert_eq!(v["p"], "30000.1");
+
+    shutdown_tx.send(true).unwrap();
+    handle.await.unwrap();
+    let sub = server.await.unwrap();
+    assert_eq!(sub["op"], "subscribe");
+    let channels: Vec<_> = sub["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["channel"].as_str().unwrap())
+        .collect();
+    assert_eq!(channels, ["trades", "tickers", "books5"]);
+}